@@ -175,6 +175,9 @@ pub struct Parameters {
     /// Anemo network settings.
     #[serde(default = "AnemoParameters::default")]
     pub anemo: AnemoParameters,
+    /// Settings of the worker's request handlers.
+    #[serde(default = "WorkerHandlerParameters::default")]
+    pub worker_handlers: WorkerHandlerParameters,
 }
 
 impl Parameters {
@@ -276,6 +279,74 @@ impl AnemoParameters {
     }
 }
 
+/// A method of the `PrimaryToWorker` service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrimaryToWorkerMethod {
+    Synchronize,
+    FetchBatches,
+    DeleteBatches,
+}
+
+impl PrimaryToWorkerMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PrimaryToWorkerMethod::Synchronize => "synchronize",
+            PrimaryToWorkerMethod::FetchBatches => "fetch_batches",
+            PrimaryToWorkerMethod::DeleteBatches => "delete_batches",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WorkerHandlerParameters {
+    /// The `PrimaryToWorker` methods served by the worker. If unspecified, all are served.
+    pub enabled_primary_to_worker_methods: Option<Vec<PrimaryToWorkerMethod>>,
+
+    /// Maximum number of concurrent calls of each `PrimaryToWorker` method. If unspecified,
+    /// the method is unbounded.
+    pub synchronize_concurrency_limit: Option<usize>,
+    pub fetch_batches_concurrency_limit: Option<usize>,
+    pub delete_batches_concurrency_limit: Option<usize>,
+    /// Whether calls beyond a concurrency limit fail, instead of waiting for a running call.
+    pub reject_calls_over_concurrency_limit: bool,
+
+    /// Average batch write latency (in ms) above which batches reported by other workers are
+    /// shed. If unspecified, they are never shed.
+    pub batch_write_latency_threshold_ms: Option<u64>,
+
+    /// Whether batches are stored partitioned by epoch and authority.
+    pub partition_batch_store: bool,
+
+    /// Delay (in ms) before deleted batches are removed from the store, during which they are
+    /// no longer served but restored if synchronized again. Their tombstones are persisted, so
+    /// that they are still removed after a restart. If unspecified, deleted batches are
+    /// removed right away.
+    pub batch_tombstone_grace_period_ms: Option<u64>,
+
+    /// Whether the worker records when it stores each batch, to report batch ages to peers.
+    pub record_batch_insert_times: bool,
+
+    /// Whether the primary attaches the certificate of the batches it asks its workers to
+    /// synchronize, and the workers verify it instead of validating certified batches.
+    pub verify_batch_certificates: bool,
+
+    /// Per-peer rate-limit (in requests/sec) and burst size for the WorkerToWorker service,
+    /// shared by all of its methods.
+    pub worker_to_worker_rate_limit: Option<f64>,
+    pub worker_to_worker_rate_limit_burst: Option<u32>,
+}
+
+impl WorkerHandlerParameters {
+    pub fn worker_to_worker_rate_limit_burst(&self) -> u32 {
+        const WORKER_TO_WORKER_RATE_LIMIT_BURST: u32 = 100;
+
+        self.worker_to_worker_rate_limit_burst
+            .unwrap_or(WORKER_TO_WORKER_RATE_LIMIT_BURST)
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PrometheusMetricsParameters {
     /// Socket address the server should be listening to.
@@ -431,6 +502,7 @@ impl Default for Parameters {
             prometheus_metrics: PrometheusMetricsParameters::default(),
             network_admin_server: NetworkAdminServerParameters::default(),
             anemo: AnemoParameters::default(),
+            worker_handlers: WorkerHandlerParameters::default(),
         }
     }
 }
@@ -531,6 +603,10 @@ impl Parameters {
             self.network_admin_server
                 .worker_network_admin_server_base_port
         );
+        info!(
+            "Worker handler parameters set to {:?}",
+            self.worker_handlers
+        );
     }
 }
 
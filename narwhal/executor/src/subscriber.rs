@@ -344,32 +344,39 @@ impl Subscriber {
             // to NetworkClient.
             // Only have one worker for now so will leave this for a future
             // optimization.
-            let request = FetchBatchesRequest {
+            let mut request = FetchBatchesRequest {
                 digests,
                 known_workers,
             };
-            let batches = loop {
-                match inner
-                    .client
-                    .fetch_batches(worker_name.clone(), request.clone())
-                    .await
-                {
-                    Ok(resp) => break resp.batches,
-                    Err(e) => {
-                        error!("Failed to fetch batches from worker {worker_name}: {e:?}");
-                        // Loop forever on failure. During shutdown, this should get cancelled.
-                        tokio::time::sleep(Duration::from_secs(1)).await;
-                        continue;
+            loop {
+                let response = loop {
+                    match inner
+                        .client
+                        .fetch_batches_v2(worker_name.clone(), request.clone())
+                        .await
+                    {
+                        Ok(resp) => break resp,
+                        Err(e) => {
+                            error!("Failed to fetch batches from worker {worker_name}: {e:?}");
+                            // Loop forever on failure. During shutdown, this should get cancelled.
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                            continue;
+                        }
                     }
+                };
+                for (digest, batch) in response.batches {
+                    let batch_fetch_duration = batch.metadata().created_at.elapsed().as_secs_f64();
+                    inner
+                        .metrics
+                        .batch_execution_latency
+                        .observe(batch_fetch_duration);
+                    fetched_batches.insert(digest, batch);
                 }
-            };
-            for (digest, batch) in batches {
-                let batch_fetch_duration = batch.metadata().created_at.elapsed().as_secs_f64();
-                inner
-                    .metrics
-                    .batch_execution_latency
-                    .observe(batch_fetch_duration);
-                fetched_batches.insert(digest, batch);
+                if !response.is_size_limit_reached {
+                    break;
+                }
+                // The worker capped its response, so request the omitted batches again.
+                request.digests = response.omitted_digests.into_iter().collect();
             }
         }
 
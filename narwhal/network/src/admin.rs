@@ -11,13 +11,24 @@ use tracing::{error, info};
 use types::ConditionalBroadcastReceiver;
 
 pub fn start_admin_server(
+    port: u16,
+    network: anemo::Network,
+    tr_shutdown: ConditionalBroadcastReceiver,
+) -> Vec<JoinHandle<()>> {
+    start_admin_server_with_routes(port, network, tr_shutdown, Router::new())
+}
+
+/// Starts the admin server, serving `routes` next to the routes common to all nodes.
+pub fn start_admin_server_with_routes(
     port: u16,
     network: anemo::Network,
     mut tr_shutdown: ConditionalBroadcastReceiver,
+    routes: Router,
 ) -> Vec<JoinHandle<()>> {
     let mut router = Router::new()
         .route("/peers", get(get_peers))
-        .route("/known_peers", get(get_known_peers));
+        .route("/known_peers", get(get_known_peers))
+        .merge(routes);
 
     router = router.layer(Extension(network));
 
@@ -3,15 +3,17 @@
 
 use std::{collections::BTreeMap, sync::Arc, time::Duration};
 
-use anemo::{PeerId, Request};
+use anemo::{types::response::StatusCode, PeerId, Request};
 use async_trait::async_trait;
 use crypto::{traits::KeyPair, NetworkKeyPair, NetworkPublicKey};
 use mysten_common::sync::notify_once::NotifyOnce;
 use parking_lot::RwLock;
 use tokio::{select, time::sleep};
 use types::{
-    error::LocalClientError, FetchBatchesRequest, FetchBatchesResponse, PrimaryToWorker,
-    WorkerOthersBatchMessage, WorkerOurBatchMessage, WorkerSynchronizeMessage, WorkerToPrimary,
+    error::{LocalClientError, UNIMPLEMENTED},
+    FetchBatchesRequest, FetchBatchesResponse, FetchBatchesV2Response, PrimaryToWorker,
+    WorkerOthersBatchMessage, WorkerOurBatchMessage, WorkerSynchronizeMessage,
+    WorkerSynchronizeV2Message, WorkerToPrimary,
 };
 
 use crate::traits::{PrimaryToWorkerClient, WorkerToPrimaryClient};
@@ -141,7 +143,26 @@ impl PrimaryToWorkerClient for NetworkClient {
             .await?;
         select! {
             resp = c.synchronize(Request::new(request)) => {
-                resp.map_err(|e| LocalClientError::Internal(format!("{e:?}")))?;
+                resp.map_err(handler_error)?;
+                Ok(())
+            },
+            () = self.shutdown_notify.wait() => {
+                Err(LocalClientError::ShuttingDown)
+            },
+        }
+    }
+
+    async fn synchronize_v2(
+        &self,
+        worker_name: NetworkPublicKey,
+        request: WorkerSynchronizeV2Message,
+    ) -> Result<(), LocalClientError> {
+        let c = self
+            .get_primary_to_worker_handler(PeerId(worker_name.0.into()))
+            .await?;
+        select! {
+            resp = c.synchronize_v2(Request::new(request)) => {
+                resp.map_err(handler_error)?;
                 Ok(())
             },
             () = self.shutdown_notify.wait() => {
@@ -160,7 +181,25 @@ impl PrimaryToWorkerClient for NetworkClient {
             .await?;
         select! {
             resp = c.fetch_batches(Request::new(request)) => {
-                Ok(resp.map_err(|e| LocalClientError::Internal(format!("{e:?}")))?.into_inner())
+                Ok(resp.map_err(handler_error)?.into_inner())
+            },
+            () = self.shutdown_notify.wait() => {
+                Err(LocalClientError::ShuttingDown)
+            },
+        }
+    }
+
+    async fn fetch_batches_v2(
+        &self,
+        worker_name: NetworkPublicKey,
+        request: FetchBatchesRequest,
+    ) -> Result<FetchBatchesV2Response, LocalClientError> {
+        let c = self
+            .get_primary_to_worker_handler(PeerId(worker_name.0.into()))
+            .await?;
+        select! {
+            resp = c.fetch_batches_v2(Request::new(request)) => {
+                Ok(resp.map_err(handler_error)?.into_inner())
             },
             () = self.shutdown_notify.wait() => {
                 Err(LocalClientError::ShuttingDown)
@@ -194,7 +233,7 @@ impl WorkerToPrimaryClient for NetworkClient {
         let c = self.get_worker_to_primary_handler().await?;
         select! {
             resp = c.report_others_batch(Request::new(request)) => {
-                resp.map_err(|e| LocalClientError::Internal(format!("{e:?}")))?;
+                resp.map_err(handler_error)?;
                 Ok(())
             },
             () = self.shutdown_notify.wait() => {
@@ -204,6 +243,17 @@ impl WorkerToPrimaryClient for NetworkClient {
     }
 }
 
+/// Maps the error of a local handler, telling apart the requests the handler refused itself,
+/// e.g. as malformed or because the method is disabled, which fail the same way if retried.
+fn handler_error(e: anemo::rpc::Status) -> LocalClientError {
+    let status = e.status();
+    if matches!(status, StatusCode::BadRequest | StatusCode::NotFound) || status == UNIMPLEMENTED {
+        LocalClientError::Rejected(format!("{e:?}"))
+    } else {
+        LocalClientError::Internal(format!("{e:?}"))
+    }
+}
+
 fn empty_peer_id() -> PeerId {
     PeerId([0u8; 32])
 }
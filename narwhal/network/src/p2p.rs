@@ -16,8 +16,9 @@ use tokio::task::JoinHandle;
 use types::{
     Batch, BatchDigest, FetchCertificatesRequest, FetchCertificatesResponse,
     GetCertificatesRequest, GetCertificatesResponse, PrimaryToPrimaryClient, PrimaryToWorkerClient,
-    RequestBatchRequest, RequestBatchesRequest, RequestBatchesResponse, WorkerBatchMessage,
-    WorkerDeleteBatchesMessage, WorkerSynchronizeMessage, WorkerToWorkerClient,
+    RequestBatchRequest, RequestBatchesRequest, RequestBatchesResponse, RequestBatchesV2Request,
+    RequestBatchesV2Response, WorkerBatchMessage, WorkerDeleteBatchesMessage,
+    WorkerSynchronizeMessage, WorkerToWorkerClient,
 };
 
 fn unreliable_send<F, R, Fut>(
@@ -243,4 +244,20 @@ impl WorkerRpc for anemo::Network {
             .map_err(|e| format_err!("Network error {:?}", e))?;
         Ok(response.into_body())
     }
+
+    async fn request_batches_v2(
+        &self,
+        peer: NetworkPublicKey,
+        request: impl anemo::types::request::IntoRequest<RequestBatchesV2Request> + Send,
+    ) -> Result<RequestBatchesV2Response> {
+        let peer_id = PeerId(peer.0.to_bytes());
+        let peer = self
+            .peer(peer_id)
+            .ok_or_else(|| format_err!("Network has no connection with peer {peer_id}"))?;
+        let response = WorkerToWorkerClient::new(peer)
+            .request_batches_v2(request)
+            .await
+            .map_err(|e| format_err!("Network error {:?}", e))?;
+        Ok(response.into_body())
+    }
 }
@@ -7,9 +7,10 @@ use crypto::NetworkPublicKey;
 use tokio::task::JoinHandle;
 use types::{
     error::LocalClientError, Batch, BatchDigest, FetchBatchesRequest, FetchBatchesResponse,
-    FetchCertificatesRequest, FetchCertificatesResponse, GetCertificatesRequest,
-    GetCertificatesResponse, RequestBatchesRequest, RequestBatchesResponse,
-    WorkerOthersBatchMessage, WorkerOurBatchMessage, WorkerSynchronizeMessage,
+    FetchBatchesV2Response, FetchCertificatesRequest, FetchCertificatesResponse,
+    GetCertificatesRequest, GetCertificatesResponse, RequestBatchesRequest, RequestBatchesResponse,
+    RequestBatchesV2Request, RequestBatchesV2Response, WorkerOthersBatchMessage,
+    WorkerOurBatchMessage, WorkerSynchronizeMessage, WorkerSynchronizeV2Message,
 };
 
 pub trait UnreliableNetwork<Request: Clone + Send + Sync> {
@@ -88,11 +89,23 @@ pub trait PrimaryToWorkerClient {
         request: WorkerSynchronizeMessage,
     ) -> Result<(), LocalClientError>;
 
+    async fn synchronize_v2(
+        &self,
+        worker_name: NetworkPublicKey,
+        request: WorkerSynchronizeV2Message,
+    ) -> Result<(), LocalClientError>;
+
     async fn fetch_batches(
         &self,
         worker_name: NetworkPublicKey,
         request: FetchBatchesRequest,
     ) -> Result<FetchBatchesResponse, LocalClientError>;
+
+    async fn fetch_batches_v2(
+        &self,
+        worker_name: NetworkPublicKey,
+        request: FetchBatchesRequest,
+    ) -> Result<FetchBatchesV2Response, LocalClientError>;
 }
 
 #[async_trait]
@@ -121,4 +134,10 @@ pub trait WorkerRpc {
         peer: NetworkPublicKey,
         request: impl anemo::types::request::IntoRequest<RequestBatchesRequest> + Send,
     ) -> Result<RequestBatchesResponse>;
+
+    async fn request_batches_v2(
+        &self,
+        peer: NetworkPublicKey,
+        request: impl anemo::types::request::IntoRequest<RequestBatchesV2Request> + Send,
+    ) -> Result<RequestBatchesV2Response>;
 }
@@ -6,6 +6,7 @@ use futures::future::try_join_all;
 use futures::stream::FuturesUnordered;
 pub use storage::{CertificateStoreCacheMetrics, NodeStorage};
 use thiserror::Error;
+use worker::PrimaryReceiverHandlerBuilderError;
 
 pub mod execution_state;
 pub mod metrics;
@@ -22,4 +23,7 @@ pub enum NodeError {
 
     #[error("Worker nodes with ids {0:?} already running")]
     WorkerNodesAlreadyRunning(Vec<WorkerId>),
+
+    #[error("Failure while configuring worker handlers: {0}")]
+    WorkerHandlersError(#[from] PrimaryReceiverHandlerBuilderError),
 }
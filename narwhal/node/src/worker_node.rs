@@ -20,7 +20,7 @@ use tokio::task::JoinHandle;
 use tracing::{info, instrument};
 use types::PreSubscribedBroadcastSender;
 use worker::metrics::{initialise_metrics, Metrics};
-use worker::{TransactionValidator, Worker, NUM_SHUTDOWN_RECEIVERS};
+use worker::{PartitionedBatchStore, TransactionValidator, Worker, NUM_SHUTDOWN_RECEIVERS};
 
 pub struct WorkerNodeInner {
     // The worker's id
@@ -90,19 +90,41 @@ impl WorkerNodeInner {
                 )
             });
 
-        let handles = Worker::spawn(
-            authority.clone(),
-            network_keypair,
-            self.id,
-            committee.clone(),
-            worker_cache.clone(),
-            self.parameters.clone(),
-            tx_validator.clone(),
-            client.clone(),
-            store.batch_store.clone(),
-            metrics,
-            &mut tx_shutdown,
-        );
+        let handles = if self.parameters.worker_handlers.partition_batch_store {
+            Worker::spawn(
+                authority.clone(),
+                network_keypair,
+                self.id,
+                committee.clone(),
+                worker_cache.clone(),
+                self.parameters.clone(),
+                tx_validator.clone(),
+                client.clone(),
+                PartitionedBatchStore::new(
+                    store.partitioned_batch_store.clone(),
+                    committee.epoch(),
+                    authority.id(),
+                ),
+                store.batch_tombstone_store.clone(),
+                metrics,
+                &mut tx_shutdown,
+            )?
+        } else {
+            Worker::spawn(
+                authority.clone(),
+                network_keypair,
+                self.id,
+                committee.clone(),
+                worker_cache.clone(),
+                self.parameters.clone(),
+                tx_validator.clone(),
+                client.clone(),
+                store.batch_store.clone(),
+                store.batch_tombstone_store.clone(),
+                metrics,
+                &mut tx_shutdown,
+            )?
+        };
 
         // store the registry
         if let Some(registry) = registry {
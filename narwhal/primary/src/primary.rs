@@ -184,6 +184,7 @@ impl Primary {
             committee.clone(),
             worker_cache.clone(),
             parameters.gc_depth,
+            parameters.worker_handlers.verify_batch_certificates,
             client.clone(),
             certificate_store.clone(),
             payload_store.clone(),
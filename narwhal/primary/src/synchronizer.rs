@@ -39,6 +39,7 @@ use types::{
     error::{AcceptNotification, DagError, DagResult},
     Certificate, CertificateAPI, CertificateDigest, Header, HeaderAPI, PrimaryToPrimaryClient,
     Round, SendCertificateRequest, SendCertificateResponse, WorkerSynchronizeMessage,
+    WorkerSynchronizeV2Message,
 };
 
 use crate::{
@@ -64,6 +65,9 @@ struct Inner {
     worker_cache: WorkerCache,
     /// The depth of the garbage collector.
     gc_depth: Round,
+    /// Whether to attach certificates to the batches our workers synchronize, for the workers
+    /// to verify them.
+    attach_batch_certificates: bool,
     /// Highest round that has been GC'ed.
     gc_round: AtomicU64,
     /// Highest round of certificate accepted into the certificate store.
@@ -99,9 +103,9 @@ struct Inner {
     metrics: Arc<PrimaryMetrics>,
     /// Background tasks broadcasting newly formed certificates.
     certificate_senders: Mutex<JoinSet<()>>,
-    /// A background task that synchronizes batches. A tuple of a header and the maximum accepted
-    /// age is sent over.
-    tx_batch_tasks: Sender<(Header, u64)>,
+    /// A background task that synchronizes batches. A tuple of a certificate and the maximum
+    /// accepted age is sent over.
+    tx_batch_tasks: Sender<(Certificate, u64)>,
     /// Aggregates certificates to use as parents for new headers.
     certificates_aggregators: Mutex<BTreeMap<Round, Box<CertificatesAggregator>>>,
     /// State for tracking suspended certificates and when they can be accepted.
@@ -306,6 +310,7 @@ impl Synchronizer {
         committee: Committee,
         worker_cache: WorkerCache,
         gc_depth: Round,
+        attach_batch_certificates: bool,
         client: NetworkClient,
         certificate_store: CertificateStore,
         payload_store: PayloadStore,
@@ -342,6 +347,7 @@ impl Synchronizer {
             committee: committee.clone(),
             worker_cache,
             gc_depth,
+            attach_batch_certificates,
             gc_round: AtomicU64::new(gc_round),
             highest_processed_round: AtomicU64::new(highest_processed_round),
             highest_received_round: AtomicU64::new(0),
@@ -516,7 +522,7 @@ impl Synchronizer {
                 loop {
                     tokio::select! {
                         result = rx_batch_tasks.recv() => {
-                            let (header, max_age) = match result {
+                            let (certificate, max_age) = match result {
                                 Some(r) => r,
                                 None => {
                                     // exit loop if the channel has been closed
@@ -530,7 +536,7 @@ impl Synchronizer {
                             };
 
                             batch_tasks.spawn(async move {
-                                Synchronizer::sync_batches_internal(inner.clone(), &header, max_age, true).await
+                                Synchronizer::sync_batches_internal(inner.clone(), certificate.header(), max_age, Some(&certificate)).await
                             });
                         },
                         Some(result) = batch_tasks.join_next() => {
@@ -720,11 +726,10 @@ impl Synchronizer {
         // Instruct workers to download any missing batches referenced in this certificate.
         // Since this header got certified, we are sure that all the data it refers to (ie. its batches and its parents) are available.
         // We can thus continue the processing of the certificate without blocking on batch synchronization.
-        let header = certificate.header().clone();
         let max_age = self.inner.gc_depth.saturating_sub(1);
         self.inner
             .tx_batch_tasks
-            .send((header.clone(), max_age))
+            .send((certificate.clone(), max_age))
             .await
             .map_err(|_| DagError::ShuttingDown)?;
 
@@ -928,25 +933,30 @@ impl Synchronizer {
     /// past the max allowed age. (`max_age == 0` means the header's round must match current
     /// round.)
     pub async fn sync_header_batches(&self, header: &Header, max_age: Round) -> DagResult<()> {
-        Synchronizer::sync_batches_internal(self.inner.clone(), header, max_age, false).await
+        Synchronizer::sync_batches_internal(self.inner.clone(), header, max_age, None).await
     }
 
     // TODO: Add batching support to synchronizer and use this call from executor.
     // pub async fn sync_certificate_batches(
     //     &self,
-    //     header: &Header,
+    //     certificate: &Certificate,
     //     network: anemo::Network,
     //     max_age: Round,
     // ) -> DagResult<()> {
-    //     Synchronizer::sync_batches_internal(self.inner.clone(), header, max_age, true)
-    //         .await
+    //     Synchronizer::sync_batches_internal(
+    //         self.inner.clone(),
+    //         certificate.header(),
+    //         max_age,
+    //         Some(certificate),
+    //     )
+    //     .await
     // }
 
     async fn sync_batches_internal(
         inner: Arc<Inner>,
         header: &Header,
         max_age: Round,
-        is_certified: bool,
+        certificate: Option<&Certificate>,
     ) -> DagResult<()> {
         if header.author() == inner.authority_id {
             debug!("skipping sync_batches for header {header}: no need to sync payload from own workers");
@@ -1010,13 +1020,29 @@ impl Synchronizer {
                 let message = WorkerSynchronizeMessage {
                     digests: digests.clone(),
                     target: header.author(),
-                    is_certified,
+                    is_certified: certificate.is_some(),
                 };
+                let certificate = certificate
+                    .filter(|_| inner.attach_batch_certificates)
+                    .cloned();
                 let client = client.clone();
                 let worker_name = worker_name.clone();
                 let inner = inner.clone();
                 async move {
-                    let result = client.synchronize(worker_name, message).await.map_err(|e| {
+                    // Certificates are only attached for workers verifying them, which serve
+                    // synchronize_v2.
+                    let result = match certificate {
+                        Some(certificate) => {
+                            let message = WorkerSynchronizeV2Message {
+                                certificate: Some(certificate),
+                                target_worker_id: Some(worker_id),
+                                ..WorkerSynchronizeV2Message::from(message)
+                            };
+                            client.synchronize_v2(worker_name, message).await
+                        }
+                        None => client.synchronize(worker_name, message).await,
+                    }
+                    .map_err(|e| {
                         backoff::Error::transient(DagError::NetworkError(format!("{e:?}")))
                     });
                     if result.is_ok() {
@@ -177,6 +177,7 @@ async fn fetch_certificates_basic() {
         fixture.committee(),
         worker_cache.clone(),
         gc_depth,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -106,6 +106,7 @@ async fn propose_header() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -208,6 +209,7 @@ async fn propose_header_failure() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -343,6 +345,7 @@ async fn run_vote_aggregator_with_param(
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -416,6 +419,7 @@ async fn shutdown_core() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -142,9 +142,11 @@ async fn get_network_peers_from_admin_server() {
         TrivialTransactionValidator::default(),
         client_1,
         store.batch_store,
+        test_utils::create_batch_tombstone_store(),
         metrics_1,
         &mut tx_shutdown_worker,
-    );
+    )
+    .unwrap();
 
     // Test getting all known peers for primary 1
     let resp = reqwest::get(format!(
@@ -322,6 +324,7 @@ async fn test_request_vote_has_missing_parents() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -482,6 +485,7 @@ async fn test_request_vote_accept_missing_parents() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -630,6 +634,7 @@ async fn test_request_vote_missing_batches() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client.clone(),
         certificate_store.clone(),
         payload_store.clone(),
@@ -766,6 +771,7 @@ async fn test_request_vote_already_voted() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client.clone(),
         certificate_store.clone(),
         payload_store.clone(),
@@ -938,6 +944,7 @@ async fn test_fetch_certificates_handler() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -1111,6 +1118,7 @@ async fn test_process_payload_availability_success() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -1270,6 +1278,7 @@ async fn test_process_payload_availability_when_failures() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -1376,6 +1385,7 @@ async fn test_request_vote_created_at_in_future() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client.clone(),
         certificate_store.clone(),
         payload_store.clone(),
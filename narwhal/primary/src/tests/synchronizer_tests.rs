@@ -56,6 +56,7 @@ async fn accept_certificates() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -156,6 +157,7 @@ async fn accept_suspended_certificates() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -259,6 +261,7 @@ async fn synchronizer_recover_basic() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client.clone(),
         certificate_store.clone(),
         payload_store.clone(),
@@ -310,6 +313,7 @@ async fn synchronizer_recover_basic() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -381,6 +385,7 @@ async fn synchronizer_recover_partial_certs() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client.clone(),
         certificate_store.clone(),
         payload_store.clone(),
@@ -434,6 +439,7 @@ async fn synchronizer_recover_partial_certs() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -497,6 +503,7 @@ async fn synchronizer_recover_previous_round() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client.clone(),
         certificate_store.clone(),
         payload_store.clone(),
@@ -564,6 +571,7 @@ async fn synchronizer_recover_previous_round() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -626,6 +634,7 @@ async fn deliver_certificate_using_dag() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificates_store,
         payload_store,
@@ -695,6 +704,7 @@ async fn deliver_certificate_using_store() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificates_store.clone(),
         payload_store.clone(),
@@ -764,6 +774,7 @@ async fn deliver_certificate_not_found_parents() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificates_store,
         payload_store,
@@ -844,6 +855,7 @@ async fn sync_batches_drops_old() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ 50,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -925,6 +937,7 @@ async fn gc_suspended_certificates() {
         fixture.committee(),
         worker_cache.clone(),
         /* gc_depth */ GC_DEPTH,
+        /* attach_batch_certificates */ false,
         client,
         certificate_store.clone(),
         payload_store.clone(),
@@ -150,9 +150,11 @@ async fn test_get_collections() {
         TrivialTransactionValidator::default(),
         client,
         store.batch_store.clone(),
+        test_utils::create_batch_tombstone_store(),
         metrics,
         &mut tx_shutdown_worker,
-    );
+    )
+    .unwrap();
 
     // Wait for tasks to start
     tokio::time::sleep(Duration::from_secs(15)).await;
@@ -379,9 +381,11 @@ async fn test_remove_collections() {
         TrivialTransactionValidator::default(),
         network_client,
         store.batch_store.clone(),
+        test_utils::create_batch_tombstone_store(),
         metrics,
         &mut tx_shutdown_worker,
-    );
+    )
+    .unwrap();
 
     // Test remove no collections
     let request = tonic::Request::new(RemoveCollectionsRequest {
@@ -1047,9 +1051,11 @@ async fn test_get_collections_with_missing_certificates() {
         TrivialTransactionValidator::default(),
         client_1,
         store_primary_1.batch_store,
+        test_utils::create_batch_tombstone_store(),
         metrics_1,
         &mut tx_shutdown_worker_1,
-    );
+    )
+    .unwrap();
 
     // Spawn the primary 2 - a peer to fetch missing certificates from
     let (tx_new_certificates_2, _) = test_utils::test_new_certificates_channel!(CHANNEL_CAPACITY);
@@ -1110,9 +1116,11 @@ async fn test_get_collections_with_missing_certificates() {
         TrivialTransactionValidator::default(),
         client_2,
         store_primary_2.batch_store,
+        test_utils::create_batch_tombstone_store(),
         metrics_2,
         &mut tx_shutdown_worker_2,
-    );
+    )
+    .unwrap();
 
     // Wait for tasks to start
     tokio::time::sleep(Duration::from_secs(15)).await;
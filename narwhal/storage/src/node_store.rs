@@ -8,7 +8,7 @@ use crate::{
     CertificateStore, CertificateStoreCache, CertificateStoreCacheMetrics, ConsensusStore,
     HeaderStore, ProposerStore,
 };
-use config::{AuthorityIdentifier, WorkerId};
+use config::{AuthorityIdentifier, Epoch, WorkerId};
 use std::num::NonZeroUsize;
 use std::sync::Arc;
 use std::time::Duration;
@@ -17,7 +17,7 @@ use store::reopen;
 use store::rocks::{default_db_options, open_cf_opts, DBMap, MetricConf, ReadWriteOptions};
 use types::{
     Batch, BatchDigest, Certificate, CertificateDigest, CommittedSubDagShell, ConsensusCommit,
-    Header, HeaderDigest, Round, SequenceNumber, VoteInfo,
+    Header, HeaderDigest, Round, SequenceNumber, TimestampMs, VoteInfo,
 };
 
 // A type alias marking the "payload" tokens sent by workers to their primary as batch acknowledgements
@@ -32,6 +32,12 @@ pub struct NodeStorage {
     pub certificate_store: CertificateStore<CertificateStoreCache>,
     pub payload_store: PayloadStore,
     pub batch_store: DBMap<BatchDigest, Batch>,
+    /// The batches of workers storing them partitioned by epoch and authority, see
+    /// `WorkerHandlerParameters::partition_batch_store`.
+    pub partitioned_batch_store: DBMap<(Epoch, AuthorityIdentifier, BatchDigest), Batch>,
+    /// The batches deleted by workers during their grace period, with the time it ends, see
+    /// `WorkerHandlerParameters::batch_tombstone_grace_period_ms`.
+    pub batch_tombstone_store: DBMap<BatchDigest, TimestampMs>,
     pub consensus_store: Arc<ConsensusStore>,
 }
 
@@ -45,6 +51,8 @@ impl NodeStorage {
     pub(crate) const CERTIFICATE_DIGEST_BY_ORIGIN_CF: &'static str = "certificate_digest_by_origin";
     pub(crate) const PAYLOAD_CF: &'static str = "payload";
     pub(crate) const BATCHES_CF: &'static str = "batches";
+    pub(crate) const PARTITIONED_BATCHES_CF: &'static str = "partitioned_batches";
+    pub(crate) const BATCH_TOMBSTONES_CF: &'static str = "batch_tombstones";
     pub(crate) const LAST_COMMITTED_CF: &'static str = "last_committed";
     pub(crate) const SUB_DAG_INDEX_CF: &'static str = "sub_dag";
     pub(crate) const COMMITTED_SUB_DAG_INDEX_CF: &'static str = "committed_sub_dag";
@@ -90,6 +98,14 @@ impl NodeStorage {
                     .optimize_for_large_values_no_scan(1 << 10)
                     .options,
             ),
+            (
+                Self::PARTITIONED_BATCHES_CF,
+                default_db_options()
+                    .optimize_for_write_throughput()
+                    .optimize_for_large_values_no_scan(1 << 10)
+                    .options,
+            ),
+            (Self::BATCH_TOMBSTONES_CF, cf_options.clone()),
             (Self::LAST_COMMITTED_CF, cf_options.clone()),
             (Self::SUB_DAG_INDEX_CF, cf_options.clone()),
             (Self::COMMITTED_SUB_DAG_INDEX_CF, cf_options),
@@ -111,6 +127,8 @@ impl NodeStorage {
             certificate_digest_by_origin_map,
             payload_map,
             batch_map,
+            partitioned_batch_map,
+            batch_tombstone_map,
             last_committed_map,
             sub_dag_index_map,
             committed_sub_dag_map,
@@ -123,6 +141,8 @@ impl NodeStorage {
             Self::CERTIFICATE_DIGEST_BY_ORIGIN_CF;<(AuthorityIdentifier, Round), CertificateDigest>,
             Self::PAYLOAD_CF;<(BatchDigest, WorkerId), PayloadToken>,
             Self::BATCHES_CF;<BatchDigest, Batch>,
+            Self::PARTITIONED_BATCHES_CF;<(Epoch, AuthorityIdentifier, BatchDigest), Batch>,
+            Self::BATCH_TOMBSTONES_CF;<BatchDigest, TimestampMs>,
             Self::LAST_COMMITTED_CF;<AuthorityIdentifier, Round>,
             Self::SUB_DAG_INDEX_CF;<SequenceNumber, CommittedSubDagShell>,
             Self::COMMITTED_SUB_DAG_INDEX_CF;<SequenceNumber, ConsensusCommit>
@@ -144,6 +164,8 @@ impl NodeStorage {
         );
         let payload_store = PayloadStore::new(payload_map);
         let batch_store = batch_map;
+        let partitioned_batch_store = partitioned_batch_map;
+        let batch_tombstone_store = batch_tombstone_map;
         let consensus_store = Arc::new(ConsensusStore::new(
             last_committed_map,
             sub_dag_index_map,
@@ -157,6 +179,8 @@ impl NodeStorage {
             certificate_store,
             payload_store,
             batch_store,
+            partitioned_batch_store,
+            batch_tombstone_store,
             consensus_store,
         }
     }
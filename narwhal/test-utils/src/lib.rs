@@ -35,15 +35,22 @@ use store::rocks::ReadWriteOptions;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tracing::info;
 use types::{
-    Batch, BatchDigest, Certificate, CertificateAPI, CertificateDigest, FetchBatchesRequest,
-    FetchBatchesResponse, FetchCertificatesRequest, FetchCertificatesResponse,
-    GetCertificatesRequest, GetCertificatesResponse, Header, HeaderAPI, HeaderV1Builder,
-    PayloadAvailabilityRequest, PayloadAvailabilityResponse, PrimaryToPrimary,
-    PrimaryToPrimaryServer, PrimaryToWorker, PrimaryToWorkerServer, RequestBatchRequest,
-    RequestBatchResponse, RequestBatchesRequest, RequestBatchesResponse, RequestVoteRequest,
-    RequestVoteResponse, Round, SendCertificateRequest, SendCertificateResponse, TimestampMs,
-    Transaction, Vote, VoteAPI, WorkerBatchMessage, WorkerDeleteBatchesMessage,
-    WorkerSynchronizeMessage, WorkerToWorker, WorkerToWorkerServer,
+    Batch, BatchDigest, BatchSizesRequest, BatchSizesResponse, Certificate, CertificateAPI,
+    CertificateDigest, FetchBatchesRequest, FetchBatchesResponse, FetchBatchesV2Response,
+    FetchCertificatesRequest, FetchCertificatesResponse, GetCertificatesRequest,
+    GetCertificatesResponse, Header, HeaderAPI, HeaderV1Builder, IntersectBatchesRequest,
+    IntersectBatchesResponse, LocateTransactionsRequest, LocateTransactionsResponse,
+    OpenBulkSyncRequest, OpenBulkSyncResponse, PayloadAvailabilityRequest,
+    PayloadAvailabilityResponse, PrimaryToPrimary, PrimaryToPrimaryServer, PrimaryToWorker,
+    PrimaryToWorkerServer, ReportBatchesResponse, RequestBatchMetadataRequest,
+    RequestBatchMetadataResponse, RequestBatchRequest, RequestBatchResponse, RequestBatchV2Request,
+    RequestBatchV2Response, RequestBatchesRequest, RequestBatchesResponse, RequestBatchesV2Request,
+    RequestBatchesV2Response, RequestBulkSyncPageRequest, RequestBulkSyncPageResponse,
+    RequestVoteRequest, RequestVoteResponse, Round, SampleBatchesRequest, SampleBatchesResponse,
+    SendCertificateRequest, SendCertificateResponse, StoreVersionRequest, StoreVersionResponse,
+    TimestampMs, Transaction, Vote, VoteAPI, WorkerBatchMessage, WorkerBatchesMessage,
+    WorkerCapabilitiesRequest, WorkerCapabilitiesResponse, WorkerDeleteBatchesMessage,
+    WorkerSynchronizeMessage, WorkerSynchronizeV2Message, WorkerToWorker, WorkerToWorkerServer,
 };
 
 pub mod cluster;
@@ -288,6 +295,14 @@ impl PrimaryToWorker for PrimaryToWorkerMockServer {
         Ok(anemo::Response::new(()))
     }
 
+    async fn synchronize_v2(
+        &self,
+        _request: anemo::Request<WorkerSynchronizeV2Message>,
+    ) -> Result<anemo::Response<()>, anemo::rpc::Status> {
+        tracing::error!("Not implemented PrimaryToWorkerMockServer::synchronize_v2");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
+
     async fn fetch_batches(
         &self,
         _request: anemo::Request<FetchBatchesRequest>,
@@ -297,6 +312,18 @@ impl PrimaryToWorker for PrimaryToWorkerMockServer {
         }))
     }
 
+    async fn fetch_batches_v2(
+        &self,
+        _request: anemo::Request<FetchBatchesRequest>,
+    ) -> Result<anemo::Response<FetchBatchesV2Response>, anemo::rpc::Status> {
+        Ok(anemo::Response::new(FetchBatchesV2Response {
+            batches: HashMap::new(),
+            is_size_limit_reached: false,
+            omitted_digests: Vec::new(),
+            batch_suppliers: None,
+        }))
+    }
+
     async fn delete_batches(
         &self,
         _request: anemo::Request<WorkerDeleteBatchesMessage>,
@@ -342,6 +369,14 @@ impl WorkerToWorker for WorkerToWorkerMockServer {
 
         Ok(anemo::Response::new(()))
     }
+    async fn report_batches(
+        &self,
+        _request: anemo::Request<WorkerBatchesMessage>,
+    ) -> Result<anemo::Response<ReportBatchesResponse>, anemo::rpc::Status> {
+        tracing::error!("Not implemented WorkerToWorkerMockServer::report_batches");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
+
     async fn request_batch(
         &self,
         _request: anemo::Request<RequestBatchRequest>,
@@ -350,6 +385,14 @@ impl WorkerToWorker for WorkerToWorkerMockServer {
         Err(anemo::rpc::Status::internal("Unimplemented"))
     }
 
+    async fn request_batch_v2(
+        &self,
+        _request: anemo::Request<RequestBatchV2Request>,
+    ) -> Result<anemo::Response<RequestBatchV2Response>, anemo::rpc::Status> {
+        tracing::error!("Not implemented WorkerToWorkerMockServer::request_batch_v2");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
+
     async fn request_batches(
         &self,
         _request: anemo::Request<RequestBatchesRequest>,
@@ -357,6 +400,86 @@ impl WorkerToWorker for WorkerToWorkerMockServer {
         tracing::error!("Not implemented WorkerToWorkerMockServer::request_batches");
         Err(anemo::rpc::Status::internal("Unimplemented"))
     }
+
+    async fn request_batches_v2(
+        &self,
+        _request: anemo::Request<RequestBatchesV2Request>,
+    ) -> Result<anemo::Response<RequestBatchesV2Response>, anemo::rpc::Status> {
+        tracing::error!("Not implemented WorkerToWorkerMockServer::request_batches_v2");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
+
+    async fn intersect_batches(
+        &self,
+        _request: anemo::Request<IntersectBatchesRequest>,
+    ) -> Result<anemo::Response<IntersectBatchesResponse>, anemo::rpc::Status> {
+        tracing::error!("Not implemented WorkerToWorkerMockServer::intersect_batches");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
+
+    async fn batch_sizes(
+        &self,
+        _request: anemo::Request<BatchSizesRequest>,
+    ) -> Result<anemo::Response<BatchSizesResponse>, anemo::rpc::Status> {
+        tracing::error!("Not implemented WorkerToWorkerMockServer::batch_sizes");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
+
+    async fn request_batch_metadata(
+        &self,
+        _request: anemo::Request<RequestBatchMetadataRequest>,
+    ) -> Result<anemo::Response<RequestBatchMetadataResponse>, anemo::rpc::Status> {
+        tracing::error!("Not implemented WorkerToWorkerMockServer::request_batch_metadata");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
+
+    async fn locate_transactions(
+        &self,
+        _request: anemo::Request<LocateTransactionsRequest>,
+    ) -> Result<anemo::Response<LocateTransactionsResponse>, anemo::rpc::Status> {
+        tracing::error!("Not implemented WorkerToWorkerMockServer::locate_transactions");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
+
+    async fn open_bulk_sync(
+        &self,
+        _request: anemo::Request<OpenBulkSyncRequest>,
+    ) -> Result<anemo::Response<OpenBulkSyncResponse>, anemo::rpc::Status> {
+        tracing::error!("Not implemented WorkerToWorkerMockServer::open_bulk_sync");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
+
+    async fn request_bulk_sync_page(
+        &self,
+        _request: anemo::Request<RequestBulkSyncPageRequest>,
+    ) -> Result<anemo::Response<RequestBulkSyncPageResponse>, anemo::rpc::Status> {
+        tracing::error!("Not implemented WorkerToWorkerMockServer::request_bulk_sync_page");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
+
+    async fn sample_batches(
+        &self,
+        _request: anemo::Request<SampleBatchesRequest>,
+    ) -> Result<anemo::Response<SampleBatchesResponse>, anemo::rpc::Status> {
+        tracing::error!("Not implemented WorkerToWorkerMockServer::sample_batches");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
+
+    async fn store_version(
+        &self,
+        _request: anemo::Request<StoreVersionRequest>,
+    ) -> Result<anemo::Response<StoreVersionResponse>, anemo::rpc::Status> {
+        tracing::error!("Not implemented WorkerToWorkerMockServer::store_version");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
+
+    async fn capabilities(
+        &self,
+        _request: anemo::Request<WorkerCapabilitiesRequest>,
+    ) -> Result<anemo::Response<WorkerCapabilitiesResponse>, anemo::rpc::Status> {
+        tracing::error!("Not implemented WorkerToWorkerMockServer::capabilities");
+        Err(anemo::rpc::Status::internal("Unimplemented"))
+    }
 }
 
 ////////////////////////////////////////////////////////////////
@@ -403,6 +526,34 @@ pub fn create_batch_store() -> DBMap<BatchDigest, Batch> {
     .unwrap()
 }
 
+const PARTITIONED_BATCHES_CF: &str = "partitioned_batches";
+
+/// Creates a batch store partitioned by epoch and authority, see `worker::PartitionedBatchStore`.
+pub fn create_partitioned_batch_store() -> DBMap<(Epoch, AuthorityIdentifier, BatchDigest), Batch> {
+    DBMap::<(Epoch, AuthorityIdentifier, BatchDigest), Batch>::open(
+        temp_dir(),
+        MetricConf::default(),
+        None,
+        Some(PARTITIONED_BATCHES_CF),
+        &ReadWriteOptions::default(),
+    )
+    .unwrap()
+}
+
+const BATCH_TOMBSTONES_CF: &str = "batch_tombstones";
+
+/// Creates a store persisting batch tombstones, see `worker::BatchTombstones`.
+pub fn create_batch_tombstone_store() -> DBMap<BatchDigest, TimestampMs> {
+    DBMap::<BatchDigest, TimestampMs>::open(
+        temp_dir(),
+        MetricConf::default(),
+        None,
+        Some(BATCH_TOMBSTONES_CF),
+        &ReadWriteOptions::default(),
+    )
+    .unwrap()
+}
+
 // Creates one certificate per authority starting and finishing at the specified rounds (inclusive).
 // Outputs a VecDeque of certificates (the certificate with higher round is on the front) and a set
 // of digests to be used as parents for the certificates of the next round.
@@ -112,6 +112,15 @@ fn build_anemo_services(out_dir: &Path) {
                 .codec_path(codec_path)
                 .build(),
         )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("synchronize_v2")
+                .route_name("SynchronizeV2")
+                .request_type("crate::WorkerSynchronizeV2Message")
+                .response_type("()")
+                .codec_path(codec_path)
+                .build(),
+        )
         .method(
             anemo_build::manual::Method::builder()
                 .name("fetch_batches")
@@ -121,6 +130,15 @@ fn build_anemo_services(out_dir: &Path) {
                 .codec_path(codec_path)
                 .build(),
         )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("fetch_batches_v2")
+                .route_name("FetchBatchesV2")
+                .request_type("crate::FetchBatchesRequest")
+                .response_type("crate::FetchBatchesV2Response")
+                .codec_path(codec_path)
+                .build(),
+        )
         .method(
             anemo_build::manual::Method::builder()
                 .name("delete_batches")
@@ -169,6 +187,15 @@ fn build_anemo_services(out_dir: &Path) {
                 .codec_path(codec_path)
                 .build(),
         )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("report_batches")
+                .route_name("ReportBatches")
+                .request_type("crate::WorkerBatchesMessage")
+                .response_type("crate::ReportBatchesResponse")
+                .codec_path(codec_path)
+                .build(),
+        )
         .method(
             anemo_build::manual::Method::builder()
                 .name("request_batch")
@@ -178,6 +205,15 @@ fn build_anemo_services(out_dir: &Path) {
                 .codec_path(codec_path)
                 .build(),
         )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("request_batch_v2")
+                .route_name("RequestBatchV2")
+                .request_type("crate::RequestBatchV2Request")
+                .response_type("crate::RequestBatchV2Response")
+                .codec_path(codec_path)
+                .build(),
+        )
         .method(
             anemo_build::manual::Method::builder()
                 .name("request_batches")
@@ -187,6 +223,96 @@ fn build_anemo_services(out_dir: &Path) {
                 .codec_path(codec_path)
                 .build(),
         )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("request_batches_v2")
+                .route_name("RequestBatchesV2")
+                .request_type("crate::RequestBatchesV2Request")
+                .response_type("crate::RequestBatchesV2Response")
+                .codec_path(codec_path)
+                .build(),
+        )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("intersect_batches")
+                .route_name("IntersectBatches")
+                .request_type("crate::IntersectBatchesRequest")
+                .response_type("crate::IntersectBatchesResponse")
+                .codec_path(codec_path)
+                .build(),
+        )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("batch_sizes")
+                .route_name("BatchSizes")
+                .request_type("crate::BatchSizesRequest")
+                .response_type("crate::BatchSizesResponse")
+                .codec_path(codec_path)
+                .build(),
+        )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("request_batch_metadata")
+                .route_name("RequestBatchMetadata")
+                .request_type("crate::RequestBatchMetadataRequest")
+                .response_type("crate::RequestBatchMetadataResponse")
+                .codec_path(codec_path)
+                .build(),
+        )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("locate_transactions")
+                .route_name("LocateTransactions")
+                .request_type("crate::LocateTransactionsRequest")
+                .response_type("crate::LocateTransactionsResponse")
+                .codec_path(codec_path)
+                .build(),
+        )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("open_bulk_sync")
+                .route_name("OpenBulkSync")
+                .request_type("crate::OpenBulkSyncRequest")
+                .response_type("crate::OpenBulkSyncResponse")
+                .codec_path(codec_path)
+                .build(),
+        )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("request_bulk_sync_page")
+                .route_name("RequestBulkSyncPage")
+                .request_type("crate::RequestBulkSyncPageRequest")
+                .response_type("crate::RequestBulkSyncPageResponse")
+                .codec_path(codec_path)
+                .build(),
+        )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("sample_batches")
+                .route_name("SampleBatches")
+                .request_type("crate::SampleBatchesRequest")
+                .response_type("crate::SampleBatchesResponse")
+                .codec_path(codec_path)
+                .build(),
+        )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("store_version")
+                .route_name("StoreVersion")
+                .request_type("crate::StoreVersionRequest")
+                .response_type("crate::StoreVersionResponse")
+                .codec_path(codec_path)
+                .build(),
+        )
+        .method(
+            anemo_build::manual::Method::builder()
+                .name("capabilities")
+                .route_name("Capabilities")
+                .request_type("crate::WorkerCapabilitiesRequest")
+                .response_type("crate::WorkerCapabilitiesResponse")
+                .codec_path(codec_path)
+                .build(),
+        )
         .build();
 
     anemo_build::manual::Builder::new()
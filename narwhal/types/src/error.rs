@@ -2,7 +2,7 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 use crate::{CertificateDigest, HeaderDigest, Round, TimestampMs, VoteDigest};
-use anemo::PeerId;
+use anemo::{types::response::StatusCode, PeerId};
 use config::Epoch;
 use fastcrypto::hash::Digest;
 use mysten_common::sync::notify_once::NotifyOnce;
@@ -32,6 +32,10 @@ macro_rules! ensure {
 
 pub type DagResult<T> = Result<T, DagError>;
 
+/// The status of a call to a method its handler does not serve, e.g. one disabled by
+/// configuration. anemo's `NotImplemented` is its counterpart of gRPC's `Unimplemented`.
+pub const UNIMPLEMENTED: StatusCode = StatusCode::NotImplemented;
+
 // Notification for certificate accepted.
 pub type AcceptNotification = Arc<NotifyOnce>;
 
@@ -160,6 +164,10 @@ pub enum LocalClientError {
     #[error("Handler encountered internal error {0}.")]
     Internal(String),
 
+    // The handler refused the request itself, e.g. as malformed, so retrying fails again.
+    #[error("Handler rejected the request: {0}.")]
+    Rejected(String),
+
     #[error("Narwhal is shutting down.")]
     ShuttingDown,
 }
@@ -26,7 +26,10 @@ use mysten_util_mem::MallocSizeOf;
 use once_cell::sync::OnceCell;
 use proptest_derive::Arbitrary;
 use roaring::RoaringBitmap;
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{self, EnumAccess, VariantAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
 use serde_with::serde_as;
 use std::{
     collections::{BTreeMap, BTreeSet, VecDeque},
@@ -85,12 +88,76 @@ impl Default for Metadata {
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, Arbitrary)]
+#[derive(Clone, Serialize, Debug, PartialEq, Eq, Arbitrary)]
 #[enum_dispatch(BatchAPI)]
 pub enum Batch {
     V1(BatchV1),
 }
 
+/// The variants of `Batch`, each being a version of the batch format.
+const BATCH_VERSIONS: &[&str] = &["V1"];
+
+/// Batches of a newer format version fail to decode with a distinct error, so that peers tell
+/// a version mismatch during a rolling upgrade apart from malformed data.
+impl<'de> Deserialize<'de> for Batch {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_enum("Batch", BATCH_VERSIONS, BatchVisitor)
+    }
+}
+
+struct BatchVisitor;
+
+impl<'de> Visitor<'de> for BatchVisitor {
+    type Value = Batch;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("enum Batch")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Batch, A::Error> {
+        let (BatchVariant(index), variant) = data.variant()?;
+        match index {
+            0 => variant.newtype_variant().map(Batch::V1),
+            _ => Err(de::Error::custom(format!(
+                "Unsupported batch version {}, this node supports up to version {}",
+                index + 1,
+                BATCH_VERSIONS.len()
+            ))),
+        }
+    }
+}
+
+/// The index of a `Batch` variant, decoded from either its index or its name.
+struct BatchVariant(u64);
+
+impl<'de> Deserialize<'de> for BatchVariant {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BatchVariantVisitor;
+
+        impl<'de> Visitor<'de> for BatchVariantVisitor {
+            type Value = BatchVariant;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("variant identifier")
+            }
+
+            fn visit_u64<E: de::Error>(self, index: u64) -> Result<BatchVariant, E> {
+                Ok(BatchVariant(index))
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<BatchVariant, E> {
+                BATCH_VERSIONS
+                    .iter()
+                    .position(|version| *version == name)
+                    .map(|index| BatchVariant(index as u64))
+                    .ok_or_else(|| de::Error::unknown_variant(name, BATCH_VERSIONS))
+            }
+        }
+
+        deserializer.deserialize_identifier(BatchVariantVisitor)
+    }
+}
+
 // TODO: Revisit if we should not impl Default for batch
 impl Default for Batch {
     fn default() -> Self {
@@ -108,6 +175,19 @@ impl Batch {
             Batch::V1(data) => data.size(),
         }
     }
+
+    /// Returns the digest and the size of the batch, computed in a single pass.
+    pub fn digest_and_size(&self) -> (BatchDigest, usize) {
+        match self {
+            Batch::V1(data) => data.digest_and_size(),
+        }
+    }
+
+    /// Returns the size of the batch once serialized, which unlike `size()` includes the
+    /// length prefixes of its transactions and its metadata.
+    pub fn serialized_size(&self) -> usize {
+        bcs::serialized_size(self).expect("Serialization should not fail")
+    }
 }
 
 impl Hash<{ crypto::DIGEST_LENGTH }> for Batch {
@@ -164,6 +244,19 @@ impl BatchV1 {
     pub fn size(&self) -> usize {
         self.transactions.iter().map(|t| t.len()).sum()
     }
+
+    /// Same as `digest()` and `size()`, but the transactions are read only once, and fed to
+    /// the hasher one at a time rather than copied, so that the memory used does not grow
+    /// with the size of the batch.
+    pub fn digest_and_size(&self) -> (BatchDigest, usize) {
+        let mut hasher = crypto::DefaultHashFunction::new();
+        let mut size = 0;
+        for transaction in &self.transactions {
+            hasher.update(transaction);
+            size += transaction.len();
+        }
+        (BatchDigest::new(hasher.finalize().into()), size)
+    }
 }
 
 #[derive(
@@ -1290,6 +1383,35 @@ pub struct WorkerSynchronizeMessage {
     pub is_certified: bool,
 }
 
+/// Used by the primary to request that the worker sync the target missing batches, with the
+/// proof that they are certified or from a given worker of the target.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorkerSynchronizeV2Message {
+    pub digests: Vec<BatchDigest>,
+    pub target: AuthorityIdentifier,
+    // See `WorkerSynchronizeMessage::is_certified`.
+    pub is_certified: bool,
+    // The certificate including the batches, if they are certified and the primary is
+    // configured to attach it, so that the worker can verify that they are rather than trust
+    // the primary.
+    pub certificate: Option<Certificate>,
+    // The id of the target's worker holding the batches. If unset, the worker syncs from the
+    // target's worker with its own id.
+    pub target_worker_id: Option<WorkerId>,
+}
+
+impl From<WorkerSynchronizeMessage> for WorkerSynchronizeV2Message {
+    fn from(message: WorkerSynchronizeMessage) -> Self {
+        Self {
+            digests: message.digests,
+            target: message.target,
+            is_certified: message.is_certified,
+            certificate: None,
+            target_worker_id: None,
+        }
+    }
+}
+
 /// Used by the primary to request that the worker fetch the missing batches and reply
 /// with all of the content.
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -1304,6 +1426,19 @@ pub struct FetchBatchesResponse {
     pub batches: HashMap<BatchDigest, Batch>,
 }
 
+/// The batches requested by the primary with fetch_batches_v2, capped in size.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FetchBatchesV2Response {
+    pub batches: HashMap<BatchDigest, Batch>,
+    // If true, the response was capped and the digests in `omitted_digests` should be
+    // requested again.
+    pub is_size_limit_reached: bool,
+    pub omitted_digests: Vec<BatchDigest>,
+    // If the worker attributes fetched batches, the worker that supplied each batch of
+    // `batches` it fetched remotely. Batches found in its own store are not listed.
+    pub batch_suppliers: Option<HashMap<BatchDigest, NetworkPublicKey>>,
+}
+
 /// Used by the primary to request that the worker delete the specified batches.
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct WorkerDeleteBatchesMessage {
@@ -1433,6 +1568,7 @@ impl From<&Vote> for VoteInfo {
 #[cfg(test)]
 mod tests {
     use crate::{Batch, BatchAPI, BatchV1, Metadata, Timestamp};
+    use fastcrypto::hash::Hash;
     use std::time::Duration;
     use tokio::time::sleep;
 
@@ -1457,4 +1593,32 @@ mod tests {
 
         assert_eq!(batch.metadata().created_at.elapsed().as_secs_f64(), 0.0);
     }
+
+    #[test]
+    fn test_digest_and_size_of_large_batch() {
+        // 32 MiB of transactions.
+        let transactions = (0..512u32)
+            .map(|i| vec![i as u8; 64 * 1024])
+            .collect::<Vec<_>>();
+        let batch = Batch::new(transactions);
+
+        let (digest, size) = batch.digest_and_size();
+        assert_eq!(digest, batch.digest());
+        assert_eq!(size, batch.size());
+        assert_eq!(size, 32 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_decode_batch_of_unsupported_version() {
+        let batch = Batch::new(vec![vec![1; 5]]);
+        let mut bytes = bcs::to_bytes(&batch).unwrap();
+        assert_eq!(bcs::from_bytes::<Batch>(&bytes).unwrap(), batch);
+
+        // The variant index of a batch of the next format version.
+        bytes[0] = 1;
+        let error = bcs::from_bytes::<Batch>(&bytes).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Unsupported batch version 2, this node supports up to version 1"));
+    }
 }
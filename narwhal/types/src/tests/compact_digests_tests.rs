@@ -0,0 +1,35 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use fastcrypto::hash::Hash;
+
+use crate::{Batch, BatchDigest, CompactBatchDigests, RequestBatchesV2Request};
+
+#[test]
+fn compact_digests_round_trip() {
+    let digests: Vec<BatchDigest> = (0..10_000u32)
+        .map(|i| Batch::new(vec![i.to_le_bytes().to_vec()]).digest())
+        .collect();
+
+    let compact = CompactBatchDigests::encode(&digests);
+    let mut expected = digests.clone();
+    expected.sort();
+    assert_eq!(compact.decode().unwrap(), expected);
+    // Sorted digests share their first byte or so with their predecessor.
+    assert!(compact.encoded_len() < digests.len() * 32);
+
+    // Large requests are compacted, and decode to the same set of digests.
+    let request = RequestBatchesV2Request::new(digests.clone());
+    assert!(request.batch_digests.is_empty());
+    assert_eq!(request.digests().unwrap(), expected);
+
+    // Small requests keep the plain form and order.
+    let request = RequestBatchesV2Request::new(digests[..10].to_vec());
+    assert!(request.compact_batch_digests.is_none());
+    assert_eq!(request.digests().unwrap(), digests[..10].to_vec());
+
+    // Truncated encodings are rejected.
+    let mut truncated = compact;
+    truncated.0.pop();
+    assert!(truncated.decode().is_err());
+}
@@ -1,21 +1,41 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{Batch, BatchDigest};
+use crate::{Batch, BatchAPI, BatchDigest, Certificate, CertificateAPI, HeaderAPI, TimestampMs};
 
+use config::{Committee, WorkerCache};
+use fastcrypto::hash::HashFunction;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
 use thiserror::Error;
 
 #[cfg(test)]
 #[path = "tests/batch_serde.rs"]
 mod batch_serde;
 
+#[cfg(test)]
+#[path = "tests/compact_digests_tests.rs"]
+mod compact_digests_tests;
+
 /// Used by workers to send a new batch.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct WorkerBatchMessage {
     pub batch: Batch,
 }
 
+/// Used by workers to send several batches at once.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WorkerBatchesMessage {
+    pub batches: Vec<Batch>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ReportBatchesResponse {
+    // The reason each batch was not accepted, or None if it was, in the same order as the
+    // batches of the request.
+    pub errors: Vec<Option<String>>,
+}
+
 /// Used by primary to ask worker for the request.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct RequestBatchRequest {
@@ -27,12 +47,252 @@ pub struct RequestBatchResponse {
     pub batch: Option<Batch>,
 }
 
+/// Used to ask workers serving request_batch_v2, i.e. of protocol version 2 or later, for a
+/// batch, a part of it, or whether they store it.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RequestBatchV2Request {
+    pub batch: BatchDigest,
+    // Ask for a certificate including the batch, see `RequestBatchV2Response::certificate`.
+    pub include_certificate: bool,
+    // Ask for a range of the serialized batch only, see `RequestBatchV2Response::slice`.
+    pub range: Option<ByteRange>,
+    // The caller already holds the batch, and only asks the worker to confirm that it stores
+    // it too, see `RequestBatchV2Response::not_modified`. Batches are content addressed, so
+    // the caller's batch is necessarily current.
+    pub caller_has_batch: bool,
+}
+
+impl From<RequestBatchRequest> for RequestBatchV2Request {
+    fn from(request: RequestBatchRequest) -> Self {
+        Self {
+            batch: request.batch,
+            include_certificate: false,
+            range: None,
+            caller_has_batch: false,
+        }
+    }
+}
+
+/// A range of bytes, clamped to the data it is applied to.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ByteRange {
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// A range of the serialized form of a batch, so that transfers of large batches over flaky
+/// links can resume where they stopped.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BatchSlice {
+    // The offset of `bytes` in the serialized batch.
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+    // The length of the whole serialized batch, to tell which ranges are left.
+    pub total_len: u64,
+}
+
+impl BatchSlice {
+    /// Slices the serialized form of `batch`.
+    pub fn new(batch: &Batch, range: ByteRange) -> Self {
+        let serialized = bcs::to_bytes(batch).expect("Serialization should not fail");
+        let start = range.offset.min(serialized.len() as u64) as usize;
+        let end = start + range.len.min((serialized.len() - start) as u64) as usize;
+        Self {
+            offset: start as u64,
+            bytes: serialized[start..end].to_vec(),
+            total_len: serialized.len() as u64,
+        }
+    }
+
+    /// Reassembles a batch from slices covering it in order, or returns None if they do not.
+    pub fn reassemble(slices: &[BatchSlice]) -> Option<Batch> {
+        let mut serialized = Vec::new();
+        for slice in slices {
+            if slice.offset != serialized.len() as u64 {
+                return None;
+            }
+            serialized.extend_from_slice(&slice.bytes);
+        }
+        if slices.last()?.total_len != serialized.len() as u64 {
+            return None;
+        }
+        bcs::from_bytes(&serialized).ok()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RequestBatchV2Response {
+    pub batch: Option<Batch>,
+    // If asked for and known to the worker, a certificate whose header includes the batch, so
+    // that light clients can check the batch is certified without fetching the header's other
+    // batches. Only served to the peers the worker designates as light clients.
+    pub certificate: Option<Certificate>,
+    // If a range was asked for and the batch is found, that range of the serialized batch,
+    // in place of `batch`.
+    pub slice: Option<BatchSlice>,
+    // If the caller has the batch, whether the worker stores it too. Neither the batch nor
+    // any of the above is then sent.
+    pub not_modified: bool,
+}
+
+impl RequestBatchV2Response {
+    /// Returns whether `certificate` proves that the batch with the given digest is certified:
+    /// the certificate must be valid for `committee` and its header must include the digest.
+    pub fn verify_certificate(
+        &self,
+        digest: &BatchDigest,
+        committee: &Committee,
+        worker_cache: &WorkerCache,
+    ) -> bool {
+        let Some(certificate) = &self.certificate else {
+            return false;
+        };
+        certificate.header().payload().contains_key(digest)
+            && certificate.verify(committee, worker_cache).is_ok()
+    }
+}
+
+impl From<RequestBatchV2Response> for RequestBatchResponse {
+    fn from(response: RequestBatchV2Response) -> Self {
+        Self {
+            batch: response.batch,
+        }
+    }
+}
+
 /// Used by primary to bulk request batches from workers local store.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct RequestBatchesRequest {
     pub batch_digests: Vec<BatchDigest>,
 }
 
+/// Used by workers to bulk request batches from workers serving request_batches_v2, i.e. of
+/// protocol version 2 or later, see `WorkerCapabilitiesResponse::protocol_version`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RequestBatchesV2Request {
+    pub batch_digests: Vec<BatchDigest>,
+    // More digests to request, in compact form. Large requests are smaller this way.
+    pub compact_batch_digests: Option<CompactBatchDigests>,
+    // Return the batches keyed by digest, see `RequestBatchesV2Response::batches_by_digest`.
+    pub keyed_by_digest: bool,
+    // Requested digests the caller learned it already has after building the request, e.g.
+    // fetched by a concurrent sync. They are skipped. At most `MAX_ALREADY_HAVE_DIGESTS`.
+    pub already_have: Vec<BatchDigest>,
+}
+
+impl RequestBatchesV2Request {
+    /// Requests of at least this many digests are sent in compact form.
+    pub const COMPACT_DIGESTS_THRESHOLD: usize = 256;
+    /// The most digests a request can mark as already held by the caller.
+    pub const MAX_ALREADY_HAVE_DIGESTS: usize = 1_024;
+
+    /// Requests the given batches, in compact form if there are enough of them.
+    pub fn new(batch_digests: Vec<BatchDigest>) -> Self {
+        if batch_digests.len() < Self::COMPACT_DIGESTS_THRESHOLD {
+            return Self::from(RequestBatchesRequest { batch_digests });
+        }
+        Self {
+            batch_digests: Vec::new(),
+            compact_batch_digests: Some(CompactBatchDigests::encode(&batch_digests)),
+            keyed_by_digest: false,
+            already_have: Vec::new(),
+        }
+    }
+
+    /// Asks for the batches keyed by digest rather than as a list.
+    pub fn keyed_by_digest(mut self) -> Self {
+        self.keyed_by_digest = true;
+        self
+    }
+
+    /// Skips the given digests, which the caller already has.
+    pub fn already_have(mut self, digests: impl IntoIterator<Item = BatchDigest>) -> Self {
+        self.already_have.extend(digests);
+        self
+    }
+
+    /// Returns every requested digest. Note that compact digests are sorted, so the order of
+    /// the digests may differ from the one they were requested in.
+    pub fn digests(self) -> Result<Vec<BatchDigest>, DigestError> {
+        let mut digests = self.batch_digests;
+        if let Some(compact_batch_digests) = &self.compact_batch_digests {
+            digests.extend(compact_batch_digests.decode()?);
+        }
+        Ok(digests)
+    }
+}
+
+impl From<RequestBatchesRequest> for RequestBatchesV2Request {
+    fn from(request: RequestBatchesRequest) -> Self {
+        Self {
+            batch_digests: request.batch_digests,
+            compact_batch_digests: None,
+            keyed_by_digest: false,
+            already_have: Vec::new(),
+        }
+    }
+}
+
+/// A set of batch digests, prefix-compressed: the digests are sorted, and each one is encoded
+/// as the length of the prefix it shares with the previous digest, followed by the remaining
+/// bytes. Since digests are uniformly distributed, the savings grow with the number of
+/// digests, by about one byte per digest every 256-fold increase.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CompactBatchDigests(Vec<u8>);
+
+impl CompactBatchDigests {
+    pub fn encode(digests: &[BatchDigest]) -> Self {
+        let mut digests = digests.to_vec();
+        digests.sort();
+        digests.dedup();
+        let mut bytes = Vec::new();
+        let mut previous: Option<&BatchDigest> = None;
+        for digest in &digests {
+            let shared = previous.map_or(0, |previous| {
+                previous
+                    .0
+                    .iter()
+                    .zip(digest.0.iter())
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            });
+            bytes.push(shared as u8);
+            bytes.extend_from_slice(&digest.0[shared..]);
+            previous = Some(digest);
+        }
+        Self(bytes)
+    }
+
+    pub fn decode(&self) -> Result<Vec<BatchDigest>, DigestError> {
+        let mut digests = Vec::new();
+        let mut previous: Option<BatchDigest> = None;
+        let mut position = 0;
+        while position < self.0.len() {
+            let shared = self.0[position] as usize;
+            if shared >= crypto::DIGEST_LENGTH || (shared > 0 && previous.is_none()) {
+                return Err(DigestError::InvalidArgumentError(position));
+            }
+            position += 1;
+            let suffix_length = crypto::DIGEST_LENGTH - shared;
+            let suffix = self
+                .0
+                .get(position..position + suffix_length)
+                .ok_or(DigestError::InvalidLengthError)?;
+            let mut digest = previous.unwrap_or(BatchDigest::new([0; crypto::DIGEST_LENGTH]));
+            digest.0[shared..].copy_from_slice(suffix);
+            position += suffix_length;
+            digests.push(digest);
+            previous = Some(digest);
+        }
+        Ok(digests)
+    }
+
+    /// The size of the encoded digests in bytes.
+    pub fn encoded_len(&self) -> usize {
+        self.0.len()
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct RequestBatchesResponse {
     pub batches: Vec<Batch>,
@@ -41,6 +301,219 @@ pub struct RequestBatchesResponse {
     pub is_size_limit_reached: bool,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RequestBatchesV2Response {
+    pub batches: Vec<Batch>,
+    // If true, the requester should request the batches it is missing again.
+    // This may not be something that can be trusted from a remote worker.
+    pub is_size_limit_reached: bool,
+    // If the serving worker annotates batch ages, the time in milliseconds since it stored each
+    // batch in `batches`, in the same order. If keyed by digest, in the order the batches of
+    // `batches_by_digest` were requested.
+    pub batch_ages_ms: Option<Vec<u64>>,
+    // The requested digests that were not looked up before the request's deadline, in the
+    // order they were requested. The requester should request them again.
+    pub deferred_digests: Vec<BatchDigest>,
+    // If the request is keyed by digest, the batches keyed by their requested digest, in
+    // which case `batches` is empty. Spares the requester from recomputing digests, at the
+    // cost of a larger response.
+    pub batches_by_digest: Option<HashMap<BatchDigest, Batch>>,
+}
+
+impl From<RequestBatchesV2Response> for RequestBatchesResponse {
+    /// Deferred digests are reported as a size limit being reached, so that legacy requesters
+    /// request them again.
+    fn from(response: RequestBatchesV2Response) -> Self {
+        let batches = match response.batches_by_digest {
+            Some(batches_by_digest) => batches_by_digest.into_values().collect(),
+            None => response.batches,
+        };
+        Self {
+            batches,
+            is_size_limit_reached: response.is_size_limit_reached
+                || !response.deferred_digests.is_empty(),
+        }
+    }
+}
+
+/// Used by a worker reconciling its store with a peer, to learn which of the given batches
+/// the peer holds without transferring them.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IntersectBatchesRequest {
+    pub batch_digests: Vec<BatchDigest>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IntersectBatchesResponse {
+    // The requested digests held by the peer, sorted.
+    pub batch_digests: Vec<BatchDigest>,
+}
+
+/// Used by workers to find which batches a peer holds and how large they are, so that the
+/// following `request_batches` calls can be packed close to the response size cap.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BatchSizesRequest {
+    pub batch_digests: Vec<BatchDigest>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BatchSizesResponse {
+    // The size of each requested batch, or None if the peer does not hold it, in the same
+    // order as the digests of the request.
+    pub batch_sizes: Vec<Option<usize>>,
+    // The maximum total size of the batches of a `request_batches` response.
+    pub max_request_batches_response_size: usize,
+}
+
+/// What a worker serves of a stored batch in place of its transactions, see
+/// `RequestBatchMetadataRequest`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BatchSummary {
+    pub transaction_count: usize,
+    // The total size of the transactions, see `Batch::size`.
+    pub size: usize,
+    pub created_at: TimestampMs,
+}
+
+impl BatchSummary {
+    pub fn new(batch: &Batch) -> Self {
+        Self {
+            transaction_count: batch.transactions().len(),
+            size: batch.size(),
+            created_at: batch.metadata().created_at,
+        }
+    }
+}
+
+/// Used to learn about batches, e.g. for monitoring or to plan fetches, without transferring
+/// their transactions.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RequestBatchMetadataRequest {
+    pub batch_digests: Vec<BatchDigest>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RequestBatchMetadataResponse {
+    // The summary of each requested batch, or None if the peer does not hold it, in the same
+    // order as the digests of the request.
+    pub batch_summaries: Vec<Option<BatchSummary>>,
+}
+
+/// The digest of a transaction, under which workers indexing transactions find the batch
+/// holding it.
+pub type TransactionDigest = [u8; crypto::DIGEST_LENGTH];
+
+/// Returns the digest of a transaction, see `TransactionDigest`.
+pub fn transaction_digest(transaction: &[u8]) -> TransactionDigest {
+    crypto::DefaultHashFunction::digest(transaction).digest
+}
+
+/// Used to find the batches holding the given transactions, on workers indexing them.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LocateTransactionsRequest {
+    pub transaction_digests: Vec<TransactionDigest>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LocateTransactionsResponse {
+    // The digest of the stored batch holding each requested transaction, or None if the
+    // transaction is not indexed, in the same order as the digests of the request.
+    pub batch_digests: Vec<Option<BatchDigest>>,
+}
+
+/// Used by a worker that is far behind to open a resumable bulk sync session with a peer.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OpenBulkSyncRequest {}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OpenBulkSyncResponse {
+    pub session_id: u64,
+}
+
+/// Used to pull the next page of a bulk sync session.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RequestBulkSyncPageRequest {
+    pub session_id: u64,
+    // The cursor of the last page the requester has persisted. If None, the page is served
+    // from the progress recorded by the server for this session.
+    pub cursor: Option<BatchDigest>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RequestBulkSyncPageResponse {
+    pub batches: Vec<Batch>,
+    // The cursor to pass when requesting the next page.
+    pub next_cursor: Option<BatchDigest>,
+    // If true, the whole store has been transferred.
+    pub is_complete: bool,
+}
+
+/// Used to spot-check that a peer actually holds the batches it claims to: the response holds
+/// a sample of the digests stored by the server, which the requester can then fetch.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SampleBatchesRequest {
+    // The key to sample after. If None, sampling starts from a random point of the store, so
+    // that the server cannot predict which batches are sampled.
+    pub cursor: Option<BatchDigest>,
+    // Every `stride`-th stored batch is sampled. Zero is treated as one.
+    pub stride: u32,
+    // The maximum number of digests to return.
+    pub max_samples: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SampleBatchesResponse {
+    pub batch_digests: Vec<BatchDigest>,
+    // The cursor to pass to continue sampling, or None once the end of the store was reached.
+    pub next_cursor: Option<BatchDigest>,
+}
+
+/// Used by clients polling a worker to tell whether its batch store changed since their
+/// last query.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StoreVersionRequest {}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StoreVersionResponse {
+    // Increases whenever a batch is stored or removed.
+    pub version: u64,
+}
+
+/// Used by peers to discover the optional features a worker serves, so that they can fall
+/// back gracefully instead of failing with `UNIMPLEMENTED` or `BadRequest` at call time.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WorkerCapabilitiesRequest {}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WorkerCapabilitiesResponse {
+    // The version of the worker to worker protocol, see `WORKER_PROTOCOL_VERSION`. Features
+    // every worker of a version serves, e.g. byte ranges in request_batch_v2, are implied by
+    // it.
+    pub protocol_version: u32,
+    // The optional features enabled on this worker.
+    pub features: BTreeSet<WorkerFeature>,
+}
+
+/// The version of the worker to worker protocol served by this build. Version 2 adds
+/// request_batch_v2 and request_batches_v2.
+pub const WORKER_PROTOCOL_VERSION: u32 = 2;
+
+/// The optional features of a worker, which depend on its configuration.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WorkerFeature {
+    // request_batch_v2 attaches certificates to the batches, see
+    // `RequestBatchV2Request::include_certificate`.
+    BatchCertificates,
+    // request_batches_v2 responses carry the age of each batch.
+    BatchAges,
+    // locate_transactions is served.
+    LocateTransactions,
+    // store_version is served.
+    StoreVersion,
+    // Batches missing from the batch store are looked up in an archive store.
+    ArchiveReads,
+}
+
 // TODO: support propagating errors from the worker to the primary.
 pub type TxResponse = tokio::sync::oneshot::Sender<BatchDigest>;
 
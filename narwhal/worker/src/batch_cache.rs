@@ -0,0 +1,375 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use config::Epoch;
+use tokio::{sync::mpsc, task::JoinHandle, time::MissedTickBehavior};
+use tracing::debug;
+use types::{Batch, BatchDigest, TransactionDigest};
+
+use crate::batch_store::{BatchStore, StoreResult};
+
+#[cfg(test)]
+#[path = "tests/batch_cache_tests.rs"]
+pub mod batch_cache_tests;
+
+/// The sizes of the tiers of a `CachedBatchStore`, and how long batches stay pinned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchCacheConfig {
+    /// The number of batches cached by recency of use.
+    pub capacity: usize,
+    /// The number of pinned batches, which are not evicted by reads of other batches.
+    pub pinned_capacity: usize,
+    /// How long batches stay pinned before they are cached by recency of use.
+    pub pin_duration: Duration,
+    /// If set, batches cached by recency of use are evicted once unused for this long, so
+    /// that memory is reclaimed when traffic shifts or quiets down.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for BatchCacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10_000,
+            pinned_capacity: 1_000,
+            pin_duration: Duration::from_secs(30),
+            idle_timeout: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct CacheTiers {
+    // Batches inserted with `insert_referenced`, and when they are unpinned.
+    pinned: HashMap<BatchDigest, (Batch, Instant)>,
+    // Other batches, evicted least recently used first: each batch with its last use, and
+    // the batches by last use, with when they were last used.
+    recent: HashMap<BatchDigest, (Batch, u64)>,
+    recent_order: BTreeMap<u64, (BatchDigest, Instant)>,
+    next_use: u64,
+}
+
+impl CacheTiers {
+    fn get(&mut self, key: &BatchDigest) -> Option<Batch> {
+        if let Some((batch, _)) = self.pinned.get(key) {
+            return Some(batch.clone());
+        }
+        let batch = self.recent.get(key)?.0.clone();
+        self.cache(*key, batch.clone(), usize::MAX);
+        Some(batch)
+    }
+
+    /// Caches `batch` as the most recently used, evicting down to `capacity` batches.
+    fn cache(&mut self, key: BatchDigest, batch: Batch, capacity: usize) {
+        if let Some((pinned, _)) = self.pinned.get_mut(&key) {
+            // Keep pinned batches pinned, but up to date.
+            *pinned = batch;
+            return;
+        }
+        let used = self.next_use;
+        self.next_use += 1;
+        if let Some((_, previous_use)) = self.recent.insert(key, (batch, used)) {
+            self.recent_order.remove(&previous_use);
+        }
+        self.recent_order.insert(used, (key, Instant::now()));
+        while self.recent.len() > capacity {
+            let Some((_, (evicted, _))) = self.recent_order.pop_first() else {
+                break;
+            };
+            self.recent.remove(&evicted);
+        }
+    }
+
+    /// Evicts the batches cached by recency of use that were last used before `cutoff`.
+    fn evict_unused_since(&mut self, cutoff: Instant) {
+        while let Some(entry) = self.recent_order.first_entry() {
+            let (evicted, last_used) = *entry.get();
+            if last_used >= cutoff {
+                break;
+            }
+            entry.remove();
+            self.recent.remove(&evicted);
+        }
+    }
+
+    /// Pins `batch` until `until`, moving expired or excess pins to the recency tier.
+    fn pin(&mut self, key: BatchDigest, batch: Batch, until: Instant, config: &BatchCacheConfig) {
+        if let Some((_, used)) = self.recent.remove(&key) {
+            self.recent_order.remove(&used);
+        }
+        self.pinned.insert(key, (batch, until));
+        let now = Instant::now();
+        let mut unpinned: Vec<_> = self
+            .pinned
+            .iter()
+            .filter(|(_, (_, until))| *until <= now)
+            .map(|(key, _)| *key)
+            .collect();
+        if self.pinned.len() - unpinned.len() > config.pinned_capacity {
+            // Unpin the batches closest to expiring.
+            let mut pins: Vec<_> = self
+                .pinned
+                .iter()
+                .filter(|(_, (_, until))| *until > now)
+                .map(|(key, (_, until))| (*until, *key))
+                .collect();
+            pins.sort();
+            let excess = pins.len() - config.pinned_capacity;
+            unpinned.extend(pins.into_iter().take(excess).map(|(_, key)| key));
+        }
+        for key in unpinned {
+            if let Some((batch, _)) = self.pinned.remove(&key) {
+                self.cache(key, batch, config.capacity);
+            }
+        }
+    }
+
+    fn invalidate(&mut self, key: &BatchDigest) {
+        self.pinned.remove(key);
+        if let Some((_, used)) = self.recent.remove(key) {
+            self.recent_order.remove(&used);
+        }
+    }
+}
+
+/// Caches the batches read from and written to a store, so that hot batches are served
+/// without hitting the store.
+///
+/// Batches are cached in two tiers. Most are evicted least recently used first, but batches
+/// inserted with `BatchStore::insert_referenced`, e.g. those synchronized for certificates,
+/// are pinned for a while, so that scans over many other batches do not evict them.
+#[derive(Clone)]
+pub struct CachedBatchStore<S> {
+    inner: S,
+    config: BatchCacheConfig,
+    tiers: Arc<Mutex<CacheTiers>>,
+}
+
+impl<S: BatchStore> CachedBatchStore<S> {
+    pub fn new(inner: S, config: BatchCacheConfig) -> Self {
+        Self {
+            inner,
+            config,
+            tiers: Arc::default(),
+        }
+    }
+
+    /// Returns whether the batch stored under `key` is cached, without counting as a use.
+    pub fn is_cached(&self, key: &BatchDigest) -> bool {
+        let tiers = self.tiers.lock().unwrap();
+        tiers.pinned.contains_key(key) || tiers.recent.contains_key(key)
+    }
+
+    /// Evicts the batches unused for longer than `BatchCacheConfig::idle_timeout`, if set.
+    /// Reads already do so, but without reads, idle batches stay cached until this is called.
+    pub fn evict_idle(&self) {
+        if let Some(idle_timeout) = self.config.idle_timeout {
+            let mut tiers = self.tiers.lock().unwrap();
+            if let Some(cutoff) = Instant::now().checked_sub(idle_timeout) {
+                tiers.evict_unused_since(cutoff);
+            }
+        }
+    }
+
+    /// Spawns a task evicting idle batches every `interval`, see `evict_idle`.
+    pub fn spawn_idle_sweeper(&self, interval: Duration) -> JoinHandle<()> {
+        let store = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                store.evict_idle();
+            }
+        })
+    }
+
+    /// Spawns a task reading the batches stored under the keys received from `keys` into the
+    /// cache, e.g. the keys of recently certified batches, which synchronize and fetches are
+    /// about to read. At most `max_batches_per_sec` batches are read per second, keys received
+    /// faster wait their turn. Keys already cached are skipped. Ends once `keys` is closed.
+    pub fn spawn_warmer(
+        &self,
+        mut keys: mpsc::Receiver<Vec<BatchDigest>>,
+        max_batches_per_sec: usize,
+    ) -> JoinHandle<()> {
+        let store = self.clone();
+        let max_batches_per_sec = max_batches_per_sec.max(1);
+        // Read in chunks of a tenth of the rate, spaced evenly over each second.
+        let chunk_size = (max_batches_per_sec / 10).max(1);
+        let period = Duration::from_secs_f64(chunk_size as f64 / max_batches_per_sec as f64);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            while let Some(keys) = keys.recv().await {
+                let keys: Vec<_> = keys
+                    .into_iter()
+                    .filter(|key| !store.is_cached(key))
+                    .collect();
+                for chunk in keys.chunks(chunk_size) {
+                    interval.tick().await;
+                    let store = store.clone();
+                    let chunk = chunk.to_vec();
+                    match tokio::task::spawn_blocking(move || store.multi_get(&chunk)).await {
+                        Ok(Ok(_)) => {}
+                        Ok(Err(e)) => debug!("Failed to warm the batch cache: {e:?}"),
+                        Err(e) => debug!("Failed to warm the batch cache: {e:?}"),
+                    }
+                }
+            }
+        })
+    }
+}
+
+impl<S: BatchStore> BatchStore for CachedBatchStore<S> {
+    fn get(&self, key: &BatchDigest) -> StoreResult<Option<Batch>> {
+        self.evict_idle();
+        if let Some(batch) = self.tiers.lock().unwrap().get(key) {
+            return Ok(Some(batch));
+        }
+        let batch = self.inner.get(key)?;
+        if let Some(batch) = &batch {
+            self.tiers
+                .lock()
+                .unwrap()
+                .cache(*key, batch.clone(), self.config.capacity);
+        }
+        Ok(batch)
+    }
+
+    fn multi_get(&self, keys: &[BatchDigest]) -> StoreResult<Vec<Option<Batch>>> {
+        self.evict_idle();
+        let mut batches: Vec<_> = {
+            let mut tiers = self.tiers.lock().unwrap();
+            keys.iter().map(|key| tiers.get(key)).collect()
+        };
+        let missed: Vec<_> = keys
+            .iter()
+            .zip(&batches)
+            .filter(|(_, batch)| batch.is_none())
+            .map(|(key, _)| *key)
+            .collect();
+        if missed.is_empty() {
+            return Ok(batches);
+        }
+        let mut read = self.inner.multi_get(&missed)?.into_iter();
+        let mut tiers = self.tiers.lock().unwrap();
+        for (key, batch) in keys.iter().zip(batches.iter_mut()) {
+            if batch.is_none() {
+                *batch = read.next().flatten();
+                if let Some(batch) = batch {
+                    tiers.cache(*key, batch.clone(), self.config.capacity);
+                }
+            }
+        }
+        Ok(batches)
+    }
+
+    fn insert(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        self.inner.insert(key, batch)?;
+        self.tiers
+            .lock()
+            .unwrap()
+            .cache(*key, batch.clone(), self.config.capacity);
+        Ok(())
+    }
+
+    fn multi_insert(&self, entries: &[(BatchDigest, Batch)]) -> StoreResult<()> {
+        self.inner.multi_insert(entries)?;
+        let mut tiers = self.tiers.lock().unwrap();
+        for (key, batch) in entries {
+            tiers.cache(*key, batch.clone(), self.config.capacity);
+        }
+        Ok(())
+    }
+
+    fn insert_indexed(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        self.inner.insert_indexed(key, batch)?;
+        self.tiers
+            .lock()
+            .unwrap()
+            .cache(*key, batch.clone(), self.config.capacity);
+        Ok(())
+    }
+
+    fn batch_of_transaction(
+        &self,
+        transaction: &TransactionDigest,
+    ) -> StoreResult<Option<BatchDigest>> {
+        self.inner.batch_of_transaction(transaction)
+    }
+
+    fn insert_referenced(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        self.inner.insert(key, batch)?;
+        let until = Instant::now() + self.config.pin_duration;
+        self.tiers
+            .lock()
+            .unwrap()
+            .pin(*key, batch.clone(), until, &self.config);
+        Ok(())
+    }
+
+    fn remove(&self, key: &BatchDigest) -> StoreResult<()> {
+        self.tiers.lock().unwrap().invalidate(key);
+        self.inner.remove(key)
+    }
+
+    fn multi_remove(&self, keys: &[BatchDigest]) -> StoreResult<()> {
+        {
+            let mut tiers = self.tiers.lock().unwrap();
+            for key in keys {
+                tiers.invalidate(key);
+            }
+        }
+        self.inner.multi_remove(keys)
+    }
+
+    fn remove_epoch(&self, epoch: Epoch) -> StoreResult<()> {
+        // Cached batches are not tracked by epoch, so drop them all.
+        {
+            let mut tiers = self.tiers.lock().unwrap();
+            let cached: Vec<_> = tiers
+                .pinned
+                .keys()
+                .chain(tiers.recent.keys())
+                .copied()
+                .collect();
+            for key in cached {
+                tiers.invalidate(&key);
+            }
+        }
+        self.inner.remove_epoch(epoch)
+    }
+
+    fn contains_key(&self, key: &BatchDigest) -> StoreResult<bool> {
+        if self.is_cached(key) {
+            return Ok(true);
+        }
+        self.inner.contains_key(key)
+    }
+
+    fn multi_contains_keys(&self, keys: &[BatchDigest]) -> StoreResult<Vec<bool>> {
+        let cached: Vec<_> = keys.iter().map(|key| self.is_cached(key)).collect();
+        if cached.iter().all(|cached| *cached) {
+            return Ok(cached);
+        }
+        self.inner.multi_contains_keys(keys)
+    }
+
+    fn entries_after(
+        &self,
+        cursor: Option<BatchDigest>,
+        limit: usize,
+    ) -> StoreResult<Vec<(BatchDigest, Batch)>> {
+        // Scans bypass the cache, so that they do not evict hot batches.
+        self.inner.entries_after(cursor, limit)
+    }
+
+    fn int_property(&self, name: &str) -> StoreResult<Option<u64>> {
+        self.inner.int_property(name)
+    }
+}
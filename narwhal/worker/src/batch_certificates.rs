@@ -0,0 +1,83 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use config::WorkerId;
+use types::{BatchDigest, Certificate, CertificateAPI, HeaderAPI};
+
+#[derive(Default)]
+struct Index {
+    // Certificates are shared by the digests of all their batches.
+    certificates: HashMap<BatchDigest, Arc<Certificate>>,
+    // The indexed digests, oldest first.
+    order: VecDeque<BatchDigest>,
+}
+
+/// Remembers the certificates our primary attaches to synchronize_v2 requests, so that
+/// request_batch_v2 can return them to light clients as proof that a batch is certified.
+///
+/// Only batches synchronized with a valid certificate attached have one, and the oldest are
+/// forgotten past `capacity` batches. Light clients are still expected to verify the
+/// certificates they are served, see `RequestBatchV2Response::verify_certificate`. Shared by the
+/// handlers of a worker.
+#[derive(Clone)]
+pub struct BatchCertificates {
+    light_clients: Arc<HashSet<anemo::PeerId>>,
+    capacity: usize,
+    index: Arc<Mutex<Index>>,
+}
+
+impl BatchCertificates {
+    pub const DEFAULT_CAPACITY: usize = 100_000;
+
+    /// Serves certificates to `light_clients` only.
+    pub fn new(light_clients: impl IntoIterator<Item = anemo::PeerId>, capacity: usize) -> Self {
+        Self {
+            light_clients: Arc::new(light_clients.into_iter().collect()),
+            capacity,
+            index: Arc::default(),
+        }
+    }
+
+    /// Returns whether certificates are served to `peer`.
+    pub fn serves(&self, peer: Option<&anemo::PeerId>) -> bool {
+        peer.map_or(false, |peer| self.light_clients.contains(peer))
+    }
+
+    /// Indexes `certificate` under the digests of its batches for `worker_id`. The certificate
+    /// must be verified by the caller.
+    pub fn record(&self, certificate: Arc<Certificate>, worker_id: WorkerId) {
+        let mut index = self.index.lock().unwrap();
+        for (digest, (batch_worker_id, _)) in certificate.header().payload() {
+            if *batch_worker_id != worker_id {
+                continue;
+            }
+            if index
+                .certificates
+                .insert(*digest, certificate.clone())
+                .is_none()
+            {
+                index.order.push_back(*digest);
+            }
+        }
+        while index.order.len() > self.capacity {
+            let Some(evicted) = index.order.pop_front() else {
+                break;
+            };
+            index.certificates.remove(&evicted);
+        }
+    }
+
+    /// Returns the certificate including the batch with the given digest, if known.
+    pub fn get(&self, digest: &BatchDigest) -> Option<Certificate> {
+        let index = self.index.lock().unwrap();
+        index
+            .certificates
+            .get(digest)
+            .map(|certificate| Certificate::clone(certificate))
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    routing::get,
+    Router,
+};
+use fastcrypto::{
+    encoding::{Encoding, Hex},
+    hash::{Hash, HashFunction},
+};
+use types::{Batch, BatchAPI, BatchDigest, TimestampMs};
+
+use crate::batch_store::{BatchStore, StoreResult};
+
+#[cfg(test)]
+#[path = "tests/batch_diagnostics_tests.rs"]
+pub mod batch_diagnostics_tests;
+
+/// What the store holds for a batch digest, for operators debugging store issues.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchDiagnostics {
+    pub digest: BatchDigest,
+    pub batch: Batch,
+    pub size: usize,
+    /// The store keeps no insertion timestamps, so this is when the batch was created.
+    pub created_at: TimestampMs,
+    /// Whether the stored batch still hashes to `digest`.
+    pub digest_matches: bool,
+}
+
+impl fmt::Display for BatchDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "batch {:?}: {} transactions, {} bytes, created at {}, digest {}",
+            self.digest,
+            self.batch.transactions().len(),
+            self.size,
+            self.created_at,
+            if self.digest_matches {
+                "matches"
+            } else {
+                "MISMATCH"
+            }
+        )
+    }
+}
+
+/// The outcome of a store self-test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoreSelfTestReport {
+    /// Why the round trip failed, if it did.
+    pub error: Option<String>,
+    pub latency: Duration,
+}
+
+impl StoreSelfTestReport {
+    pub fn is_healthy(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl fmt::Display for StoreSelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error {
+            None => write!(f, "healthy")?,
+            Some(error) => write!(f, "unhealthy: {error}")?,
+        }
+        write!(f, " ({} ms)", self.latency.as_millis())
+    }
+}
+
+/// The key the self-test batch is stored under. It is not the digest of any batch, and so
+/// never collides with the keys of real batches.
+pub(crate) fn self_test_key() -> BatchDigest {
+    let mut hasher = crypto::DefaultHashFunction::new();
+    hasher.update(b"narwhal-worker-store-self-test");
+    BatchDigest::new(hasher.finalize().into())
+}
+
+/// Inspects the batches of a store the way the worker handlers address them. Served on the
+/// admin server only, reads are not bounded by the handlers' read permits.
+#[derive(Clone)]
+pub struct BatchDiagnosticsService<S> {
+    store: S,
+}
+
+impl<S: BatchStore> BatchDiagnosticsService<S> {
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    /// Returns the diagnostics of the batch with the given digest, or None if it is not
+    /// stored.
+    pub fn diagnose(&self, digest: &BatchDigest) -> StoreResult<Option<BatchDiagnostics>> {
+        Ok(self.store.get(digest)?.map(|batch| BatchDiagnostics {
+            digest: *digest,
+            size: batch.size(),
+            created_at: batch.metadata().created_at,
+            digest_matches: batch.digest() == *digest,
+            batch,
+        }))
+    }
+
+    /// Writes a synthetic batch, reads it back and removes it, exercising the whole store
+    /// write and read path.
+    pub fn self_test(&self) -> StoreSelfTestReport {
+        let start = Instant::now();
+        let error = self.self_test_round_trip().err();
+        StoreSelfTestReport {
+            error,
+            latency: start.elapsed(),
+        }
+    }
+
+    fn self_test_round_trip(&self) -> Result<(), String> {
+        let key = self_test_key();
+        let batch = Batch::new(vec![b"self-test".to_vec()]);
+        self.store
+            .insert(&key, &batch)
+            .map_err(|e| format!("write failed: {e}"))?;
+        let read = self.store.get(&key);
+        // Clean up before reporting a bad read.
+        self.store
+            .remove(&key)
+            .map_err(|e| format!("remove failed: {e}"))?;
+        match read {
+            Ok(Some(read)) if read == batch => {}
+            Ok(Some(_)) => return Err("read back a different batch".to_string()),
+            Ok(None) => return Err("batch missing after write".to_string()),
+            Err(e) => return Err(format!("read failed: {e}")),
+        }
+        match self.store.contains_key(&key) {
+            Ok(false) => Ok(()),
+            Ok(true) => Err("batch still present after remove".to_string()),
+            Err(e) => Err(format!("read failed: {e}")),
+        }
+    }
+
+    /// Routes serving the diagnostics of a hex encoded batch digest, and the store
+    /// self-test, on the admin server.
+    pub fn admin_routes(&self) -> Router {
+        Router::new()
+            .route(
+                "/batch_diagnostics/:digest",
+                get(get_batch_diagnostics::<S>),
+            )
+            .route("/store_self_test", get(get_store_self_test::<S>))
+            .layer(Extension(self.clone()))
+    }
+}
+
+async fn get_batch_diagnostics<S: BatchStore>(
+    Extension(service): Extension<BatchDiagnosticsService<S>>,
+    Path(digest): Path<String>,
+) -> (StatusCode, String) {
+    let digest = match Hex::decode(&digest)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+    {
+        Some(bytes) => BatchDigest::new(bytes),
+        None => return (StatusCode::BAD_REQUEST, format!("Invalid digest {digest}")),
+    };
+    match service.diagnose(&digest) {
+        Ok(Some(diagnostics)) => (StatusCode::OK, diagnostics.to_string()),
+        Ok(None) => (StatusCode::NOT_FOUND, format!("Batch {digest:?} not found")),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read batch {digest:?}: {e:?}"),
+        ),
+    }
+}
+
+async fn get_store_self_test<S: BatchStore>(
+    Extension(service): Extension<BatchDiagnosticsService<S>>,
+) -> (StatusCode, String) {
+    let report = service.self_test();
+    let status = if report.is_healthy() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, report.to_string())
+}
@@ -0,0 +1,100 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::VecDeque;
+
+use types::{Batch, BatchDigest};
+
+use crate::batch_store::{BatchStore, StoreResult};
+
+#[cfg(test)]
+#[path = "tests/batch_export_tests.rs"]
+pub mod batch_export_tests;
+
+/// Streams the whole content of a batch store, in key order, as chunks of (key, batch)
+/// entries, e.g. to back up a worker without stopping it.
+///
+/// The store is read page by page while it keeps serving, so the export is not a point in
+/// time snapshot: batches are immutable, but batches written or removed during the export
+/// are only included if their key had not been passed yet. A `PartitionedBatchStore` only
+/// exports its own partition.
+pub struct BatchExport<S> {
+    store: S,
+    max_chunk_size: usize,
+    // The last key read from the store, and the entries read but not exported yet.
+    cursor: Option<BatchDigest>,
+    pending: VecDeque<(BatchDigest, Batch)>,
+    exhausted: bool,
+}
+
+impl<S: BatchStore> BatchExport<S> {
+    /// Number of entries read from the store at once.
+    const PAGE_LEN: usize = 100;
+
+    /// Exports `store` in chunks of at most `max_chunk_size` bytes of batches. A chunk holds
+    /// at least one entry, so a batch larger than `max_chunk_size` is exported alone.
+    pub fn new(store: S, max_chunk_size: usize) -> Self {
+        Self {
+            store,
+            max_chunk_size,
+            cursor: None,
+            pending: VecDeque::new(),
+            exhausted: false,
+        }
+    }
+
+    fn read_page(&mut self) -> StoreResult<()> {
+        let page = self.store.entries_after(self.cursor, Self::PAGE_LEN)?;
+        self.exhausted = page.len() < Self::PAGE_LEN;
+        if let Some((key, _)) = page.last() {
+            self.cursor = Some(*key);
+        }
+        self.pending.extend(page);
+        Ok(())
+    }
+}
+
+impl<S: BatchStore> Iterator for BatchExport<S> {
+    type Item = StoreResult<Vec<(BatchDigest, Batch)>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Vec::new();
+        let mut chunk_size = 0;
+        loop {
+            if self.pending.is_empty() {
+                if self.exhausted {
+                    break;
+                }
+                if let Err(e) = self.read_page() {
+                    // Stop after reporting the failure.
+                    self.exhausted = true;
+                    return Some(Err(e));
+                }
+                continue;
+            }
+            let batch_size = self.pending[0].1.size();
+            if !chunk.is_empty() && chunk_size + batch_size > self.max_chunk_size {
+                break;
+            }
+            chunk.extend(self.pending.pop_front());
+            chunk_size += batch_size;
+        }
+        (!chunk.is_empty()).then_some(Ok(chunk))
+    }
+}
+
+/// Writes the chunks of a `BatchExport` to `store`, e.g. to restore a backup into a fresh
+/// store. Stops at the first failed chunk. Returns the number of batches imported.
+pub fn import_batches<S: BatchStore>(
+    store: &S,
+    chunks: impl IntoIterator<Item = StoreResult<Vec<(BatchDigest, Batch)>>>,
+) -> StoreResult<usize> {
+    let mut imported = 0;
+    for chunk in chunks {
+        for (key, batch) in chunk? {
+            store.insert(&key, &batch)?;
+            imported += 1;
+        }
+    }
+    Ok(imported)
+}
@@ -3,102 +3,280 @@
 
 use std::{
     collections::{HashMap, HashSet, VecDeque},
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::Duration,
 };
 
-use anemo::Network;
+use anemo::{Network, PeerId};
 use anyhow::bail;
 use async_trait::async_trait;
 use crypto::NetworkPublicKey;
 use fastcrypto::hash::Hash;
-use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
+use futures::{future::join, stream::FuturesUnordered, FutureExt, StreamExt};
 use itertools::Itertools;
 use network::WorkerRpc;
 use prometheus::IntGauge;
 use rand::{rngs::ThreadRng, seq::SliceRandom};
-use store::{rocks::DBMap, Map};
+use store::rocks::DBMap;
 use tokio::{
     select,
+    sync::{mpsc, Notify},
     time::{sleep, sleep_until, Instant},
 };
 use tracing::debug;
-use types::{Batch, BatchDigest, RequestBatchesRequest, RequestBatchesResponse};
+use types::{
+    Batch, BatchDigest, RequestBatchesRequest, RequestBatchesResponse, RequestBatchesV2Request,
+};
 
-use crate::metrics::WorkerMetrics;
+use crate::{
+    batch_store::BatchStore,
+    metrics::WorkerMetrics,
+    peer_protocol_versions::PeerProtocolVersions,
+    peer_selection::{PeerSelector, PeerStats, RandomOrder},
+};
 
 const REMOTE_PARALLEL_FETCH_INTERVAL: Duration = Duration::from_secs(2);
+// Fetched batches buffered between fetch_into() and fetch().
+const FETCH_CHANNEL_CAPACITY: usize = 1_000;
+// How often a fetch over its outstanding bytes budget checks whether the caller drained it.
+const OUTSTANDING_BYTES_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
-pub struct BatchFetcher {
+#[derive(Clone)]
+pub struct BatchFetcher<S = DBMap<BatchDigest, Batch>> {
     name: NetworkPublicKey,
     network: Arc<dyn RequestBatchesNetwork>,
-    batch_store: DBMap<BatchDigest, Batch>,
+    batch_store: S,
+    // If set, a fetch contacts at most this many of the known workers.
+    max_peers_per_fetch: Option<usize>,
+    // If set, a fetch stops requesting batches from workers while the batches it sent but the
+    // caller did not receive yet, plus the largest response so far, would exceed this many bytes.
+    max_outstanding_bytes: Option<usize>,
+    // Orders the known workers at every round of a fetch.
+    peer_selector: Arc<dyn PeerSelector>,
+    // What past fetches observed of each peer, for the selector.
+    peer_stats: Arc<Mutex<HashMap<NetworkPublicKey, PeerStats>>>,
+    // The fetches in progress, so that they can be cancelled.
+    in_flight: InFlightFetches,
     metrics: Arc<WorkerMetrics>,
 }
 
-impl BatchFetcher {
+impl<S: BatchStore> BatchFetcher<S> {
     pub fn new(
         name: NetworkPublicKey,
         network: Network,
-        batch_store: DBMap<BatchDigest, Batch>,
+        batch_store: S,
         metrics: Arc<WorkerMetrics>,
     ) -> Self {
         Self {
             name,
-            network: Arc::new(RequestBatchesNetworkImpl { network }),
+            network: Arc::new(RequestBatchesNetworkImpl {
+                network,
+                protocol_versions: PeerProtocolVersions::default(),
+            }),
             batch_store,
+            max_peers_per_fetch: None,
+            max_outstanding_bytes: None,
+            peer_selector: Arc::new(RandomOrder),
+            peer_stats: Arc::default(),
+            in_flight: InFlightFetches::default(),
             metrics,
         }
     }
 
+    /// Selects the workers requested at every round of a fetch with `peer_selector`, instead
+    /// of requesting them in a random order.
+    pub fn with_peer_selector(mut self, peer_selector: impl PeerSelector) -> Self {
+        self.peer_selector = Arc::new(peer_selector);
+        self
+    }
+
+    /// Bounds the number of distinct workers contacted by a single fetch. Since fetches are
+    /// retried until every batch is found, the cap should be at least the number of workers
+    /// guaranteed to hold the batches, e.g. f+1 for certified batches.
+    pub fn with_max_peers_per_fetch(mut self, max_peers_per_fetch: usize) -> Self {
+        self.max_peers_per_fetch = Some(max_peers_per_fetch);
+        self
+    }
+
+    /// Bounds the bytes of fetched batches held between the workers and the caller of
+    /// `fetch_into()`. A fetch over the budget waits for the caller to receive batches before
+    /// requesting more, so the budget only bounds memory if the channel passed to
+    /// `fetch_into()` is drained as batches are processed. A single response may still exceed
+    /// the budget.
+    pub fn with_max_outstanding_bytes(mut self, max_outstanding_bytes: usize) -> Self {
+        self.max_outstanding_bytes = Some(max_outstanding_bytes);
+        self
+    }
+
     /// Bulk fetches payload from local storage and remote workers.
-    /// This function performs infinite retries and blocks until all batches are available.
+    /// This function performs infinite retries and blocks until all batches are available,
+    /// or cancelled with `cancel()`.
     pub async fn fetch(
         &self,
         digests: HashSet<BatchDigest>,
         known_workers: HashSet<NetworkPublicKey>,
     ) -> HashMap<BatchDigest, Batch> {
+        self.fetch_and_attribute(digests, known_workers, None).await
+    }
+
+    /// Cancels the fetches in progress of the given digests, e.g. because the batches were
+    /// deleted. The requests to remote workers asking for cancelled batches are aborted, and
+    /// the fetches return without the cancelled batches once no other batch is missing.
+    pub fn cancel(&self, digests: &HashSet<BatchDigest>) {
+        self.in_flight.cancel(digests);
+    }
+
+    /// Like `fetch()`, but also returns the worker that supplied each batch fetched
+    /// remotely. Batches found in local storage have no supplier.
+    pub async fn fetch_with_suppliers(
+        &self,
+        digests: HashSet<BatchDigest>,
+        known_workers: HashSet<NetworkPublicKey>,
+    ) -> (
+        HashMap<BatchDigest, Batch>,
+        HashMap<BatchDigest, NetworkPublicKey>,
+    ) {
+        let mut suppliers = HashMap::new();
+        let batches = self
+            .fetch_and_attribute(digests, known_workers, Some(&mut suppliers))
+            .await;
+        (batches, suppliers)
+    }
+
+    async fn fetch_and_attribute(
+        &self,
+        digests: HashSet<BatchDigest>,
+        known_workers: HashSet<NetworkPublicKey>,
+        suppliers: Option<&mut HashMap<BatchDigest, NetworkPublicKey>>,
+    ) -> HashMap<BatchDigest, Batch> {
+        let (sender, mut receiver) = mpsc::channel(FETCH_CHANNEL_CAPACITY);
+        let mut fetched_batches = HashMap::new();
+        let collect = async {
+            while let Some((digest, batch)) = receiver.recv().await {
+                fetched_batches.insert(digest, batch);
+            }
+        };
+        join(
+            self.fetch_into_attributed(digests, known_workers, sender, suppliers),
+            collect,
+        )
+        .await;
+        fetched_batches
+    }
+
+    /// Like `fetch()`, but sends the batches to `sender` as they become available instead of
+    /// returning them all at once. The capacity of the channel bounds the number of fetched
+    /// batches held in memory. Returns early if the receiver is dropped.
+    pub async fn fetch_into(
+        &self,
+        digests: HashSet<BatchDigest>,
+        known_workers: HashSet<NetworkPublicKey>,
+        sender: mpsc::Sender<(BatchDigest, Batch)>,
+    ) {
+        self.fetch_into_attributed(digests, known_workers, sender, None)
+            .await
+    }
+
+    async fn fetch_into_attributed(
+        &self,
+        digests: HashSet<BatchDigest>,
+        known_workers: HashSet<NetworkPublicKey>,
+        sender: mpsc::Sender<(BatchDigest, Batch)>,
+        suppliers: Option<&mut HashMap<BatchDigest, NetworkPublicKey>>,
+    ) {
+        let mut contacted_workers = HashSet::new();
+        self.fetch_into_from(
+            digests,
+            known_workers,
+            sender,
+            &mut contacted_workers,
+            suppliers,
+        )
+        .await;
+        self.metrics
+            .batch_fetch_contacted_peers
+            .observe(contacted_workers.len() as f64);
+    }
+
+    async fn fetch_into_from(
+        &self,
+        digests: HashSet<BatchDigest>,
+        known_workers: HashSet<NetworkPublicKey>,
+        sender: mpsc::Sender<(BatchDigest, Batch)>,
+        contacted_workers: &mut HashSet<NetworkPublicKey>,
+        mut suppliers: Option<&mut HashMap<BatchDigest, NetworkPublicKey>>,
+    ) {
         debug!(
             "Attempting to fetch {} digests from {} workers",
             digests.len(),
             known_workers.len()
         );
 
+        let fetch = self.in_flight.register(&digests);
         let mut remaining_digests = digests;
-        let mut fetched_batches = HashMap::new();
+        let mut outstanding = OutstandingBytes::default();
         // TODO: verify known_workers meets quorum threshold, or just use all other workers.
-        let known_workers = known_workers
+        let mut known_workers = known_workers
             .into_iter()
             .filter(|worker| worker != &self.name)
             .collect_vec();
+        if let Some(max_peers) = self.max_peers_per_fetch {
+            // Pick the contacted workers at random, to spread fetches over the known workers.
+            known_workers.shuffle(&mut ThreadRng::default());
+            known_workers.truncate(max_peers);
+        }
 
         loop {
+            fetch.take_cancelled(&mut remaining_digests);
             if remaining_digests.is_empty() {
-                return fetched_batches;
+                return;
             }
 
             // Fetch from local storage.
             let _timer = self.metrics.worker_local_fetch_latency.start_timer();
-            fetched_batches.extend(self.fetch_local(remaining_digests.clone()).await);
-            remaining_digests.retain(|d| !fetched_batches.contains_key(d));
+            let local_batches = self.fetch_local(remaining_digests.clone()).await;
+            drop(_timer);
+            for (digest, batch) in local_batches {
+                if remaining_digests.remove(&digest) {
+                    let size = batch.size();
+                    if sender.send((digest, batch)).await.is_err() {
+                        return;
+                    }
+                    outstanding.record(size);
+                }
+            }
             if remaining_digests.is_empty() {
-                return fetched_batches;
+                return;
             }
-            drop(_timer);
 
             // Fetch from remote workers.
             // TODO: Can further parallelize this by target worker_id if necessary.
             let _timer = self.metrics.worker_remote_fetch_latency.start_timer();
-            let mut known_workers: Vec<_> = known_workers.iter().collect();
-            known_workers.shuffle(&mut ThreadRng::default());
-            let mut known_workers = VecDeque::from(known_workers);
+            let peer_stats = self.peer_stats.lock().unwrap().clone();
+            let mut known_workers = VecDeque::from(self.peer_selector.select(
+                &remaining_digests,
+                &known_workers,
+                &peer_stats,
+            ));
             let mut stagger = Duration::from_secs(0);
             let mut futures = FuturesUnordered::new();
 
             loop {
                 assert!(!remaining_digests.is_empty());
+                if let Some(max_outstanding_bytes) = self.max_outstanding_bytes {
+                    // Let the caller drain the batches before requesting more.
+                    while outstanding.exceeds(max_outstanding_bytes, &sender) {
+                        if sender.is_closed() {
+                            return;
+                        }
+                        sleep(OUTSTANDING_BYTES_POLL_INTERVAL).await;
+                    }
+                }
                 if let Some(worker) = known_workers.pop_front() {
-                    let future = self.fetch_remote(worker.clone(), remaining_digests.clone());
+                    contacted_workers.insert(worker.clone());
+                    let future = self
+                        .fetch_remote(worker.clone(), remaining_digests.clone())
+                        .map(move |batches| (worker, batches));
                     futures.push(future.boxed());
                 } else {
                     // No more worker to fetch from. This happens after sending requests to all
@@ -109,19 +287,34 @@ impl BatchFetcher {
                 let mut interval = Box::pin(sleep(stagger));
                 select! {
                     result = futures.next() => {
-                        if let Some(remote_batches) = result {
-                            let new_batches: HashMap<_, _> = remote_batches.iter().filter(|(d, _)| remaining_digests.remove(d)).collect();
-                            fetched_batches.extend(new_batches.iter().map(|(d, b)| (**d, (*b).clone())));
+                        if let Some((worker, remote_batches)) = result {
+                            let new_batches = remote_batches.into_iter().filter(|(d, _)| remaining_digests.remove(d)).collect_vec();
+                            outstanding.record_response(new_batches.iter().map(|(_, batch)| batch.size()).sum());
                             // Also persist the batches, so they are available after restarts.
-                            let mut write_batch = self.batch_store.batch();
-                            write_batch.insert_batch(&self.batch_store, new_batches).unwrap();
-                            write_batch.write().unwrap();
+                            self.batch_store.multi_insert(&new_batches).unwrap();
+                            if let Some(suppliers) = suppliers.as_deref_mut() {
+                                suppliers.extend(new_batches.iter().map(|(digest, _)| (*digest, worker.clone())));
+                            }
+                            for (digest, batch) in new_batches {
+                                let size = batch.size();
+                                if sender.send((digest, batch)).await.is_err() {
+                                    return;
+                                }
+                                outstanding.record(size);
+                            }
                             if remaining_digests.is_empty() {
-                                return fetched_batches;
+                                return;
                             }
                         }
                     }
                     _ = interval.as_mut() => { /* continue to fire out another fetch */ }
+                    _ = fetch.cancelled() => {
+                        if fetch.take_cancelled(&mut remaining_digests) {
+                            // Abort the requests asking for the cancelled batches by dropping
+                            // them, and request the remaining batches again.
+                            break;
+                        }
+                    }
                 }
             }
 
@@ -139,9 +332,10 @@ impl BatchFetcher {
         // Continue to bulk request from local worker until no remaining digests
         // are available.
         debug!("Local attempt to fetch {} digests", digests.len());
+        let digests = digests.into_iter().collect_vec();
         let local_batches = self
             .batch_store
-            .multi_get(digests.clone().into_iter())
+            .multi_get(&digests)
             .expect("Failed to get batches");
         for (digest, batch) in digests.into_iter().zip(local_batches.into_iter()) {
             if let Some(batch) = batch {
@@ -179,13 +373,20 @@ impl BatchFetcher {
                 "Remote attempt #{attempt} to fetch {} digests from {worker}",
                 digests.len(),
             );
-            let deadline = Instant::now() + timeout;
+            let start = Instant::now();
+            let deadline = start + timeout;
             let request_batch_guard =
                 PendingGuard::make_inc(&self.metrics.pending_remote_request_batch);
             let response = self
                 .safe_request_batches(digests.clone(), worker.clone(), timeout)
                 .await;
             drop(request_batch_guard);
+            self.peer_stats
+                .lock()
+                .unwrap()
+                .entry(worker.clone())
+                .or_default()
+                .record(response.is_ok().then(|| start.elapsed()));
             match response {
                 Ok(remote_batches) => {
                     self.metrics
@@ -265,6 +466,127 @@ impl BatchFetcher {
     }
 }
 
+/// The registry of the fetches in progress of a `BatchFetcher`.
+#[derive(Clone, Default)]
+struct InFlightFetches {
+    inner: Arc<Mutex<InFlightFetchesInner>>,
+}
+
+#[derive(Default)]
+struct InFlightFetchesInner {
+    next_id: u64,
+    fetches: HashMap<u64, InFlightFetchEntry>,
+}
+
+struct InFlightFetchEntry {
+    digests: HashSet<BatchDigest>,
+    // The digests cancelled since the fetch last took them.
+    cancelled: HashSet<BatchDigest>,
+    notify: Arc<Notify>,
+}
+
+impl InFlightFetches {
+    fn register(&self, digests: &HashSet<BatchDigest>) -> InFlightFetch {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        let notify = Arc::new(Notify::new());
+        inner.fetches.insert(
+            id,
+            InFlightFetchEntry {
+                digests: digests.clone(),
+                cancelled: HashSet::new(),
+                notify: notify.clone(),
+            },
+        );
+        InFlightFetch {
+            fetches: self.clone(),
+            id,
+            notify,
+        }
+    }
+
+    fn cancel(&self, digests: &HashSet<BatchDigest>) {
+        let mut inner = self.inner.lock().unwrap();
+        for entry in inner.fetches.values_mut() {
+            let cancelled = entry.digests.intersection(digests).copied().collect_vec();
+            if cancelled.is_empty() {
+                continue;
+            }
+            for digest in cancelled {
+                entry.digests.remove(&digest);
+                entry.cancelled.insert(digest);
+            }
+            entry.notify.notify_one();
+        }
+    }
+}
+
+/// A fetch registered in `InFlightFetches`, removed from it when dropped.
+struct InFlightFetch {
+    fetches: InFlightFetches,
+    id: u64,
+    notify: Arc<Notify>,
+}
+
+impl InFlightFetch {
+    /// Completes once digests of the fetch were cancelled.
+    async fn cancelled(&self) {
+        self.notify.notified().await
+    }
+
+    /// Removes the cancelled digests from `remaining`, returning whether any was removed.
+    fn take_cancelled(&self, remaining: &mut HashSet<BatchDigest>) -> bool {
+        let mut inner = self.fetches.inner.lock().unwrap();
+        let Some(entry) = inner.fetches.get_mut(&self.id) else {
+            return false;
+        };
+        let mut removed = false;
+        for digest in entry.cancelled.drain() {
+            removed |= remaining.remove(&digest);
+        }
+        removed
+    }
+}
+
+impl Drop for InFlightFetch {
+    fn drop(&mut self) {
+        self.fetches.inner.lock().unwrap().fetches.remove(&self.id);
+    }
+}
+
+/// Tracks the bytes of the batches a fetch sent to its caller that are still buffered in the
+/// channel, i.e. not received yet.
+#[derive(Default)]
+struct OutstandingBytes {
+    // The sizes of the batches sent, oldest first, from which the received ones are dropped.
+    sent: VecDeque<usize>,
+    total: usize,
+    largest_response: usize,
+}
+
+impl OutstandingBytes {
+    fn record(&mut self, size: usize) {
+        self.sent.push_back(size);
+        self.total += size;
+    }
+
+    fn record_response(&mut self, size: usize) {
+        self.largest_response = self.largest_response.max(size);
+    }
+
+    /// Returns whether another response would take the outstanding bytes over `limit`. Never
+    /// true once the caller received every batch, so that fetches make progress.
+    fn exceeds(&mut self, limit: usize, sender: &mpsc::Sender<(BatchDigest, Batch)>) -> bool {
+        // The channel delivers in order, so the batches still buffered are the newest ones.
+        let buffered = sender.max_capacity() - sender.capacity();
+        while self.sent.len() > buffered {
+            self.total -= self.sent.pop_front().unwrap();
+        }
+        self.total > 0 && self.total + self.largest_response > limit
+    }
+}
+
 // todo - make it generic so that other can reuse
 struct PendingGuard<'a> {
     metric: &'a IntGauge,
@@ -297,6 +619,7 @@ pub trait RequestBatchesNetwork: Send + Sync {
 
 struct RequestBatchesNetworkImpl {
     network: anemo::Network,
+    protocol_versions: PeerProtocolVersions,
 }
 
 #[async_trait]
@@ -307,6 +630,21 @@ impl RequestBatchesNetwork for RequestBatchesNetworkImpl {
         worker: NetworkPublicKey,
         timeout: Duration,
     ) -> anyhow::Result<RequestBatchesResponse> {
+        // Large requests are smaller in compact form, which only workers serving
+        // request_batches_v2 decode.
+        if batch_digests.len() >= RequestBatchesV2Request::COMPACT_DIGESTS_THRESHOLD {
+            if let Some(peer) = self.network.peer(PeerId(worker.0.to_bytes())) {
+                if self.protocol_versions.serves_v2(&peer).await {
+                    let request = anemo::Request::new(RequestBatchesV2Request::new(batch_digests))
+                        .with_timeout(timeout);
+                    return Ok(self
+                        .network
+                        .request_batches_v2(worker, request)
+                        .await?
+                        .into());
+                }
+            }
+        }
         let request =
             anemo::Request::new(RequestBatchesRequest { batch_digests }).with_timeout(timeout);
         self.network.request_batches(worker, request).await
@@ -321,7 +659,13 @@ mod tests {
     use fastcrypto::traits::KeyPair;
     use itertools::Itertools;
     use rand::rngs::StdRng;
-    use std::collections::HashMap;
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Mutex,
+        },
+    };
 
     #[tokio::test]
     pub async fn test_fetcher() {
@@ -339,6 +683,11 @@ mod tests {
             name: test_pk(0),
             network: Arc::new(network.clone()),
             batch_store: batch_store.clone(),
+            max_peers_per_fetch: None,
+            max_outstanding_bytes: None,
+            peer_selector: Arc::new(RandomOrder),
+            peer_stats: Arc::default(),
+            in_flight: InFlightFetches::default(),
             metrics: Arc::new(WorkerMetrics::default()),
         };
         let expected_batches = HashMap::from_iter(vec![
@@ -374,6 +723,11 @@ mod tests {
             name: test_pk(0),
             network: Arc::new(network.clone()),
             batch_store,
+            max_peers_per_fetch: None,
+            max_outstanding_bytes: None,
+            peer_selector: Arc::new(RandomOrder),
+            peer_stats: Arc::default(),
+            in_flight: InFlightFetches::default(),
             metrics: Arc::new(WorkerMetrics::default()),
         };
         let expected_batches = HashMap::from_iter(vec![
@@ -405,6 +759,11 @@ mod tests {
             name: test_pk(0),
             network: Arc::new(network.clone()),
             batch_store,
+            max_peers_per_fetch: None,
+            max_outstanding_bytes: None,
+            peer_selector: Arc::new(RandomOrder),
+            peer_stats: Arc::default(),
+            in_flight: InFlightFetches::default(),
             metrics: Arc::new(WorkerMetrics::default()),
         };
         let expected_batches = HashMap::from_iter(vec![
@@ -435,6 +794,11 @@ mod tests {
             name: test_pk(0),
             network: Arc::new(network.clone()),
             batch_store,
+            max_peers_per_fetch: None,
+            max_outstanding_bytes: None,
+            peer_selector: Arc::new(RandomOrder),
+            peer_stats: Arc::default(),
+            in_flight: InFlightFetches::default(),
             metrics: Arc::new(WorkerMetrics::default()),
         };
         let expected_batches = HashMap::from_iter(vec![
@@ -479,24 +843,229 @@ mod tests {
             name: test_pk(0),
             network: Arc::new(network.clone()),
             batch_store,
+            max_peers_per_fetch: None,
+            max_outstanding_bytes: None,
+            peer_selector: Arc::new(RandomOrder),
+            peer_stats: Arc::default(),
+            in_flight: InFlightFetches::default(),
             metrics: Arc::new(WorkerMetrics::default()),
         };
         let fetched_batches = fetcher.fetch(digests, known_workers).await;
         assert_eq!(fetched_batches, expected_batches);
     }
 
+    #[tokio::test]
+    pub async fn test_fetcher_max_peers_per_fetch() {
+        let mut network = TestRequestBatchesNetwork::new();
+        let batch_store = test_utils::create_batch_store();
+        // With a response size limit of 2, the 12 batches take 6 responses, each of which
+        // has the fetcher contact another worker.
+        let batches: Vec<_> = (0..12).map(|i| Batch::new(vec![vec![i]])).collect();
+        let candidates: Vec<_> = (1..=10).collect();
+        for batch in &batches {
+            network.put(&candidates, batch.clone());
+        }
+        let (digests, known_workers) = (
+            HashSet::from_iter(batches.iter().map(|batch| batch.digest())),
+            HashSet::from_iter(test_pks(&candidates)),
+        );
+        let metrics = Arc::new(WorkerMetrics::default());
+        let fetcher = BatchFetcher {
+            name: test_pk(0),
+            network: Arc::new(network.clone()),
+            batch_store,
+            max_peers_per_fetch: None,
+            max_outstanding_bytes: None,
+            peer_selector: Arc::new(RandomOrder),
+            peer_stats: Arc::default(),
+            in_flight: InFlightFetches::default(),
+            metrics: metrics.clone(),
+        }
+        .with_max_peers_per_fetch(3);
+        let fetched_batches = fetcher.fetch(digests, known_workers).await;
+        assert_eq!(fetched_batches.len(), batches.len());
+        assert_eq!(network.requested_workers.lock().unwrap().len(), 3);
+        assert_eq!(metrics.batch_fetch_contacted_peers.get_sample_count(), 1);
+        assert_eq!(metrics.batch_fetch_contacted_peers.get_sample_sum(), 3.0);
+    }
+
+    #[tokio::test]
+    pub async fn test_fetcher_max_outstanding_bytes() {
+        let mut network = TestRequestBatchesNetwork::new();
+        let batch_store = test_utils::create_batch_store();
+        // With a response size limit of 2, each response holds a single 1000 bytes batch, and
+        // each has the fetcher contact another worker.
+        let batches: Vec<_> = (0..10).map(|i| Batch::new(vec![vec![i; 1_000]])).collect();
+        let candidates: Vec<_> = (1..=10).collect();
+        for batch in &batches {
+            network.put(&candidates, batch.clone());
+        }
+        let (digests, known_workers) = (
+            HashSet::from_iter(batches.iter().map(|batch| batch.digest())),
+            HashSet::from_iter(test_pks(&candidates)),
+        );
+        let fetcher = BatchFetcher {
+            name: test_pk(0),
+            network: Arc::new(network.clone()),
+            batch_store,
+            max_peers_per_fetch: None,
+            max_outstanding_bytes: None,
+            peer_selector: Arc::new(RandomOrder),
+            peer_stats: Arc::default(),
+            in_flight: InFlightFetches::default(),
+            metrics: Arc::new(WorkerMetrics::default()),
+        }
+        .with_max_outstanding_bytes(3_000);
+
+        let (sender, mut receiver) = mpsc::channel(FETCH_CHANNEL_CAPACITY);
+        let fetch = tokio::spawn(async move {
+            fetcher.fetch_into(digests, known_workers, sender).await;
+        });
+        let requests = || network.requested_workers.lock().unwrap().len();
+
+        // The fetcher stops once the budget is used by batches the caller did not receive.
+        sleep(Duration::from_millis(200)).await;
+        assert_eq!(requests(), 3);
+
+        // Receiving batches frees the budget, but never lets more than 3 batches be held.
+        let mut received = 0;
+        while receiver.recv().await.is_some() {
+            received += 1;
+            sleep(Duration::from_millis(50)).await;
+            assert!(requests() - received <= 3);
+        }
+        assert_eq!(received, batches.len());
+        fetch.await.unwrap();
+    }
+
+    #[tokio::test]
+    pub async fn test_fetcher_with_suppliers() {
+        let mut network = TestRequestBatchesNetwork::new();
+        let batch_store = test_utils::create_batch_store();
+        // One batch is available locally, the others from a single worker each.
+        let local_batch = Batch::new(vec![vec![0]]);
+        batch_store
+            .insert(&local_batch.digest(), &local_batch)
+            .unwrap();
+        let remote_batches: Vec<_> = (1..=3).map(|i| (i, Batch::new(vec![vec![i]]))).collect();
+        for (worker, batch) in &remote_batches {
+            network.put(&[*worker], batch.clone());
+        }
+        let (digests, known_workers) = (
+            remote_batches
+                .iter()
+                .map(|(_, batch)| batch.digest())
+                .chain([local_batch.digest()])
+                .collect(),
+            HashSet::from_iter(test_pks(&[1, 2, 3])),
+        );
+        let fetcher = BatchFetcher {
+            name: test_pk(0),
+            network: Arc::new(network.clone()),
+            batch_store,
+            max_peers_per_fetch: None,
+            max_outstanding_bytes: None,
+            peer_selector: Arc::new(RandomOrder),
+            peer_stats: Arc::default(),
+            in_flight: InFlightFetches::default(),
+            metrics: Arc::new(WorkerMetrics::default()),
+        };
+        let (fetched_batches, suppliers) =
+            fetcher.fetch_with_suppliers(digests, known_workers).await;
+        assert_eq!(fetched_batches.len(), 4);
+        let expected_suppliers: HashMap<_, _> = remote_batches
+            .iter()
+            .map(|(worker, batch)| (batch.digest(), test_pk(*worker)))
+            .collect();
+        assert_eq!(suppliers, expected_suppliers);
+    }
+
+    #[tokio::test]
+    pub async fn test_fetcher_cancel() {
+        let network = PendingRequestBatchesNetwork::default();
+        let batch = Batch::new(vec![vec![1]]);
+        let (digests, known_workers) = (
+            HashSet::from_iter(vec![batch.digest()]),
+            HashSet::from_iter(test_pks(&[1, 2])),
+        );
+        let fetcher = BatchFetcher {
+            name: test_pk(0),
+            network: Arc::new(network.clone()),
+            batch_store: test_utils::create_batch_store(),
+            max_peers_per_fetch: None,
+            max_outstanding_bytes: None,
+            peer_selector: Arc::new(RandomOrder),
+            peer_stats: Arc::default(),
+            in_flight: InFlightFetches::default(),
+            metrics: Arc::new(WorkerMetrics::default()),
+        };
+        let fetch = tokio::spawn({
+            let fetcher = fetcher.clone();
+            let digests = digests.clone();
+            async move { fetcher.fetch(digests, known_workers).await }
+        });
+
+        // The first worker never responds, and the next is only requested after a while.
+        sleep(Duration::from_millis(200)).await;
+        assert_eq!(network.started.load(Ordering::SeqCst), 1);
+        assert_eq!(network.aborted.load(Ordering::SeqCst), 0);
+
+        // Cancelling the only batch ends the fetch and aborts its request.
+        fetcher.cancel(&digests);
+        let fetched_batches = tokio::time::timeout(Duration::from_secs(1), fetch)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(fetched_batches.is_empty());
+        assert_eq!(network.started.load(Ordering::SeqCst), 1);
+        assert_eq!(network.aborted.load(Ordering::SeqCst), 1);
+        assert!(fetcher.in_flight.inner.lock().unwrap().fetches.is_empty());
+    }
+
     // TODO: add test for timeouts, failures and retries.
 
+    // A network whose requests never complete, counting the requests aborted by the fetcher.
+    #[derive(Clone, Default)]
+    struct PendingRequestBatchesNetwork {
+        started: Arc<AtomicUsize>,
+        aborted: Arc<AtomicUsize>,
+    }
+
+    struct AbortGuard(Arc<AtomicUsize>);
+
+    impl Drop for AbortGuard {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[async_trait]
+    impl RequestBatchesNetwork for PendingRequestBatchesNetwork {
+        async fn request_batches(
+            &self,
+            _digests: Vec<BatchDigest>,
+            _worker: NetworkPublicKey,
+            _timeout: Duration,
+        ) -> anyhow::Result<RequestBatchesResponse> {
+            self.started.fetch_add(1, Ordering::SeqCst);
+            let _guard = AbortGuard(self.aborted.clone());
+            std::future::pending().await
+        }
+    }
+
     #[derive(Clone)]
     struct TestRequestBatchesNetwork {
         // Worker name -> batch digests it has -> batches.
         data: HashMap<NetworkPublicKey, HashMap<BatchDigest, Batch>>,
+        // The workers that were sent a request.
+        requested_workers: Arc<Mutex<HashSet<NetworkPublicKey>>>,
     }
 
     impl TestRequestBatchesNetwork {
         pub fn new() -> Self {
             Self {
                 data: HashMap::new(),
+                requested_workers: Arc::default(),
             }
         }
 
@@ -517,6 +1086,10 @@ mod tests {
             worker: NetworkPublicKey,
             _timeout: Duration,
         ) -> anyhow::Result<RequestBatchesResponse> {
+            self.requested_workers
+                .lock()
+                .unwrap()
+                .insert(worker.clone());
             // Use this to simulate server side response size limit in RequestBatches
             const MAX_REQUEST_BATCHES_RESPONSE_SIZE: usize = 2;
             const MAX_READ_BATCH_DIGESTS: usize = 5;
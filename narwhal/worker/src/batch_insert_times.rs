@@ -0,0 +1,64 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use types::{now, BatchDigest, TimestampMs};
+
+#[derive(Default)]
+struct Index {
+    stored_at: HashMap<BatchDigest, TimestampMs>,
+    // The indexed digests, oldest first.
+    order: VecDeque<BatchDigest>,
+}
+
+/// Remembers when the worker handlers stored each batch, so that request_batches can annotate
+/// its responses with batch ages.
+///
+/// Only batches stored since the worker started are known, and the oldest are forgotten past
+/// `capacity` batches. Shared by the handlers of a worker.
+#[derive(Clone)]
+pub struct BatchInsertTimes {
+    capacity: usize,
+    index: Arc<Mutex<Index>>,
+}
+
+impl BatchInsertTimes {
+    pub const DEFAULT_CAPACITY: usize = 100_000;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            index: Arc::default(),
+        }
+    }
+
+    /// Records that the batch was just stored, unless it already was.
+    pub fn record(&self, digest: BatchDigest) {
+        let mut index = self.index.lock().unwrap();
+        if let Entry::Vacant(entry) = index.stored_at.entry(digest) {
+            entry.insert(now());
+            index.order.push_back(digest);
+        }
+        while index.order.len() > self.capacity {
+            let Some(evicted) = index.order.pop_front() else {
+                break;
+            };
+            index.stored_at.remove(&evicted);
+        }
+    }
+
+    /// Returns when the batch was stored, if known.
+    pub fn get(&self, digest: &BatchDigest) -> Option<TimestampMs> {
+        self.index.lock().unwrap().stored_at.get(digest).copied()
+    }
+}
+
+impl Default for BatchInsertTimes {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{sync::Arc, time::Duration};
+
+use fastcrypto::hash::Hash;
+use tokio::task::JoinHandle;
+use tracing::{error, warn};
+use types::BatchDigest;
+
+use crate::{
+    batch_diagnostics::self_test_key,
+    batch_store::{BatchStore, StoreResult},
+    metrics::WorkerMetrics,
+};
+
+#[cfg(test)]
+#[path = "tests/batch_integrity_tests.rs"]
+pub mod batch_integrity_tests;
+
+/// How fast a `BatchIntegrityScanner` goes over the store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntegrityScanConfig {
+    /// The number of batches checked at once.
+    pub page_len: usize,
+    /// The pause between pages, which bounds the read rate of the scan.
+    pub page_interval: Duration,
+    /// The pause between full passes over the store.
+    pub pass_interval: Duration,
+}
+
+impl Default for IntegrityScanConfig {
+    fn default() -> Self {
+        Self {
+            page_len: 100,
+            page_interval: Duration::from_millis(100),
+            pass_interval: Duration::from_secs(3_600),
+        }
+    }
+}
+
+/// Periodically checks that the batches of a store still hash to the keys they are stored
+/// under, to surface disk corruption before a read of the corrupted batch fails.
+///
+/// Mismatches are logged and metered, and if a quarantine store is set, the corrupted entries
+/// are moved there, so that they are no longer served and can be synchronized again.
+pub struct BatchIntegrityScanner<S> {
+    store: S,
+    quarantine: Option<S>,
+    config: IntegrityScanConfig,
+    metrics: Arc<WorkerMetrics>,
+}
+
+impl<S: BatchStore> BatchIntegrityScanner<S> {
+    pub fn new(store: S, config: IntegrityScanConfig, metrics: Arc<WorkerMetrics>) -> Self {
+        Self {
+            store,
+            quarantine: None,
+            config,
+            metrics,
+        }
+    }
+
+    /// Moves corrupted entries to `quarantine` instead of only reporting them.
+    pub fn with_quarantine(mut self, quarantine: S) -> Self {
+        self.quarantine = Some(quarantine);
+        self
+    }
+
+    /// Spawns the task scanning the store until the worker shuts down.
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut cursor = None;
+            loop {
+                match self.scan_page(cursor) {
+                    Ok(Some(last_key)) => {
+                        cursor = Some(last_key);
+                        tokio::time::sleep(self.config.page_interval).await;
+                    }
+                    Ok(None) => {
+                        cursor = None;
+                        tokio::time::sleep(self.config.pass_interval).await;
+                    }
+                    Err(e) => {
+                        // Resume where the scan stopped.
+                        warn!("Failed to scan batch store for corruption: {e:?}");
+                        tokio::time::sleep(self.config.page_interval).await;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Checks the page of entries after `cursor`, returning the last key checked, or None
+    /// once the whole store was checked.
+    fn scan_page(&self, cursor: Option<BatchDigest>) -> StoreResult<Option<BatchDigest>> {
+        let page = self
+            .store
+            .entries_after(cursor, self.config.page_len.max(1))?;
+        let last_key = page.last().map(|(key, _)| *key);
+        for (key, batch) in page {
+            // The store self-test writes a batch under a key that is not its digest.
+            if key == self_test_key() {
+                continue;
+            }
+            if batch.digest() == key {
+                self.record("match");
+                continue;
+            }
+            error!("Batch stored under {key:?} does not match its key, it may be corrupted");
+            self.record("mismatch");
+            if let Some(quarantine) = &self.quarantine {
+                quarantine.insert(&key, &batch)?;
+                self.store.remove(&key)?;
+                self.record("quarantined");
+            }
+        }
+        Ok(last_key)
+    }
+
+    fn record(&self, outcome: &str) {
+        self.metrics
+            .batch_integrity_checks
+            .with_label_values(&[outcome])
+            .inc();
+    }
+}
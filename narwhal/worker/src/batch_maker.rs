@@ -11,7 +11,7 @@ use futures::{Future, StreamExt};
 use mysten_metrics::metered_channel::{Receiver, Sender};
 use mysten_metrics::{monitored_scope, spawn_logged_monitored_task};
 use network::{client::NetworkClient, WorkerToPrimaryClient};
-use store::{rocks::DBMap, Map};
+use store::rocks::DBMap;
 use tokio::{
     task::JoinHandle,
     time::{sleep, Duration, Instant},
@@ -22,7 +22,7 @@ use types::{
     TxResponse, WorkerOurBatchMessage,
 };
 
-use crate::metrics::WorkerMetrics;
+use crate::{batch_store::BatchStore, metrics::WorkerMetrics};
 
 #[cfg(feature = "trace_transaction")]
 use byteorder::{BigEndian, ReadBytesExt};
@@ -37,7 +37,7 @@ pub const MAX_PARALLEL_BATCH: usize = 100;
 pub mod batch_maker_tests;
 
 /// Assemble clients transactions into batches.
-pub struct BatchMaker {
+pub struct BatchMaker<S = DBMap<BatchDigest, Batch>> {
     // Our worker's id.
     id: WorkerId,
     /// The preferred batch size (in bytes).
@@ -58,10 +58,10 @@ pub struct BatchMaker {
     /// The network client to send our batches to the primary.
     client: NetworkClient,
     /// The batch store to store our own batches.
-    store: DBMap<BatchDigest, Batch>,
+    store: S,
 }
 
-impl BatchMaker {
+impl<S: BatchStore> BatchMaker<S> {
     #[must_use]
     pub fn spawn(
         id: WorkerId,
@@ -72,7 +72,7 @@ impl BatchMaker {
         tx_quorum_waiter: Sender<(Batch, tokio::sync::oneshot::Sender<()>)>,
         node_metrics: Arc<WorkerMetrics>,
         client: NetworkClient,
-        store: DBMap<BatchDigest, Batch>,
+        store: S,
     ) -> JoinHandle<()> {
         spawn_logged_monitored_task!(
             async move {
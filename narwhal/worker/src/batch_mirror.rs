@@ -0,0 +1,83 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::mpsc;
+use tracing::warn;
+use types::{Batch, BatchDigest};
+
+use crate::{batch_store::BatchStore, metrics::WorkerMetrics};
+
+/// Mirrors accepted batches to a backup store, e.g. for disaster recovery.
+///
+/// Batches are queued and written in the background, so mirroring never delays or fails
+/// the acknowledgement of the primary write. Failed writes are retried a bounded number of
+/// times; batches that cannot be queued or written are dropped and metered.
+#[derive(Clone)]
+pub struct BatchMirror {
+    sender: mpsc::Sender<(BatchDigest, Batch)>,
+    metrics: Arc<WorkerMetrics>,
+}
+
+impl BatchMirror {
+    pub const DEFAULT_QUEUE_CAPACITY: usize = 1_000;
+    pub const DEFAULT_MAX_RETRIES: usize = 5;
+    pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+    /// Spawns the task writing to the backup store. It stops once every handle is dropped.
+    pub fn spawn<B: BatchStore>(
+        backup: B,
+        queue_capacity: usize,
+        max_retries: usize,
+        retry_delay: Duration,
+        metrics: Arc<WorkerMetrics>,
+    ) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<(BatchDigest, Batch)>(queue_capacity);
+        let task_metrics = metrics.clone();
+        tokio::spawn(async move {
+            while let Some((key, batch)) = receiver.recv().await {
+                let mut attempt = 0;
+                loop {
+                    match backup.insert(&key, &batch) {
+                        Ok(()) => {
+                            task_metrics
+                                .batch_mirror_writes
+                                .with_label_values(&["success"])
+                                .inc();
+                            break;
+                        }
+                        Err(e) if attempt < max_retries => {
+                            attempt += 1;
+                            task_metrics
+                                .batch_mirror_writes
+                                .with_label_values(&["retry"])
+                                .inc();
+                            warn!("Failed to mirror batch {key}, retrying: {e:?}");
+                            tokio::time::sleep(retry_delay).await;
+                        }
+                        Err(e) => {
+                            task_metrics
+                                .batch_mirror_writes
+                                .with_label_values(&["failed"])
+                                .inc();
+                            warn!("Giving up mirroring batch {key}: {e:?}");
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        Self { sender, metrics }
+    }
+
+    /// Queues the batch stored under `key` for mirroring, dropping it if the queue is full.
+    pub fn mirror(&self, key: BatchDigest, batch: Batch) {
+        if self.sender.try_send((key, batch)).is_err() {
+            self.metrics
+                .batch_mirror_writes
+                .with_label_values(&["dropped"])
+                .inc();
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+use types::{now, Batch, BatchDigest, TimestampMs};
+
+use crate::metrics::WorkerMetrics;
+
+/// A batch newly stored by the worker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredBatch {
+    pub digest: BatchDigest,
+    /// Size in bytes of the batch.
+    pub size: usize,
+    /// The worker the batch was received from, if known.
+    pub sender: Option<anemo::PeerId>,
+    /// When the batch was stored.
+    pub stored_at: TimestampMs,
+}
+
+/// Notifies external tooling, e.g. analytics or explorers, of every batch newly stored by
+/// the worker handlers.
+///
+/// Notifications go through a bounded channel. When the consumer falls behind they are
+/// dropped and metered, so observing never stalls the handlers.
+#[derive(Clone)]
+pub struct BatchObserver {
+    sender: mpsc::Sender<StoredBatch>,
+    metrics: Arc<WorkerMetrics>,
+}
+
+impl BatchObserver {
+    pub const DEFAULT_QUEUE_CAPACITY: usize = 1_000;
+
+    /// Returns the observer, and the receiving end of its notifications.
+    pub fn new(
+        queue_capacity: usize,
+        metrics: Arc<WorkerMetrics>,
+    ) -> (Self, mpsc::Receiver<StoredBatch>) {
+        let (sender, receiver) = mpsc::channel(queue_capacity);
+        (Self { sender, metrics }, receiver)
+    }
+
+    /// Notifies that the batch was just stored, dropping the notification if the queue is
+    /// full.
+    pub fn observe(&self, digest: BatchDigest, batch: &Batch, sender: Option<anemo::PeerId>) {
+        let stored_batch = StoredBatch {
+            digest,
+            size: batch.size(),
+            sender,
+            stored_at: now(),
+        };
+        let status = match self.sender.try_send(stored_batch) {
+            Ok(()) => "sent",
+            Err(_) => "dropped",
+        };
+        self.metrics
+            .stored_batch_notifications
+            .with_label_values(&[status])
+            .inc();
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::Semaphore;
+use tracing::debug;
+use types::BatchDigest;
+
+use crate::batch_store::BatchStore;
+
+#[derive(Default)]
+struct InsertionOrder {
+    // The position of each recorded key, and the keys by position.
+    positions: HashMap<BatchDigest, u64>,
+    keys: BTreeMap<u64, BatchDigest>,
+    next_position: u64,
+}
+
+/// Warms the store with the batches stored around the same time as a served batch, since
+/// batches created together tend to be requested together.
+///
+/// Serving a batch reads the `radius` batches stored right before and after it in the
+/// background, which caches them in front of the store, see `CachedBatchStore`. Only the
+/// last `capacity` stored batches are remembered, and at most `max_in_flight` prefetches
+/// run at once: reads past that are not prefetched for.
+#[derive(Clone)]
+pub struct BatchPrefetcher {
+    radius: usize,
+    capacity: usize,
+    order: Arc<Mutex<InsertionOrder>>,
+    in_flight: Arc<Semaphore>,
+}
+
+impl BatchPrefetcher {
+    pub const DEFAULT_RADIUS: usize = 4;
+    pub const DEFAULT_CAPACITY: usize = 100_000;
+    pub const DEFAULT_MAX_IN_FLIGHT: usize = 4;
+
+    pub fn new(radius: usize, capacity: usize, max_in_flight: usize) -> Self {
+        Self {
+            radius,
+            capacity,
+            order: Arc::default(),
+            in_flight: Arc::new(Semaphore::new(max_in_flight)),
+        }
+    }
+
+    /// Records that a batch was stored under `key`.
+    pub fn record(&self, key: BatchDigest) {
+        let mut order = self.order.lock().unwrap();
+        let position = order.next_position;
+        order.next_position += 1;
+        if let Some(previous) = order.positions.insert(key, position) {
+            order.keys.remove(&previous);
+        }
+        order.keys.insert(position, key);
+        while order.keys.len() > self.capacity {
+            let Some((_, evicted)) = order.keys.pop_first() else {
+                break;
+            };
+            order.positions.remove(&evicted);
+        }
+    }
+
+    /// Returns the keys stored within `radius` batches of `key`, before and after it.
+    pub fn neighbors(&self, key: &BatchDigest) -> Vec<BatchDigest> {
+        let order = self.order.lock().unwrap();
+        let Some(&position) = order.positions.get(key) else {
+            return Vec::new();
+        };
+        let before = order.keys.range(..position).rev().take(self.radius);
+        let after = order.keys.range(position + 1..).take(self.radius);
+        before.chain(after).map(|(_, key)| *key).collect()
+    }
+
+    /// Reads the neighbors of the batch stored under `key` from `store` in the background.
+    pub fn prefetch<S: BatchStore>(&self, store: &S, key: &BatchDigest) {
+        let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+            return;
+        };
+        let neighbors = self.neighbors(key);
+        if neighbors.is_empty() {
+            return;
+        }
+        let store = store.clone();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            if let Err(e) = store.multi_get(&neighbors) {
+                debug!("Failed to prefetch {} batches: {e:?}", neighbors.len());
+            }
+        });
+    }
+}
+
+impl Default for BatchPrefetcher {
+    fn default() -> Self {
+        Self::new(
+            Self::DEFAULT_RADIUS,
+            Self::DEFAULT_CAPACITY,
+            Self::DEFAULT_MAX_IN_FLIGHT,
+        )
+    }
+}
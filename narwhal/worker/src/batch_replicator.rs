@@ -0,0 +1,100 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{sync::Arc, time::Duration};
+
+use anemo::Network;
+use futures::future::join_all;
+use tokio::sync::mpsc;
+use tracing::debug;
+use types::{Batch, WorkerBatchMessage, WorkerToWorkerClient};
+
+use crate::metrics::WorkerMetrics;
+
+#[cfg(test)]
+#[path = "tests/batch_replicator_tests.rs"]
+pub mod batch_replicator_tests;
+
+/// Pushes newly accepted batches to other workers, so that they are available from more
+/// peers before any primary asks for them.
+///
+/// Batches are queued and pushed in the background, so replication never delays the
+/// acknowledgement of `report_batch`. Each batch is pushed once, with a timeout, to
+/// `replication_factor` connected peers other than the one that sent it, picked in
+/// rotation among the candidates. Batches that cannot be queued are dropped. Every push is
+/// metered by outcome.
+#[derive(Clone)]
+pub struct BatchReplicator {
+    sender: mpsc::Sender<(Batch, Option<anemo::PeerId>)>,
+    metrics: Arc<WorkerMetrics>,
+}
+
+impl BatchReplicator {
+    pub const DEFAULT_QUEUE_CAPACITY: usize = 1_000;
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Spawns the task pushing batches to `peers`, e.g. the workers with our id of the other
+    /// authorities. It stops once every handle is dropped.
+    pub fn spawn(
+        network: Network,
+        peers: Vec<anemo::PeerId>,
+        replication_factor: usize,
+        queue_capacity: usize,
+        timeout: Duration,
+        metrics: Arc<WorkerMetrics>,
+    ) -> Self {
+        let (sender, mut receiver) =
+            mpsc::channel::<(Batch, Option<anemo::PeerId>)>(queue_capacity);
+        let task_metrics = metrics.clone();
+        tokio::spawn(async move {
+            let mut next_peer = 0;
+            while let Some((batch, source)) = receiver.recv().await {
+                let targets: Vec<_> = (0..peers.len())
+                    .map(|i| peers[(next_peer + i) % peers.len()])
+                    .filter(|peer_id| Some(*peer_id) != source)
+                    .filter_map(|peer_id| network.peer(peer_id))
+                    .take(replication_factor)
+                    .collect();
+                next_peer = (next_peer + 1) % peers.len().max(1);
+                if targets.len() < replication_factor {
+                    debug!(
+                        "Replicating batch to {} peers only, fewer than the {replication_factor} requested",
+                        targets.len()
+                    );
+                }
+                let pushes = targets.into_iter().map(|peer| {
+                    let request = anemo::Request::new(WorkerBatchMessage {
+                        batch: batch.clone(),
+                    })
+                    .with_timeout(timeout);
+                    async move { WorkerToWorkerClient::new(peer).report_batch(request).await }
+                });
+                for result in join_all(pushes).await {
+                    let outcome = match result {
+                        Ok(_) => "success",
+                        Err(e) => {
+                            debug!("Failed to replicate batch: {e:?}");
+                            "failed"
+                        }
+                    };
+                    task_metrics
+                        .batch_replication_pushes
+                        .with_label_values(&[outcome])
+                        .inc();
+                }
+            }
+        });
+        Self { sender, metrics }
+    }
+
+    /// Queues `batch`, received from `source`, for replication, dropping it if the queue
+    /// is full.
+    pub fn replicate(&self, batch: Batch, source: Option<anemo::PeerId>) {
+        if self.sender.try_send((batch, source)).is_err() {
+            self.metrics
+                .batch_replication_pushes
+                .with_label_values(&["dropped"])
+                .inc();
+        }
+    }
+}
@@ -0,0 +1,455 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Bound,
+    sync::{Arc, RwLock},
+};
+
+use config::{AuthorityIdentifier, Epoch};
+use store::{rocks::DBMap, TypedStoreError};
+use types::{transaction_digest, Batch, BatchAPI, BatchDigest, BatchSummary, TransactionDigest};
+
+#[cfg(test)]
+#[path = "tests/batch_store_tests.rs"]
+pub mod batch_store_tests;
+
+/// Convenience type to propagate store errors.
+pub type StoreResult<T> = Result<T, TypedStoreError>;
+
+/// The storage backend of the worker handlers. Batches are keyed by digest, and keys are
+/// ordered so that the whole store can be scanned in pages.
+pub trait BatchStore: Clone + Send + Sync + 'static {
+    fn get(&self, key: &BatchDigest) -> StoreResult<Option<Batch>>;
+
+    fn multi_get(&self, keys: &[BatchDigest]) -> StoreResult<Vec<Option<Batch>>>;
+
+    /// Returns the summary of the batch stored under each of the keys. Backends keeping
+    /// summaries aside from the batches should serve them without reading the batches.
+    fn multi_get_summaries(&self, keys: &[BatchDigest]) -> StoreResult<Vec<Option<BatchSummary>>> {
+        Ok(self
+            .multi_get(keys)?
+            .iter()
+            .map(|batch| batch.as_ref().map(BatchSummary::new))
+            .collect())
+    }
+
+    fn insert(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()>;
+
+    /// Inserts a batch referenced by a recent certificate. Caching backends keep such
+    /// batches around longer, see `CachedBatchStore`.
+    fn insert_referenced(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        self.insert(key, batch)
+    }
+
+    /// Inserts several batches, atomically if the backend allows it.
+    fn multi_insert(&self, entries: &[(BatchDigest, Batch)]) -> StoreResult<()> {
+        for (key, batch) in entries {
+            self.insert(key, batch)?;
+        }
+        Ok(())
+    }
+
+    /// Like `insert`, also indexing the batch digest under the digest of each of its
+    /// transactions in the same write, see `batch_of_transaction`. Backends without a
+    /// transaction index only insert the batch.
+    fn insert_indexed(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        self.insert(key, batch)
+    }
+
+    /// Returns the digest of the batch indexed under the given transaction digest, if any. The
+    /// index is not updated when batches are removed, so the batch may no longer be stored.
+    fn batch_of_transaction(
+        &self,
+        _transaction: &TransactionDigest,
+    ) -> StoreResult<Option<BatchDigest>> {
+        Ok(None)
+    }
+
+    fn remove(&self, key: &BatchDigest) -> StoreResult<()>;
+
+    fn multi_remove(&self, keys: &[BatchDigest]) -> StoreResult<()>;
+
+    /// Removes the batches of `epoch`, for backends partitioning batches by epoch, see
+    /// `PartitionedBatchStore`. Other backends do not know the epoch of their batches, and
+    /// keep them. Backends shared by several authorities only remove those of their own.
+    fn remove_epoch(&self, _epoch: Epoch) -> StoreResult<()> {
+        Ok(())
+    }
+
+    fn contains_key(&self, key: &BatchDigest) -> StoreResult<bool>;
+
+    /// Returns whether each of the keys is present, without reading the batches if the
+    /// backend allows it.
+    fn multi_contains_keys(&self, keys: &[BatchDigest]) -> StoreResult<Vec<bool>> {
+        Ok(self
+            .multi_get(keys)?
+            .iter()
+            .map(|batch| batch.is_some())
+            .collect())
+    }
+
+    /// Returns up to `limit` entries in key order, starting right after `cursor`, or from
+    /// the beginning of the store if `cursor` is None.
+    fn entries_after(
+        &self,
+        cursor: Option<BatchDigest>,
+        limit: usize,
+    ) -> StoreResult<Vec<(BatchDigest, Batch)>>;
+
+    /// Returns the value of an integer RocksDB property of the store, e.g.
+    /// `rocksdb.compaction-pending`, or None if the backend does not have it.
+    fn int_property(&self, _name: &str) -> StoreResult<Option<u64>> {
+        Ok(None)
+    }
+}
+
+/// The default backend, persisting batches to RocksDB.
+impl BatchStore for DBMap<BatchDigest, Batch> {
+    fn get(&self, key: &BatchDigest) -> StoreResult<Option<Batch>> {
+        store::Map::get(self, key)
+    }
+
+    fn multi_get(&self, keys: &[BatchDigest]) -> StoreResult<Vec<Option<Batch>>> {
+        store::Map::multi_get(self, keys)
+    }
+
+    fn insert(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        store::Map::insert(self, key, batch)
+    }
+
+    fn multi_insert(&self, entries: &[(BatchDigest, Batch)]) -> StoreResult<()> {
+        store::Map::multi_insert(self, entries.iter().map(|(key, batch)| (key, batch)))
+    }
+
+    fn remove(&self, key: &BatchDigest) -> StoreResult<()> {
+        store::Map::remove(self, key)
+    }
+
+    fn multi_remove(&self, keys: &[BatchDigest]) -> StoreResult<()> {
+        store::Map::multi_remove(self, keys)
+    }
+
+    fn contains_key(&self, key: &BatchDigest) -> StoreResult<bool> {
+        store::Map::contains_key(self, key)
+    }
+
+    fn multi_contains_keys(&self, keys: &[BatchDigest]) -> StoreResult<Vec<bool>> {
+        // Skip deserializing the batches.
+        Ok(store::Map::multi_get_raw_bytes(self, keys)?
+            .iter()
+            .map(|bytes| bytes.is_some())
+            .collect())
+    }
+
+    fn entries_after(
+        &self,
+        cursor: Option<BatchDigest>,
+        limit: usize,
+    ) -> StoreResult<Vec<(BatchDigest, Batch)>> {
+        let lower_bound = match cursor {
+            Some(cursor) => Bound::Excluded(cursor),
+            None => Bound::Unbounded,
+        };
+        Ok(
+            store::Map::range_iter(self, (lower_bound, Bound::Unbounded))
+                .take(limit)
+                .collect(),
+        )
+    }
+
+    fn int_property(&self, name: &str) -> StoreResult<Option<u64>> {
+        self.rocksdb
+            .property_int_value_cf(&self.cf(), name)
+            .map_err(|e| TypedStoreError::RocksDBError(e.into_string()))
+    }
+}
+
+/// A RocksDB backend that also indexes batches by the digests of their transactions. Both
+/// maps must belong to the same database, so that a batch and its index entries are written
+/// atomically.
+#[derive(Clone)]
+pub struct IndexedBatchStore {
+    batches: DBMap<BatchDigest, Batch>,
+    transactions: DBMap<TransactionDigest, BatchDigest>,
+}
+
+impl IndexedBatchStore {
+    pub fn new(
+        batches: DBMap<BatchDigest, Batch>,
+        transactions: DBMap<TransactionDigest, BatchDigest>,
+    ) -> Self {
+        Self {
+            batches,
+            transactions,
+        }
+    }
+}
+
+impl BatchStore for IndexedBatchStore {
+    fn get(&self, key: &BatchDigest) -> StoreResult<Option<Batch>> {
+        BatchStore::get(&self.batches, key)
+    }
+
+    fn multi_get(&self, keys: &[BatchDigest]) -> StoreResult<Vec<Option<Batch>>> {
+        BatchStore::multi_get(&self.batches, keys)
+    }
+
+    fn insert(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        BatchStore::insert(&self.batches, key, batch)
+    }
+
+    fn multi_insert(&self, entries: &[(BatchDigest, Batch)]) -> StoreResult<()> {
+        BatchStore::multi_insert(&self.batches, entries)
+    }
+
+    fn insert_indexed(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        let mut write_batch = self.batches.batch();
+        write_batch.insert_batch(&self.batches, std::iter::once((key, batch)))?;
+        write_batch.insert_batch(
+            &self.transactions,
+            batch
+                .transactions()
+                .iter()
+                .map(|transaction| (transaction_digest(transaction), key)),
+        )?;
+        write_batch.write()
+    }
+
+    fn batch_of_transaction(
+        &self,
+        transaction: &TransactionDigest,
+    ) -> StoreResult<Option<BatchDigest>> {
+        store::Map::get(&self.transactions, transaction)
+    }
+
+    fn remove(&self, key: &BatchDigest) -> StoreResult<()> {
+        BatchStore::remove(&self.batches, key)
+    }
+
+    fn multi_remove(&self, keys: &[BatchDigest]) -> StoreResult<()> {
+        BatchStore::multi_remove(&self.batches, keys)
+    }
+
+    fn contains_key(&self, key: &BatchDigest) -> StoreResult<bool> {
+        BatchStore::contains_key(&self.batches, key)
+    }
+
+    fn multi_contains_keys(&self, keys: &[BatchDigest]) -> StoreResult<Vec<bool>> {
+        BatchStore::multi_contains_keys(&self.batches, keys)
+    }
+
+    fn entries_after(
+        &self,
+        cursor: Option<BatchDigest>,
+        limit: usize,
+    ) -> StoreResult<Vec<(BatchDigest, Batch)>> {
+        BatchStore::entries_after(&self.batches, cursor, limit)
+    }
+
+    fn int_property(&self, name: &str) -> StoreResult<Option<u64>> {
+        BatchStore::int_property(&self.batches, name)
+    }
+}
+
+/// A RocksDB backend partitioning batches by epoch and authority. Batches are keyed by
+/// epoch, then authority, then digest, so that the batches of an epoch are a contiguous
+/// range of the map, removed at once with `BatchStore::remove_epoch`, and several
+/// authorities can share the map, e.g. when colocated in one test process, without any of
+/// them observing the batches of the others. Every component of an authority must go
+/// through the same partition, see `PartitionedBatchStore::new`.
+#[derive(Clone)]
+pub struct PartitionedBatchStore {
+    batches: DBMap<(Epoch, AuthorityIdentifier, BatchDigest), Batch>,
+    epoch: Epoch,
+    authority: AuthorityIdentifier,
+}
+
+impl PartitionedBatchStore {
+    /// Returns the partition of `authority` for `epoch` within `batches`.
+    pub fn new(
+        batches: DBMap<(Epoch, AuthorityIdentifier, BatchDigest), Batch>,
+        epoch: Epoch,
+        authority: AuthorityIdentifier,
+    ) -> Self {
+        Self {
+            batches,
+            epoch,
+            authority,
+        }
+    }
+
+    fn key(&self, digest: &BatchDigest) -> (Epoch, AuthorityIdentifier, BatchDigest) {
+        (self.epoch, self.authority, *digest)
+    }
+}
+
+impl BatchStore for PartitionedBatchStore {
+    fn get(&self, key: &BatchDigest) -> StoreResult<Option<Batch>> {
+        store::Map::get(&self.batches, &self.key(key))
+    }
+
+    fn multi_get(&self, keys: &[BatchDigest]) -> StoreResult<Vec<Option<Batch>>> {
+        store::Map::multi_get(&self.batches, keys.iter().map(|key| self.key(key)))
+    }
+
+    fn insert(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        store::Map::insert(&self.batches, &self.key(key), batch)
+    }
+
+    fn multi_insert(&self, entries: &[(BatchDigest, Batch)]) -> StoreResult<()> {
+        store::Map::multi_insert(
+            &self.batches,
+            entries.iter().map(|(key, batch)| (self.key(key), batch)),
+        )
+    }
+
+    fn remove(&self, key: &BatchDigest) -> StoreResult<()> {
+        store::Map::remove(&self.batches, &self.key(key))
+    }
+
+    fn multi_remove(&self, keys: &[BatchDigest]) -> StoreResult<()> {
+        store::Map::multi_remove(&self.batches, keys.iter().map(|key| self.key(key)))
+    }
+
+    fn remove_epoch(&self, epoch: Epoch) -> StoreResult<()> {
+        // Only the batches of our authority, the others sharing the map remove their own.
+        // Range deletes exclude their upper bound.
+        let start = (epoch, self.authority, BatchDigest::new([0; 32]));
+        let end = (epoch, self.authority, BatchDigest::new([u8::MAX; 32]));
+        let mut batch = self.batches.batch();
+        batch.delete_range(&self.batches, &start, &end)?;
+        batch.delete_batch(&self.batches, [end])?;
+        batch.write()
+    }
+
+    fn contains_key(&self, key: &BatchDigest) -> StoreResult<bool> {
+        store::Map::contains_key(&self.batches, &self.key(key))
+    }
+
+    fn multi_contains_keys(&self, keys: &[BatchDigest]) -> StoreResult<Vec<bool>> {
+        // Skip deserializing the batches.
+        Ok(
+            store::Map::multi_get_raw_bytes(&self.batches, keys.iter().map(|key| self.key(key)))?
+                .iter()
+                .map(|bytes| bytes.is_some())
+                .collect(),
+        )
+    }
+
+    fn entries_after(
+        &self,
+        cursor: Option<BatchDigest>,
+        limit: usize,
+    ) -> StoreResult<Vec<(BatchDigest, Batch)>> {
+        let lower_bound = match cursor {
+            Some(cursor) => Bound::Excluded(self.key(&cursor)),
+            None => Bound::Included(self.key(&BatchDigest::new([0; 32]))),
+        };
+        let upper_bound = Bound::Included(self.key(&BatchDigest::new([u8::MAX; 32])));
+        Ok(
+            store::Map::range_iter(&self.batches, (lower_bound, upper_bound))
+                .take(limit)
+                .map(|((_, _, digest), batch)| (digest, batch))
+                .collect(),
+        )
+    }
+
+    fn int_property(&self, name: &str) -> StoreResult<Option<u64>> {
+        self.batches
+            .rocksdb
+            .property_int_value_cf(&self.batches.cf(), name)
+            .map_err(|e| TypedStoreError::RocksDBError(e.into_string()))
+    }
+}
+
+/// An in-memory backend, mostly useful for tests.
+#[derive(Clone, Default)]
+pub struct MemoryBatchStore {
+    batches: Arc<RwLock<BTreeMap<BatchDigest, Batch>>>,
+    transactions: Arc<RwLock<HashMap<TransactionDigest, BatchDigest>>>,
+}
+
+impl BatchStore for MemoryBatchStore {
+    fn get(&self, key: &BatchDigest) -> StoreResult<Option<Batch>> {
+        Ok(self.batches.read().unwrap().get(key).cloned())
+    }
+
+    fn multi_get(&self, keys: &[BatchDigest]) -> StoreResult<Vec<Option<Batch>>> {
+        let batches = self.batches.read().unwrap();
+        Ok(keys.iter().map(|key| batches.get(key).cloned()).collect())
+    }
+
+    fn insert(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        self.batches.write().unwrap().insert(*key, batch.clone());
+        Ok(())
+    }
+
+    fn multi_insert(&self, entries: &[(BatchDigest, Batch)]) -> StoreResult<()> {
+        self.batches
+            .write()
+            .unwrap()
+            .extend(entries.iter().cloned());
+        Ok(())
+    }
+
+    fn insert_indexed(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        // Hold both locks, so that readers never see the batch without its index entries.
+        let mut batches = self.batches.write().unwrap();
+        let mut transactions = self.transactions.write().unwrap();
+        batches.insert(*key, batch.clone());
+        for transaction in batch.transactions() {
+            transactions.insert(transaction_digest(transaction), *key);
+        }
+        Ok(())
+    }
+
+    fn batch_of_transaction(
+        &self,
+        transaction: &TransactionDigest,
+    ) -> StoreResult<Option<BatchDigest>> {
+        Ok(self.transactions.read().unwrap().get(transaction).copied())
+    }
+
+    fn remove(&self, key: &BatchDigest) -> StoreResult<()> {
+        self.batches.write().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn multi_remove(&self, keys: &[BatchDigest]) -> StoreResult<()> {
+        let mut batches = self.batches.write().unwrap();
+        for key in keys {
+            batches.remove(key);
+        }
+        Ok(())
+    }
+
+    fn contains_key(&self, key: &BatchDigest) -> StoreResult<bool> {
+        Ok(self.batches.read().unwrap().contains_key(key))
+    }
+
+    fn multi_contains_keys(&self, keys: &[BatchDigest]) -> StoreResult<Vec<bool>> {
+        let batches = self.batches.read().unwrap();
+        Ok(keys.iter().map(|key| batches.contains_key(key)).collect())
+    }
+
+    fn entries_after(
+        &self,
+        cursor: Option<BatchDigest>,
+        limit: usize,
+    ) -> StoreResult<Vec<(BatchDigest, Batch)>> {
+        let lower_bound = match cursor {
+            Some(cursor) => Bound::Excluded(cursor),
+            None => Bound::Unbounded,
+        };
+        Ok(self
+            .batches
+            .read()
+            .unwrap()
+            .range((lower_bound, Bound::Unbounded))
+            .take(limit)
+            .map(|(key, batch)| (*key, batch.clone()))
+            .collect())
+    }
+}
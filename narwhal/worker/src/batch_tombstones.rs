@@ -0,0 +1,162 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use store::{rocks::DBMap, Map};
+use tracing::warn;
+use types::{now, BatchDigest, TimestampMs};
+
+use crate::batch_store::StoreResult;
+
+/// Batches deleted by `delete_batches` that are no longer served but not removed from the
+/// store yet, so that a mistaken delete can be undone during the grace period. Shared by the
+/// handlers of a worker, and keyed by store key.
+#[derive(Clone)]
+pub struct BatchTombstones {
+    // The id of the deletion that tombstoned each key.
+    tombstones: Arc<Mutex<HashMap<BatchDigest, u64>>>,
+    next_deletion: Arc<Mutex<u64>>,
+    grace_period: Duration,
+    // If set, the end of the grace period of each tombstone is persisted, so that the
+    // batches are still not served, and removed, after a restart.
+    store: Option<DBMap<BatchDigest, TimestampMs>>,
+}
+
+impl BatchTombstones {
+    /// With a zero `grace_period`, batches are removed immediately, as without tombstones.
+    pub fn new(grace_period: Duration) -> Self {
+        Self {
+            tombstones: Arc::default(),
+            next_deletion: Arc::default(),
+            grace_period,
+            store: None,
+        }
+    }
+
+    /// Like `new`, persisting the tombstones to `store`. The tombstones persisted before a
+    /// restart are loaded back, see `reload`.
+    pub fn persisted(grace_period: Duration, store: DBMap<BatchDigest, TimestampMs>) -> Self {
+        Self {
+            store: Some(store),
+            ..Self::new(grace_period)
+        }
+    }
+
+    pub fn grace_period(&self) -> Duration {
+        self.grace_period
+    }
+
+    /// Returns whether the batch stored under `key` is deleted.
+    pub fn contains(&self, key: &BatchDigest) -> bool {
+        self.tombstones.lock().unwrap().contains_key(key)
+    }
+
+    /// Lifts the tombstones of the given keys, so that their batches are served again and
+    /// not removed. Returns the number of tombstones lifted.
+    pub fn restore(&self, keys: &[BatchDigest]) -> usize {
+        let mut tombstones = self.tombstones.lock().unwrap();
+        let restored: Vec<_> = keys
+            .iter()
+            .filter(|key| tombstones.remove(key).is_some())
+            .copied()
+            .collect();
+        self.unpersist(&restored);
+        restored.len()
+    }
+
+    /// Tombstones the given keys, returning the id of this deletion. Fails if the tombstones
+    /// cannot be persisted, in which case the keys are not tombstoned.
+    pub(crate) fn mark(&self, keys: &[BatchDigest]) -> StoreResult<u64> {
+        let deletion = self.next_deletion();
+        let mut tombstones = self.tombstones.lock().unwrap();
+        // Persisted with the tombstones locked, see `unpersist`.
+        if let Some(store) = &self.store {
+            let deadline = now() + self.grace_period.as_millis() as TimestampMs;
+            store.multi_insert(keys.iter().map(|key| (key, deadline)))?;
+        }
+        for key in keys {
+            tombstones.insert(*key, deletion);
+        }
+        Ok(deletion)
+    }
+
+    /// Loads back the tombstones persisted before a restart. Returns the keys tombstoned by
+    /// each deletion, with the id of the deletion and the remainder of its grace period.
+    pub(crate) fn reload(&self) -> StoreResult<Vec<(u64, Vec<BatchDigest>, Duration)>> {
+        let Some(store) = &self.store else {
+            return Ok(Vec::new());
+        };
+        // The keys of a deletion share the end of its grace period.
+        let mut deletions: BTreeMap<TimestampMs, Vec<BatchDigest>> = BTreeMap::new();
+        for (key, deadline) in store.unbounded_iter() {
+            deletions.entry(deadline).or_default().push(key);
+        }
+        let reloaded_at = now();
+        let mut tombstones = self.tombstones.lock().unwrap();
+        Ok(deletions
+            .into_iter()
+            .map(|(deadline, keys)| {
+                let deletion = self.next_deletion();
+                for key in &keys {
+                    tombstones.insert(*key, deletion);
+                }
+                let remaining = Duration::from_millis(deadline.saturating_sub(reloaded_at));
+                (deletion, keys, remaining)
+            })
+            .collect())
+    }
+
+    fn next_deletion(&self) -> u64 {
+        let mut next_deletion = self.next_deletion.lock().unwrap();
+        *next_deletion += 1;
+        *next_deletion
+    }
+
+    /// Returns the given keys still tombstoned by `deletion`, i.e. not restored or deleted
+    /// again since.
+    pub(crate) fn expired(&self, keys: &[BatchDigest], deletion: u64) -> Vec<BatchDigest> {
+        let tombstones = self.tombstones.lock().unwrap();
+        keys.iter()
+            .filter(|key| tombstones.get(key) == Some(&deletion))
+            .copied()
+            .collect()
+    }
+
+    /// Drops the tombstones placed by `deletion` on the given keys, once their batches are
+    /// removed from the store.
+    pub(crate) fn clear(&self, keys: &[BatchDigest], deletion: u64) {
+        let mut tombstones = self.tombstones.lock().unwrap();
+        let cleared: Vec<_> = keys
+            .iter()
+            .filter(|key| tombstones.get(key) == Some(&deletion))
+            .copied()
+            .collect();
+        for key in &cleared {
+            tombstones.remove(key);
+        }
+        self.unpersist(&cleared);
+    }
+
+    // Called with the tombstones locked, so that the persisted tombstones are updated in the
+    // same order as the ones in memory.
+    fn unpersist(&self, keys: &[BatchDigest]) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        if keys.is_empty() {
+            return;
+        }
+        if let Err(e) = store.multi_remove(keys) {
+            // The batches of restored tombstones would be removed after a restart.
+            warn!(
+                "Failed to remove {} persisted batch tombstones: {e:?}",
+                keys.len()
+            );
+        }
+    }
+}
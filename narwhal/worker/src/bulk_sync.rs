@@ -0,0 +1,133 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use types::BatchDigest;
+
+/// Tracks the progress of the bulk sync sessions served by this worker.
+///
+/// A far-behind worker opens a session and then pulls the whole store page by page. The
+/// server records the cursor of the last page each requester acknowledged, so a requester
+/// retrying after a failure resumes where it left off instead of starting over. Sessions
+/// stay open after completion, so the last page can be retried too, until evicted.
+///
+/// Sessions are bound to the peer that opened them: other peers can neither read nor advance
+/// them. Each peer holds at most `max_sessions_per_peer` sessions, so a single peer opening
+/// sessions in a loop only evicts its own.
+#[derive(Clone)]
+pub struct BulkSyncSessions {
+    inner: Arc<Mutex<Inner>>,
+    max_sessions: usize,
+    max_sessions_per_peer: usize,
+    max_page_size: usize,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_session_id: u64,
+    sessions: BTreeMap<u64, Session>,
+}
+
+struct Session {
+    peer: Option<anemo::PeerId>,
+    // Cursor of the last page acknowledged by the requester.
+    cursor: Option<BatchDigest>,
+}
+
+impl BulkSyncSessions {
+    pub const DEFAULT_MAX_SESSIONS: usize = 16;
+    pub const DEFAULT_MAX_SESSIONS_PER_PEER: usize = 2;
+    pub const DEFAULT_MAX_PAGE_SIZE: usize = 6_000_000;
+
+    pub fn new(max_sessions: usize, max_sessions_per_peer: usize, max_page_size: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner::default())),
+            max_sessions,
+            max_sessions_per_peer,
+            max_page_size,
+        }
+    }
+
+    /// Maximum total size in bytes of the batches returned in a single page.
+    pub fn max_page_size(&self) -> usize {
+        self.max_page_size
+    }
+
+    /// Opens a new session of `peer` starting from the beginning of the store. When `peer`
+    /// holds too many sessions, its oldest one is evicted, and when too many sessions are open
+    /// overall, the oldest one of any peer is.
+    pub fn open(&self, peer: Option<anemo::PeerId>) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        let session_id = inner.next_session_id;
+        inner.next_session_id += 1;
+        inner
+            .sessions
+            .insert(session_id, Session { peer, cursor: None });
+        // Session ids increase, so the map iterates the sessions of `peer` oldest first.
+        let peer_sessions: Vec<_> = inner
+            .sessions
+            .iter()
+            .filter(|(_, session)| session.peer == peer)
+            .map(|(id, _)| *id)
+            .collect();
+        let excess = peer_sessions
+            .len()
+            .saturating_sub(self.max_sessions_per_peer);
+        for id in &peer_sessions[..excess] {
+            inner.sessions.remove(id);
+        }
+        while inner.sessions.len() > self.max_sessions {
+            inner.sessions.pop_first();
+        }
+        session_id
+    }
+
+    /// Returns the cursor recorded for the session, or None if the session is unknown or was
+    /// not opened by `peer`.
+    pub fn progress(
+        &self,
+        peer: Option<&anemo::PeerId>,
+        session_id: u64,
+    ) -> Option<Option<BatchDigest>> {
+        self.inner
+            .lock()
+            .unwrap()
+            .sessions
+            .get(&session_id)
+            .filter(|session| session.peer.as_ref() == peer)
+            .map(|session| session.cursor)
+    }
+
+    /// Records the cursor acknowledged by `peer` for a session it opened.
+    pub fn record(
+        &self,
+        peer: Option<&anemo::PeerId>,
+        session_id: u64,
+        cursor: Option<BatchDigest>,
+    ) {
+        if let Some(session) = self
+            .inner
+            .lock()
+            .unwrap()
+            .sessions
+            .get_mut(&session_id)
+            .filter(|session| session.peer.as_ref() == peer)
+        {
+            session.cursor = cursor;
+        }
+    }
+}
+
+impl Default for BulkSyncSessions {
+    fn default() -> Self {
+        Self::new(
+            Self::DEFAULT_MAX_SESSIONS,
+            Self::DEFAULT_MAX_SESSIONS_PER_PEER,
+            Self::DEFAULT_MAX_PAGE_SIZE,
+        )
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{extract::Extension, http::StatusCode, routing::get, Router};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::batch_store::{BatchStore, StoreResult};
+
+#[cfg(test)]
+#[path = "tests/capacity_planning_tests.rs"]
+pub mod capacity_planning_tests;
+
+/// Tells how many more bytes the batch store may grow by.
+pub trait DiskSpace: Send + Sync + 'static {
+    /// Returns the bytes left for the store, given the bytes it currently takes, or None if
+    /// unknown.
+    fn available_bytes(&self, store_bytes: u64) -> Option<u64>;
+}
+
+/// A fixed budget of disk space for the batch store, e.g. the size of its volume.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiskBudget(pub u64);
+
+impl DiskSpace for DiskBudget {
+    fn available_bytes(&self, store_bytes: u64) -> Option<u64> {
+        Some(self.0.saturating_sub(store_bytes))
+    }
+}
+
+/// How the batch store grows, and when it is projected to fill the disk.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapacityProjection {
+    /// Estimated by RocksDB, see `rocksdb.estimate-num-keys`.
+    pub batch_count: u64,
+    /// The bytes the store takes on disk, see `rocksdb.total-sst-files-size`.
+    pub total_bytes: u64,
+    /// The growth of `total_bytes` over the recent samples, or None before two samples.
+    pub ingest_bytes_per_sec: Option<f64>,
+    pub available_bytes: Option<u64>,
+    /// None unless the store is growing and the available space is known.
+    pub time_to_full: Option<Duration>,
+}
+
+impl fmt::Display for CapacityProjection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} batches, {} bytes",
+            self.batch_count, self.total_bytes
+        )?;
+        match self.ingest_bytes_per_sec {
+            Some(rate) => write!(f, ", ingesting {rate:.0} bytes/s")?,
+            None => write!(f, ", ingest rate unknown")?,
+        }
+        if let Some(available) = self.available_bytes {
+            write!(f, ", {available} bytes available")?;
+        }
+        match self.time_to_full {
+            Some(time_to_full) => write!(f, ", full in {} s", time_to_full.as_secs()),
+            None => write!(f, ", not projected to fill"),
+        }
+    }
+}
+
+/// Returns when `available_bytes` are used up at `ingest_bytes_per_sec`, or None if the
+/// store is not growing.
+fn time_to_full(available_bytes: u64, ingest_bytes_per_sec: f64) -> Option<Duration> {
+    (ingest_bytes_per_sec > 0.0)
+        .then(|| Duration::from_secs_f64(available_bytes as f64 / ingest_bytes_per_sec))
+}
+
+/// Projects when the batch store fills the disk, from the growth of its size over the
+/// recent samples. The store keeps no insertion timestamps, so the ingest rate is only known
+/// once the planner sampled the store twice. Served on the admin server.
+#[derive(Clone)]
+pub struct CapacityPlanner<S> {
+    store: S,
+    disk: Arc<dyn DiskSpace>,
+    // The size of the store at each recent sample, oldest first.
+    samples: Arc<Mutex<VecDeque<(Instant, u64)>>>,
+    window: usize,
+}
+
+impl<S: BatchStore> CapacityPlanner<S> {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+    /// With the default interval, the ingest rate is averaged over the last hour.
+    pub const DEFAULT_WINDOW: usize = 60;
+
+    /// Averages the ingest rate over the last `window` samples.
+    pub fn new(store: S, disk: impl DiskSpace, window: usize) -> Self {
+        Self {
+            store,
+            disk: Arc::new(disk),
+            samples: Arc::default(),
+            window: window.max(2),
+        }
+    }
+
+    /// Spawns the task sampling the store every `interval`, until the worker shuts down.
+    pub fn spawn(&self, interval: Duration) -> JoinHandle<()> {
+        let planner = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                let planner = planner.clone();
+                // Reading properties may block on RocksDB.
+                match tokio::task::spawn_blocking(move || planner.sample()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => warn!("Failed to sample batch store size: {e:?}"),
+                    Err(e) => warn!("Failed to sample batch store size: {e}"),
+                }
+            }
+        })
+    }
+
+    /// Records the current size of the store.
+    pub fn sample(&self) -> StoreResult<()> {
+        let total_bytes = self.total_bytes()?;
+        self.record(Instant::now(), total_bytes);
+        Ok(())
+    }
+
+    fn record(&self, at: Instant, total_bytes: u64) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() >= self.window {
+            samples.pop_front();
+        }
+        samples.push_back((at, total_bytes));
+    }
+
+    fn total_bytes(&self) -> StoreResult<u64> {
+        Ok(self
+            .store
+            .int_property("rocksdb.total-sst-files-size")?
+            .unwrap_or_default())
+    }
+
+    /// Returns the bytes per second the store grew by over the recent samples.
+    fn ingest_bytes_per_sec(&self) -> Option<f64> {
+        let samples = self.samples.lock().unwrap();
+        let ((first_at, first_bytes), (last_at, last_bytes)) = (samples.front()?, samples.back()?);
+        let elapsed = last_at.duration_since(*first_at).as_secs_f64();
+        // Shrinking stores, e.g. after garbage collection, are not growing.
+        (elapsed > 0.0).then(|| last_bytes.saturating_sub(*first_bytes) as f64 / elapsed)
+    }
+
+    /// Projects when the store fills the disk, as of now.
+    pub fn projection(&self) -> StoreResult<CapacityProjection> {
+        let total_bytes = self.total_bytes()?;
+        let batch_count = self
+            .store
+            .int_property("rocksdb.estimate-num-keys")?
+            .unwrap_or_default();
+        let ingest_bytes_per_sec = self.ingest_bytes_per_sec();
+        let available_bytes = self.disk.available_bytes(total_bytes);
+        let time_to_full = available_bytes
+            .zip(ingest_bytes_per_sec)
+            .and_then(|(available, rate)| time_to_full(available, rate));
+        Ok(CapacityProjection {
+            batch_count,
+            total_bytes,
+            ingest_bytes_per_sec,
+            available_bytes,
+            time_to_full,
+        })
+    }
+
+    /// Routes serving the projection on the admin server.
+    pub fn admin_routes(&self) -> Router {
+        Router::new()
+            .route("/capacity_projection", get(get_capacity_projection::<S>))
+            .layer(Extension(self.clone()))
+    }
+}
+
+async fn get_capacity_projection<S: BatchStore>(
+    Extension(planner): Extension<CapacityPlanner<S>>,
+) -> (StatusCode, String) {
+    match planner.projection() {
+        Ok(projection) => (StatusCode::OK, projection.to_string()),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read batch store properties: {e:?}"),
+        ),
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    task::JoinHandle,
+};
+use tracing::{info, warn};
+
+use crate::batch_store::BatchStore;
+
+#[cfg(test)]
+#[path = "tests/compaction_throttle_tests.rs"]
+pub mod compaction_throttle_tests;
+
+/// When a `CompactionThrottle` considers the store busy compacting, and how far it throttles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompactionThrottleConfig {
+    /// The store is compacting once this many compactions run, see
+    /// `rocksdb.num-running-compactions`.
+    pub running_compactions: u64,
+    /// The store is compacting once this many bytes are pending compaction, see
+    /// `rocksdb.estimate-pending-compaction-bytes`.
+    pub pending_compaction_bytes: u64,
+    /// The synchronize calls served concurrently while the store is not compacting.
+    pub concurrency: usize,
+    /// The synchronize calls served concurrently while the store is compacting.
+    pub compaction_concurrency: usize,
+    /// How often the store is checked for compaction.
+    pub sample_interval: Duration,
+}
+
+impl Default for CompactionThrottleConfig {
+    fn default() -> Self {
+        Self {
+            running_compactions: 2,
+            pending_compaction_bytes: 64 << 30,
+            concurrency: 64,
+            compaction_concurrency: 8,
+            sample_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Serves fewer synchronize calls at once while the batch store is busy compacting, since
+/// the reads of each call then slow down and pile up. Calls in excess wait for a permit.
+///
+/// Calls already served when compaction starts are not interrupted, so concurrency only
+/// drops to the reduced limit as they complete. It recovers as soon as compaction settles.
+#[derive(Clone)]
+pub struct CompactionThrottle {
+    config: CompactionThrottleConfig,
+    compacting: Arc<AtomicBool>,
+    permits: Arc<Semaphore>,
+    compaction_permits: Arc<Semaphore>,
+}
+
+/// Held while a synchronize call is served.
+pub struct CompactionThrottlePermit {
+    _permit: OwnedSemaphorePermit,
+    _compaction_permit: Option<OwnedSemaphorePermit>,
+}
+
+impl CompactionThrottle {
+    pub fn new(config: CompactionThrottleConfig) -> Self {
+        Self {
+            config,
+            compacting: Arc::default(),
+            permits: Arc::new(Semaphore::new(config.concurrency)),
+            compaction_permits: Arc::new(Semaphore::new(config.compaction_concurrency)),
+        }
+    }
+
+    /// Spawns the task checking `store` for compaction, until the worker shuts down.
+    pub fn spawn_sampler<S: BatchStore>(&self, store: S) -> JoinHandle<()> {
+        let throttle = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(throttle.config.sample_interval);
+            loop {
+                interval.tick().await;
+                let (throttle, store) = (throttle.clone(), store.clone());
+                // Reading properties may block on RocksDB.
+                if let Err(e) = tokio::task::spawn_blocking(move || throttle.sample(&store)).await {
+                    warn!("Failed to check the batch store for compaction: {e}");
+                }
+            }
+        })
+    }
+
+    /// Checks whether `store` is compacting, per the thresholds of the configuration.
+    pub fn sample<S: BatchStore>(&self, store: &S) {
+        let property = |name: &str| match store.int_property(name) {
+            Ok(value) => value.unwrap_or_default(),
+            Err(e) => {
+                warn!("Failed to read batch store property {name}: {e:?}");
+                0
+            }
+        };
+        let compacting = property("rocksdb.num-running-compactions")
+            >= self.config.running_compactions
+            || property("rocksdb.estimate-pending-compaction-bytes")
+                >= self.config.pending_compaction_bytes;
+        if self.compacting.swap(compacting, Ordering::Relaxed) != compacting {
+            info!("Batch store compacting: {compacting}, throttling synchronize accordingly");
+        }
+    }
+
+    pub fn is_compacting(&self) -> bool {
+        self.compacting.load(Ordering::Relaxed)
+    }
+
+    /// Waits until one more synchronize call may be served.
+    pub async fn acquire(&self) -> CompactionThrottlePermit {
+        // Take the scarcer permit first, so that waiting for it does not hold a regular one.
+        let compaction_permit = match self.is_compacting() {
+            true => Some(
+                self.compaction_permits
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("Compaction throttle semaphore should never be closed"),
+            ),
+            false => None,
+        };
+        let permit = self
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Compaction throttle semaphore should never be closed");
+        CompactionThrottlePermit {
+            _permit: permit,
+            _compaction_permit: compaction_permit,
+        }
+    }
+}
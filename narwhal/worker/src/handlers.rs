@@ -5,101 +5,1233 @@
 use anemo::{types::response::StatusCode, Network};
 use anyhow::Result;
 use async_trait::async_trait;
-use config::{AuthorityIdentifier, Committee, WorkerCache, WorkerId};
+use config::{
+    AuthorityIdentifier, Committee, Epoch, Parameters, PrimaryToWorkerMethod, WorkerCache,
+    WorkerId, WorkerInfo,
+};
+use crypto::NetworkPublicKey;
 use fastcrypto::hash::Hash;
+use futures::{stream, StreamExt};
 use itertools::Itertools;
 use network::{client::NetworkClient, WorkerToPrimaryClient};
-use std::{collections::HashSet, time::Duration};
-use store::{rocks::DBMap, Map};
-use tracing::{debug, trace};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use store::{rocks::DBMap, TypedStoreError};
+use thiserror::Error;
+use tokio::{
+    sync::{mpsc, OwnedSemaphorePermit},
+    task::JoinHandle,
+};
+use tracing::{debug, trace, warn};
 use types::{
-    Batch, BatchDigest, FetchBatchesRequest, FetchBatchesResponse, PrimaryToWorker,
-    RequestBatchRequest, RequestBatchResponse, RequestBatchesRequest, RequestBatchesResponse,
-    WorkerBatchMessage, WorkerDeleteBatchesMessage, WorkerOthersBatchMessage,
-    WorkerSynchronizeMessage, WorkerToWorker, WorkerToWorkerClient,
+    error::{LocalClientError, UNIMPLEMENTED},
+    now, Batch, BatchAPI, BatchDigest, BatchSizesRequest, BatchSizesResponse, BatchSlice,
+    Certificate, CertificateAPI, FetchBatchesRequest, FetchBatchesResponse, FetchBatchesV2Response,
+    HeaderAPI, IntersectBatchesRequest, IntersectBatchesResponse, LocateTransactionsRequest,
+    LocateTransactionsResponse, OpenBulkSyncRequest, OpenBulkSyncResponse, PrimaryToWorker,
+    ReportBatchesResponse, RequestBatchMetadataRequest, RequestBatchMetadataResponse,
+    RequestBatchRequest, RequestBatchResponse, RequestBatchV2Request, RequestBatchV2Response,
+    RequestBatchesRequest, RequestBatchesResponse, RequestBatchesV2Request,
+    RequestBatchesV2Response, RequestBulkSyncPageRequest, RequestBulkSyncPageResponse,
+    SampleBatchesRequest, SampleBatchesResponse, StoreVersionRequest, StoreVersionResponse,
+    WorkerBatchMessage, WorkerBatchesMessage, WorkerCapabilitiesRequest,
+    WorkerCapabilitiesResponse, WorkerDeleteBatchesMessage, WorkerFeature,
+    WorkerOthersBatchMessage, WorkerSynchronizeMessage, WorkerSynchronizeV2Message, WorkerToWorker,
+    WorkerToWorkerClient, WORKER_PROTOCOL_VERSION,
 };
 
-use crate::{batch_fetcher::BatchFetcher, TransactionValidator};
+use crate::{
+    batch_certificates::BatchCertificates,
+    batch_diagnostics::self_test_key,
+    batch_fetcher::BatchFetcher,
+    batch_insert_times::BatchInsertTimes,
+    batch_mirror::BatchMirror,
+    batch_observer::BatchObserver,
+    batch_prefetch::BatchPrefetcher,
+    batch_replicator::BatchReplicator,
+    batch_store::{BatchStore, StoreResult},
+    batch_tombstones::BatchTombstones,
+    bulk_sync::BulkSyncSessions,
+    compaction_throttle::CompactionThrottle,
+    in_flight_syncs::InFlightSyncs,
+    method_permits::{MethodConcurrencyLimits, MethodPermits},
+    metrics::WorkerMetrics,
+    others_batch_reporter::OthersBatchReporter,
+    peer_protocol_versions::PeerProtocolVersions,
+    peer_rate_limits::PeerRateLimits,
+    peer_reciprocity::PeerReciprocity,
+    peer_roles::PeerRoles,
+    read_permits::{ReadPriority, StoreReadPermits},
+    read_transform::BatchReadTransform,
+    request_batches_audit::RequestBatchesAudit,
+    response_buffers::{PooledBuffers, ResponseBufferPool, ResponseBuffers},
+    size_limit_events::SizeLimitEvents,
+    store_migration::StoreMigration,
+    store_version::StoreVersion,
+    tx_dedup::TransactionDedup,
+    validation_permits::ValidationPermits,
+    validator_breaker::ValidatorCircuitBreaker,
+    write_backpressure::WriteBackpressure,
+    write_coalescer::WriteCoalescer,
+    write_permits::StoreWritePermits,
+    TransactionValidator, ValidationError, ValidationErrorKind,
+};
 
 #[cfg(test)]
 #[path = "tests/handlers_tests.rs"]
 pub mod handlers_tests;
 
+/// The default cap on the total size of the batches returned by a single `fetch_batches_v2`
+/// call.
+pub const DEFAULT_MAX_FETCH_BATCHES_RESPONSE_SIZE: usize = 6_000_000;
+
+/// The default cap on the number of batches returned by a single `request_batches` call.
+pub const DEFAULT_MAX_REQUEST_BATCHES_RESPONSE_COUNT: usize = 10_000;
+
+/// The maximum total size of the batches of a `request_batches` response.
+const MAX_REQUEST_BATCHES_RESPONSE_SIZE: usize = 6_000_000;
+
+/// The bytes of a `request_batches` response frame taken by anemo headers and by the fixed
+/// fields of the response, on top of its batches and digests.
+const RESPONSE_FRAME_OVERHEAD: usize = 4 * 1024;
+
+/// The number of digests of a bulk request read from the store at once.
+const BATCH_DIGESTS_READ_CHUNK_SIZE: usize = 200;
+
+/// The default number of batches of a `report_batches` call validated concurrently.
+pub const DEFAULT_REPORT_BATCHES_PARALLELISM: usize = 8;
+
+/// The delay before retrying to remove tombstoned batches, doubled after each failure up to
+/// `MAX_TOMBSTONE_REMOVAL_RETRY_DELAY`.
+const TOMBSTONE_REMOVAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_TOMBSTONE_REMOVAL_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// How `synchronize` treats batches that the primary marks as certified.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CertifiedBatchVerification {
+    /// Trust the primary and skip validation.
+    #[default]
+    Trust,
+    /// Reject batches whose digest was not requested by the primary.
+    Digest,
+    /// Check digests and fully validate the batches, as if they were not certified.
+    Full,
+    /// Check digests, and verify the certificate attached by the primary to synchronize_v2
+    /// requests against the committee. Batches that it does not prove certified are validated.
+    Certificate,
+}
+
+/// How `synchronize` treats a batch that fails validation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InvalidBatchPolicy {
+    /// Fail the whole call, discarding the rest of the response.
+    #[default]
+    FailFast,
+    /// Drop the invalid batch and keep storing the valid ones. The invalid batch is then
+    /// requested from the next worker.
+    SkipInvalid,
+}
+
+/// Which `PrimaryToWorker` methods a `PrimaryReceiverHandler` serves. Disabled methods are
+/// rejected without side effects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EnabledPrimaryToWorkerMethods {
+    pub synchronize: bool,
+    pub fetch_batches: bool,
+    pub delete_batches: bool,
+}
+
+impl Default for EnabledPrimaryToWorkerMethods {
+    fn default() -> Self {
+        Self {
+            synchronize: true,
+            fetch_batches: true,
+            delete_batches: true,
+        }
+    }
+}
+
+/// How `delete_batches` splits large requests: each chunk of digests is removed in a single
+/// atomic write batch, with up to `concurrency` chunks being written at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeleteBatchesChunking {
+    pub chunk_size: usize,
+    pub concurrency: usize,
+}
+
+impl Default for DeleteBatchesChunking {
+    fn default() -> Self {
+        Self {
+            chunk_size: 1_000,
+            concurrency: 4,
+        }
+    }
+}
+
+/// Errors returned by the worker handlers, each mapped to the appropriate anemo status.
+#[derive(Debug, Error)]
+pub enum WorkerHandlerError {
+    #[error("failed to read from batch store: {0:?}")]
+    StoreRead(TypedStoreError),
+    #[error("failed to write to batch store: {0:?}")]
+    StoreWrite(TypedStoreError),
+    #[error("failed to remove from batch store: {0:?}")]
+    StoreRemove(TypedStoreError),
+    #[error("Invalid batch: {0}")]
+    InvalidBatch(String),
+    #[error("Invalid batch digests: {0}")]
+    InvalidDigests(String),
+    #[error("Batch {digest} of {size} bytes is below the minimum of {min} bytes")]
+    UndersizedBatch {
+        digest: BatchDigest,
+        size: usize,
+        min: usize,
+    },
+    #[error("Certified batch {digest} from {worker} was not requested")]
+    UnrequestedBatch {
+        digest: BatchDigest,
+        worker: NetworkPublicKey,
+    },
+    #[error("{0}")]
+    NotFound(String),
+    #[error("Not connected with worker peer {0}")]
+    PeerNotConnected(NetworkPublicKey),
+    #[error("No worker of {0} is reachable to synchronize from, please retry later")]
+    NoReachableSyncTarget(AuthorityIdentifier),
+    #[error("The primary asked worker to sync with an unknown node: {0}")]
+    UnknownNode(String),
+    #[error("Worker cache of epoch {epoch} does not know the workers of committee member {target}, retry after reconfiguration: {reason}")]
+    StaleWorkerCache {
+        epoch: Epoch,
+        target: AuthorityIdentifier,
+        reason: String,
+    },
+    #[error("Request of {size} items exceeds the limit of {limit}")]
+    SizeExceeded { size: usize, limit: usize },
+    #[error("Response of up to {size} bytes exceeds the frame limit of {limit} bytes, please request fewer digests at once")]
+    ResponseTooLarge { size: usize, limit: usize },
+    #[error(
+        "{0}() is unsupported via RPC interface, please call via local worker handler instead"
+    )]
+    UnsupportedViaRpc(&'static str),
+    #[error("{0}() is disabled on this worker")]
+    MethodDisabled(&'static str),
+    #[error("{0}() is only served to committee peers")]
+    ObserverDenied(&'static str),
+    #[error("Batch store writes are backing up, please retry later")]
+    Overloaded,
+    #[error("Too many concurrent {0}() calls, please retry later")]
+    ConcurrencyLimitExceeded(&'static str),
+    #[error("Batch {0} only holds transactions already stored in other batches")]
+    RedundantBatch(BatchDigest),
+    #[error("Batch store operation timed out after {0:?}, please retry later")]
+    StoreTimeout(Duration),
+    #[error("Transaction validator is failing, please retry later")]
+    ValidatorUnavailable,
+    #[error("Batch validation could not complete, please retry later: {0}")]
+    ValidationUnavailable(String),
+    #[error("Peer {0} exceeded its request rate limit")]
+    RateLimited(anemo::PeerId),
+    #[error("Peer {0} was served far more batches than it reported, please retry later")]
+    NotReciprocating(anemo::PeerId),
+    #[error("Request deadline exceeded")]
+    DeadlineExceeded,
+    #[error("Failed to report batch to primary: {0}")]
+    ReportToPrimary(String),
+    #[error("failed to synchronize batches!")]
+    SyncFailed,
+    #[error("Synchronize gave up after {budget} attempts, still missing batches {missing:?}")]
+    SyncBudgetExhausted {
+        budget: usize,
+        missing: Vec<BatchDigest>,
+    },
+}
+
+impl From<WorkerHandlerError> for anemo::rpc::Status {
+    fn from(error: WorkerHandlerError) -> Self {
+        let message = error.to_string();
+        match error {
+            WorkerHandlerError::InvalidBatch(_)
+            | WorkerHandlerError::InvalidDigests(_)
+            | WorkerHandlerError::UndersizedBatch { .. }
+            | WorkerHandlerError::UnrequestedBatch { .. }
+            | WorkerHandlerError::RedundantBatch(_)
+            | WorkerHandlerError::SizeExceeded { .. }
+            | WorkerHandlerError::UnsupportedViaRpc(_)
+            | WorkerHandlerError::ObserverDenied(_) => {
+                anemo::rpc::Status::new_with_message(StatusCode::BadRequest, message)
+            }
+            WorkerHandlerError::NotFound(_) => {
+                anemo::rpc::Status::new_with_message(StatusCode::NotFound, message)
+            }
+            WorkerHandlerError::MethodDisabled(_) => {
+                anemo::rpc::Status::new_with_message(UNIMPLEMENTED, message)
+            }
+            WorkerHandlerError::ConcurrencyLimitExceeded(_)
+            | WorkerHandlerError::RateLimited(_)
+            | WorkerHandlerError::NotReciprocating(_) => {
+                anemo::rpc::Status::new_with_message(StatusCode::TooManyRequests, message)
+            }
+            // Transient conditions, the caller should retry later.
+            WorkerHandlerError::StaleWorkerCache { .. }
+            | WorkerHandlerError::Overloaded
+            | WorkerHandlerError::StoreTimeout(_)
+            | WorkerHandlerError::ValidatorUnavailable
+            | WorkerHandlerError::ValidationUnavailable(_)
+            | WorkerHandlerError::DeadlineExceeded
+            | WorkerHandlerError::PeerNotConnected(_)
+            | WorkerHandlerError::NoReachableSyncTarget(_)
+            | WorkerHandlerError::SyncBudgetExhausted { .. }
+            // The resources to serve the request are exhausted, rather than the request being
+            // invalid: it succeeds once split into smaller ones.
+            | WorkerHandlerError::ResponseTooLarge { .. } => {
+                anemo::rpc::Status::new_with_message(StatusCode::ServiceUnavailable, message)
+            }
+            WorkerHandlerError::StoreRead(_)
+            | WorkerHandlerError::StoreWrite(_)
+            | WorkerHandlerError::StoreRemove(_)
+            | WorkerHandlerError::UnknownNode(_)
+            | WorkerHandlerError::ReportToPrimary(_)
+            | WorkerHandlerError::SyncFailed => anemo::rpc::Status::internal(message),
+        }
+    }
+}
+
+/// The response header telling caching proxies whether a response can be cached.
+pub const CACHE_CONTROL_HEADER_KEY: &str = "cache-control";
+/// Batches are content-addressed, so responses made only of the requested batches never
+/// change and can be cached indefinitely.
+pub const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// The request header holding the id correlating the requests of an operation across nodes,
+/// e.g. for distributed tracing. Propagated by synchronize to the requests it issues.
+pub const TRACE_ID_HEADER_KEY: &str = "trace-id";
+
+fn cacheable<T>(mut response: anemo::Response<T>) -> anemo::Response<T> {
+    response.headers_mut().insert(
+        CACHE_CONTROL_HEADER_KEY.to_owned(),
+        IMMUTABLE_CACHE_CONTROL.to_owned(),
+    );
+    response
+}
+
+/// Returns a request for `body` timing out after `timeout`, propagating `trace_id` if set.
+fn traced_request<T>(body: T, timeout: Duration, trace_id: Option<&String>) -> anemo::Request<T> {
+    let mut request = anemo::Request::new(body).with_timeout(timeout);
+    if let Some(trace_id) = trace_id {
+        request
+            .headers_mut()
+            .insert(TRACE_ID_HEADER_KEY.to_owned(), trace_id.clone());
+    }
+    request
+}
+
+/// Converts a response of the newer version of a method into the response of its legacy
+/// version, keeping its headers.
+fn into_legacy_response<T, U: From<T>>(response: anemo::Response<T>) -> anemo::Response<U> {
+    let headers = response.headers().clone();
+    let mut legacy = anemo::Response::new(U::from(response.into_body()));
+    legacy.headers_mut().extend(headers);
+    legacy
+}
+
+/// The most bytes `len` bytes may take once compressed by the snappy codec of the network,
+/// which expands incompressible inputs, see `snap::raw::max_compress_len`.
+fn max_compressed_len(len: usize) -> usize {
+    32 + len + len / 6
+}
+
+impl From<ValidationError> for WorkerHandlerError {
+    fn from(error: ValidationError) -> Self {
+        match error.kind {
+            ValidationErrorKind::Permanent => WorkerHandlerError::InvalidBatch(error.to_string()),
+            ValidationErrorKind::Transient => {
+                WorkerHandlerError::ValidationUnavailable(error.to_string())
+            }
+        }
+    }
+}
+
+/// Validates a batch, accounting the outcome in the circuit breaker if any.
+async fn validate_batch<V: TransactionValidator>(
+    validator: &V,
+    breaker: Option<&ValidatorCircuitBreaker>,
+    permits: Option<&ValidationPermits>,
+    batch: &Batch,
+) -> Result<(), ValidationError> {
+    let _permit = match permits {
+        Some(permits) => Some(permits.acquire().await),
+        None => None,
+    };
+    let result = validator.validate_batch(batch).await;
+    if let Some(breaker) = breaker {
+        breaker.record(result.is_ok());
+    }
+    result.map_err(Into::into)
+}
+
+/// Validates `batches` with a single `TransactionValidator::validate_batches` call, which
+/// takes a single validation permit.
+async fn validate_batches<V: TransactionValidator>(
+    validator: &V,
+    breaker: Option<&ValidatorCircuitBreaker>,
+    permits: Option<&ValidationPermits>,
+    batches: &[&Batch],
+) -> Vec<Result<(), ValidationError>> {
+    if batches.is_empty() {
+        return Vec::new();
+    }
+    let _permit = match permits {
+        Some(permits) => Some(permits.acquire().await),
+        None => None,
+    };
+    let results = validator.validate_batches(batches).await;
+    assert_eq!(
+        results.len(),
+        batches.len(),
+        "TransactionValidator::validate_batches must return a result per batch"
+    );
+    results
+        .into_iter()
+        .map(|result| {
+            if let Some(breaker) = breaker {
+                breaker.record(result.is_ok());
+            }
+            result.map_err(Into::into)
+        })
+        .collect()
+}
+
+/// Returns when a request must be answered by, if its caller set a timeout.
+fn request_deadline<T>(inherit: bool, request: &anemo::Request<T>) -> Option<Instant> {
+    inherit
+        .then(|| request.timeout())
+        .flatten()
+        .map(|timeout| Instant::now() + timeout)
+}
+
+/// Runs a handler method, failing with `DeadlineExceeded` once `deadline` passes, since the
+/// caller stopped waiting for the response by then.
+async fn within_deadline<T>(
+    deadline: Option<Instant>,
+    method: impl Future<Output = Result<T, anemo::rpc::Status>>,
+) -> Result<T, anemo::rpc::Status> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline.into(), method)
+            .await
+            .map_err(|_| WorkerHandlerError::DeadlineExceeded)?,
+        None => method.await,
+    }
+}
+
+/// Fails with `ValidatorUnavailable` if the circuit breaker is open.
+fn check_validator_breaker(
+    breaker: Option<&ValidatorCircuitBreaker>,
+) -> Result<(), WorkerHandlerError> {
+    match breaker {
+        Some(breaker) if breaker.should_reject() => Err(WorkerHandlerError::ValidatorUnavailable),
+        _ => Ok(()),
+    }
+}
+
+/// Awaits a blocking store task, failing with `StoreTimeout` if it does not complete within
+/// `timeout`.
+async fn await_store_task<T>(
+    task: &mut JoinHandle<T>,
+    timeout: Option<Duration>,
+) -> Result<T, WorkerHandlerError> {
+    let result = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, task)
+            .await
+            .map_err(|_| WorkerHandlerError::StoreTimeout(timeout))?,
+        None => task.await,
+    };
+    Ok(result.expect("Batch store task should not panic"))
+}
+
+/// Runs a store operation. With a timeout, the operation runs on a blocking task, so that a
+/// hung store fails the operation instead of hanging the handler.
+async fn with_store_timeout<S: BatchStore, T: Send + 'static>(
+    store: &S,
+    timeout: Option<Duration>,
+    op: impl FnOnce(&S) -> T + Send + 'static,
+) -> Result<T, WorkerHandlerError> {
+    if timeout.is_none() {
+        return Ok(op(store));
+    }
+    let store = store.clone();
+    await_store_task(
+        &mut tokio::task::spawn_blocking(move || op(&store)),
+        timeout,
+    )
+    .await
+}
+
 /// Defines how the network receiver handles incoming workers messages.
 #[derive(Clone)]
-pub struct WorkerReceiverHandler<V> {
+pub struct WorkerReceiverHandler<V, S = DBMap<BatchDigest, Batch>> {
+    pub authority_id: AuthorityIdentifier,
     pub id: WorkerId,
     pub client: NetworkClient,
-    pub store: DBMap<BatchDigest, Batch>,
+    pub store: S,
     pub validator: V,
+    // Bounds concurrent store reads, shared with the `PrimaryReceiverHandler`.
+    pub read_permits: StoreReadPermits,
+    // Progress of the bulk sync sessions served to far-behind workers.
+    pub bulk_sync_sessions: BulkSyncSessions,
+    // Accounts the batch requests served to each peer.
+    pub metrics: Arc<WorkerMetrics>,
+    // If set, a failed store read of a request_batches chunk is retried this many times,
+    // after which the chunk's batches are omitted from the response instead of failing the
+    // whole request.
+    pub request_batches_chunk_retries: Option<usize>,
+    // Caps the number of batches in a request_batches response, on top of the byte cap, so
+    // that many tiny batches can't produce an enormous response.
+    pub max_request_batches_response_count: usize,
+    // If set, annotate request_batches_v2 responses with the time since each batch was
+    // stored, for callers making freshness-aware decisions, e.g. anti-entropy dedup. Shared
+    // with the `PrimaryReceiverHandler`.
+    pub batch_insert_times: Option<BatchInsertTimes>,
+    // If set, report_batch is rejected while batch store writes are slow.
+    pub write_backpressure: Option<WriteBackpressure>,
+    // If set, batches served to other workers are read from this store, e.g. a read-only
+    // secondary instance, while writes still go to `store`.
+    pub read_store: Option<S>,
+    // If set, accepted batches are also written to a backup store in the background.
+    pub mirror: Option<BatchMirror>,
+    // If set, notified of every newly stored batch.
+    pub observer: Option<BatchObserver>,
+    // If set, reports to our primary are bounded by a timeout and retried in the background
+    // on failure, instead of blocking report_batch until the primary responds.
+    pub others_batch_reporter: Option<OthersBatchReporter>,
+    // If set, new batches in report_batch are written to this staging store while they are
+    // being validated, and moved into the store once valid. Invalid batches are removed from
+    // the staging store, and are never visible to readers of the store.
+    pub speculative_write: Option<S>,
+    // Recent request_batches calls truncated by a size limit, served on the admin server.
+    pub size_limit_events: SizeLimitEvents,
+    // If set, store operations failing to complete within this timeout fail the request
+    // instead of hanging the handler.
+    pub store_timeout: Option<Duration>,
+    // If set, batches made only of transactions already stored in other batches are
+    // declined.
+    pub tx_dedup: Option<TransactionDedup>,
+    // How many batches of a report_batches call are validated concurrently.
+    pub report_batches_parallelism: usize,
+    // If set, batches deleted by the primary are not served during their grace period.
+    // Shared with the `PrimaryReceiverHandler`.
+    pub tombstones: Option<BatchTombstones>,
+    // If set, rewrites the batches served to its designated peers.
+    pub read_transform: Option<BatchReadTransform>,
+    // Report accepted batches to our primary. Only disabled by workers running without a
+    // primary, e.g. ingestion tooling.
+    pub notify_primary: bool,
+    // If set, batches are rejected without validation while the validator keeps failing.
+    pub validator_breaker: Option<ValidatorCircuitBreaker>,
+    // If set, rate limits the requests of each peer, to every method.
+    pub peer_rate_limits: Option<PeerRateLimits>,
+    // Stop working on requests once the timeout set by their caller expires.
+    pub inherit_request_deadline: bool,
+    // If set, newly accepted batches are pushed to other workers in the background.
+    pub replicator: Option<BatchReplicator>,
+    // If set, the batch writes of report_batch are coalesced with concurrent ones into fewer
+    // store writes. Must write to `store`.
+    pub write_coalescer: Option<WriteCoalescer>,
+    // If set, batches are not served to peers that report far fewer batches than they
+    // request.
+    pub reciprocity: Option<PeerReciprocity>,
+    // If set, request_batch_v2 returns the certificates of batches to the light clients asking
+    // for them. Shared with the `PrimaryReceiverHandler`, which records them.
+    pub batch_certificates: Option<BatchCertificates>,
+    // If set, the `max_frame_size` of the anemo network serving the handler. request_batches
+    // then also bounds its responses by their encoded size, rather than failing to send them.
+    pub max_response_frame_size: Option<usize>,
+    // Index the batches stored by report_batch by the digests of their transactions, if the
+    // store supports it, and serve locate_transactions. Adds a write per transaction, and
+    // bypasses the write coalescer.
+    pub index_transactions: bool,
+    // If set, bounds the batches validated concurrently across all calls. Shared with the
+    // `PrimaryReceiverHandler`.
+    pub validation_permits: Option<ValidationPermits>,
+    // If set, serving a batch with request_batch prefetches the batches stored around the
+    // same time, for follow-up requests to be served from the cache.
+    pub prefetcher: Option<BatchPrefetcher>,
+    // Batches reported with fewer bytes of transactions are refused, 0 to accept all. Honest
+    // workers seal partial batches after `max_batch_delay` under low load, so a minimum above
+    // a single transaction rejects their batches too. Only applies to reported batches:
+    // batches fetched by synchronize are part of certificates, and are stored regardless.
+    pub min_batch_size: usize,
+    // If set, logs the last request_batches calls served, served on the admin server.
+    pub request_batches_audit: Option<RequestBatchesAudit>,
+    // If set, batches missing from the read store are looked up in this store, e.g. a slower
+    // store older batches are moved to, before being reported missing. Must key batches like
+    // `store`.
+    pub archive_store: Option<S>,
+    // Validate the batches of a report_batches call with a single
+    // `TransactionValidator::validate_batches` call, instead of one by one.
+    pub validate_batches_together: bool,
+    // If set, bounds the concurrent batch store writes of report_batch and report_batches.
+    pub write_permits: Option<StoreWritePermits>,
+    // If set, the version of `store` served by store_version. Must be the version of the
+    // `VersionedBatchStore` the handler writes to.
+    pub store_version: Option<StoreVersion>,
+    // If set, batches are served from the migration snapshot, and reported batches refused,
+    // while a migration of the store is in progress. Shared with the
+    // `PrimaryReceiverHandler`.
+    pub store_migration: Option<StoreMigration<S>>,
+    // If set, request_batches reuses its scratch buffers across requests.
+    pub response_buffers: Option<ResponseBufferPool>,
+    // If set, peers outside the committee are only served metadata, under their own rate
+    // limits.
+    pub peer_roles: Option<PeerRoles>,
 }
 
-#[async_trait]
-impl<V: TransactionValidator> WorkerToWorker for WorkerReceiverHandler<V> {
-    async fn report_batch(
+impl<V, S> WorkerReceiverHandler<V, S> {
+    /// Creates a handler with every optional component unset and every setting at its
+    /// default. Other settings are overridden by setting the corresponding fields, e.g. with
+    /// the struct update syntax.
+    pub fn new(
+        authority_id: AuthorityIdentifier,
+        id: WorkerId,
+        client: NetworkClient,
+        store: S,
+        validator: V,
+        metrics: Arc<WorkerMetrics>,
+    ) -> Self {
+        Self {
+            authority_id,
+            id,
+            client,
+            store,
+            validator,
+            read_permits: StoreReadPermits::default(),
+            bulk_sync_sessions: BulkSyncSessions::default(),
+            metrics,
+            request_batches_chunk_retries: None,
+            max_request_batches_response_count: DEFAULT_MAX_REQUEST_BATCHES_RESPONSE_COUNT,
+            batch_insert_times: None,
+            write_backpressure: None,
+            read_store: None,
+            mirror: None,
+            observer: None,
+            others_batch_reporter: None,
+            speculative_write: None,
+            size_limit_events: SizeLimitEvents::default(),
+            store_timeout: None,
+            tx_dedup: None,
+            report_batches_parallelism: DEFAULT_REPORT_BATCHES_PARALLELISM,
+            tombstones: None,
+            read_transform: None,
+            notify_primary: true,
+            validator_breaker: None,
+            peer_rate_limits: None,
+            inherit_request_deadline: false,
+            replicator: None,
+            write_coalescer: None,
+            reciprocity: None,
+            batch_certificates: None,
+            max_response_frame_size: None,
+            index_transactions: false,
+            validation_permits: None,
+            prefetcher: None,
+            min_batch_size: 0,
+            request_batches_audit: None,
+            archive_store: None,
+            validate_batches_together: false,
+            write_permits: None,
+            store_version: None,
+            store_migration: None,
+            response_buffers: None,
+            peer_roles: None,
+        }
+    }
+
+    /// The read transform applying to the batches served to `peer`, if any.
+    fn read_transform_for(&self, peer: Option<&anemo::PeerId>) -> Option<&BatchReadTransform> {
+        self.read_transform
+            .as_ref()
+            .filter(|transform| transform.applies_to(peer))
+    }
+
+    fn is_tombstoned(&self, key: &BatchDigest) -> bool {
+        self.tombstones
+            .as_ref()
+            .map_or(false, |tombstones| tombstones.contains(key))
+    }
+
+    /// Fails with `RateLimited` if `peer` is over its rate limit. Local requests are not
+    /// rate limited.
+    fn check_rate_limit(&self, peer: Option<&anemo::PeerId>) -> Result<(), WorkerHandlerError> {
+        match (&self.peer_rate_limits, peer) {
+            (Some(limits), Some(peer)) if !limits.try_acquire(*peer) => {
+                Err(WorkerHandlerError::RateLimited(*peer))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if the role of `peer` is not served `method`, which returns or stores batches if
+    /// `serves_batches`, or if `peer` is an observer over its rate limit.
+    fn check_peer_role(
         &self,
-        request: anemo::Request<WorkerBatchMessage>,
-    ) -> Result<anemo::Response<()>, anemo::rpc::Status> {
-        let message = request.into_body();
-        if let Err(err) = self.validator.validate_batch(&message.batch).await {
-            return Err(anemo::rpc::Status::new_with_message(
-                StatusCode::BadRequest,
-                format!("Invalid batch: {err}"),
-            ));
+        peer: Option<&anemo::PeerId>,
+        method: &'static str,
+        serves_batches: bool,
+    ) -> Result<(), WorkerHandlerError> {
+        match &self.peer_roles {
+            Some(roles) => roles.check(peer, method, serves_batches),
+            None => Ok(()),
         }
-        let digest = message.batch.digest();
-        self.store.insert(&digest, &message.batch).map_err(|e| {
-            anemo::rpc::Status::internal(format!("failed to write to batch store: {e:?}"))
-        })?;
-        self.client
-            .report_others_batch(WorkerOthersBatchMessage {
-                digest,
-                worker_id: self.id,
-            })
-            .await
-            .map_err(|e| anemo::rpc::Status::internal(e.to_string()))?;
-        Ok(anemo::Response::new(()))
     }
 
-    async fn request_batch(
+    /// Fails with `NotReciprocating` if `peer` reported far fewer batches than it was served.
+    fn check_reciprocity(&self, peer: Option<&anemo::PeerId>) -> Result<(), WorkerHandlerError> {
+        match (&self.reciprocity, peer) {
+            (Some(reciprocity), Some(peer)) if !reciprocity.is_reciprocating(peer) => {
+                Err(WorkerHandlerError::NotReciprocating(*peer))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn request_deadline<T>(&self, request: &anemo::Request<T>) -> Option<Instant> {
+        request_deadline(self.inherit_request_deadline, request)
+    }
+}
+
+impl<V: TransactionValidator, S: BatchStore> WorkerReceiverHandler<V, S> {
+    /// The store serving reads to other workers: the migration snapshot while the store is
+    /// migrated.
+    fn read_store(&self) -> S {
+        if let Some(snapshot) = self
+            .store_migration
+            .as_ref()
+            .and_then(|migration| migration.snapshot())
+        {
+            return snapshot;
+        }
+        self.read_store.as_ref().unwrap_or(&self.store).clone()
+    }
+
+    /// Returns the batch served by `request_batch` to local callers, for components in the
+    /// same process, which can call this directly rather than through an RPC.
+    pub async fn get_batch(
         &self,
-        request: anemo::Request<RequestBatchRequest>,
-    ) -> Result<anemo::Response<RequestBatchResponse>, anemo::rpc::Status> {
-        // TODO [issue #7]: Do some accounting to prevent bad actors from monopolizing our resources
-        let batch = request.into_body().batch;
-        let batch = self.store.get(&batch).map_err(|e| {
-            anemo::rpc::Status::internal(format!("failed to read from batch store: {e:?}"))
-        })?;
+        digest: &BatchDigest,
+    ) -> Result<Option<Batch>, WorkerHandlerError> {
+        let batch = self.read_batch(digest).await?;
+        Ok(match self.read_transform_for(None) {
+            Some(read_transform) => batch.map(|batch| read_transform.apply(batch)),
+            None => batch,
+        })
+    }
 
-        Ok(anemo::Response::new(RequestBatchResponse { batch }))
+    async fn read_batch(&self, digest: &BatchDigest) -> Result<Option<Batch>, WorkerHandlerError> {
+        let _permit = self.read_permits.acquire(ReadPriority::Bulk).await;
+        let key = *digest;
+        if self.is_tombstoned(&key) {
+            return Ok(None);
+        }
+        let store_op = move |store: &S| store.get(&key);
+        let batch = with_store_timeout(&self.read_store(), self.store_timeout, store_op)
+            .await?
+            .map_err(WorkerHandlerError::StoreRead)?;
+        let mut batches = vec![batch];
+        self.read_archive(&[key], &mut batches).await?;
+        Ok(batches.pop().flatten())
     }
 
-    async fn request_batches(
+    /// Returns whether the batch with the given digest is served, without reading it.
+    async fn contains_batch(&self, digest: &BatchDigest) -> Result<bool, WorkerHandlerError> {
+        let _permit = self.read_permits.acquire(ReadPriority::Bulk).await;
+        let key = *digest;
+        if self.is_tombstoned(&key) {
+            return Ok(false);
+        }
+        for store in std::iter::once(self.read_store()).chain(self.archive_store.clone()) {
+            let store_op = move |store: &S| store.contains_key(&key);
+            if with_store_timeout(&store, self.store_timeout, store_op)
+                .await?
+                .map_err(WorkerHandlerError::StoreRead)?
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Fills the batches missing from `batches`, read under `keys`, with the ones found in
+    /// the archive store, if any.
+    async fn read_archive(
         &self,
-        request: anemo::Request<RequestBatchesRequest>,
-    ) -> Result<anemo::Response<RequestBatchesResponse>, anemo::rpc::Status> {
-        const MAX_REQUEST_BATCHES_RESPONSE_SIZE: usize = 6_000_000;
-        const BATCH_DIGESTS_READ_CHUNK_SIZE: usize = 200;
+        keys: &[BatchDigest],
+        batches: &mut [Option<Batch>],
+    ) -> Result<(), WorkerHandlerError> {
+        let Some(archive_store) = &self.archive_store else {
+            return Ok(());
+        };
+        let (indices, missing_keys): (Vec<_>, Vec<_>) = batches
+            .iter()
+            .enumerate()
+            .filter(|(_, batch)| batch.is_none())
+            .map(|(i, _)| (i, keys[i]))
+            .unzip();
+        if missing_keys.is_empty() {
+            return Ok(());
+        }
+        let store_op = move |store: &S| store.multi_get(&missing_keys);
+        let archived_batches = with_store_timeout(archive_store, self.store_timeout, store_op)
+            .await?
+            .map_err(WorkerHandlerError::StoreRead)?;
+        for (i, archived_batch) in indices.into_iter().zip(archived_batches) {
+            let outcome = if archived_batch.is_some() {
+                "hit"
+            } else {
+                "miss"
+            };
+            self.metrics
+                .archive_batch_reads
+                .with_label_values(&[outcome])
+                .inc();
+            batches[i] = archived_batch;
+        }
+        Ok(())
+    }
 
-        let digests_to_fetch = request.into_body().batch_digests;
-        let digests_chunks = digests_to_fetch
-            .chunks(BATCH_DIGESTS_READ_CHUNK_SIZE)
-            .map(|chunk| chunk.to_vec())
-            .collect_vec();
+    /// Validates the batch while writing it to the `staging` store. Once the batch
+    /// is valid, it is moved into the store, returning the latency of that write. The staged
+    /// write is rolled back if the batch is invalid.
+    async fn validate_with_speculative_write(
+        &self,
+        staging: &S,
+        digest: BatchDigest,
+        batch: Batch,
+    ) -> Result<(Batch, Duration), WorkerHandlerError>
+    where
+        V: TransactionValidator,
+    {
+        let staging_store = staging.clone();
+        let staged_batch = batch.clone();
+        let mut staging_task =
+            tokio::task::spawn_blocking(move || staging_store.insert(&digest, &staged_batch));
+        let (validation, staged) = futures::join!(
+            validate_batch(
+                &self.validator,
+                self.validator_breaker.as_ref(),
+                self.validation_permits.as_ref(),
+                &batch,
+            ),
+            await_store_task(&mut staging_task, self.store_timeout)
+        );
+        let result = match validation {
+            Ok(()) => {
+                let index_transactions = self.index_transactions;
+                let store_op = move |store: &S| {
+                    let write_start = Instant::now();
+                    if index_transactions {
+                        store.insert_indexed(&digest, &batch)
+                    } else {
+                        store.insert(&digest, &batch)
+                    }
+                    .map(|()| (batch, write_start.elapsed()))
+                };
+                with_store_timeout(&self.store, self.store_timeout, store_op)
+                    .await
+                    .and_then(|result| result.map_err(WorkerHandlerError::StoreWrite))
+            }
+            Err(err) => Err(err.into()),
+        };
+        match staged {
+            Ok(Ok(())) => {
+                let store_op = move |store: &S| store.remove(&digest);
+                let removal = with_store_timeout(staging, self.store_timeout, store_op)
+                    .await
+                    .and_then(|result| result.map_err(WorkerHandlerError::StoreRemove));
+                if let Err(e) = removal {
+                    warn!("Failed to remove staged batch {digest}: {e:?}");
+                }
+            }
+            Ok(Err(_)) => {}
+            // The staged write may still go through, remove it once it does.
+            Err(_) => {
+                let staging = staging.clone();
+                tokio::spawn(async move {
+                    if let Ok(Ok(())) = staging_task.await {
+                        if let Err(e) = staging.remove(&digest) {
+                            warn!("Failed to remove staged batch {digest}: {e:?}");
+                        }
+                    }
+                });
+            }
+        }
+        result
+    }
+
+    /// Validates and stores a batch reported by a peer, and reports it to our primary. If
+    /// `validation` is set, the batch was already validated with this result.
+    async fn accept_batch(
+        &self,
+        batch: Batch,
+        peer: Option<anemo::PeerId>,
+        validation: Option<Result<(), WorkerHandlerError>>,
+    ) -> Result<(), WorkerHandlerError> {
+        let _migration_guard = self
+            .store_migration
+            .as_ref()
+            .map(StoreMigration::write)
+            .transpose()?;
+        let validated = match validation {
+            Some(result) => {
+                result?;
+                true
+            }
+            None => false,
+        };
+        // Batches can be large, so read them once, without copying.
+        let (digest, size) = batch.digest_and_size();
+        trace!("Accepting batch {digest} of {size} bytes from {peer:?}");
+        if size < self.min_batch_size {
+            return Err(WorkerHandlerError::UndersizedBatch {
+                digest,
+                size,
+                min: self.min_batch_size,
+            });
+        }
+        // Only batches that were not stored yet are reported to the observer, deduplicated,
+        // or written speculatively, since rolling back would remove the previously stored
+        // copy.
+        let is_new = if self.observer.is_some()
+            || self.speculative_write.is_some()
+            || self.tx_dedup.is_some()
+            || self.replicator.is_some()
+        {
+            let store_op = move |store: &S| store.contains_key(&digest);
+            !with_store_timeout(&self.store, self.store_timeout, store_op)
+                .await?
+                .map_err(WorkerHandlerError::StoreRead)?
+        } else {
+            false
+        };
+        if let Some(tx_dedup) = &self.tx_dedup {
+            if is_new && tx_dedup.is_redundant(&batch) {
+                return Err(WorkerHandlerError::RedundantBatch(digest));
+            }
+        }
+        let staging = self
+            .speculative_write
+            .as_ref()
+            .filter(|_| is_new && !validated);
+        let (batch, write_latency) = if let Some(staging) = staging {
+            let _write_permit = self.acquire_write_permit().await?;
+            self.validate_with_speculative_write(staging, digest, batch)
+                .await?
+        } else {
+            if !validated {
+                let breaker = self.validator_breaker.as_ref();
+                let permits = self.validation_permits.as_ref();
+                validate_batch(&self.validator, breaker, permits, &batch).await?;
+            }
+            let _write_permit = self.acquire_write_permit().await?;
+            let write_start = Instant::now();
+            // The coalescer does not index transactions.
+            let write_coalescer = self
+                .write_coalescer
+                .as_ref()
+                .filter(|_| !self.index_transactions);
+            let batch = match write_coalescer {
+                Some(coalescer) => match self.store_timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, coalescer.insert(digest, batch))
+                        .await
+                        .map_err(|_| WorkerHandlerError::StoreTimeout(timeout))?,
+                    None => coalescer.insert(digest, batch).await,
+                },
+                None => {
+                    let index_transactions = self.index_transactions;
+                    let store_op = move |store: &S| {
+                        if index_transactions {
+                            store.insert_indexed(&digest, &batch)
+                        } else {
+                            store.insert(&digest, &batch)
+                        }
+                        .map(|()| batch)
+                    };
+                    with_store_timeout(&self.store, self.store_timeout, store_op).await?
+                }
+            }
+            .map_err(WorkerHandlerError::StoreWrite)?;
+            (batch, write_start.elapsed())
+        };
+        if let Some(backpressure) = &self.write_backpressure {
+            backpressure.record(write_latency);
+        }
+        if let (Some(reciprocity), Some(peer)) = (&self.reciprocity, peer) {
+            reciprocity.record_received(peer, size);
+        }
+        if let Some(tx_dedup) = &self.tx_dedup {
+            tx_dedup.record(&batch);
+        }
+        // A batch deleted by mistake and reported again is served again.
+        if let Some(tombstones) = &self.tombstones {
+            tombstones.restore(&[digest]);
+        }
+        if let Some(insert_times) = &self.batch_insert_times {
+            insert_times.record(digest);
+        }
+        if let Some(prefetcher) = &self.prefetcher {
+            prefetcher.record(digest);
+        }
+        if let Some(observer) = self.observer.as_ref().filter(|_| is_new) {
+            observer.observe(digest, &batch, peer);
+        }
+        if let Some(replicator) = self.replicator.as_ref().filter(|_| is_new) {
+            replicator.replicate(batch.clone(), peer);
+        }
+        if let Some(mirror) = &self.mirror {
+            mirror.mirror(digest, batch);
+        }
+        if !self.notify_primary {
+            debug!("Not reporting batch {digest} to the primary");
+            return Ok(());
+        }
+        let message = WorkerOthersBatchMessage {
+            digest,
+            worker_id: self.id,
+        };
+        let result = match &self.others_batch_reporter {
+            Some(reporter) => reporter.report(message).await,
+            None => self.client.report_others_batch(message).await,
+        };
+        match result {
+            // The batch is stored, and reporting it again would be rejected all the same, so
+            // only failures the caller can retry past fail the report.
+            Err(LocalClientError::Rejected(e)) => {
+                warn!("Primary rejected batch {digest}, which is stored nonetheless: {e}");
+                Ok(())
+            }
+            result => result.map_err(|e| WorkerHandlerError::ReportToPrimary(e.to_string())),
+        }
+    }
+
+    /// Serves request_batch and request_batch_v2, once the peer was checked.
+    async fn serve_request_batch(
+        &self,
+        peer: Option<anemo::PeerId>,
+        request: RequestBatchV2Request,
+    ) -> Result<anemo::Response<RequestBatchV2Response>, anemo::rpc::Status> {
+        if request.caller_has_batch {
+            let not_modified = self.contains_batch(&request.batch).await?;
+            self.metrics
+                .record_peer_batch_request(peer.as_ref(), "request_batch", 0);
+            return Ok(anemo::Response::new(RequestBatchV2Response {
+                batch: None,
+                certificate: None,
+                slice: None,
+                not_modified,
+            }));
+        }
+        let batch = self.read_batch(&request.batch).await?;
+        if let Some(prefetcher) = self.prefetcher.as_ref().filter(|_| batch.is_some()) {
+            prefetcher.prefetch(&self.read_store(), &request.batch);
+        }
+        let size = batch.as_ref().map_or(0, |batch| batch.size());
+        self.metrics
+            .record_peer_batch_request(peer.as_ref(), "request_batch", size);
+        if let (Some(reciprocity), Some(peer)) = (&self.reciprocity, peer) {
+            reciprocity.record_served(peer, size);
+        }
+
+        let certificate = match &self.batch_certificates {
+            Some(certificates)
+                if request.include_certificate && certificates.serves(peer.as_ref()) =>
+            {
+                certificates.get(&request.batch)
+            }
+            _ => None,
+        };
+
+        // A missing batch may be stored later, so only found batches are cacheable. Neither are
+        // transformed batches, which differ between peers, nor responses asking for a
+        // certificate, which may be recorded later.
+        let read_transform = self.read_transform_for(peer.as_ref());
+        let is_cacheable =
+            batch.is_some() && read_transform.is_none() && !request.include_certificate;
+        let batch = match read_transform {
+            Some(read_transform) => batch.map(|batch| read_transform.apply(batch)),
+            None => batch,
+        };
+        let (batch, slice) = match (batch, request.range) {
+            (Some(batch), Some(range)) => (None, Some(BatchSlice::new(&batch, range))),
+            (batch, _) => (batch, None),
+        };
+        let response = anemo::Response::new(RequestBatchV2Response {
+            batch,
+            certificate,
+            slice,
+            not_modified: false,
+        });
+        Ok(if is_cacheable {
+            cacheable(response)
+        } else {
+            response
+        })
+    }
+
+    /// Serves request_batches and request_batches_v2, once the peer was checked.
+    async fn serve_request_batches(
+        &self,
+        peer: Option<anemo::PeerId>,
+        deadline: Option<Instant>,
+        mut request: RequestBatchesV2Request,
+    ) -> Result<anemo::Response<RequestBatchesV2Response>, anemo::rpc::Status> {
+        let keyed_by_digest = request.keyed_by_digest;
+        if request.already_have.len() > RequestBatchesV2Request::MAX_ALREADY_HAVE_DIGESTS {
+            return Err(WorkerHandlerError::SizeExceeded {
+                size: request.already_have.len(),
+                limit: RequestBatchesV2Request::MAX_ALREADY_HAVE_DIGESTS,
+            }
+            .into());
+        }
+        let already_have: HashSet<_> = std::mem::take(&mut request.already_have)
+            .into_iter()
+            .collect();
+        let requested_digests = request
+            .digests()
+            .map_err(|e| WorkerHandlerError::InvalidDigests(e.to_string()))?;
+        // Duplicates are never useful, so only read and return each batch once, in the order
+        // first requested.
+        let requested_len = requested_digests.len();
+        let digests_to_fetch = requested_digests.into_iter().unique().collect_vec();
+        let duplicates = requested_len - digests_to_fetch.len();
+        if duplicates > 0 {
+            debug!("Dropping {duplicates} duplicate digests from request_batches");
+            self.metrics
+                .request_batches_duplicate_digests
+                .inc_by(duplicates as u64);
+        }
+        let digests_to_fetch = if already_have.is_empty() {
+            digests_to_fetch
+        } else {
+            let unique_len = digests_to_fetch.len();
+            let digests_to_fetch = digests_to_fetch
+                .into_iter()
+                .filter(|digest| !already_have.contains(digest))
+                .collect_vec();
+            let skipped = unique_len - digests_to_fetch.len();
+            debug!("Skipping {skipped} digests of request_batches the caller already has");
+            self.metrics
+                .request_batches_already_have_digests
+                .inc_by(skipped as u64);
+            digests_to_fetch
+        };
+        let mut buffers = match &self.response_buffers {
+            Some(pool) => pool.take(),
+            None => PooledBuffers::unpooled(),
+        };
+        let ResponseBuffers {
+            chunk_digests,
+            keys,
+            // The requested digest of each batch, in the same order.
+            batch_digests,
+        } = &mut *buffers;
         let mut batches = Vec::new();
         let mut total_size = 0;
         let mut is_size_limit_reached = false;
+        let mut deferred_digests = Vec::new();
+        let mut slowest_chunk_read = Duration::ZERO;
+        // What is left of the frame once its overhead is accounted for. The digests of the
+        // request are charged up front, as any of them may be returned as deferred.
+        let frame_budget = self.max_response_frame_size.map(|max_frame_size| {
+            max_frame_size.saturating_sub(
+                RESPONSE_FRAME_OVERHEAD + digests_to_fetch.len() * crypto::DIGEST_LENGTH,
+            )
+        });
+        let mut total_encoded_size = 0;
 
-        for digests_chunks in digests_chunks {
-            let stored_batches = self.store.multi_get(digests_chunks).map_err(|e| {
-                anemo::rpc::Status::internal(format!("failed to read from batch store: {e:?}"))
-            })?;
+        for (i, digests_chunk) in digests_to_fetch
+            .chunks(BATCH_DIGESTS_READ_CHUNK_SIZE)
+            .enumerate()
+        {
+            // Take a permit per chunk rather than holding one for the whole request.
+            let _permit = self.read_permits.acquire(ReadPriority::Bulk).await;
+            // Rather than time out, return what was read so far if the next chunk is not
+            // expected to be read before the caller's deadline.
+            if deadline.map_or(false, |deadline| {
+                deadline.saturating_duration_since(Instant::now()) <= slowest_chunk_read
+            }) {
+                deferred_digests = digests_to_fetch[i * BATCH_DIGESTS_READ_CHUNK_SIZE..].to_vec();
+                debug!(
+                    "Deferring {} digests of request_batches past the caller's deadline",
+                    deferred_digests.len()
+                );
+                break;
+            }
+            let read_start = Instant::now();
+            chunk_digests.clear();
+            keys.clear();
+            for digest in digests_chunk {
+                let key = *digest;
+                if !self.is_tombstoned(&key) {
+                    chunk_digests.push(*digest);
+                    keys.push(key);
+                }
+            }
+            let mut stored_batches = match self.request_batches_chunk_retries {
+                None => {
+                    // The store reads the keys off this task, so lend it the buffer.
+                    let store_keys = std::mem::take(keys);
+                    let store_op = move |store: &S| {
+                        let stored_batches = store.multi_get(&store_keys);
+                        (store_keys, stored_batches)
+                    };
+                    let (store_keys, stored_batches) =
+                        with_store_timeout(&self.read_store(), self.store_timeout, store_op)
+                            .await?;
+                    *keys = store_keys;
+                    stored_batches.map_err(WorkerHandlerError::StoreRead)?
+                }
+                Some(retries) => self.multi_get_with_retries(keys, retries).await?,
+            };
+            self.read_archive(keys, &mut stored_batches).await?;
+            slowest_chunk_read = slowest_chunk_read.max(read_start.elapsed());
 
-            for stored_batch in stored_batches.into_iter().flatten() {
+            for (digest, stored_batch) in chunk_digests
+                .drain(..)
+                .zip(stored_batches)
+                .filter_map(|(digest, batch)| Some((digest, batch?)))
+            {
                 let batch_size = stored_batch.size();
-                if total_size + batch_size <= MAX_REQUEST_BATCHES_RESPONSE_SIZE {
+                // Each batch may also come with its digest and age.
+                let encoded_size = frame_budget.map_or(0, |_| {
+                    stored_batch.serialized_size() + crypto::DIGEST_LENGTH + 8
+                });
+                let fits_frame = frame_budget.map_or(true, |frame_budget| {
+                    max_compressed_len(total_encoded_size + encoded_size) <= frame_budget
+                });
+                if !fits_frame && batches.is_empty() {
+                    // Not even the first batch fits, so the requester would never make
+                    // progress by fetching the remaining batches in follow-up requests.
+                    return Err(WorkerHandlerError::ResponseTooLarge {
+                        size: max_compressed_len(encoded_size)
+                            + RESPONSE_FRAME_OVERHEAD
+                            + digests_to_fetch.len() * crypto::DIGEST_LENGTH,
+                        limit: self.max_response_frame_size.unwrap_or_default(),
+                    }
+                    .into());
+                }
+                // Any cap being hit is reported as `is_size_limit_reached`, so that the
+                // requester fetches the remaining batches in a follow-up request.
+                if batches.len() < self.max_request_batches_response_count
+                    && total_size + batch_size <= MAX_REQUEST_BATCHES_RESPONSE_SIZE
+                    && fits_frame
+                {
                     batches.push(stored_batch);
+                    batch_digests.push(digest);
                     total_size += batch_size;
+                    total_encoded_size += encoded_size;
                 } else {
                     is_size_limit_reached = true;
                     break;
@@ -107,155 +1239,1941 @@ impl<V: TransactionValidator> WorkerToWorker for WorkerReceiverHandler<V> {
             }
         }
 
-        Ok(anemo::Response::new(RequestBatchesResponse {
+        self.metrics
+            .record_peer_batch_request(peer.as_ref(), "request_batches", total_size);
+        if let (Some(reciprocity), Some(peer)) = (&self.reciprocity, peer) {
+            reciprocity.record_served(peer, total_size);
+        }
+        if is_size_limit_reached {
+            self.size_limit_events
+                .record(peer, digests_to_fetch.len(), batches.len());
+        }
+        if let Some(audit) = &self.request_batches_audit {
+            audit.record(
+                peer,
+                requested_len,
+                batches.len(),
+                total_size,
+                is_size_limit_reached,
+            );
+        }
+        // Batches stored before the worker started fall back to their creation time, when
+        // our own batches are stored.
+        let batch_ages_ms = self.batch_insert_times.as_ref().map(|insert_times| {
+            let now = now();
+            batches
+                .iter()
+                .zip(batch_digests.iter())
+                .map(|(batch, digest)| {
+                    let stored_at = insert_times
+                        .get(digest)
+                        .unwrap_or(batch.metadata().created_at);
+                    now.saturating_sub(stored_at)
+                })
+                .collect()
+        });
+
+        // Only complete responses are cacheable. Batch ages change over time, and transformed
+        // batches differ between peers.
+        let read_transform = self.read_transform_for(peer.as_ref());
+        let is_cacheable = batches.len() == digests_to_fetch.len()
+            && deferred_digests.is_empty()
+            && batch_ages_ms.is_none()
+            && read_transform.is_none();
+        let batches = match read_transform {
+            Some(read_transform) => batches
+                .into_iter()
+                .map(|batch| read_transform.apply(batch))
+                .collect(),
+            None => batches,
+        };
+        let (batches, batches_by_digest) = if keyed_by_digest {
+            (
+                Vec::new(),
+                Some(batch_digests.drain(..).zip(batches).collect()),
+            )
+        } else {
+            (batches, None)
+        };
+        let response = anemo::Response::new(RequestBatchesV2Response {
             batches,
             is_size_limit_reached,
-        }))
+            batch_ages_ms,
+            deferred_digests,
+            batches_by_digest,
+        });
+        Ok(if is_cacheable {
+            cacheable(response)
+        } else {
+            response
+        })
     }
-}
 
-/// Defines how the network receiver handles incoming primary messages.
-pub struct PrimaryReceiverHandler<V> {
-    // The id of this authority.
-    pub authority_id: AuthorityIdentifier,
-    // The id of this worker.
-    pub id: WorkerId,
-    // The committee information.
-    pub committee: Committee,
-    // The worker information cache.
-    pub worker_cache: WorkerCache,
-    // The batch store
-    pub store: DBMap<BatchDigest, Batch>,
-    // Timeout on RequestBatch RPC.
-    pub request_batch_timeout: Duration,
-    // Number of random nodes to query when retrying batch requests.
-    pub request_batch_retry_nodes: usize,
-    // Synchronize header payloads from other workers.
-    pub network: Option<Network>,
-    // Fetch certificate payloads from other workers.
-    pub batch_fetcher: Option<BatchFetcher>,
-    // Validate incoming batches
-    pub validator: V,
-}
+    /// Waits for a store write permit, if writes are bounded.
+    async fn acquire_write_permit(
+        &self,
+    ) -> Result<Option<OwnedSemaphorePermit>, WorkerHandlerError> {
+        match &self.write_permits {
+            Some(write_permits) => write_permits.acquire().await.map(Some),
+            None => Ok(None),
+        }
+    }
 
-#[async_trait]
-impl<V: TransactionValidator> PrimaryToWorker for PrimaryReceiverHandler<V> {
-    async fn synchronize(
+    /// Reads the given keys, retrying on failure. If every attempt fails, all the keys are
+    /// reported missing.
+    async fn multi_get_with_retries(
         &self,
-        request: anemo::Request<WorkerSynchronizeMessage>,
-    ) -> Result<anemo::Response<()>, anemo::rpc::Status> {
-        let Some(network) = self.network.as_ref() else {
-            return Err(anemo::rpc::Status::new_with_message(
-                StatusCode::BadRequest,
-                "synchronize() is unsupported via RPC interface, please call via local worker handler instead",
-            ));
-        };
-        let message = request.body();
-        let mut missing = HashSet::new();
-        for digest in message.digests.iter() {
-            // Check if we already have the batch.
-            match self.store.get(digest) {
-                Ok(None) => {
-                    missing.insert(*digest);
-                    debug!("Requesting sync for batch {digest}");
-                }
-                Ok(Some(_)) => {
-                    trace!("Digest {digest} already in store, nothing to sync");
+        keys: &[BatchDigest],
+        retries: usize,
+    ) -> Result<Vec<Option<Batch>>, WorkerHandlerError> {
+        let mut attempt = 0;
+        loop {
+            let attempt_keys = keys.to_vec();
+            let store_op = move |store: &S| store.multi_get(&attempt_keys);
+            match with_store_timeout(&self.read_store(), self.store_timeout, store_op).await? {
+                Ok(batches) => return Ok(batches),
+                Err(e) if attempt < retries => {
+                    attempt += 1;
+                    debug!("Retrying failed batch store read (attempt {attempt}/{retries}): {e:?}");
                 }
                 Err(e) => {
-                    return Err(anemo::rpc::Status::internal(format!(
-                        "failed to read from batch store: {e:?}"
-                    )));
+                    warn!(
+                        "Omitting {} batches after failing to read them from the batch store: {e:?}",
+                        keys.len()
+                    );
+                    return Ok(vec![None; keys.len()]);
                 }
-            };
-        }
-        if missing.is_empty() {
-            return Ok(anemo::Response::new(()));
+            }
         }
+    }
+}
 
-        let worker_name = match self.worker_cache.worker(
-            self.committee
-                .authority(&message.target)
-                .unwrap()
-                .protocol_key(),
-            &self.id,
-        ) {
-            Ok(worker_info) => worker_info.name,
-            Err(e) => {
-                return Err(anemo::rpc::Status::internal(format!(
-                    "The primary asked worker to sync with an unknown node: {e}"
-                )));
+#[async_trait]
+impl<V: TransactionValidator, S: BatchStore> WorkerToWorker for WorkerReceiverHandler<V, S> {
+    async fn report_batch(
+        &self,
+        request: anemo::Request<WorkerBatchMessage>,
+    ) -> Result<anemo::Response<()>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            if let Some(backpressure) = &self.write_backpressure {
+                if backpressure.should_shed() {
+                    return Err(WorkerHandlerError::Overloaded.into());
+                }
             }
-        };
-        let Some(peer) = network.peer(anemo::PeerId(worker_name.0.to_bytes())) else {
-            return Err(anemo::rpc::Status::internal(format!(
-                "Not connected with worker peer {worker_name}"
-            )));
-        };
-        let mut client = WorkerToWorkerClient::new(peer.clone());
+            check_validator_breaker(self.validator_breaker.as_ref())?;
+            let peer = request.peer_id().copied();
+            self.check_rate_limit(peer.as_ref())?;
+            self.check_peer_role(peer.as_ref(), "report_batch", true)?;
+            self.accept_batch(request.into_body().batch, peer, None)
+                .await?;
+            Ok(anemo::Response::new(()))
+        })
+        .await
+    }
 
-        // Attempt to retrieve missing batches.
-        // Retried at a higher level in Synchronizer::sync_batches_internal().
-        let request = RequestBatchesRequest {
-            batch_digests: missing.iter().cloned().collect(),
-        };
-        debug!("Sending RequestBatchesRequest to {worker_name}: {request:?}");
-        let response = client
-            .request_batches(anemo::Request::new(request).with_timeout(self.request_batch_timeout))
-            .await?
-            .into_inner();
-        for batch in response.batches {
-            if !message.is_certified {
-                // This batch is not part of a certificate, so we need to validate it.
-                if let Err(err) = self.validator.validate_batch(&batch).await {
-                    return Err(anemo::rpc::Status::new_with_message(
-                        StatusCode::BadRequest,
-                        format!("Invalid batch: {err}"),
-                    ));
+    async fn report_batches(
+        &self,
+        request: anemo::Request<WorkerBatchesMessage>,
+    ) -> Result<anemo::Response<ReportBatchesResponse>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            if let Some(backpressure) = &self.write_backpressure {
+                if backpressure.should_shed() {
+                    return Err(WorkerHandlerError::Overloaded.into());
                 }
             }
-            let digest = batch.digest();
-            if missing.remove(&digest) {
-                self.store.insert(&digest, &batch).map_err(|e| {
-                    anemo::rpc::Status::internal(format!("failed to write to batch store: {e:?}"))
-                })?;
+            check_validator_breaker(self.validator_breaker.as_ref())?;
+            let peer = request.peer_id().copied();
+            self.check_rate_limit(peer.as_ref())?;
+            self.check_peer_role(peer.as_ref(), "report_batches", true)?;
+            let batches = request.into_body().batches;
+            // Validate concurrently, or all at once, then store the valid batches in order.
+            // `buffered` yields the results in the order of the batches.
+            let validations: Vec<_> = if self.validate_batches_together {
+                let breaker = self.validator_breaker.as_ref();
+                let permits = self.validation_permits.as_ref();
+                validate_batches(
+                    &self.validator,
+                    breaker,
+                    permits,
+                    &batches.iter().collect_vec(),
+                )
+                .await
+                .into_iter()
+                .map(|result| result.map_err(WorkerHandlerError::from))
+                .collect()
+            } else {
+                stream::iter(&batches)
+                    .map(|batch| async move {
+                        let breaker = self.validator_breaker.as_ref();
+                        let permits = self.validation_permits.as_ref();
+                        validate_batch(&self.validator, breaker, permits, batch)
+                            .await
+                            .map_err(WorkerHandlerError::from)
+                    })
+                    .buffered(self.report_batches_parallelism.max(1))
+                    .collect()
+                    .await
+            };
+            let mut errors = Vec::with_capacity(batches.len());
+            for (batch, validation) in batches.into_iter().zip(validations) {
+                let result = self.accept_batch(batch, peer, Some(validation)).await;
+                errors.push(result.err().map(|err| err.to_string()));
             }
-        }
+            Ok(anemo::Response::new(ReportBatchesResponse { errors }))
+        })
+        .await
+    }
 
-        if missing.is_empty() {
-            return Ok(anemo::Response::new(()));
-        }
-        Err(anemo::rpc::Status::internal(
-            "failed to synchronize batches!",
-        ))
+    async fn request_batch(
+        &self,
+        request: anemo::Request<RequestBatchRequest>,
+    ) -> Result<anemo::Response<RequestBatchResponse>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            // TODO [issue #7]: Do some accounting to prevent bad actors from monopolizing our resources
+            let peer = request.peer_id().copied();
+            self.check_rate_limit(peer.as_ref())?;
+            self.check_peer_role(peer.as_ref(), "request_batch", true)?;
+            self.check_reciprocity(peer.as_ref())?;
+            let request = RequestBatchV2Request::from(request.into_body());
+            self.serve_request_batch(peer, request)
+                .await
+                .map(into_legacy_response)
+        })
+        .await
     }
 
-    async fn fetch_batches(
+    async fn request_batch_v2(
         &self,
-        request: anemo::Request<FetchBatchesRequest>,
-    ) -> Result<anemo::Response<FetchBatchesResponse>, anemo::rpc::Status> {
-        let Some(batch_fetcher) = self.batch_fetcher.as_ref() else {
-            return Err(anemo::rpc::Status::new_with_message(
-                StatusCode::BadRequest,
-                "fetch_batches() is unsupported via RPC interface, please call via local worker handler instead",
+        request: anemo::Request<RequestBatchV2Request>,
+    ) -> Result<anemo::Response<RequestBatchV2Response>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            let peer = request.peer_id().copied();
+            self.check_rate_limit(peer.as_ref())?;
+            self.check_peer_role(peer.as_ref(), "request_batch", true)?;
+            self.check_reciprocity(peer.as_ref())?;
+            self.serve_request_batch(peer, request.into_body()).await
+        })
+        .await
+    }
+
+    async fn request_batches(
+        &self,
+        request: anemo::Request<RequestBatchesRequest>,
+    ) -> Result<anemo::Response<RequestBatchesResponse>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            let peer = request.peer_id().copied();
+            self.check_rate_limit(peer.as_ref())?;
+            self.check_peer_role(peer.as_ref(), "request_batches", true)?;
+            self.check_reciprocity(peer.as_ref())?;
+            let request = RequestBatchesV2Request::from(request.into_body());
+            self.serve_request_batches(peer, deadline, request)
+                .await
+                .map(into_legacy_response)
+        })
+        .await
+    }
+
+    async fn request_batches_v2(
+        &self,
+        request: anemo::Request<RequestBatchesV2Request>,
+    ) -> Result<anemo::Response<RequestBatchesV2Response>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            let peer = request.peer_id().copied();
+            self.check_rate_limit(peer.as_ref())?;
+            self.check_peer_role(peer.as_ref(), "request_batches", true)?;
+            self.check_reciprocity(peer.as_ref())?;
+            self.serve_request_batches(peer, deadline, request.into_body())
+                .await
+        })
+        .await
+    }
+
+    async fn intersect_batches(
+        &self,
+        request: anemo::Request<IntersectBatchesRequest>,
+    ) -> Result<anemo::Response<IntersectBatchesResponse>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            const MAX_INTERSECT_BATCHES_DIGESTS: usize = 100_000;
+            const BATCH_DIGESTS_CONTAINS_CHUNK_SIZE: usize = 1_000;
+
+            self.check_rate_limit(request.peer_id())?;
+            self.check_peer_role(request.peer_id(), "intersect_batches", false)?;
+            let mut digests = request.into_body().batch_digests;
+            if digests.len() > MAX_INTERSECT_BATCHES_DIGESTS {
+                return Err(WorkerHandlerError::SizeExceeded {
+                    size: digests.len(),
+                    limit: MAX_INTERSECT_BATCHES_DIGESTS,
+                }
+                .into());
+            }
+            digests.sort();
+            digests.dedup();
+
+            let mut held = Vec::new();
+            for chunk in digests.chunks(BATCH_DIGESTS_CONTAINS_CHUNK_SIZE) {
+                // Take a permit per chunk rather than holding one for the whole request.
+                let _permit = self.read_permits.acquire(ReadPriority::Bulk).await;
+                let keys = chunk.to_vec();
+                let store_op = move |store: &S| store.multi_contains_keys(&keys);
+                let contained =
+                    with_store_timeout(&self.read_store(), self.store_timeout, store_op)
+                        .await?
+                        .map_err(WorkerHandlerError::StoreRead)?;
+                held.extend(
+                    chunk
+                        .iter()
+                        .zip(contained)
+                        .filter(|(digest, contained)| *contained && !self.is_tombstoned(digest))
+                        .map(|(digest, _)| *digest),
+                );
+            }
+
+            Ok(anemo::Response::new(IntersectBatchesResponse {
+                batch_digests: held,
+            }))
+        })
+        .await
+    }
+
+    async fn batch_sizes(
+        &self,
+        request: anemo::Request<BatchSizesRequest>,
+    ) -> Result<anemo::Response<BatchSizesResponse>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            const MAX_BATCH_SIZES_DIGESTS: usize = 10_000;
+
+            self.check_rate_limit(request.peer_id())?;
+            self.check_peer_role(request.peer_id(), "batch_sizes", false)?;
+            let digests = request.into_body().batch_digests;
+            if digests.len() > MAX_BATCH_SIZES_DIGESTS {
+                return Err(WorkerHandlerError::SizeExceeded {
+                    size: digests.len(),
+                    limit: MAX_BATCH_SIZES_DIGESTS,
+                }
+                .into());
+            }
+
+            let mut batch_sizes = Vec::with_capacity(digests.len());
+            for chunk in digests.chunks(BATCH_DIGESTS_READ_CHUNK_SIZE) {
+                // Take a permit per chunk rather than holding one for the whole request.
+                let _permit = self.read_permits.acquire(ReadPriority::Bulk).await;
+                let keys = chunk.to_vec();
+                let tombstoned = keys.iter().map(|key| self.is_tombstoned(key)).collect_vec();
+                let store_op = move |store: &S| store.multi_get(&keys);
+                let stored_batches =
+                    with_store_timeout(&self.read_store(), self.store_timeout, store_op)
+                        .await?
+                        .map_err(WorkerHandlerError::StoreRead)?;
+                // Tombstoned batches are not served by request_batches, so report them missing.
+                batch_sizes.extend(
+                    stored_batches
+                        .into_iter()
+                        .zip(tombstoned)
+                        .map(|(batch, tombstoned)| batch.filter(|_| !tombstoned).map(|b| b.size())),
+                );
+            }
+
+            Ok(anemo::Response::new(BatchSizesResponse {
+                batch_sizes,
+                max_request_batches_response_size: MAX_REQUEST_BATCHES_RESPONSE_SIZE,
+            }))
+        })
+        .await
+    }
+
+    async fn request_batch_metadata(
+        &self,
+        request: anemo::Request<RequestBatchMetadataRequest>,
+    ) -> Result<anemo::Response<RequestBatchMetadataResponse>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            const MAX_BATCH_METADATA_DIGESTS: usize = 10_000;
+
+            self.check_rate_limit(request.peer_id())?;
+            self.check_peer_role(request.peer_id(), "request_batch_metadata", false)?;
+            let digests = request.into_body().batch_digests;
+            if digests.len() > MAX_BATCH_METADATA_DIGESTS {
+                return Err(WorkerHandlerError::SizeExceeded {
+                    size: digests.len(),
+                    limit: MAX_BATCH_METADATA_DIGESTS,
+                }
+                .into());
+            }
+
+            let mut batch_summaries = Vec::with_capacity(digests.len());
+            for chunk in digests.chunks(BATCH_DIGESTS_READ_CHUNK_SIZE) {
+                // Take a permit per chunk rather than holding one for the whole request.
+                let _permit = self.read_permits.acquire(ReadPriority::Bulk).await;
+                let keys = chunk.to_vec();
+                let tombstoned = keys.iter().map(|key| self.is_tombstoned(key)).collect_vec();
+                let store_op = move |store: &S| store.multi_get_summaries(&keys);
+                let summaries =
+                    with_store_timeout(&self.read_store(), self.store_timeout, store_op)
+                        .await?
+                        .map_err(WorkerHandlerError::StoreRead)?;
+                // Tombstoned batches are not served by request_batches, so report them missing.
+                batch_summaries.extend(
+                    summaries
+                        .into_iter()
+                        .zip(tombstoned)
+                        .map(|(summary, tombstoned)| summary.filter(|_| !tombstoned)),
+                );
+            }
+
+            Ok(anemo::Response::new(RequestBatchMetadataResponse {
+                batch_summaries,
+            }))
+        })
+        .await
+    }
+
+    async fn locate_transactions(
+        &self,
+        request: anemo::Request<LocateTransactionsRequest>,
+    ) -> Result<anemo::Response<LocateTransactionsResponse>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            const MAX_LOCATE_TRANSACTIONS_DIGESTS: usize = 10_000;
+
+            if !self.index_transactions {
+                return Err(WorkerHandlerError::MethodDisabled("locate_transactions").into());
+            }
+            self.check_rate_limit(request.peer_id())?;
+            self.check_peer_role(request.peer_id(), "locate_transactions", false)?;
+            let transaction_digests = request.into_body().transaction_digests;
+            if transaction_digests.len() > MAX_LOCATE_TRANSACTIONS_DIGESTS {
+                return Err(WorkerHandlerError::SizeExceeded {
+                    size: transaction_digests.len(),
+                    limit: MAX_LOCATE_TRANSACTIONS_DIGESTS,
+                }
+                .into());
+            }
+
+            let mut batch_digests = Vec::with_capacity(transaction_digests.len());
+            for chunk in transaction_digests.chunks(BATCH_DIGESTS_READ_CHUNK_SIZE) {
+                // Take a permit per chunk rather than holding one for the whole request.
+                let _permit = self.read_permits.acquire(ReadPriority::Bulk).await;
+                let chunk = chunk.to_vec();
+                let store_op = move |store: &S| {
+                    chunk
+                        .iter()
+                        .map(|transaction| store.batch_of_transaction(transaction))
+                        .collect::<StoreResult<Vec<_>>>()
+                };
+                let indexed = with_store_timeout(&self.store, self.store_timeout, store_op)
+                    .await?
+                    .map_err(WorkerHandlerError::StoreRead)?;
+                // The index outlives removed batches, so only report the batches still served.
+                let present_keys = indexed
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .filter(|key| !self.is_tombstoned(key))
+                    .collect_vec();
+                let store_op = move |store: &S| {
+                    let contained = store.multi_contains_keys(&present_keys)?;
+                    Ok::<_, TypedStoreError>(
+                        present_keys
+                            .into_iter()
+                            .zip(contained)
+                            .filter_map(|(key, contained)| contained.then_some(key))
+                            .collect::<HashSet<_>>(),
+                    )
+                };
+                let stored = with_store_timeout(&self.store, self.store_timeout, store_op)
+                    .await?
+                    .map_err(WorkerHandlerError::StoreRead)?;
+                batch_digests.extend(
+                    indexed
+                        .into_iter()
+                        .map(|digest| digest.filter(|digest| stored.contains(digest))),
+                );
+            }
+
+            Ok(anemo::Response::new(LocateTransactionsResponse {
+                batch_digests,
+            }))
+        })
+        .await
+    }
+
+    async fn open_bulk_sync(
+        &self,
+        request: anemo::Request<OpenBulkSyncRequest>,
+    ) -> Result<anemo::Response<OpenBulkSyncResponse>, anemo::rpc::Status> {
+        self.check_rate_limit(request.peer_id())?;
+        self.check_peer_role(request.peer_id(), "open_bulk_sync", true)?;
+        self.check_reciprocity(request.peer_id())?;
+        let session_id = self.bulk_sync_sessions.open(request.peer_id().copied());
+        debug!("Opened bulk sync session {session_id}");
+        Ok(anemo::Response::new(OpenBulkSyncResponse { session_id }))
+    }
+
+    async fn request_bulk_sync_page(
+        &self,
+        request: anemo::Request<RequestBulkSyncPageRequest>,
+    ) -> Result<anemo::Response<RequestBulkSyncPageResponse>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            self.check_rate_limit(request.peer_id())?;
+            self.check_peer_role(request.peer_id(), "request_bulk_sync_page", true)?;
+            self.check_reciprocity(request.peer_id())?;
+            let peer = request.peer_id().copied();
+            let RequestBulkSyncPageRequest { session_id, cursor } = request.into_body();
+            let Some(progress) = self.bulk_sync_sessions.progress(peer.as_ref(), session_id) else {
+                return Err(WorkerHandlerError::NotFound(format!(
+                    "Unknown or expired bulk sync session {session_id}"
+                ))
+                .into());
+            };
+            // The requester acknowledges the pages it persisted by sending their cursor. Without
+            // one, resume from the progress recorded for the session.
+            let cursor = match cursor {
+                Some(cursor) => {
+                    self.bulk_sync_sessions
+                        .record(peer.as_ref(), session_id, Some(cursor));
+                    Some(cursor)
+                }
+                None => progress,
+            };
+
+            const STORE_SCAN_CHUNK_SIZE: usize = 200;
+
+            let mut batches = Vec::new();
+            let mut total_size = 0;
+            let mut next_cursor = cursor;
+            let is_complete = 'scan: loop {
+                // Take a permit per chunk rather than holding one for the whole page.
+                let _permit = self.read_permits.acquire(ReadPriority::Bulk).await;
+                let scan_cursor = next_cursor;
+                let store_op =
+                    move |store: &S| store.entries_after(scan_cursor, STORE_SCAN_CHUNK_SIZE);
+                let entries = with_store_timeout(&self.read_store(), self.store_timeout, store_op)
+                    .await?
+                    .map_err(WorkerHandlerError::StoreRead)?;
+                let is_last_chunk = entries.len() < STORE_SCAN_CHUNK_SIZE;
+                for (key, batch) in entries {
+                    if self.is_tombstoned(&key) {
+                        next_cursor = Some(key);
+                        continue;
+                    }
+                    let batch_size = batch.size();
+                    if !batches.is_empty()
+                        && total_size + batch_size > self.bulk_sync_sessions.max_page_size()
+                    {
+                        break 'scan false;
+                    }
+                    total_size += batch_size;
+                    batches.push(batch);
+                    next_cursor = Some(key);
+                }
+                if is_last_chunk {
+                    break true;
+                }
+            };
+
+            if let (Some(reciprocity), Some(peer)) = (&self.reciprocity, peer) {
+                reciprocity.record_served(peer, total_size);
+            }
+            Ok(anemo::Response::new(RequestBulkSyncPageResponse {
+                batches,
+                next_cursor,
+                is_complete,
+            }))
+        })
+        .await
+    }
+
+    async fn sample_batches(
+        &self,
+        request: anemo::Request<SampleBatchesRequest>,
+    ) -> Result<anemo::Response<SampleBatchesResponse>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            const MAX_SAMPLE_BATCHES_SAMPLES: usize = 10_000;
+            // Bounds the work done per request whatever the stride.
+            const MAX_SAMPLE_BATCHES_SCANNED_KEYS: usize = 100_000;
+            const STORE_SCAN_CHUNK_SIZE: usize = 200;
+
+            self.check_rate_limit(request.peer_id())?;
+            self.check_peer_role(request.peer_id(), "sample_batches", false)?;
+            let SampleBatchesRequest {
+                cursor,
+                stride,
+                max_samples,
+            } = request.into_body();
+            let max_samples = max_samples as usize;
+            if max_samples > MAX_SAMPLE_BATCHES_SAMPLES {
+                return Err(WorkerHandlerError::SizeExceeded {
+                    size: max_samples,
+                    limit: MAX_SAMPLE_BATCHES_SAMPLES,
+                }
+                .into());
+            }
+            let stride = stride.max(1) as usize;
+            // Without a cursor, start from a random key and wrap around once the end of the
+            // store is reached, stopping at the start key.
+            let start = cursor.unwrap_or_else(|| BatchDigest::new(rand::random()));
+            let can_wrap = cursor.is_none();
+            let mut wrapped = false;
+
+            let mut batch_digests = Vec::new();
+            let mut scanned = 0;
+            let mut next_cursor = Some(start);
+            'scan: while batch_digests.len() < max_samples {
+                if scanned >= MAX_SAMPLE_BATCHES_SCANNED_KEYS {
+                    break;
+                }
+                // Take a permit per chunk rather than holding one for the whole request.
+                let _permit = self.read_permits.acquire(ReadPriority::Bulk).await;
+                let scan_cursor = next_cursor;
+                let store_op =
+                    move |store: &S| store.entries_after(scan_cursor, STORE_SCAN_CHUNK_SIZE);
+                let entries = with_store_timeout(&self.read_store(), self.store_timeout, store_op)
+                    .await?
+                    .map_err(WorkerHandlerError::StoreRead)?;
+                let is_last_chunk = entries.len() < STORE_SCAN_CHUNK_SIZE;
+                for (key, batch) in entries {
+                    if wrapped && key >= start {
+                        // Wrapped around to where sampling started: the whole store was seen.
+                        next_cursor = None;
+                        break 'scan;
+                    }
+                    scanned += 1;
+                    next_cursor = Some(key);
+                    // Only sample batches that request_batch would serve: skip the corrupted
+                    // and tombstoned entries.
+                    if key == self_test_key() || key != batch.digest() || self.is_tombstoned(&key) {
+                        continue;
+                    }
+                    if scanned % stride == 0 {
+                        batch_digests.push(batch.digest());
+                        if batch_digests.len() == max_samples {
+                            break 'scan;
+                        }
+                    }
+                }
+                if is_last_chunk {
+                    next_cursor = None;
+                    if !can_wrap || wrapped {
+                        break;
+                    }
+                    wrapped = true;
+                }
+            }
+
+            Ok(anemo::Response::new(SampleBatchesResponse {
+                batch_digests,
+                next_cursor,
+            }))
+        })
+        .await
+    }
+
+    async fn store_version(
+        &self,
+        request: anemo::Request<StoreVersionRequest>,
+    ) -> Result<anemo::Response<StoreVersionResponse>, anemo::rpc::Status> {
+        let peer = request.peer_id().copied();
+        self.check_rate_limit(peer.as_ref())?;
+        self.check_peer_role(peer.as_ref(), "store_version", false)?;
+        let Some(store_version) = &self.store_version else {
+            return Err(WorkerHandlerError::MethodDisabled("store_version").into());
+        };
+        Ok(anemo::Response::new(StoreVersionResponse {
+            version: store_version.current(),
+        }))
+    }
+
+    async fn capabilities(
+        &self,
+        request: anemo::Request<WorkerCapabilitiesRequest>,
+    ) -> Result<anemo::Response<WorkerCapabilitiesResponse>, anemo::rpc::Status> {
+        let peer = request.peer_id().copied();
+        self.check_rate_limit(peer.as_ref())?;
+        self.check_peer_role(peer.as_ref(), "capabilities", false)?;
+        let features = [
+            (
+                WorkerFeature::BatchCertificates,
+                self.batch_certificates
+                    .as_ref()
+                    .map_or(false, |certificates| certificates.serves(peer.as_ref())),
+            ),
+            (WorkerFeature::BatchAges, self.batch_insert_times.is_some()),
+            (WorkerFeature::LocateTransactions, self.index_transactions),
+            (WorkerFeature::StoreVersion, self.store_version.is_some()),
+            (WorkerFeature::ArchiveReads, self.archive_store.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(feature, enabled)| enabled.then_some(feature))
+        .collect();
+        Ok(anemo::Response::new(WorkerCapabilitiesResponse {
+            protocol_version: WORKER_PROTOCOL_VERSION,
+            features,
+        }))
+    }
+}
+
+/// Defines how the network receiver handles incoming primary messages.
+pub struct PrimaryReceiverHandler<V, S = DBMap<BatchDigest, Batch>> {
+    // The id of this authority.
+    pub authority_id: AuthorityIdentifier,
+    // The id of this worker.
+    pub id: WorkerId,
+    // The committee information.
+    pub committee: Committee,
+    // The worker information cache.
+    pub worker_cache: WorkerCache,
+    // The batch store
+    pub store: S,
+    // Timeout on RequestBatch RPC.
+    pub request_batch_timeout: Duration,
+    // Number of random nodes to query when retrying batch requests.
+    pub request_batch_retry_nodes: usize,
+    // Synchronize header payloads from other workers.
+    pub network: Option<Network>,
+    // The protocol version of the workers synchronize requests batches from, so that the
+    // newer versions of request_batches are only used with workers serving them.
+    pub peer_protocol_versions: PeerProtocolVersions,
+    // Fetch certificate payloads from other workers.
+    pub batch_fetcher: Option<BatchFetcher<S>>,
+    // Validate incoming batches
+    pub validator: V,
+    // Bounds concurrent store reads, shared with the `WorkerReceiverHandler`.
+    pub read_permits: StoreReadPermits,
+    // Maximum total size in bytes of the batches returned by a single fetch_batches_v2 call.
+    pub max_fetch_batches_response_size: usize,
+    // How much to trust the primary when it marks synchronized batches as certified.
+    pub certified_batch_verification: CertifiedBatchVerification,
+    // Whether synchronize fails on the first invalid batch, or skips it.
+    pub invalid_batch_policy: InvalidBatchPolicy,
+    // The methods served by this handler.
+    pub enabled_methods: EnabledPrimaryToWorkerMethods,
+    // How large delete_batches requests are split into write batches.
+    pub delete_batches_chunking: DeleteBatchesChunking,
+    // If set, notified of every batch stored by synchronize.
+    pub observer: Option<BatchObserver>,
+    // If set, records when synchronize stores each batch. Shared with the
+    // `WorkerReceiverHandler`, which annotates request_batches_v2 responses with batch ages.
+    pub batch_insert_times: Option<BatchInsertTimes>,
+    // If set, store operations failing to complete within this timeout fail the request
+    // instead of hanging the handler.
+    pub store_timeout: Option<Duration>,
+    // If set, delete_batches only tombstones the batches, and removes them once the grace
+    // period elapses. Shared with the `WorkerReceiverHandler`.
+    pub tombstones: Option<BatchTombstones>,
+    // Bounds the concurrent calls of each method.
+    pub method_permits: MethodPermits,
+    // Attempt to connect to the target's workers synchronize finds disconnected, instead of
+    // failing over to the next worker right away.
+    pub reconnect_missing_peers: bool,
+    // If set, uncertified syncs are rejected without fetching while the validator keeps
+    // failing.
+    pub validator_breaker: Option<ValidatorCircuitBreaker>,
+    // Stop working on requests once the timeout set by their caller expires.
+    pub inherit_request_deadline: bool,
+    // How many batches of a synchronize response are validated concurrently. Batches are
+    // still stored in the order of the response.
+    pub synchronize_validation_parallelism: usize,
+    // Report in fetch_batches_v2 responses the worker that supplied each batch, e.g. to find
+    // the workers that do not serve batches.
+    pub attribute_batch_suppliers: bool,
+    // If set, records the certificates attached to synchronize_v2 requests, for the
+    // `WorkerReceiverHandler` to serve them.
+    pub batch_certificates: Option<BatchCertificates>,
+    // If set, a synchronize call contacts at most this many workers in total, after which
+    // it fails with the batches still missing. Batches recovered so far are kept.
+    pub synchronize_attempt_budget: Option<usize>,
+    // Index the batches stored by synchronize by the digests of their transactions, if the
+    // store supports it. See `WorkerReceiverHandler::index_transactions`.
+    pub index_transactions: bool,
+    // If set, bounds the batches validated concurrently across all calls. Shared with the
+    // `WorkerReceiverHandler`.
+    pub validation_permits: Option<ValidationPermits>,
+    // If set, serves fewer synchronize calls at once while the store is compacting.
+    pub compaction_throttle: Option<CompactionThrottle>,
+    // If set, overlapping synchronize calls fetch each missing batch once.
+    pub in_flight_syncs: Option<InFlightSyncs>,
+    // Validate the batches of a synchronize response with a single
+    // `TransactionValidator::validate_batches` call, instead of one by one.
+    pub validate_batches_together: bool,
+    // If set, a failed store read while synchronize checks which batches are missing is
+    // retried this many times, after which the batch is treated as missing and fetched
+    // instead of failing the whole call.
+    pub synchronize_read_retries: Option<usize>,
+    // Record the age of the batches deleted by delete_batches, which costs reading their
+    // summaries before deleting them.
+    pub record_deleted_batch_ages: bool,
+    // If set, synchronize and delete_batches are refused while a migration of the store is
+    // in progress. Shared with the `WorkerReceiverHandler`.
+    pub store_migration: Option<StoreMigration<S>>,
+    // Check whether recovered batches are stored before writing them, since overlapping
+    // synchronize calls may store them concurrently. Writes are idempotent, so this only
+    // saves redundant writes, at the cost of a read per batch.
+    pub dedup_synchronize_writes: bool,
+    pub metrics: Arc<WorkerMetrics>,
+}
+
+impl<V, S: BatchStore> PrimaryReceiverHandler<V, S> {
+    /// Starts building a handler. Settings that are not set explicitly default to the
+    /// corresponding node `Parameters` defaults.
+    pub fn builder(
+        authority_id: AuthorityIdentifier,
+        id: WorkerId,
+        committee: Committee,
+        worker_cache: WorkerCache,
+        store: S,
+        validator: V,
+        metrics: Arc<WorkerMetrics>,
+    ) -> PrimaryReceiverHandlerBuilder<V, S> {
+        let parameters = Parameters::default();
+        PrimaryReceiverHandlerBuilder {
+            authority_id,
+            id,
+            committee,
+            worker_cache,
+            store,
+            validator,
+            request_batch_timeout: parameters.sync_retry_delay,
+            request_batch_retry_nodes: parameters.sync_retry_nodes,
+            network: None,
+            batch_fetcher: None,
+            read_permits: StoreReadPermits::default(),
+            max_fetch_batches_response_size: DEFAULT_MAX_FETCH_BATCHES_RESPONSE_SIZE,
+            certified_batch_verification: CertifiedBatchVerification::default(),
+            invalid_batch_policy: InvalidBatchPolicy::default(),
+            enabled_methods: EnabledPrimaryToWorkerMethods::default(),
+            delete_batches_chunking: DeleteBatchesChunking::default(),
+            observer: None,
+            batch_insert_times: None,
+            store_timeout: None,
+            tombstones: None,
+            method_concurrency_limits: MethodConcurrencyLimits::default(),
+            reconnect_missing_peers: false,
+            validator_breaker: None,
+            inherit_request_deadline: false,
+            synchronize_validation_parallelism: 1,
+            attribute_batch_suppliers: false,
+            batch_certificates: None,
+            synchronize_attempt_budget: None,
+            index_transactions: false,
+            validation_permits: None,
+            compaction_throttle: None,
+            in_flight_syncs: None,
+            validate_batches_together: false,
+            synchronize_read_retries: None,
+            record_deleted_batch_ages: false,
+            store_migration: None,
+            dedup_synchronize_writes: false,
+            metrics,
+        }
+    }
+
+    /// Streaming variant of `fetch_batches()`, for fetches too large to buffer in a single
+    /// response. Batches are sent as the `BatchFetcher` produces them, with at most `window`
+    /// fetched batches buffered ahead of the consumer. Since anemo does not support streaming
+    /// responses, this is only available to local callers.
+    pub fn fetch_batches_stream(
+        &self,
+        request: FetchBatchesRequest,
+        window: usize,
+    ) -> Result<mpsc::Receiver<(BatchDigest, Batch)>, WorkerHandlerError> {
+        if !self.enabled_methods.fetch_batches {
+            return Err(WorkerHandlerError::MethodDisabled("fetch_batches_stream"));
+        }
+        let Some(batch_fetcher) = self.batch_fetcher.clone() else {
+            return Err(WorkerHandlerError::UnsupportedViaRpc("fetch_batches_stream"));
+        };
+        let (sender, receiver) = mpsc::channel(window.max(1));
+        tokio::spawn(async move {
+            batch_fetcher
+                .fetch_into(request.digests, request.known_workers, sender)
+                .await
+        });
+        Ok(receiver)
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum PrimaryReceiverHandlerBuilderError {
+    #[error("A network is required to serve synchronize()")]
+    MissingNetwork,
+    #[error("A batch fetcher is required to serve fetch_batches()")]
+    MissingBatchFetcher,
+    #[error("The legacy RPC handler only serves delete_batches(), the {0} would be unused")]
+    UnusedComponent(&'static str),
+    #[error(
+        "Worker cache epoch {worker_cache_epoch} does not match committee epoch {committee_epoch}"
+    )]
+    EpochMismatch {
+        worker_cache_epoch: Epoch,
+        committee_epoch: Epoch,
+    },
+}
+
+/// Builds a `PrimaryReceiverHandler`, checking that it is configured for the interface it
+/// serves.
+pub struct PrimaryReceiverHandlerBuilder<V, S = DBMap<BatchDigest, Batch>> {
+    authority_id: AuthorityIdentifier,
+    id: WorkerId,
+    committee: Committee,
+    worker_cache: WorkerCache,
+    store: S,
+    validator: V,
+    request_batch_timeout: Duration,
+    request_batch_retry_nodes: usize,
+    network: Option<Network>,
+    batch_fetcher: Option<BatchFetcher<S>>,
+    read_permits: StoreReadPermits,
+    max_fetch_batches_response_size: usize,
+    certified_batch_verification: CertifiedBatchVerification,
+    invalid_batch_policy: InvalidBatchPolicy,
+    enabled_methods: EnabledPrimaryToWorkerMethods,
+    delete_batches_chunking: DeleteBatchesChunking,
+    observer: Option<BatchObserver>,
+    batch_insert_times: Option<BatchInsertTimes>,
+    store_timeout: Option<Duration>,
+    tombstones: Option<BatchTombstones>,
+    method_concurrency_limits: MethodConcurrencyLimits,
+    reconnect_missing_peers: bool,
+    validator_breaker: Option<ValidatorCircuitBreaker>,
+    inherit_request_deadline: bool,
+    synchronize_validation_parallelism: usize,
+    attribute_batch_suppliers: bool,
+    batch_certificates: Option<BatchCertificates>,
+    synchronize_attempt_budget: Option<usize>,
+    index_transactions: bool,
+    validation_permits: Option<ValidationPermits>,
+    compaction_throttle: Option<CompactionThrottle>,
+    in_flight_syncs: Option<InFlightSyncs>,
+    validate_batches_together: bool,
+    synchronize_read_retries: Option<usize>,
+    record_deleted_batch_ages: bool,
+    store_migration: Option<StoreMigration<S>>,
+    dedup_synchronize_writes: bool,
+    metrics: Arc<WorkerMetrics>,
+}
+
+impl<V, S> PrimaryReceiverHandlerBuilder<V, S> {
+    pub fn request_batch_timeout(mut self, request_batch_timeout: Duration) -> Self {
+        self.request_batch_timeout = request_batch_timeout;
+        self
+    }
+
+    pub fn request_batch_retry_nodes(mut self, request_batch_retry_nodes: usize) -> Self {
+        self.request_batch_retry_nodes = request_batch_retry_nodes;
+        self
+    }
+
+    pub fn network(mut self, network: Network) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    pub fn batch_fetcher(mut self, batch_fetcher: BatchFetcher<S>) -> Self {
+        self.batch_fetcher = Some(batch_fetcher);
+        self
+    }
+
+    pub fn read_permits(mut self, read_permits: StoreReadPermits) -> Self {
+        self.read_permits = read_permits;
+        self
+    }
+
+    pub fn max_fetch_batches_response_size(mut self, max_size: usize) -> Self {
+        self.max_fetch_batches_response_size = max_size;
+        self
+    }
+
+    pub fn certified_batch_verification(
+        mut self,
+        certified_batch_verification: CertifiedBatchVerification,
+    ) -> Self {
+        self.certified_batch_verification = certified_batch_verification;
+        self
+    }
+
+    pub fn invalid_batch_policy(mut self, invalid_batch_policy: InvalidBatchPolicy) -> Self {
+        self.invalid_batch_policy = invalid_batch_policy;
+        self
+    }
+
+    pub fn enabled_methods(mut self, enabled_methods: EnabledPrimaryToWorkerMethods) -> Self {
+        self.enabled_methods = enabled_methods;
+        self
+    }
+
+    pub fn delete_batches_chunking(mut self, chunking: DeleteBatchesChunking) -> Self {
+        self.delete_batches_chunking = chunking;
+        self
+    }
+
+    pub fn observer(mut self, observer: BatchObserver) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    pub fn batch_insert_times(mut self, batch_insert_times: BatchInsertTimes) -> Self {
+        self.batch_insert_times = Some(batch_insert_times);
+        self
+    }
+
+    pub fn store_timeout(mut self, store_timeout: Duration) -> Self {
+        self.store_timeout = Some(store_timeout);
+        self
+    }
+
+    pub fn tombstones(mut self, tombstones: BatchTombstones) -> Self {
+        self.tombstones = Some(tombstones);
+        self
+    }
+
+    pub fn method_concurrency_limits(mut self, limits: MethodConcurrencyLimits) -> Self {
+        self.method_concurrency_limits = limits;
+        self
+    }
+
+    pub fn reconnect_missing_peers(mut self, reconnect_missing_peers: bool) -> Self {
+        self.reconnect_missing_peers = reconnect_missing_peers;
+        self
+    }
+
+    pub fn validator_breaker(mut self, validator_breaker: ValidatorCircuitBreaker) -> Self {
+        self.validator_breaker = Some(validator_breaker);
+        self
+    }
+
+    pub fn inherit_request_deadline(mut self, inherit_request_deadline: bool) -> Self {
+        self.inherit_request_deadline = inherit_request_deadline;
+        self
+    }
+
+    /// Validates up to `parallelism` batches of a synchronize response at once, rather than
+    /// one after the other.
+    pub fn synchronize_validation_parallelism(mut self, parallelism: usize) -> Self {
+        self.synchronize_validation_parallelism = parallelism;
+        self
+    }
+
+    /// Reports in `fetch_batches_v2` responses the worker that supplied each batch. Off by
+    /// default, since it grows every response.
+    pub fn attribute_batch_suppliers(mut self, attribute_batch_suppliers: bool) -> Self {
+        self.attribute_batch_suppliers = attribute_batch_suppliers;
+        self
+    }
+
+    pub fn batch_certificates(mut self, batch_certificates: BatchCertificates) -> Self {
+        self.batch_certificates = Some(batch_certificates);
+        self
+    }
+
+    /// Bounds the number of workers a single synchronize call contacts, including attempts
+    /// to reconnect, to bound the RPCs a single call can cause.
+    pub fn synchronize_attempt_budget(mut self, budget: usize) -> Self {
+        self.synchronize_attempt_budget = Some(budget);
+        self
+    }
+
+    pub fn index_transactions(mut self, index_transactions: bool) -> Self {
+        self.index_transactions = index_transactions;
+        self
+    }
+
+    pub fn validation_permits(mut self, validation_permits: ValidationPermits) -> Self {
+        self.validation_permits = Some(validation_permits);
+        self
+    }
+
+    /// Throttles synchronize while the store is compacting. The caller is expected to spawn
+    /// the sampler of the throttle, see `CompactionThrottle::spawn_sampler`.
+    pub fn compaction_throttle(mut self, compaction_throttle: CompactionThrottle) -> Self {
+        self.compaction_throttle = Some(compaction_throttle);
+        self
+    }
+
+    /// Deduplicates the batches fetched by overlapping synchronize calls.
+    pub fn in_flight_syncs(mut self, in_flight_syncs: InFlightSyncs) -> Self {
+        self.in_flight_syncs = Some(in_flight_syncs);
+        self
+    }
+
+    /// Validates the batches of each synchronize response with a single
+    /// `TransactionValidator::validate_batches` call, e.g. to verify their signatures at once.
+    pub fn validate_batches_together(mut self, validate_batches_together: bool) -> Self {
+        self.validate_batches_together = validate_batches_together;
+        self
+    }
+
+    pub fn synchronize_read_retries(mut self, retries: usize) -> Self {
+        self.synchronize_read_retries = Some(retries);
+        self
+    }
+
+    pub fn record_deleted_batch_ages(mut self, record_deleted_batch_ages: bool) -> Self {
+        self.record_deleted_batch_ages = record_deleted_batch_ages;
+        self
+    }
+
+    pub fn store_migration(mut self, store_migration: StoreMigration<S>) -> Self {
+        self.store_migration = Some(store_migration);
+        self
+    }
+
+    pub fn dedup_synchronize_writes(mut self, dedup_synchronize_writes: bool) -> Self {
+        self.dedup_synchronize_writes = dedup_synchronize_writes;
+        self
+    }
+
+    /// Builds the handler registered as the local worker handler, which serves every
+    /// method and so requires both a network and a batch fetcher.
+    pub fn build(self) -> Result<PrimaryReceiverHandler<V, S>, PrimaryReceiverHandlerBuilderError> {
+        self.check_epochs()?;
+        if self.network.is_none() {
+            return Err(PrimaryReceiverHandlerBuilderError::MissingNetwork);
+        }
+        if self.batch_fetcher.is_none() {
+            return Err(PrimaryReceiverHandlerBuilderError::MissingBatchFetcher);
+        }
+        Ok(self.into_handler())
+    }
+
+    /// Builds the handler served over the legacy RPC interface, which only serves
+    /// delete_batches().
+    pub fn build_legacy_rpc(
+        self,
+    ) -> Result<PrimaryReceiverHandler<V, S>, PrimaryReceiverHandlerBuilderError> {
+        self.check_epochs()?;
+        if self.network.is_some() {
+            return Err(PrimaryReceiverHandlerBuilderError::UnusedComponent(
+                "network",
+            ));
+        }
+        if self.batch_fetcher.is_some() {
+            return Err(PrimaryReceiverHandlerBuilderError::UnusedComponent(
+                "batch fetcher",
             ));
+        }
+        Ok(self.into_handler())
+    }
+
+    /// The committee and the worker cache must describe the same epoch, otherwise worker
+    /// lookups during synchronize() would be inconsistent.
+    fn check_epochs(&self) -> Result<(), PrimaryReceiverHandlerBuilderError> {
+        if self.worker_cache.epoch() != self.committee.epoch() {
+            return Err(PrimaryReceiverHandlerBuilderError::EpochMismatch {
+                worker_cache_epoch: self.worker_cache.epoch(),
+                committee_epoch: self.committee.epoch(),
+            });
+        }
+        Ok(())
+    }
+
+    fn into_handler(self) -> PrimaryReceiverHandler<V, S> {
+        PrimaryReceiverHandler {
+            authority_id: self.authority_id,
+            id: self.id,
+            committee: self.committee,
+            worker_cache: self.worker_cache,
+            store: self.store,
+            request_batch_timeout: self.request_batch_timeout,
+            request_batch_retry_nodes: self.request_batch_retry_nodes,
+            network: self.network,
+            peer_protocol_versions: PeerProtocolVersions::default(),
+            batch_fetcher: self.batch_fetcher,
+            validator: self.validator,
+            read_permits: self.read_permits,
+            max_fetch_batches_response_size: self.max_fetch_batches_response_size,
+            certified_batch_verification: self.certified_batch_verification,
+            invalid_batch_policy: self.invalid_batch_policy,
+            enabled_methods: self.enabled_methods,
+            delete_batches_chunking: self.delete_batches_chunking,
+            observer: self.observer,
+            batch_insert_times: self.batch_insert_times,
+            store_timeout: self.store_timeout,
+            tombstones: self.tombstones,
+            method_permits: MethodPermits::new(self.method_concurrency_limits),
+            reconnect_missing_peers: self.reconnect_missing_peers,
+            validator_breaker: self.validator_breaker,
+            inherit_request_deadline: self.inherit_request_deadline,
+            synchronize_validation_parallelism: self.synchronize_validation_parallelism,
+            attribute_batch_suppliers: self.attribute_batch_suppliers,
+            batch_certificates: self.batch_certificates,
+            synchronize_attempt_budget: self.synchronize_attempt_budget,
+            index_transactions: self.index_transactions,
+            validation_permits: self.validation_permits,
+            compaction_throttle: self.compaction_throttle,
+            in_flight_syncs: self.in_flight_syncs,
+            validate_batches_together: self.validate_batches_together,
+            synchronize_read_retries: self.synchronize_read_retries,
+            record_deleted_batch_ages: self.record_deleted_batch_ages,
+            store_migration: self.store_migration,
+            dedup_synchronize_writes: self.dedup_synchronize_writes,
+            metrics: self.metrics,
+        }
+    }
+}
+
+impl<V, S: BatchStore> PrimaryReceiverHandler<V, S> {
+    fn request_deadline<T>(&self, request: &anemo::Request<T>) -> Option<Instant> {
+        request_deadline(self.inherit_request_deadline, request)
+    }
+
+    /// Returns whether a batch is stored under `key`. If `synchronize_read_retries` is set,
+    /// failed reads are retried, and the batch is reported missing if every attempt fails.
+    async fn is_stored(&self, key: BatchDigest) -> Result<bool, WorkerHandlerError> {
+        let mut attempt = 0;
+        loop {
+            let store_op = move |store: &S| store.get(&key);
+            let e = match with_store_timeout(&self.store, self.store_timeout, store_op).await? {
+                Ok(batch) => return Ok(batch.is_some()),
+                Err(e) => e,
+            };
+            match self.synchronize_read_retries {
+                None => return Err(WorkerHandlerError::StoreRead(e)),
+                Some(retries) if attempt < retries => {
+                    attempt += 1;
+                    debug!("Retrying failed batch store read (attempt {attempt}/{retries}): {e:?}");
+                }
+                Some(_) => {
+                    warn!("Treating batch {key} as missing after failing to read it: {e:?}");
+                    return Ok(false);
+                }
+            }
+        }
+    }
+
+    /// Returns the id of the target's worker to sync from, our own id unless set.
+    fn target_worker_id(&self, message: &WorkerSynchronizeV2Message) -> WorkerId {
+        message.target_worker_id.unwrap_or(self.id)
+    }
+
+    /// Returns whether the certificate attached to `message` proves that its digests are
+    /// certified: it must be authored by the target, include every digest for the target's
+    /// worker id, and be valid for our committee. Returns the certificate if so. Its signatures
+    /// are verified on a blocking task.
+    async fn verify_certified_digests(
+        &self,
+        message: &WorkerSynchronizeV2Message,
+    ) -> Option<Arc<Certificate>> {
+        let Some(certificate) = &message.certificate else {
+            warn!("No certificate attached to batches marked as certified, validating them");
+            return None;
         };
-        let request = request.into_body();
-        let batches = batch_fetcher
-            .fetch(request.digests, request.known_workers)
-            .await;
-        Ok(anemo::Response::new(FetchBatchesResponse { batches }))
+        let header = certificate.header();
+        let target_worker_id = self.target_worker_id(message);
+        let includes_digests = header.author() == message.target
+            && message.digests.iter().all(|digest| {
+                header
+                    .payload()
+                    .get(digest)
+                    .map_or(false, |(worker_id, _)| *worker_id == target_worker_id)
+            });
+        if !includes_digests {
+            warn!(
+                "Certificate {} does not include the batches marked as certified, validating them",
+                certificate.digest()
+            );
+            return None;
+        }
+        let certificate = Arc::new(certificate.clone());
+        let committee = self.committee.clone();
+        let worker_cache = self.worker_cache.clone();
+        let verification = tokio::task::spawn_blocking(move || {
+            certificate
+                .verify(&committee, &worker_cache)
+                .map(|()| certificate)
+        })
+        .await;
+        match verification {
+            Ok(Ok(certificate)) => Some(certificate),
+            Ok(Err(e)) => {
+                warn!(
+                    "Invalid certificate attached to batches marked as certified, validating them: {e}"
+                );
+                None
+            }
+            Err(e) => {
+                warn!("Failed to verify the certificate attached to certified batches, validating them: {e}");
+                None
+            }
+        }
+    }
+
+    /// Returns a handle to the given worker peer. If not connected and
+    /// `reconnect_missing_peers` is set, attempts to connect first.
+    /// Serves fetch_batches and fetch_batches_v2, capping the total size of the returned
+    /// batches at `max_response_size`.
+    async fn serve_fetch_batches(
+        &self,
+        request: FetchBatchesRequest,
+        max_response_size: usize,
+    ) -> Result<FetchBatchesV2Response, WorkerHandlerError> {
+        let Some(batch_fetcher) = self.batch_fetcher.as_ref() else {
+            return Err(WorkerHandlerError::UnsupportedViaRpc("fetch_batches"));
+        };
+        let (fetched_batches, mut batch_suppliers) = if self.attribute_batch_suppliers {
+            let (batches, suppliers) = batch_fetcher
+                .fetch_with_suppliers(request.digests, request.known_workers)
+                .await;
+            (batches, Some(suppliers))
+        } else {
+            let batches = batch_fetcher
+                .fetch(request.digests, request.known_workers)
+                .await;
+            (batches, None)
+        };
+
+        // Cap the response size, reporting the digests left out so they can be requested again.
+        // At least one batch is always returned, so the caller is guaranteed to make progress.
+        let mut batches = HashMap::new();
+        let mut omitted_digests = Vec::new();
+        let mut total_size = 0;
+        for (digest, batch) in fetched_batches
+            .into_iter()
+            .sorted_by_key(|(digest, _)| *digest)
+        {
+            let batch_size = batch.size();
+            if omitted_digests.is_empty()
+                && (batches.is_empty() || total_size + batch_size <= max_response_size)
+            {
+                batches.insert(digest, batch);
+                total_size += batch_size;
+            } else {
+                omitted_digests.push(digest);
+            }
+        }
+        if !omitted_digests.is_empty() {
+            debug!(
+                "Capped fetch_batches response at {total_size} bytes, omitting {} batches",
+                omitted_digests.len()
+            );
+        }
+
+        if let Some(suppliers) = batch_suppliers.as_mut() {
+            suppliers.retain(|digest, _| batches.contains_key(digest));
+        }
+
+        Ok(FetchBatchesV2Response {
+            batches,
+            is_size_limit_reached: !omitted_digests.is_empty(),
+            omitted_digests,
+            batch_suppliers,
+        })
+    }
+
+    async fn worker_peer(
+        &self,
+        network: &Network,
+        worker_info: &WorkerInfo,
+    ) -> Option<anemo::Peer> {
+        let peer_id = anemo::PeerId(worker_info.name.0.to_bytes());
+        if let Some(peer) = network.peer(peer_id) {
+            return Some(peer);
+        }
+        if !self.reconnect_missing_peers {
+            return None;
+        }
+        let address = worker_info.worker_address.to_anemo_address().ok()?;
+        match tokio::time::timeout(
+            self.request_batch_timeout,
+            network.connect_with_peer_id(address, peer_id),
+        )
+        .await
+        {
+            Ok(Ok(_)) => network.peer(peer_id),
+            Ok(Err(e)) => {
+                debug!(
+                    "Failed to reconnect with worker peer {}: {e:?}",
+                    worker_info.name
+                );
+                None
+            }
+            Err(_) => {
+                debug!(
+                    "Timed out reconnecting with worker peer {}",
+                    worker_info.name
+                );
+                None
+            }
+        }
+    }
+
+    /// Records the age of the batches about to be deleted, from the creation time kept in
+    /// their summaries. Failing to read the summaries does not fail the deletion.
+    async fn observe_deleted_batch_ages(&self, digests: &[BatchDigest]) {
+        let digests = digests.to_vec();
+        let store_op = move |store: &S| store.multi_get_summaries(&digests);
+        let summaries = match with_store_timeout(&self.store, self.store_timeout, store_op).await {
+            Ok(Ok(summaries)) => summaries,
+            Ok(Err(e)) => {
+                warn!("Failed to read the summaries of deleted batches: {e:?}");
+                return;
+            }
+            Err(e) => {
+                warn!("Failed to read the summaries of deleted batches: {e:?}");
+                return;
+            }
+        };
+        let now = now();
+        for summary in summaries.into_iter().flatten() {
+            let age_ms = now.saturating_sub(summary.created_at);
+            self.metrics
+                .deleted_batch_age
+                .observe(age_ms as f64 / 1000.0);
+        }
+    }
+
+    /// Removes the given batches, returning the number of digests removed. Chunks are
+    /// removed atomically, but if one of them fails, the chunks already removed stay removed.
+    pub async fn remove_batches(
+        &self,
+        digests: Vec<BatchDigest>,
+    ) -> Result<usize, WorkerHandlerError> {
+        remove_in_chunks(
+            &self.store,
+            &digests,
+            self.delete_batches_chunking,
+            self.store_timeout,
+        )
+        .await
+    }
+
+    /// Serves the batches deleted during their grace period again, returning the number of
+    /// batches restored.
+    pub fn restore_batches(&self, digests: &[BatchDigest]) -> usize {
+        let Some(tombstones) = &self.tombstones else {
+            return 0;
+        };
+        tombstones.restore(digests)
+    }
+
+    /// Tombstones the given batches, and removes them once the grace period elapses unless
+    /// they are restored in the meantime.
+    fn tombstone_batches(
+        &self,
+        tombstones: &BatchTombstones,
+        digests: &[BatchDigest],
+    ) -> Result<(), WorkerHandlerError> {
+        let keys = digests.to_vec();
+        let deletion = tombstones
+            .mark(&keys)
+            .map_err(WorkerHandlerError::StoreWrite)?;
+        self.remove_after_grace_period(tombstones, keys, deletion, tombstones.grace_period());
+        Ok(())
+    }
+
+    /// Resumes the removal of the batches tombstoned before a restart, once the rest of their
+    /// grace period elapses.
+    pub fn resume_tombstoned_removals(&self) -> StoreResult<()> {
+        let Some(tombstones) = &self.tombstones else {
+            return Ok(());
+        };
+        for (deletion, keys, remaining) in tombstones.reload()? {
+            debug!(
+                "Resuming the removal of {} tombstoned batches in {remaining:?}",
+                keys.len()
+            );
+            self.remove_after_grace_period(tombstones, keys, deletion, remaining);
+        }
+        Ok(())
+    }
+
+    /// Removes the batches still tombstoned by `deletion` once `grace_period` elapses,
+    /// retrying failed removals with exponential backoff.
+    fn remove_after_grace_period(
+        &self,
+        tombstones: &BatchTombstones,
+        keys: Vec<BatchDigest>,
+        deletion: u64,
+        grace_period: Duration,
+    ) {
+        let tombstones = tombstones.clone();
+        let store = self.store.clone();
+        let chunking = self.delete_batches_chunking;
+        let store_timeout = self.store_timeout;
+        tokio::spawn(async move {
+            tokio::time::sleep(grace_period).await;
+            let mut retry_delay = TOMBSTONE_REMOVAL_RETRY_DELAY;
+            loop {
+                // Batches restored in the meantime are kept.
+                let keys = tombstones.expired(&keys, deletion);
+                if keys.is_empty() {
+                    return;
+                }
+                match remove_in_chunks(&store, &keys, chunking, store_timeout).await {
+                    Ok(removed) => {
+                        debug!("Removed {removed} batches after their grace period");
+                        tombstones.clear(&keys, deletion);
+                        return;
+                    }
+                    // The batches stay tombstoned, so they are still not served.
+                    Err(e) => warn!(
+                        "Failed to remove tombstoned batches, retrying in {retry_delay:?}: {e:?}"
+                    ),
+                }
+                tokio::time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_TOMBSTONE_REMOVAL_RETRY_DELAY);
+            }
+        });
+    }
+}
+
+/// Removes the given keys, returning the number of keys removed. Chunks are removed
+/// atomically, but if one of them fails, the chunks already removed stay removed.
+async fn remove_in_chunks<S: BatchStore>(
+    store: &S,
+    keys: &[BatchDigest],
+    chunking: DeleteBatchesChunking,
+    store_timeout: Option<Duration>,
+) -> Result<usize, WorkerHandlerError> {
+    let DeleteBatchesChunking {
+        chunk_size,
+        concurrency,
+    } = chunking;
+    let mut removals = stream::iter(keys.chunks(chunk_size.max(1)).map(|chunk| {
+        let store = store.clone();
+        let chunk = chunk.to_vec();
+        async move {
+            let mut task = tokio::task::spawn_blocking(move || {
+                store.multi_remove(&chunk).map(|()| chunk.len())
+            });
+            await_store_task(&mut task, store_timeout).await
+        }
+    }))
+    .buffer_unordered(concurrency.max(1));
+
+    let mut removed = 0;
+    while let Some(result) = removals.next().await {
+        removed += result?.map_err(WorkerHandlerError::StoreRemove)?;
+    }
+    Ok(removed)
+}
+
+impl<V: TransactionValidator, S: BatchStore> PrimaryReceiverHandler<V, S> {
+    /// Serves synchronize and synchronize_v2, propagating `trace_id` to the requests issued.
+    async fn serve_synchronize(
+        &self,
+        deadline: Option<Instant>,
+        trace_id: Option<String>,
+        message: &WorkerSynchronizeV2Message,
+    ) -> Result<anemo::Response<()>, anemo::rpc::Status> {
+        within_deadline(deadline, async move {
+            if !self.enabled_methods.synchronize {
+                return Err(WorkerHandlerError::MethodDisabled("synchronize").into());
+            }
+            let _method_permit = self
+                .method_permits
+                .acquire(PrimaryToWorkerMethod::Synchronize)
+                .await?;
+            let _migration_guard = self
+                .store_migration
+                .as_ref()
+                .map(StoreMigration::write)
+                .transpose()?;
+            let _throttle_permit = match &self.compaction_throttle {
+                Some(throttle) => Some(throttle.acquire().await),
+                None => None,
+            };
+            let Some(network) = self.network.as_ref() else {
+                return Err(WorkerHandlerError::UnsupportedViaRpc("synchronize").into());
+            };
+            // Claim the digests before checking the store: the batches fetched by the calls
+            // that claimed them first are stored by the time the wait completes.
+            let mut sync_claim = match &self.in_flight_syncs {
+                Some(in_flight_syncs) => {
+                    let (claim, in_flight) = in_flight_syncs.claim(message.digests.iter().copied());
+                    in_flight.wait().await;
+                    Some(claim)
+                }
+                None => None,
+            };
+            let permit = self.read_permits.acquire(ReadPriority::Sync).await;
+            let mut missing = HashSet::new();
+            for digest in message.digests.iter() {
+                // A batch deleted during its grace period is still stored: the primary needs
+                // it again, so serve it again rather than fetching it.
+                if self.restore_batches(&[*digest]) > 0 {
+                    debug!("Restored tombstoned batch {digest} requested for sync");
+                }
+                // Check if we already have the batch.
+                if self.is_stored(*digest).await? {
+                    trace!("Digest {digest} already in store, nothing to sync");
+                } else {
+                    missing.insert(*digest);
+                    debug!("Requesting sync for batch {digest}");
+                }
+            }
+            drop(permit);
+            if let Some(claim) = &mut sync_claim {
+                claim.retain(&missing);
+            }
+            // Only verify the certificate once there are batches to fetch, or to serve it to
+            // light clients.
+            let needs_proof = message.is_certified
+                && self.certified_batch_verification == CertifiedBatchVerification::Certificate;
+            let verified_certificate = if (needs_proof && !missing.is_empty())
+                || (self.batch_certificates.is_some() && message.certificate.is_some())
+            {
+                self.verify_certified_digests(message).await
+            } else {
+                None
+            };
+            if let (Some(certificates), Some(certificate)) =
+                (&self.batch_certificates, &verified_certificate)
+            {
+                certificates.record(certificate.clone(), self.target_worker_id(message));
+            }
+            if missing.is_empty() {
+                return Ok(anemo::Response::new(()));
+            }
+            let is_certified =
+                message.is_certified && (!needs_proof || verified_certificate.is_some());
+            if !is_certified {
+                check_validator_breaker(self.validator_breaker.as_ref())?;
+            }
+
+            let Some(target) = self.committee.authority(&message.target) else {
+                return Err(WorkerHandlerError::UnknownNode(message.target.to_string()).into());
+            };
+            let target = target.protocol_key();
+            let target_worker_id = self.target_worker_id(message);
+            // The target is a member of the committee, so a miss means that the worker cache
+            // lags behind it, e.g. during reconfiguration. The caller retries once it is updated.
+            let preferred_worker = match self.worker_cache.worker(target, &target_worker_id) {
+                Ok(worker_info) => worker_info,
+                Err(e) => {
+                    return Err(WorkerHandlerError::StaleWorkerCache {
+                        epoch: self.worker_cache.epoch(),
+                        target: message.target,
+                        reason: e.to_string(),
+                    }
+                    .into());
+                }
+            };
+            // Prefer the target's worker with our id, but fall back to its other workers in case
+            // the preferred one is lagging and does not have all the missing batches.
+            let mut workers = vec![preferred_worker.clone()];
+            workers.extend(
+                self.worker_cache
+                    .our_workers(target)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|worker_info| worker_info.name != preferred_worker.name),
+            );
+
+            let requested: HashSet<_> = message.digests.iter().cloned().collect();
+            let originally_missing = missing.clone();
+            let mut last_error = None;
+            let mut attempts = 0;
+            let mut unreachable_workers = 0;
+            let target_workers = workers.len();
+            for worker_info in workers {
+                if missing.is_empty() {
+                    break;
+                }
+                if let Some(budget) = self.synchronize_attempt_budget {
+                    if attempts >= budget {
+                        debug!(
+                            "Synchronize attempt budget of {budget} exhausted, {} batches missing",
+                            missing.len()
+                        );
+                        last_error = Some(
+                            WorkerHandlerError::SyncBudgetExhausted {
+                                budget,
+                                missing: missing.iter().copied().sorted().collect(),
+                            }
+                            .into(),
+                        );
+                        break;
+                    }
+                }
+                attempts += 1;
+                let worker_name = worker_info.name.clone();
+                let Some(peer) = self.worker_peer(network, &worker_info).await else {
+                    debug!("Not connected with worker peer {worker_name}, trying next worker");
+                    last_error = Some(WorkerHandlerError::PeerNotConnected(worker_name).into());
+                    unreachable_workers += 1;
+                    continue;
+                };
+                let mut client = WorkerToWorkerClient::new(peer.clone());
+
+                // Attempt to retrieve missing batches.
+                // Retried at a higher level in Synchronizer::sync_batches_internal().
+                let batch_digests = missing.iter().cloned().collect_vec();
+                // Do not wait for the worker past our own caller's deadline.
+                let request_timeout = deadline.map_or(self.request_batch_timeout, |deadline| {
+                    self.request_batch_timeout
+                        .min(deadline.saturating_duration_since(Instant::now()))
+                });
+                // Large requests are smaller in compact form, which only workers serving
+                // request_batches_v2 decode.
+                let response = if batch_digests.len()
+                    >= RequestBatchesV2Request::COMPACT_DIGESTS_THRESHOLD
+                    && self.peer_protocol_versions.serves_v2(&peer).await
+                {
+                    let request = RequestBatchesV2Request::new(batch_digests);
+                    debug!("Sending RequestBatchesV2Request to {worker_name}: {request:?}");
+                    let request = traced_request(request, request_timeout, trace_id.as_ref());
+                    client
+                        .request_batches_v2(request)
+                        .await
+                        .map(into_legacy_response::<_, RequestBatchesResponse>)
+                } else {
+                    let request = RequestBatchesRequest { batch_digests };
+                    debug!("Sending RequestBatchesRequest to {worker_name}: {request:?}");
+                    let request = traced_request(request, request_timeout, trace_id.as_ref());
+                    client.request_batches(request).await
+                };
+                let response = match response {
+                    Ok(response) => response.into_inner(),
+                    Err(e) => {
+                        debug!(
+                            "RequestBatchesRequest to {worker_name} failed, trying next worker: {e:?}"
+                        );
+                        last_error = Some(e);
+                        continue;
+                    }
+                };
+                // Batches that are not part of a certificate need to be validated. Validate them
+                // ahead of the batch being stored, in the order of the response.
+                let needs_validation = !is_certified
+                    || self.certified_batch_verification == CertifiedBatchVerification::Full;
+                let breaker = self.validator_breaker.as_ref();
+                let permits = self.validation_permits.as_ref();
+                let mut batches = if needs_validation && self.validate_batches_together {
+                    let validations = validate_batches(
+                        &self.validator,
+                        breaker,
+                        permits,
+                        &response.batches.iter().collect_vec(),
+                    )
+                    .await;
+                    stream::iter(response.batches.into_iter().zip(validations.into_iter().map(Some)))
+                        .left_stream()
+                } else {
+                    stream::iter(response.batches)
+                        .map(|batch| async move {
+                            let validation = match needs_validation {
+                                true => Some(
+                                    validate_batch(&self.validator, breaker, permits, &batch)
+                                        .await,
+                                ),
+                                false => None,
+                            };
+                            (batch, validation)
+                        })
+                        .buffered(self.synchronize_validation_parallelism.max(1))
+                        .right_stream()
+                };
+                while let Some((batch, validation)) = batches.next().await {
+                    let digest = batch.digest();
+                    if !originally_missing.contains(&digest) {
+                        // Such batches are dropped below, but they hint at a buggy or malicious peer.
+                        self.metrics.synchronize_unrequested_batches.inc();
+                        warn!("Worker {worker_name} sent batch {digest} which was not requested");
+                    }
+                    if is_certified
+                        && self.certified_batch_verification != CertifiedBatchVerification::Trust
+                        && !requested.contains(&digest)
+                    {
+                        return Err(WorkerHandlerError::UnrequestedBatch {
+                            digest,
+                            worker: worker_name,
+                        }
+                        .into());
+                    }
+                    if let Some(Err(err)) = validation {
+                        // Batches that could not be validated may be valid, so only skip those
+                        // that are invalid.
+                        if self.invalid_batch_policy == InvalidBatchPolicy::FailFast
+                            || err.kind == ValidationErrorKind::Transient
+                        {
+                            return Err(WorkerHandlerError::from(err).into());
+                        }
+                        self.metrics.synchronize_invalid_batches.inc();
+                        warn!(
+                            "Worker {worker_name} sent invalid batch {digest}, skipping it: {err}"
+                        );
+                        last_error = Some(WorkerHandlerError::from(err).into());
+                        continue;
+                    }
+                    if missing.remove(&digest) {
+                        let index_transactions = self.index_transactions;
+                        let dedup_writes = self.dedup_synchronize_writes;
+                        let store_op = move |store: &S| -> StoreResult<(Batch, bool)> {
+                            if dedup_writes && store.contains_key(&digest)? {
+                                return Ok((batch, false));
+                            }
+                            // Indexing takes precedence over keeping certified batches cached.
+                            if index_transactions {
+                                store.insert_indexed(&digest, &batch)?;
+                            } else if is_certified {
+                                store.insert_referenced(&digest, &batch)?;
+                            } else {
+                                store.insert(&digest, &batch)?;
+                            }
+                            Ok((batch, true))
+                        };
+                        let (batch, written) =
+                            with_store_timeout(&self.store, self.store_timeout, store_op)
+                                .await?
+                                .map_err(WorkerHandlerError::StoreWrite)?;
+                        if !written {
+                            // The batch was stored by a concurrent call, which notified the
+                            // observer.
+                            trace!("Batch {digest} already stored, skipping its write");
+                            self.metrics.synchronize_dedup_writes.inc();
+                            continue;
+                        }
+                        if let Some(insert_times) = &self.batch_insert_times {
+                            insert_times.record(digest);
+                        }
+                        if let Some(observer) = &self.observer {
+                            observer.observe(digest, &batch, Some(peer.peer_id()));
+                        }
+                    }
+                }
+                if !missing.is_empty() {
+                    debug!(
+                        "Worker {worker_name} is missing {} batches, trying next worker",
+                        missing.len()
+                    );
+                }
+            }
+
+            if missing.is_empty() {
+                return Ok(anemo::Response::new(()));
+            }
+            // Tell apart a target none of whose workers could be queried, a connectivity or
+            // configuration problem, from workers failing to serve the batches.
+            if unreachable_workers == target_workers {
+                self.metrics.synchronize_no_reachable_target.inc();
+                return Err(WorkerHandlerError::NoReachableSyncTarget(message.target).into());
+            }
+            Err(last_error.unwrap_or_else(|| WorkerHandlerError::SyncFailed.into()))
+        })
+        .await
+    }
+}
+
+#[async_trait]
+impl<V: TransactionValidator, S: BatchStore> PrimaryToWorker for PrimaryReceiverHandler<V, S> {
+    async fn synchronize(
+        &self,
+        request: anemo::Request<WorkerSynchronizeMessage>,
+    ) -> Result<anemo::Response<()>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        let trace_id = request.headers().get(TRACE_ID_HEADER_KEY).cloned();
+        let message = WorkerSynchronizeV2Message::from(request.into_body());
+        self.serve_synchronize(deadline, trace_id, &message).await
+    }
+
+    async fn synchronize_v2(
+        &self,
+        request: anemo::Request<WorkerSynchronizeV2Message>,
+    ) -> Result<anemo::Response<()>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        let trace_id = request.headers().get(TRACE_ID_HEADER_KEY).cloned();
+        self.serve_synchronize(deadline, trace_id, request.body())
+            .await
+    }
+
+    async fn fetch_batches(
+        &self,
+        request: anemo::Request<FetchBatchesRequest>,
+    ) -> Result<anemo::Response<FetchBatchesResponse>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            if !self.enabled_methods.fetch_batches {
+                return Err(WorkerHandlerError::MethodDisabled("fetch_batches").into());
+            }
+            let _method_permit = self
+                .method_permits
+                .acquire(PrimaryToWorkerMethod::FetchBatches)
+                .await?;
+            // Legacy callers cannot request omitted batches again, so the response is not
+            // capped.
+            let response = self
+                .serve_fetch_batches(request.into_body(), usize::MAX)
+                .await?;
+            Ok(anemo::Response::new(FetchBatchesResponse {
+                batches: response.batches,
+            }))
+        })
+        .await
+    }
+
+    async fn fetch_batches_v2(
+        &self,
+        request: anemo::Request<FetchBatchesRequest>,
+    ) -> Result<anemo::Response<FetchBatchesV2Response>, anemo::rpc::Status> {
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            if !self.enabled_methods.fetch_batches {
+                return Err(WorkerHandlerError::MethodDisabled("fetch_batches").into());
+            }
+            let _method_permit = self
+                .method_permits
+                .acquire(PrimaryToWorkerMethod::FetchBatches)
+                .await?;
+            let response = self
+                .serve_fetch_batches(request.into_body(), self.max_fetch_batches_response_size)
+                .await?;
+            Ok(anemo::Response::new(response))
+        })
+        .await
     }
 
     async fn delete_batches(
         &self,
         request: anemo::Request<WorkerDeleteBatchesMessage>,
     ) -> Result<anemo::Response<()>, anemo::rpc::Status> {
-        for digest in request.into_body().digests {
-            self.store.remove(&digest).map_err(|e| {
-                anemo::rpc::Status::internal(format!("failed to remove from batch store: {e:?}"))
-            })?;
-        }
-        Ok(anemo::Response::new(()))
+        let deadline = self.request_deadline(&request);
+        within_deadline(deadline, async move {
+            if !self.enabled_methods.delete_batches {
+                return Err(WorkerHandlerError::MethodDisabled("delete_batches").into());
+            }
+            let _method_permit = self
+                .method_permits
+                .acquire(PrimaryToWorkerMethod::DeleteBatches)
+                .await?;
+            let _migration_guard = self
+                .store_migration
+                .as_ref()
+                .map(StoreMigration::write)
+                .transpose()?;
+            let digests = request.into_body().digests;
+            // The deleted batches are no longer needed, so stop fetching them.
+            if let Some(batch_fetcher) = self.batch_fetcher.as_ref() {
+                batch_fetcher.cancel(&digests.iter().copied().collect());
+            }
+            if self.record_deleted_batch_ages {
+                self.observe_deleted_batch_ages(&digests).await;
+            }
+            match self
+                .tombstones
+                .as_ref()
+                .filter(|tombstones| !tombstones.grace_period().is_zero())
+            {
+                Some(tombstones) => {
+                    self.tombstone_batches(tombstones, &digests)?;
+                    debug!("Tombstoned {} batches", digests.len());
+                }
+                None => {
+                    let removed = self.remove_batches(digests).await?;
+                    debug!("Removed {removed} batches");
+                }
+            }
+            Ok(anemo::Response::new(()))
+        })
+        .await
     }
 }
@@ -0,0 +1,99 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::watch;
+use types::BatchDigest;
+
+type Registry = Arc<Mutex<HashMap<BatchDigest, watch::Receiver<()>>>>;
+
+/// Tracks the digests synchronize calls are fetching, so that overlapping calls await the
+/// batch being fetched by another call instead of requesting it again. Shared by the
+/// synchronize calls of a worker.
+///
+/// Calls only wait on calls that claimed their digests before them, so waits never form a
+/// cycle. A call failing to fetch a digest releases it all the same: the calls awaiting it
+/// then find it missing from the store, and fetch it themselves.
+#[derive(Clone, Default)]
+pub struct InFlightSyncs {
+    in_flight: Registry,
+}
+
+/// The digests claimed by a synchronize call, released once dropped.
+pub struct SyncClaim {
+    in_flight: Registry,
+    done: HashMap<BatchDigest, watch::Sender<()>>,
+}
+
+/// Digests claimed by other calls, see `InFlightSyncs::claim`.
+pub struct InFlight(Vec<watch::Receiver<()>>);
+
+impl InFlightSyncs {
+    /// Claims the digests no other call is fetching, and returns the others, to be awaited.
+    pub fn claim(&self, digests: impl IntoIterator<Item = BatchDigest>) -> (SyncClaim, InFlight) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let mut done = HashMap::new();
+        let mut awaited = Vec::new();
+        for digest in digests {
+            if done.contains_key(&digest) {
+                continue;
+            }
+            match in_flight.get(&digest) {
+                Some(receiver) => awaited.push(receiver.clone()),
+                None => {
+                    let (sender, receiver) = watch::channel(());
+                    in_flight.insert(digest, receiver);
+                    done.insert(digest, sender);
+                }
+            }
+        }
+        let claim = SyncClaim {
+            in_flight: self.in_flight.clone(),
+            done,
+        };
+        (claim, InFlight(awaited))
+    }
+
+    /// Returns whether a call is fetching `digest`.
+    pub fn is_in_flight(&self, digest: &BatchDigest) -> bool {
+        self.in_flight.lock().unwrap().contains_key(digest)
+    }
+}
+
+impl InFlight {
+    /// Waits until the calls fetching the digests released them.
+    pub async fn wait(self) {
+        for mut receiver in self.0 {
+            // Fails once the sender is dropped, which is what is awaited.
+            while receiver.changed().await.is_ok() {}
+        }
+    }
+}
+
+impl SyncClaim {
+    /// Releases the claimed digests not in `missing`, e.g. found in the store.
+    pub fn retain(&mut self, missing: &HashSet<BatchDigest>) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        self.done.retain(|digest, _| {
+            let keep = missing.contains(digest);
+            if !keep {
+                in_flight.remove(digest);
+            }
+            keep
+        });
+    }
+}
+
+impl Drop for SyncClaim {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        for digest in self.done.keys() {
+            in_flight.remove(digest);
+        }
+        // The senders are dropped next, waking the calls awaiting the digests.
+    }
+}
@@ -8,20 +8,101 @@
     rust_2021_compatibility
 )]
 
+mod batch_cache;
+mod batch_certificates;
+mod batch_diagnostics;
+mod batch_export;
 mod batch_fetcher;
+mod batch_insert_times;
+mod batch_integrity;
 mod batch_maker;
+mod batch_mirror;
+mod batch_observer;
+mod batch_prefetch;
+mod batch_replicator;
+mod batch_store;
+#[cfg(test)]
+#[path = "tests/batch_store_utils.rs"]
+pub mod batch_store_utils;
+mod batch_tombstones;
+mod bulk_sync;
+mod capacity_planning;
 mod client;
+mod compaction_throttle;
 mod handlers;
+mod in_flight_syncs;
+mod method_permits;
+mod others_batch_reporter;
+mod peer_protocol_versions;
+mod peer_rate_limits;
+mod peer_reciprocity;
+mod peer_roles;
+mod peer_selection;
 mod quorum_waiter;
+mod read_permits;
+mod read_transform;
+mod request_batches_audit;
+mod response_buffers;
+mod size_limit_events;
+mod store_migration;
+mod store_stats;
+mod store_version;
 mod transactions_server;
+mod tx_dedup;
 mod tx_validator;
+mod validation_permits;
+mod validator_breaker;
 mod worker;
+mod write_backpressure;
+mod write_coalescer;
+mod write_permits;
 
 pub mod metrics;
 
+pub use crate::batch_cache::{BatchCacheConfig, CachedBatchStore};
+pub use crate::batch_certificates::BatchCertificates;
+pub use crate::batch_diagnostics::{
+    BatchDiagnostics, BatchDiagnosticsService, StoreSelfTestReport,
+};
+pub use crate::batch_export::{import_batches, BatchExport};
+pub use crate::batch_insert_times::BatchInsertTimes;
+pub use crate::batch_integrity::{BatchIntegrityScanner, IntegrityScanConfig};
+pub use crate::batch_observer::{BatchObserver, StoredBatch};
+pub use crate::batch_prefetch::BatchPrefetcher;
+pub use crate::batch_replicator::BatchReplicator;
+pub use crate::batch_store::{
+    BatchStore, IndexedBatchStore, MemoryBatchStore, PartitionedBatchStore,
+};
+pub use crate::batch_tombstones::BatchTombstones;
+pub use crate::capacity_planning::{CapacityPlanner, CapacityProjection, DiskBudget, DiskSpace};
 pub use crate::client::LocalNarwhalClient;
-pub use crate::tx_validator::{TransactionValidator, TrivialTransactionValidator};
+pub use crate::compaction_throttle::{
+    CompactionThrottle, CompactionThrottleConfig, CompactionThrottlePermit,
+};
+pub use crate::handlers::PrimaryReceiverHandlerBuilderError;
+pub use crate::in_flight_syncs::{InFlight, InFlightSyncs, SyncClaim};
+pub use crate::method_permits::OverLimitPolicy;
+pub use crate::peer_rate_limits::{PeerBucket, PeerRateLimits};
+pub use crate::peer_reciprocity::{PeerBalance, PeerReciprocity};
+pub use crate::peer_roles::{PeerRole, PeerRoles};
+pub use crate::peer_selection::{
+    KnownFirst, LatencyWeighted, PeerSelector, PeerStats, RandomOrder, RoundRobin,
+};
+pub use crate::read_transform::BatchReadTransform;
+pub use crate::request_batches_audit::{RequestBatchesAudit, ServedRequestBatches};
+pub use crate::response_buffers::ResponseBufferPool;
+pub use crate::store_migration::{StoreMigration, StoreWriteGuard};
+pub use crate::store_stats::{StoreStatsSampler, SAMPLED_PROPERTIES};
+pub use crate::store_version::{StoreVersion, VersionedBatchStore};
+pub use crate::tx_dedup::TransactionDedup;
+pub use crate::tx_validator::{
+    TransactionValidator, TrivialTransactionValidator, ValidationError, ValidationErrorKind,
+};
+pub use crate::validation_permits::ValidationPermits;
+pub use crate::validator_breaker::ValidatorCircuitBreaker;
 pub use crate::worker::Worker;
+pub use crate::write_coalescer::WriteCoalescer;
+pub use crate::write_permits::StoreWritePermits;
 
 /// The number of shutdown receivers to create on startup. We need one per component loop.
 pub const NUM_SHUTDOWN_RECEIVERS: u64 = 26;
@@ -0,0 +1,76 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+
+use config::PrimaryToWorkerMethod;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::handlers::WorkerHandlerError;
+
+/// What happens to calls beyond a method's concurrency limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverLimitPolicy {
+    /// Wait for a running call of the same method to complete.
+    #[default]
+    Queue,
+    /// Fail the call, so that the caller retries later.
+    Reject,
+}
+
+/// The maximum number of concurrent calls of each `PrimaryToWorker` method. Methods without
+/// a limit are unbounded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MethodConcurrencyLimits {
+    pub synchronize: Option<usize>,
+    pub fetch_batches: Option<usize>,
+    pub delete_batches: Option<usize>,
+    pub over_limit: OverLimitPolicy,
+}
+
+/// Bounds the number of concurrent calls of each `PrimaryToWorker` method.
+///
+/// Every method draws from its own permit pool, so that a burst of calls of one method,
+/// e.g. delete_batches, never holds up the others, e.g. synchronize.
+#[derive(Clone, Default)]
+pub struct MethodPermits {
+    synchronize: Option<Arc<Semaphore>>,
+    fetch_batches: Option<Arc<Semaphore>>,
+    delete_batches: Option<Arc<Semaphore>>,
+    over_limit: OverLimitPolicy,
+}
+
+impl MethodPermits {
+    pub fn new(limits: MethodConcurrencyLimits) -> Self {
+        let semaphore = |limit: Option<usize>| limit.map(|limit| Arc::new(Semaphore::new(limit)));
+        Self {
+            synchronize: semaphore(limits.synchronize),
+            fetch_batches: semaphore(limits.fetch_batches),
+            delete_batches: semaphore(limits.delete_batches),
+            over_limit: limits.over_limit,
+        }
+    }
+
+    /// Acquires a permit to run a call of `method`, released on drop. Returns None if the
+    /// method is unbounded.
+    pub async fn acquire(
+        &self,
+        method: PrimaryToWorkerMethod,
+    ) -> Result<Option<OwnedSemaphorePermit>, WorkerHandlerError> {
+        let semaphore = match method {
+            PrimaryToWorkerMethod::Synchronize => &self.synchronize,
+            PrimaryToWorkerMethod::FetchBatches => &self.fetch_batches,
+            PrimaryToWorkerMethod::DeleteBatches => &self.delete_batches,
+        };
+        let Some(semaphore) = semaphore.clone() else {
+            return Ok(None);
+        };
+        let permit = match self.over_limit {
+            OverLimitPolicy::Queue => semaphore.acquire_owned().await.ok(),
+            OverLimitPolicy::Reject => semaphore.try_acquire_owned().ok(),
+        };
+        permit
+            .map(Some)
+            .ok_or(WorkerHandlerError::ConcurrencyLimitExceeded(method.name()))
+    }
+}
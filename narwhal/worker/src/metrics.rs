@@ -5,10 +5,14 @@ use network::metrics::{NetworkConnectionMetrics, NetworkMetrics};
 use prometheus::{
     default_registry, register_histogram_vec_with_registry, register_histogram_with_registry,
     register_int_counter_vec_with_registry, register_int_counter_with_registry,
-    register_int_gauge_with_registry, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge,
-    Registry,
+    register_int_gauge_vec_with_registry, register_int_gauge_with_registry, Histogram,
+    HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Registry,
+};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
-use std::time::Duration;
 use tonic::Code;
 
 const LATENCY_SEC_BUCKETS: &[f64] = &[
@@ -17,6 +21,18 @@ const LATENCY_SEC_BUCKETS: &[f64] = &[
     12.5, 15., 17.5, 20., 25., 30., 60., 90., 120., 180., 300.,
 ];
 
+const PEER_COUNT_BUCKETS: &[f64] = &[
+    0., 1., 2., 3., 4., 5., 7., 10., 15., 20., 30., 50., 75., 100., 150., 200.,
+];
+
+const WRITE_BATCH_COUNT_BUCKETS: &[f64] = &[
+    1., 2., 3., 5., 7., 10., 15., 20., 30., 50., 75., 100., 200., 500., 1000.,
+];
+
+const BATCH_AGE_SEC_BUCKETS: &[f64] = &[
+    1., 5., 10., 30., 60., 120., 300., 600., 1200., 1800., 3600., 7200., 14400., 28800., 86400.,
+];
+
 #[derive(Clone)]
 pub struct Metrics {
     pub worker_metrics: Option<WorkerMetrics>,
@@ -71,6 +87,44 @@ pub struct WorkerMetrics {
     pub worker_remote_fetch_latency: Histogram,
     /// The number of pending remote calls to request_batch
     pub pending_remote_request_batch: IntGauge,
+    /// Number of request_batch / request_batches calls served, per peer
+    pub peer_batch_requests: IntCounterVec,
+    /// Size in bytes of the batches served to request_batch / request_batches, per peer
+    pub peer_batch_request_bytes: IntCounterVec,
+    /// Number of batch writes to the backup store, by status
+    pub batch_mirror_writes: IntCounterVec,
+    /// Number of pushes of accepted batches to other workers, by outcome
+    pub batch_replication_pushes: IntCounterVec,
+    /// Number of batches received in synchronize responses that were not requested
+    pub synchronize_unrequested_batches: IntCounter,
+    /// Number of invalid batches received in synchronize responses and skipped
+    pub synchronize_invalid_batches: IntCounter,
+    /// Number of batches recovered by synchronize and not written, since already stored
+    pub synchronize_dedup_writes: IntCounter,
+    /// Number of synchronize calls failed since no worker of the target was reachable
+    pub synchronize_no_reachable_target: IntCounter,
+    /// Number of duplicate digests received in request_batches requests and dropped
+    pub request_batches_duplicate_digests: IntCounter,
+    /// Number of digests of request_batches requests skipped because the caller already has them
+    pub request_batches_already_have_digests: IntCounter,
+    /// Number of stored batch notifications to the batch observer, by status
+    pub stored_batch_notifications: IntCounterVec,
+    /// Number of attempts to report batches of other authorities to our primary, by outcome
+    pub report_others_batch_outcomes: IntCounterVec,
+    /// Number of distinct workers contacted by a batch fetch
+    pub batch_fetch_contacted_peers: Histogram,
+    /// Number of batches committed by each coalesced store write
+    pub coalesced_write_batches: Histogram,
+    /// Number of stored batches checked by the integrity scanner, by outcome
+    pub batch_integrity_checks: IntCounterVec,
+    /// Number of batches missing from the batch store looked up in the archive store, by outcome
+    pub archive_batch_reads: IntCounterVec,
+    /// Age in seconds of the batches deleted by delete_batches, at deletion time
+    pub deleted_batch_age: Histogram,
+    /// The last sampled value of RocksDB properties of the batch store, by property
+    pub batch_store_property: IntGaugeVec,
+    /// The peers that have their own label in the per peer metrics
+    labeled_peers: Arc<Mutex<HashSet<anemo::PeerId>>>,
 }
 
 impl WorkerMetrics {
@@ -139,8 +193,163 @@ impl WorkerMetrics {
                 registry
             )
             .unwrap(),
+            peer_batch_requests: register_int_counter_vec_with_registry!(
+                "peer_batch_requests",
+                "Number of request_batch / request_batches calls served, per peer",
+                &["peer", "method"],
+                registry
+            )
+            .unwrap(),
+            peer_batch_request_bytes: register_int_counter_vec_with_registry!(
+                "peer_batch_request_bytes",
+                "Size in bytes of the batches served to request_batch / request_batches, per peer",
+                &["peer", "method"],
+                registry
+            )
+            .unwrap(),
+            batch_mirror_writes: register_int_counter_vec_with_registry!(
+                "batch_mirror_writes",
+                "Number of batch writes to the backup store, by status",
+                &["status"],
+                registry
+            )
+            .unwrap(),
+            batch_replication_pushes: register_int_counter_vec_with_registry!(
+                "batch_replication_pushes",
+                "Number of pushes of accepted batches to other workers, by outcome",
+                &["outcome"],
+                registry
+            )
+            .unwrap(),
+            synchronize_unrequested_batches: register_int_counter_with_registry!(
+                "synchronize_unrequested_batches",
+                "Number of batches received in synchronize responses that were not requested",
+                registry
+            )
+            .unwrap(),
+            synchronize_invalid_batches: register_int_counter_with_registry!(
+                "synchronize_invalid_batches",
+                "Number of invalid batches received in synchronize responses and skipped",
+                registry
+            )
+            .unwrap(),
+            synchronize_dedup_writes: register_int_counter_with_registry!(
+                "synchronize_dedup_writes",
+                "Number of batches recovered by synchronize and not written, since already stored",
+                registry
+            )
+            .unwrap(),
+            synchronize_no_reachable_target: register_int_counter_with_registry!(
+                "synchronize_no_reachable_target",
+                "Number of synchronize calls failed since no worker of the target was reachable",
+                registry
+            )
+            .unwrap(),
+            request_batches_duplicate_digests: register_int_counter_with_registry!(
+                "request_batches_duplicate_digests",
+                "Number of duplicate digests received in request_batches requests and dropped",
+                registry
+            )
+            .unwrap(),
+            request_batches_already_have_digests: register_int_counter_with_registry!(
+                "request_batches_already_have_digests",
+                "Number of digests of request_batches requests skipped because the caller already has them",
+                registry
+            )
+            .unwrap(),
+            stored_batch_notifications: register_int_counter_vec_with_registry!(
+                "stored_batch_notifications",
+                "Number of stored batch notifications to the batch observer, by status",
+                &["status"],
+                registry
+            )
+            .unwrap(),
+            report_others_batch_outcomes: register_int_counter_vec_with_registry!(
+                "report_others_batch_outcomes",
+                "Number of attempts to report batches of other authorities to our primary, by outcome",
+                &["status"],
+                registry
+            )
+            .unwrap(),
+            batch_fetch_contacted_peers: register_histogram_with_registry!(
+                "batch_fetch_contacted_peers",
+                "Number of distinct workers contacted by a batch fetch",
+                PEER_COUNT_BUCKETS.to_vec(),
+                registry
+            )
+            .unwrap(),
+            coalesced_write_batches: register_histogram_with_registry!(
+                "coalesced_write_batches",
+                "Number of batches committed by each coalesced store write",
+                WRITE_BATCH_COUNT_BUCKETS.to_vec(),
+                registry
+            )
+            .unwrap(),
+            batch_integrity_checks: register_int_counter_vec_with_registry!(
+                "batch_integrity_checks",
+                "Number of stored batches checked by the integrity scanner, by outcome",
+                &["outcome"],
+                registry
+            )
+            .unwrap(),
+            archive_batch_reads: register_int_counter_vec_with_registry!(
+                "archive_batch_reads",
+                "Number of batches missing from the batch store looked up in the archive store, by outcome",
+                &["outcome"],
+                registry
+            )
+            .unwrap(),
+            deleted_batch_age: register_histogram_with_registry!(
+                "deleted_batch_age",
+                "Age in seconds of the batches deleted by delete_batches, at deletion time",
+                BATCH_AGE_SEC_BUCKETS.to_vec(),
+                registry
+            )
+            .unwrap(),
+            batch_store_property: register_int_gauge_vec_with_registry!(
+                "batch_store_property",
+                "The last sampled value of RocksDB properties of the batch store, by property",
+                &["property"],
+                registry
+            )
+            .unwrap(),
+            labeled_peers: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Maximum number of peers with their own label in the per peer metrics. Further peers
+    /// are all accounted under the "other" label, to keep the metrics cardinality bounded.
+    pub const MAX_LABELED_PEERS: usize = 256;
+
+    /// Returns the label under which requests from the given peer are accounted.
+    pub fn peer_label(&self, peer: Option<&anemo::PeerId>) -> String {
+        let Some(peer) = peer else {
+            return "unknown".to_string();
+        };
+        let mut labeled_peers = self.labeled_peers.lock().unwrap();
+        if labeled_peers.contains(peer) || labeled_peers.len() < Self::MAX_LABELED_PEERS {
+            labeled_peers.insert(*peer);
+            format!("{peer}")
+        } else {
+            "other".to_string()
         }
     }
+
+    /// Accounts a batch request served to the given peer.
+    pub fn record_peer_batch_request(
+        &self,
+        peer: Option<&anemo::PeerId>,
+        method: &str,
+        bytes: usize,
+    ) {
+        let peer = self.peer_label(peer);
+        self.peer_batch_requests
+            .with_label_values(&[&peer, method])
+            .inc();
+        self.peer_batch_request_bytes
+            .with_label_values(&[&peer, method])
+            .inc_by(bytes as u64);
+    }
 }
 
 impl Default for WorkerMetrics {
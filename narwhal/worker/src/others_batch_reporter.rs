@@ -0,0 +1,143 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{sync::Arc, time::Duration};
+
+use network::{client::NetworkClient, WorkerToPrimaryClient};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+use types::{error::LocalClientError, WorkerOthersBatchMessage};
+
+use crate::metrics::WorkerMetrics;
+
+/// Reports batches received from other authorities to our primary, bounding how long
+/// `report_batch` waits on a slow primary.
+///
+/// Each report is attempted with a timeout, a bounded number of times. Reports that still
+/// fail are queued and retried in the background until the primary accepts them, so the
+/// batch is acknowledged to the peer regardless of the primary's latency.
+#[derive(Clone)]
+pub struct OthersBatchReporter {
+    client: NetworkClient,
+    timeout: Duration,
+    max_retries: usize,
+    sender: mpsc::Sender<WorkerOthersBatchMessage>,
+    metrics: Arc<WorkerMetrics>,
+}
+
+impl OthersBatchReporter {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+    pub const DEFAULT_MAX_RETRIES: usize = 2;
+    pub const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+    pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+    /// Spawns the task retrying queued reports. It stops once every handle is dropped, or
+    /// when the node shuts down.
+    pub fn spawn(
+        client: NetworkClient,
+        timeout: Duration,
+        max_retries: usize,
+        queue_capacity: usize,
+        retry_delay: Duration,
+        metrics: Arc<WorkerMetrics>,
+    ) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<WorkerOthersBatchMessage>(queue_capacity);
+        let task_client = client.clone();
+        let task_metrics = metrics.clone();
+        tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                loop {
+                    match Self::try_report(&task_client, timeout, &message).await {
+                        Ok(()) => {
+                            task_metrics
+                                .report_others_batch_outcomes
+                                .with_label_values(&["reported_in_background"])
+                                .inc();
+                            break;
+                        }
+                        Err(LocalClientError::ShuttingDown) => return,
+                        Err(e @ LocalClientError::Rejected(_)) => {
+                            warn!("Primary rejected report of batch {}: {e}", message.digest);
+                            break;
+                        }
+                        Err(e) => {
+                            debug!("Failed to report batch {} to primary: {e}", message.digest);
+                            tokio::time::sleep(retry_delay).await;
+                        }
+                    }
+                }
+            }
+        });
+        Self {
+            client,
+            timeout,
+            max_retries,
+            sender,
+            metrics,
+        }
+    }
+
+    /// Reports the batch to our primary, or queues the report for background retries if
+    /// the primary is slow or failing. Only fails if the report can't be queued either.
+    pub async fn report(&self, message: WorkerOthersBatchMessage) -> Result<(), LocalClientError> {
+        let mut attempt = 0;
+        loop {
+            match Self::try_report(&self.client, self.timeout, &message).await {
+                Ok(()) => {
+                    self.record("reported");
+                    return Ok(());
+                }
+                Err(LocalClientError::ShuttingDown) => return Err(LocalClientError::ShuttingDown),
+                // Neither retries nor background reports would be accepted.
+                Err(e @ LocalClientError::Rejected(_)) => {
+                    self.record("rejected");
+                    return Err(e);
+                }
+                Err(e) if attempt < self.max_retries => {
+                    attempt += 1;
+                    self.record("retry");
+                    debug!(
+                        "Retrying to report batch {} to primary: {e}",
+                        message.digest
+                    );
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to report batch {} to primary, retrying in the background: {e}",
+                        message.digest
+                    );
+                    return match self.sender.try_send(message) {
+                        Ok(()) => {
+                            self.record("deferred");
+                            Ok(())
+                        }
+                        Err(_) => {
+                            self.record("failed");
+                            Err(e)
+                        }
+                    };
+                }
+            }
+        }
+    }
+
+    async fn try_report(
+        client: &NetworkClient,
+        timeout: Duration,
+        message: &WorkerOthersBatchMessage,
+    ) -> Result<(), LocalClientError> {
+        match tokio::time::timeout(timeout, client.report_others_batch(message.clone())).await {
+            Ok(result) => result,
+            Err(_) => Err(LocalClientError::Internal(format!(
+                "timed out after {timeout:?}"
+            ))),
+        }
+    }
+
+    fn record(&self, status: &str) {
+        self.metrics
+            .report_others_batch_outcomes
+            .with_label_values(&[status])
+            .inc();
+    }
+}
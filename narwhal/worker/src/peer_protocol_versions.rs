@@ -0,0 +1,68 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anemo::types::response::StatusCode;
+use tracing::debug;
+use types::{error::UNIMPLEMENTED, WorkerCapabilitiesRequest, WorkerToWorkerClient};
+
+/// The worker to worker protocol version of peers that do not serve the capabilities RPC.
+pub const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+/// The worker to worker protocol version of each peer, learned through the capabilities RPC,
+/// so that requests are only sent in the shapes a peer can decode. Versions are learned again
+/// once `REFRESH_INTERVAL` has elapsed, to pick up peers that were upgraded.
+#[derive(Clone, Default)]
+pub struct PeerProtocolVersions {
+    versions: Arc<Mutex<HashMap<anemo::PeerId, (u32, Instant)>>>,
+}
+
+impl PeerProtocolVersions {
+    const REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+    const CAPABILITIES_TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// Returns whether `peer` serves the methods added in protocol version 2, e.g.
+    /// request_batches_v2.
+    pub async fn serves_v2(&self, peer: &anemo::Peer) -> bool {
+        self.version(peer).await >= 2
+    }
+
+    /// Returns the protocol version of `peer`, asking it first if not known. Peers that cannot
+    /// be asked are assumed to be of the legacy version, which every worker serves.
+    pub async fn version(&self, peer: &anemo::Peer) -> u32 {
+        let peer_id = peer.peer_id();
+        let known = self.versions.lock().unwrap().get(&peer_id).copied();
+        if let Some((version, learned_at)) = known {
+            if learned_at.elapsed() < Self::REFRESH_INTERVAL {
+                return version;
+            }
+        }
+        let request = anemo::Request::new(WorkerCapabilitiesRequest {})
+            .with_timeout(Self::CAPABILITIES_TIMEOUT);
+        let version = match WorkerToWorkerClient::new(peer.clone())
+            .capabilities(request)
+            .await
+        {
+            Ok(response) => response.into_body().protocol_version,
+            // Peers predating the capabilities RPC do not know the method.
+            Err(e) if e.status() == StatusCode::NotFound || e.status() == UNIMPLEMENTED => {
+                LEGACY_PROTOCOL_VERSION
+            }
+            Err(e) => {
+                // Not remembered, so that the peer is asked again on the next request.
+                debug!("Failed to learn the protocol version of worker {peer_id}: {e:?}");
+                return LEGACY_PROTOCOL_VERSION;
+            }
+        };
+        self.versions
+            .lock()
+            .unwrap()
+            .insert(peer_id, (version, Instant::now()));
+        version
+    }
+}
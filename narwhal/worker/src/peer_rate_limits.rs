@@ -0,0 +1,148 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use axum::{
+    extract::{Extension, Path},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use fastcrypto::encoding::{Encoding, Hex};
+
+/// The fill level of a peer's rate limit bucket.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeerBucket {
+    pub peer: anemo::PeerId,
+    /// The requests the peer can make right away.
+    pub tokens: f64,
+    pub burst: u32,
+}
+
+impl fmt::Display for PeerBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:.1}/{} tokens",
+            Hex::encode(self.peer.0),
+            self.tokens,
+            self.burst
+        )
+    }
+}
+
+/// Rate limits the requests of each peer with a token bucket: a peer can make `burst`
+/// requests at once, and regains `per_second` requests every second.
+///
+/// Buckets can be inspected and reset from the admin server, e.g. to restore a peer
+/// throttled because of a misconfiguration without restarting the worker.
+#[derive(Clone)]
+pub struct PeerRateLimits {
+    per_second: f64,
+    burst: u32,
+    // The tokens left in each peer's bucket, and when they were last refilled. Peers without
+    // a bucket have a full one.
+    buckets: Arc<Mutex<HashMap<anemo::PeerId, (f64, Instant)>>>,
+}
+
+impl PeerRateLimits {
+    /// The fraction of the burst below which a peer is reported as near its limit.
+    const NEAR_LIMIT_FRACTION: f64 = 0.1;
+
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        Self {
+            per_second,
+            burst: burst.max(1),
+            buckets: Arc::default(),
+        }
+    }
+
+    /// Takes a token from the bucket of `peer`, returning false if it is empty.
+    pub fn try_acquire(&self, peer: anemo::PeerId) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+        let now = Instant::now();
+        let (tokens, refilled_at) = buckets.entry(peer).or_insert((self.burst as f64, now));
+        *tokens = (*tokens + refilled_at.elapsed().as_secs_f64() * self.per_second)
+            .min(self.burst as f64);
+        *refilled_at = now;
+        if *tokens < 1.0 {
+            return false;
+        }
+        *tokens -= 1.0;
+        true
+    }
+
+    /// Returns the buckets of the peers near or over their limit, emptiest first.
+    pub fn near_limit(&self) -> Vec<PeerBucket> {
+        let buckets = self.buckets.lock().unwrap();
+        let threshold = (self.burst as f64 * Self::NEAR_LIMIT_FRACTION).max(1.0);
+        let mut near_limit: Vec<_> = buckets
+            .iter()
+            .map(|(peer, (tokens, refilled_at))| PeerBucket {
+                peer: *peer,
+                tokens: (tokens + refilled_at.elapsed().as_secs_f64() * self.per_second)
+                    .min(self.burst as f64),
+                burst: self.burst,
+            })
+            .filter(|bucket| bucket.tokens < threshold)
+            .collect();
+        near_limit.sort_by(|a, b| a.tokens.total_cmp(&b.tokens));
+        near_limit
+    }
+
+    /// Refills the bucket of `peer`, returning whether it was tracked.
+    pub fn reset(&self, peer: &anemo::PeerId) -> bool {
+        self.buckets.lock().unwrap().remove(peer).is_some()
+    }
+
+    /// Routes listing the peers near their limit and resetting the bucket of a hex encoded
+    /// peer id. Only served on the admin server, which listens on localhost.
+    pub fn admin_routes(&self) -> Router {
+        Router::new()
+            .route("/peer_rate_limits", get(get_peer_rate_limits))
+            .route("/peer_rate_limits/:peer/reset", post(reset_peer_rate_limit))
+            .layer(Extension(self.clone()))
+    }
+}
+
+async fn get_peer_rate_limits(
+    Extension(limits): Extension<PeerRateLimits>,
+) -> (StatusCode, Json<Vec<String>>) {
+    (
+        StatusCode::OK,
+        Json(
+            limits
+                .near_limit()
+                .iter()
+                .map(|bucket| bucket.to_string())
+                .collect(),
+        ),
+    )
+}
+
+async fn reset_peer_rate_limit(
+    Extension(limits): Extension<PeerRateLimits>,
+    Path(peer): Path<String>,
+) -> (StatusCode, String) {
+    let Some(peer_id) = Hex::decode(&peer)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(anemo::PeerId)
+    else {
+        return (StatusCode::BAD_REQUEST, format!("Invalid peer id {peer}"));
+    };
+    if limits.reset(&peer_id) {
+        (StatusCode::OK, format!("Reset the rate limit of {peer}"))
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            format!("Peer {peer} is not rate limited"),
+        )
+    }
+}
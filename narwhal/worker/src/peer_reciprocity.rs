@@ -0,0 +1,76 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// The bytes of batches exchanged with a peer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PeerBalance {
+    /// Served to the peer through request_batch, request_batches and bulk sync pages.
+    pub served: u64,
+    /// Reported by the peer and accepted.
+    pub received: u64,
+}
+
+/// Refuses to serve batches to peers that take far more than they give, to discourage
+/// free-riding.
+///
+/// A peer is refused once it was served more than `grace_bytes`, and more than `max_ratio`
+/// times the bytes of the batches it reported to us. It is served again as soon as it reports
+/// enough batches. Both bounds should be generous: workers of a busy authority legitimately
+/// request more than they report, and the policy is only meant to catch peers that report
+/// next to nothing.
+#[derive(Clone)]
+pub struct PeerReciprocity {
+    grace_bytes: u64,
+    max_ratio: f64,
+    balances: Arc<Mutex<HashMap<anemo::PeerId, PeerBalance>>>,
+}
+
+impl PeerReciprocity {
+    pub const DEFAULT_GRACE_BYTES: u64 = 1 << 30;
+    pub const DEFAULT_MAX_RATIO: f64 = 100.0;
+
+    pub fn new(grace_bytes: u64, max_ratio: f64) -> Self {
+        Self {
+            grace_bytes,
+            max_ratio,
+            balances: Arc::default(),
+        }
+    }
+
+    pub fn record_served(&self, peer: anemo::PeerId, bytes: usize) {
+        let mut balances = self.balances.lock().unwrap();
+        balances.entry(peer).or_default().served += bytes as u64;
+    }
+
+    pub fn record_received(&self, peer: anemo::PeerId, bytes: usize) {
+        let mut balances = self.balances.lock().unwrap();
+        balances.entry(peer).or_default().received += bytes as u64;
+    }
+
+    /// Returns whether `peer` gave enough in return for the batches it was served.
+    pub fn is_reciprocating(&self, peer: &anemo::PeerId) -> bool {
+        let balance = self.balance(peer);
+        balance.served <= self.grace_bytes
+            || balance.served as f64 <= balance.received as f64 * self.max_ratio
+    }
+
+    pub fn balance(&self, peer: &anemo::PeerId) -> PeerBalance {
+        self.balances
+            .lock()
+            .unwrap()
+            .get(peer)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl Default for PeerReciprocity {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_GRACE_BYTES, Self::DEFAULT_MAX_RATIO)
+    }
+}
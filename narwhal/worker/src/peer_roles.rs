@@ -0,0 +1,84 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{collections::HashSet, sync::Arc};
+
+use config::WorkerCache;
+
+use crate::{handlers::WorkerHandlerError, peer_rate_limits::PeerRateLimits};
+
+/// The role of a peer calling the worker to worker interface.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeerRole {
+    /// A worker of the committee, or a local caller.
+    Committee,
+    /// Any other peer, e.g. an explorer or a monitoring node.
+    Observer,
+}
+
+/// Classifies the peers of the worker to worker interface by identity, and applies the
+/// policy of their role.
+///
+/// Committee workers are fully served. Observers are only served the methods returning
+/// metadata, e.g. batch_sizes or request_batch_metadata, under rate limits of their own.
+/// Methods returning or storing batches are refused to them, so that they cannot take the
+/// bandwidth the committee needs.
+#[derive(Clone)]
+pub struct PeerRoles {
+    committee: Arc<HashSet<anemo::PeerId>>,
+    observer_rate_limits: PeerRateLimits,
+}
+
+impl PeerRoles {
+    pub fn new(
+        committee: impl IntoIterator<Item = anemo::PeerId>,
+        observer_rate_limits: PeerRateLimits,
+    ) -> Self {
+        Self {
+            committee: Arc::new(committee.into_iter().collect()),
+            observer_rate_limits,
+        }
+    }
+
+    /// Classifies the workers of `worker_cache` as committee peers.
+    pub fn from_worker_cache(
+        worker_cache: &WorkerCache,
+        observer_rate_limits: PeerRateLimits,
+    ) -> Self {
+        Self::new(
+            worker_cache
+                .all_workers()
+                .into_iter()
+                .map(|(network_key, _)| anemo::PeerId(network_key.0.to_bytes())),
+            observer_rate_limits,
+        )
+    }
+
+    /// Returns the role of `peer`. Calls without a peer are local.
+    pub fn role(&self, peer: Option<&anemo::PeerId>) -> PeerRole {
+        match peer {
+            Some(peer) if !self.committee.contains(peer) => PeerRole::Observer,
+            _ => PeerRole::Committee,
+        }
+    }
+
+    /// Applies the policy of the role of `peer` to a call of `method`, which returns or
+    /// stores batches if `serves_batches`.
+    pub fn check(
+        &self,
+        peer: Option<&anemo::PeerId>,
+        method: &'static str,
+        serves_batches: bool,
+    ) -> Result<(), WorkerHandlerError> {
+        let (PeerRole::Observer, Some(peer)) = (self.role(peer), peer) else {
+            return Ok(());
+        };
+        if serves_batches {
+            return Err(WorkerHandlerError::ObserverDenied(method));
+        }
+        if !self.observer_rate_limits.try_acquire(*peer) {
+            return Err(WorkerHandlerError::RateLimited(*peer));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crypto::NetworkPublicKey;
+use rand::{rngs::ThreadRng, seq::SliceRandom};
+use types::BatchDigest;
+
+#[cfg(test)]
+#[path = "tests/peer_selection_tests.rs"]
+pub mod peer_selection_tests;
+
+/// What the `BatchFetcher` observed of a peer's past fetches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PeerStats {
+    /// The smoothed latency of the successful requests, if any.
+    pub latency: Option<Duration>,
+    pub successes: u64,
+    pub failures: u64,
+}
+
+impl PeerStats {
+    /// Records the outcome of a request, `latency` being None if it failed.
+    pub fn record(&mut self, latency: Option<Duration>) {
+        let Some(latency) = latency else {
+            self.failures += 1;
+            return;
+        };
+        self.successes += 1;
+        // Weighs the latest request by 1/4.
+        self.latency = Some(match self.latency {
+            Some(smoothed) => (smoothed * 3 + latency) / 4,
+            None => latency,
+        });
+    }
+}
+
+/// Decides which of the known workers a `BatchFetcher` requests batches from, and in which
+/// order. Called at every round of a fetch, with the digests still missing.
+pub trait PeerSelector: Send + Sync + 'static {
+    /// Returns the peers to request `digests` from, in order. Only peers of `known_workers`
+    /// are requested, and peers not returned are skipped for the round.
+    fn select(
+        &self,
+        digests: &HashSet<BatchDigest>,
+        known_workers: &[NetworkPublicKey],
+        stats: &HashMap<NetworkPublicKey, PeerStats>,
+    ) -> Vec<NetworkPublicKey>;
+}
+
+/// Requests the known workers in a random order, spreading fetches over them. The default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RandomOrder;
+
+impl PeerSelector for RandomOrder {
+    fn select(
+        &self,
+        _digests: &HashSet<BatchDigest>,
+        known_workers: &[NetworkPublicKey],
+        _stats: &HashMap<NetworkPublicKey, PeerStats>,
+    ) -> Vec<NetworkPublicKey> {
+        let mut peers = known_workers.to_vec();
+        peers.shuffle(&mut ThreadRng::default());
+        peers
+    }
+}
+
+/// Requests the known workers in a fixed order, starting from the next worker at every round.
+#[derive(Debug, Default)]
+pub struct RoundRobin {
+    next: AtomicUsize,
+}
+
+impl PeerSelector for RoundRobin {
+    fn select(
+        &self,
+        _digests: &HashSet<BatchDigest>,
+        known_workers: &[NetworkPublicKey],
+        _stats: &HashMap<NetworkPublicKey, PeerStats>,
+    ) -> Vec<NetworkPublicKey> {
+        let mut peers = known_workers.to_vec();
+        if peers.is_empty() {
+            return peers;
+        }
+        peers.sort_by_key(|peer| peer.0.to_bytes());
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % peers.len();
+        peers.rotate_left(start);
+        peers
+    }
+}
+
+/// Requests the known workers by increasing latency. Workers that never served a request are
+/// requested last, in a random order.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LatencyWeighted;
+
+impl PeerSelector for LatencyWeighted {
+    fn select(
+        &self,
+        _digests: &HashSet<BatchDigest>,
+        known_workers: &[NetworkPublicKey],
+        stats: &HashMap<NetworkPublicKey, PeerStats>,
+    ) -> Vec<NetworkPublicKey> {
+        let mut peers = known_workers.to_vec();
+        peers.shuffle(&mut ThreadRng::default());
+        // Stable, so that the workers without latency stay shuffled.
+        peers.sort_by_key(|peer| {
+            stats
+                .get(peer)
+                .and_then(|stats| stats.latency)
+                .unwrap_or(Duration::MAX)
+        });
+        peers
+    }
+}
+
+/// Requests first the known workers that served the most requests, then the others in a
+/// random order.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KnownFirst;
+
+impl PeerSelector for KnownFirst {
+    fn select(
+        &self,
+        _digests: &HashSet<BatchDigest>,
+        known_workers: &[NetworkPublicKey],
+        stats: &HashMap<NetworkPublicKey, PeerStats>,
+    ) -> Vec<NetworkPublicKey> {
+        let mut peers = known_workers.to_vec();
+        peers.shuffle(&mut ThreadRng::default());
+        peers.sort_by_key(|peer| {
+            std::cmp::Reverse(stats.get(peer).map_or(0, |stats| stats.successes))
+        });
+        peers
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// The priority of a read against the batch store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadPriority {
+    /// Reads issued by `synchronize()`, which gate consensus progress.
+    Sync,
+    /// Reads issued when serving `request_batch()` / `request_batches()` to other workers.
+    Bulk,
+}
+
+/// Bounds the number of concurrent reads against the batch store.
+///
+/// Sync and bulk reads draw from separate permit pools, so a burst of bulk requests from
+/// other workers can saturate its own pool without ever delaying the reads that consensus
+/// is waiting on.
+#[derive(Clone)]
+pub struct StoreReadPermits {
+    sync: Arc<Semaphore>,
+    bulk: Arc<Semaphore>,
+}
+
+impl StoreReadPermits {
+    pub const DEFAULT_SYNC_PERMITS: usize = 64;
+    pub const DEFAULT_BULK_PERMITS: usize = 32;
+
+    pub fn new(sync_permits: usize, bulk_permits: usize) -> Self {
+        Self {
+            sync: Arc::new(Semaphore::new(sync_permits)),
+            bulk: Arc::new(Semaphore::new(bulk_permits)),
+        }
+    }
+
+    /// Waits for a read permit of the given priority. The permit is released on drop.
+    pub async fn acquire(&self, priority: ReadPriority) -> OwnedSemaphorePermit {
+        let semaphore = match priority {
+            ReadPriority::Sync => self.sync.clone(),
+            ReadPriority::Bulk => self.bulk.clone(),
+        };
+        semaphore
+            .acquire_owned()
+            .await
+            .expect("Store read semaphore should never be closed")
+    }
+
+    /// Returns the number of currently available permits of the given priority.
+    pub fn available(&self, priority: ReadPriority) -> usize {
+        match priority {
+            ReadPriority::Sync => self.sync.available_permits(),
+            ReadPriority::Bulk => self.bulk.available_permits(),
+        }
+    }
+}
+
+impl Default for StoreReadPermits {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_SYNC_PERMITS, Self::DEFAULT_BULK_PERMITS)
+    }
+}
@@ -0,0 +1,36 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{collections::HashSet, sync::Arc};
+
+use types::Batch;
+
+/// Rewrites the batches served to a designated set of peers, e.g. to redact them for light
+/// clients. Transformed batches no longer match their digest, so they are only served to
+/// the designated peers, and other workers always receive the stored batches.
+#[derive(Clone)]
+pub struct BatchReadTransform {
+    peers: Arc<HashSet<anemo::PeerId>>,
+    transform: Arc<dyn Fn(Batch) -> Batch + Send + Sync>,
+}
+
+impl BatchReadTransform {
+    pub fn new(
+        peers: impl IntoIterator<Item = anemo::PeerId>,
+        transform: impl Fn(Batch) -> Batch + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            peers: Arc::new(peers.into_iter().collect()),
+            transform: Arc::new(transform),
+        }
+    }
+
+    /// Returns whether batches served to `peer` are transformed.
+    pub fn applies_to(&self, peer: Option<&anemo::PeerId>) -> bool {
+        peer.map_or(false, |peer| self.peers.contains(peer))
+    }
+
+    pub fn apply(&self, batch: Batch) -> Batch {
+        (self.transform)(batch)
+    }
+}
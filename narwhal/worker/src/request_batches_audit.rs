@@ -0,0 +1,118 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use axum::{extract::Extension, http::StatusCode, routing::get, Json, Router};
+use types::{now, TimestampMs};
+
+/// A request_batches call served by the worker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServedRequestBatches {
+    pub peer: Option<anemo::PeerId>,
+    /// Number of digests requested, duplicates included.
+    pub requested: usize,
+    /// Number of batches served.
+    pub served: usize,
+    /// Bytes of transactions served.
+    pub bytes: usize,
+    pub is_size_limit_reached: bool,
+    pub timestamp: TimestampMs,
+}
+
+impl fmt::Display for ServedRequestBatches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.peer {
+            Some(peer) => write!(f, "{peer}")?,
+            None => write!(f, "unknown peer")?,
+        }
+        write!(
+            f,
+            " requested {} batches, served {} of {} bytes{} at {}",
+            self.requested,
+            self.served,
+            self.bytes,
+            if self.is_size_limit_reached {
+                " (size limited)"
+            } else {
+                ""
+            },
+            self.timestamp
+        )
+    }
+}
+
+/// Keeps the most recent request_batches calls served, so that operators can reconstruct
+/// recent serving activity when investigating an incident. The oldest calls are evicted once
+/// `capacity` is reached.
+#[derive(Clone)]
+pub struct RequestBatchesAudit {
+    served: Arc<Mutex<VecDeque<ServedRequestBatches>>>,
+    capacity: usize,
+}
+
+impl RequestBatchesAudit {
+    pub const DEFAULT_CAPACITY: usize = 1_000;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            served: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    pub fn record(
+        &self,
+        peer: Option<anemo::PeerId>,
+        requested: usize,
+        served: usize,
+        bytes: usize,
+        is_size_limit_reached: bool,
+    ) {
+        let mut log = self.served.lock().unwrap();
+        if log.len() >= self.capacity {
+            log.pop_front();
+        }
+        if self.capacity > 0 {
+            log.push_back(ServedRequestBatches {
+                peer,
+                requested,
+                served,
+                bytes,
+                is_size_limit_reached,
+                timestamp: now(),
+            });
+        }
+    }
+
+    /// Returns the recorded calls, oldest first.
+    pub fn recent(&self) -> Vec<ServedRequestBatches> {
+        self.served.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Routes serving the recorded calls on the admin server.
+    pub fn admin_routes(&self) -> Router {
+        Router::new()
+            .route("/request_batches_audit", get(get_request_batches_audit))
+            .layer(Extension(self.clone()))
+    }
+}
+
+impl Default for RequestBatchesAudit {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+async fn get_request_batches_audit(
+    Extension(audit): Extension<RequestBatchesAudit>,
+) -> (StatusCode, Json<Vec<String>>) {
+    (
+        StatusCode::OK,
+        Json(audit.recent().iter().map(|s| s.to_string()).collect()),
+    )
+}
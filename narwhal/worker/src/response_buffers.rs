@@ -0,0 +1,148 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+use types::BatchDigest;
+
+// Buffers grown past this many digests in total are dropped rather than pooled, so that a
+// few huge requests do not pin their memory for good.
+const MAX_RETAINED_DIGESTS: usize = 16_384;
+
+/// The scratch buffers request_batches assembles a response with. The batches of the
+/// response are handed over to the response, so they cannot be reused.
+#[derive(Default)]
+pub(crate) struct ResponseBuffers {
+    // The digests of the chunk being read, and their keys in the store.
+    pub chunk_digests: Vec<BatchDigest>,
+    pub keys: Vec<BatchDigest>,
+    // The requested digest of each batch of the response.
+    pub batch_digests: Vec<BatchDigest>,
+}
+
+impl ResponseBuffers {
+    fn clear(&mut self) {
+        self.chunk_digests.clear();
+        self.keys.clear();
+        self.batch_digests.clear();
+    }
+
+    fn capacity(&self) -> usize {
+        self.chunk_digests.capacity() + self.keys.capacity() + self.batch_digests.capacity()
+    }
+}
+
+#[derive(Default)]
+struct PoolInner {
+    free: Vec<ResponseBuffers>,
+    created: u64,
+    reused: u64,
+}
+
+/// Reuses the scratch buffers of request_batches across requests, which saves allocating
+/// them for every request under high request rates. Up to `max_pooled` sets of buffers are
+/// kept, enough for as many concurrent requests.
+#[derive(Clone)]
+pub struct ResponseBufferPool {
+    max_pooled: usize,
+    inner: Arc<Mutex<PoolInner>>,
+}
+
+impl ResponseBufferPool {
+    pub const DEFAULT_MAX_POOLED: usize = 64;
+
+    pub fn new(max_pooled: usize) -> Self {
+        Self {
+            max_pooled,
+            inner: Arc::default(),
+        }
+    }
+
+    /// Takes a set of buffers from the pool, or creates one if none is free. The buffers are
+    /// returned to the pool on drop.
+    pub(crate) fn take(&self) -> PooledBuffers {
+        let mut inner = self.inner.lock().unwrap();
+        let buffers = match inner.free.pop() {
+            Some(buffers) => {
+                inner.reused += 1;
+                buffers
+            }
+            None => {
+                inner.created += 1;
+                ResponseBuffers::default()
+            }
+        };
+        PooledBuffers {
+            buffers,
+            pool: Some(self.clone()),
+        }
+    }
+
+    /// Returns the number of sets of buffers created, when none was free for a request.
+    pub fn created(&self) -> u64 {
+        self.inner.lock().unwrap().created
+    }
+
+    /// Returns the number of requests served with buffers from the pool.
+    pub fn reused(&self) -> u64 {
+        self.inner.lock().unwrap().reused
+    }
+
+    fn put(&self, mut buffers: ResponseBuffers) {
+        if buffers.capacity() > MAX_RETAINED_DIGESTS {
+            return;
+        }
+        buffers.clear();
+        let mut inner = self.inner.lock().unwrap();
+        if inner.free.len() < self.max_pooled {
+            inner.free.push(buffers);
+        }
+    }
+}
+
+impl Default for ResponseBufferPool {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MAX_POOLED)
+    }
+}
+
+/// Buffers taken from a `ResponseBufferPool`, if any, and returned to it on drop.
+pub(crate) struct PooledBuffers {
+    buffers: ResponseBuffers,
+    pool: Option<ResponseBufferPool>,
+}
+
+impl PooledBuffers {
+    /// Buffers allocated for a single request.
+    pub fn unpooled() -> Self {
+        Self {
+            buffers: ResponseBuffers::default(),
+            pool: None,
+        }
+    }
+}
+
+impl Deref for PooledBuffers {
+    type Target = ResponseBuffers;
+
+    fn deref(&self) -> &ResponseBuffers {
+        &self.buffers
+    }
+}
+
+impl DerefMut for PooledBuffers {
+    fn deref_mut(&mut self) -> &mut ResponseBuffers {
+        &mut self.buffers
+    }
+}
+
+impl Drop for PooledBuffers {
+    fn drop(&mut self) {
+        if let Some(pool) = self.pool.take() {
+            pool.put(std::mem::take(&mut self.buffers));
+        }
+    }
+}
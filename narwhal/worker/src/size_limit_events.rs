@@ -0,0 +1,97 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use axum::{extract::Extension, http::StatusCode, routing::get, Json, Router};
+use types::{now, TimestampMs};
+
+/// A request_batches call whose response was truncated by a size limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SizeLimitEvent {
+    pub peer: Option<anemo::PeerId>,
+    /// Number of batches requested.
+    pub requested: usize,
+    /// Number of batches served.
+    pub served: usize,
+    pub timestamp: TimestampMs,
+}
+
+impl fmt::Display for SizeLimitEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.peer {
+            Some(peer) => write!(f, "{peer}")?,
+            None => write!(f, "unknown peer")?,
+        }
+        write!(
+            f,
+            " requested {} batches, served {} at {}",
+            self.requested, self.served, self.timestamp
+        )
+    }
+}
+
+/// Keeps the most recent size limited requests, so that operators can find the callers that
+/// consistently over-request. The oldest events are evicted once `capacity` is reached.
+#[derive(Clone)]
+pub struct SizeLimitEvents {
+    events: Arc<Mutex<VecDeque<SizeLimitEvent>>>,
+    capacity: usize,
+}
+
+impl SizeLimitEvents {
+    pub const DEFAULT_CAPACITY: usize = 100;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    pub fn record(&self, peer: Option<anemo::PeerId>, requested: usize, served: usize) {
+        let mut events = self.events.lock().unwrap();
+        if events.len() >= self.capacity {
+            events.pop_front();
+        }
+        if self.capacity > 0 {
+            events.push_back(SizeLimitEvent {
+                peer,
+                requested,
+                served,
+                timestamp: now(),
+            });
+        }
+    }
+
+    /// Returns the recorded events, oldest first.
+    pub fn recent(&self) -> Vec<SizeLimitEvent> {
+        self.events.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Routes serving the recorded events on the admin server.
+    pub fn admin_routes(&self) -> Router {
+        Router::new()
+            .route("/size_limit_events", get(get_size_limit_events))
+            .layer(Extension(self.clone()))
+    }
+}
+
+impl Default for SizeLimitEvents {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+async fn get_size_limit_events(
+    Extension(events): Extension<SizeLimitEvents>,
+) -> (StatusCode, Json<Vec<String>>) {
+    (
+        StatusCode::OK,
+        Json(events.recent().iter().map(|e| e.to_string()).collect()),
+    )
+}
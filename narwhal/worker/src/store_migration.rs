@@ -0,0 +1,81 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
+
+use crate::handlers::WorkerHandlerError;
+
+#[cfg(test)]
+#[path = "tests/store_migration_tests.rs"]
+pub mod store_migration_tests;
+
+/// Coordinates a migration of the batch store, e.g. a RocksDB format upgrade, with the
+/// handlers sharing it, so that workers keep serving batches meanwhile.
+///
+/// During the migration window, writes are paused and reads are served from a read-only
+/// snapshot of the store taken before the migration. Starting the window waits for the
+/// writes in progress to drain. Writes attempted during the window, or while it drains,
+/// fail with `Overloaded` so that their callers retry later.
+#[derive(Clone)]
+pub struct StoreMigration<S> {
+    writes: Arc<RwLock<()>>,
+    window: Arc<Mutex<Option<MigrationWindow<S>>>>,
+}
+
+struct MigrationWindow<S> {
+    snapshot: S,
+    _paused_writes: OwnedRwLockWriteGuard<()>,
+}
+
+/// Held while writing to the store, which delays the start of a migration window.
+pub struct StoreWriteGuard {
+    _guard: OwnedRwLockReadGuard<()>,
+}
+
+impl<S> Default for StoreMigration<S> {
+    fn default() -> Self {
+        Self {
+            writes: Arc::default(),
+            window: Arc::default(),
+        }
+    }
+}
+
+impl<S: Clone> StoreMigration<S> {
+    /// Pauses writes, waiting for the writes in progress to complete, then serves reads from
+    /// `snapshot` until `end()`.
+    pub async fn begin(&self, snapshot: S) {
+        let paused_writes = self.writes.clone().write_owned().await;
+        *self.window.lock().unwrap() = Some(MigrationWindow {
+            snapshot,
+            _paused_writes: paused_writes,
+        });
+    }
+
+    /// Ends the migration window, resuming writes and reads from the store. Returns whether
+    /// a window was open.
+    pub fn end(&self) -> bool {
+        self.window.lock().unwrap().take().is_some()
+    }
+
+    /// Returns the snapshot to serve reads from, if a migration window is open.
+    pub fn snapshot(&self) -> Option<S> {
+        self.window
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|window| window.snapshot.clone())
+    }
+
+    /// Allows a write, unless writes are paused or draining for a migration.
+    pub fn write(&self) -> Result<StoreWriteGuard, WorkerHandlerError> {
+        let guard = self
+            .writes
+            .clone()
+            .try_read_owned()
+            .map_err(|_| WorkerHandlerError::Overloaded)?;
+        Ok(StoreWriteGuard { _guard: guard })
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::{batch_store::BatchStore, metrics::WorkerMetrics};
+
+#[cfg(test)]
+#[path = "tests/store_stats_tests.rs"]
+pub mod store_stats_tests;
+
+/// The RocksDB properties of the batch store exported by `StoreStatsSampler`. RocksDB does
+/// not report write amplification as a property, but pending compaction bytes and the
+/// number of files per level track it.
+pub const SAMPLED_PROPERTIES: &[&str] = &[
+    "rocksdb.compaction-pending",
+    "rocksdb.estimate-pending-compaction-bytes",
+    "rocksdb.num-running-compactions",
+    "rocksdb.num-running-flushes",
+    "rocksdb.cur-size-all-mem-tables",
+    "rocksdb.total-sst-files-size",
+    "rocksdb.live-sst-files-size",
+    "rocksdb.estimate-num-keys",
+    "rocksdb.num-files-at-level0",
+    "rocksdb.num-files-at-level1",
+    "rocksdb.num-files-at-level2",
+    "rocksdb.num-files-at-level3",
+    "rocksdb.num-files-at-level4",
+    "rocksdb.num-files-at-level5",
+    "rocksdb.num-files-at-level6",
+];
+
+/// Periodically exports RocksDB properties of the batch store to the
+/// `batch_store_property` gauges, to surface storage health that request latencies hide.
+/// Backends without the properties, e.g. in-memory stores, export nothing.
+pub struct StoreStatsSampler;
+
+impl StoreStatsSampler {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Spawns the task sampling `store` every `interval`, until the worker shuts down.
+    pub fn spawn<S: BatchStore>(
+        store: S,
+        interval: Duration,
+        metrics: Arc<WorkerMetrics>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                let (store, metrics) = (store.clone(), metrics.clone());
+                // Reading properties may block on RocksDB.
+                if let Err(e) =
+                    tokio::task::spawn_blocking(move || Self::sample(&store, &metrics)).await
+                {
+                    warn!("Failed to sample batch store properties: {e}");
+                }
+            }
+        })
+    }
+
+    /// Sets the gauge of each of the `SAMPLED_PROPERTIES` the store has.
+    pub fn sample<S: BatchStore>(store: &S, metrics: &WorkerMetrics) {
+        for property in SAMPLED_PROPERTIES {
+            match store.int_property(property) {
+                Ok(Some(value)) => metrics
+                    .batch_store_property
+                    .with_label_values(&[property])
+                    .set(value.try_into().unwrap_or(i64::MAX)),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to read batch store property {property}: {e:?}"),
+            }
+        }
+    }
+}
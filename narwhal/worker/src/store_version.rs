@@ -0,0 +1,209 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use config::Epoch;
+use tokio::task::JoinHandle;
+use tracing::warn;
+use types::{Batch, BatchDigest, BatchSummary, TransactionDigest};
+
+use crate::batch_store::{BatchStore, StoreResult};
+
+#[cfg(test)]
+#[path = "tests/store_version_tests.rs"]
+pub mod store_version_tests;
+
+/// A version of the batch store, incremented on every write through a
+/// `VersionedBatchStore`, for clients polling a worker to cheaply tell whether anything
+/// changed since their last query, see the store_version RPC.
+///
+/// The version is kept in memory and persisted periodically. To stay monotonic across
+/// restarts without persisting every write, what is persisted is a high-water mark
+/// `reservation` versions ahead of the current one, which the version resumes from. Fewer
+/// than `reservation` writes must thus happen between two persists.
+#[derive(Clone)]
+pub struct StoreVersion {
+    version: Arc<AtomicU64>,
+    reservation: u64,
+    path: Option<PathBuf>,
+}
+
+impl StoreVersion {
+    pub const DEFAULT_RESERVATION: u64 = 1_000_000;
+    pub const DEFAULT_PERSIST_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// A version starting from 0, which is never persisted.
+    pub fn in_memory() -> Self {
+        Self {
+            version: Arc::default(),
+            reservation: 0,
+            path: None,
+        }
+    }
+
+    /// Resumes the version persisted at `path`, if any, and persists it there from now on.
+    pub fn load(path: impl Into<PathBuf>, reservation: u64) -> io::Result<Self> {
+        let path = path.into();
+        let version = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents
+                .trim()
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        let store_version = Self {
+            version: Arc::new(AtomicU64::new(version)),
+            reservation,
+            path: Some(path),
+        };
+        // Reserve the versions of this run before serving any of them.
+        store_version.persist()?;
+        Ok(store_version)
+    }
+
+    /// Returns the current version.
+    pub fn current(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    fn increment(&self) {
+        self.version.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Persists the high-water mark of the version, if it has a path.
+    pub fn persist(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let high_water_mark = self.current().saturating_add(self.reservation);
+        // Write then rename, so that a crash never leaves a partially written version.
+        let staged = staged_path(path);
+        std::fs::write(&staged, high_water_mark.to_string())?;
+        std::fs::rename(&staged, path)
+    }
+
+    /// Spawns the task persisting the version every `interval`, until the worker shuts down.
+    pub fn spawn_persister(self, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            loop {
+                interval.tick().await;
+                let store_version = self.clone();
+                match tokio::task::spawn_blocking(move || store_version.persist()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => warn!("Failed to persist the batch store version: {e}"),
+                    Err(e) => warn!("Failed to persist the batch store version: {e}"),
+                }
+            }
+        })
+    }
+}
+
+fn staged_path(path: &Path) -> PathBuf {
+    let mut staged = path.as_os_str().to_owned();
+    staged.push(".tmp");
+    staged.into()
+}
+
+/// Wraps a batch store to increment a `StoreVersion` on every successful write.
+#[derive(Clone)]
+pub struct VersionedBatchStore<S> {
+    inner: S,
+    version: StoreVersion,
+}
+
+impl<S: BatchStore> VersionedBatchStore<S> {
+    pub fn new(inner: S, version: StoreVersion) -> Self {
+        Self { inner, version }
+    }
+
+    pub fn version(&self) -> &StoreVersion {
+        &self.version
+    }
+
+    fn versioned(&self, result: StoreResult<()>) -> StoreResult<()> {
+        if result.is_ok() {
+            self.version.increment();
+        }
+        result
+    }
+}
+
+impl<S: BatchStore> BatchStore for VersionedBatchStore<S> {
+    fn get(&self, key: &BatchDigest) -> StoreResult<Option<Batch>> {
+        self.inner.get(key)
+    }
+
+    fn multi_get(&self, keys: &[BatchDigest]) -> StoreResult<Vec<Option<Batch>>> {
+        self.inner.multi_get(keys)
+    }
+
+    fn multi_get_summaries(&self, keys: &[BatchDigest]) -> StoreResult<Vec<Option<BatchSummary>>> {
+        self.inner.multi_get_summaries(keys)
+    }
+
+    fn insert(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        self.versioned(self.inner.insert(key, batch))
+    }
+
+    fn insert_referenced(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        self.versioned(self.inner.insert_referenced(key, batch))
+    }
+
+    fn multi_insert(&self, entries: &[(BatchDigest, Batch)]) -> StoreResult<()> {
+        self.versioned(self.inner.multi_insert(entries))
+    }
+
+    fn insert_indexed(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        self.versioned(self.inner.insert_indexed(key, batch))
+    }
+
+    fn batch_of_transaction(
+        &self,
+        transaction: &TransactionDigest,
+    ) -> StoreResult<Option<BatchDigest>> {
+        self.inner.batch_of_transaction(transaction)
+    }
+
+    fn remove(&self, key: &BatchDigest) -> StoreResult<()> {
+        self.versioned(self.inner.remove(key))
+    }
+
+    fn multi_remove(&self, keys: &[BatchDigest]) -> StoreResult<()> {
+        self.versioned(self.inner.multi_remove(keys))
+    }
+
+    fn remove_epoch(&self, epoch: Epoch) -> StoreResult<()> {
+        self.versioned(self.inner.remove_epoch(epoch))
+    }
+
+    fn contains_key(&self, key: &BatchDigest) -> StoreResult<bool> {
+        self.inner.contains_key(key)
+    }
+
+    fn multi_contains_keys(&self, keys: &[BatchDigest]) -> StoreResult<Vec<bool>> {
+        self.inner.multi_contains_keys(keys)
+    }
+
+    fn entries_after(
+        &self,
+        cursor: Option<BatchDigest>,
+        limit: usize,
+    ) -> StoreResult<Vec<(BatchDigest, Batch)>> {
+        self.inner.entries_after(cursor, limit)
+    }
+
+    fn int_property(&self, name: &str) -> StoreResult<Option<u64>> {
+        self.inner.int_property(name)
+    }
+}
@@ -0,0 +1,96 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use fastcrypto::hash::Hash;
+
+use super::*;
+use crate::MemoryBatchStore;
+
+#[test]
+fn pinned_batch_survives_eviction_pressure() {
+    let store = CachedBatchStore::new(
+        MemoryBatchStore::default(),
+        BatchCacheConfig {
+            capacity: 4,
+            pinned_capacity: 1,
+            pin_duration: Duration::from_secs(60),
+            idle_timeout: None,
+        },
+    );
+
+    let pinned = Batch::new(vec![vec![0]]);
+    let unpinned = Batch::new(vec![vec![1]]);
+    store.insert_referenced(&pinned.digest(), &pinned).unwrap();
+    store.insert(&unpinned.digest(), &unpinned).unwrap();
+
+    // Read many more batches than the cache holds.
+    for i in 2..100u8 {
+        let batch = Batch::new(vec![vec![i]]);
+        store.insert(&batch.digest(), &batch).unwrap();
+        store.get(&batch.digest()).unwrap();
+    }
+
+    assert!(store.is_cached(&pinned.digest()));
+    assert!(!store.is_cached(&unpinned.digest()));
+    // Evicted batches are still read from the store.
+    assert_eq!(store.get(&unpinned.digest()).unwrap(), Some(unpinned));
+
+    // Removed batches are no longer cached.
+    store.remove(&pinned.digest()).unwrap();
+    assert!(!store.is_cached(&pinned.digest()));
+    assert_eq!(store.get(&pinned.digest()).unwrap(), None);
+}
+
+#[test]
+fn idle_batches_are_evicted() {
+    let store = CachedBatchStore::new(
+        MemoryBatchStore::default(),
+        BatchCacheConfig {
+            idle_timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        },
+    );
+
+    let idle = Batch::new(vec![vec![0]]);
+    let used = Batch::new(vec![vec![1]]);
+    store.insert(&idle.digest(), &idle).unwrap();
+    store.insert(&used.digest(), &used).unwrap();
+
+    std::thread::sleep(Duration::from_millis(120));
+    store.get(&used.digest()).unwrap();
+    std::thread::sleep(Duration::from_millis(120));
+
+    // Only the batch left untouched beyond the timeout is evicted.
+    store.evict_idle();
+    assert!(!store.is_cached(&idle.digest()));
+    assert!(store.is_cached(&used.digest()));
+    // Evicted batches are still read from the store.
+    assert_eq!(store.get(&idle.digest()).unwrap(), Some(idle));
+}
+
+#[tokio::test]
+async fn warmer_caches_fed_batches() {
+    let inner = MemoryBatchStore::default();
+    let batches: Vec<_> = (0..10u8).map(|i| Batch::new(vec![vec![i]])).collect();
+    for batch in &batches {
+        inner.insert(&batch.digest(), batch).unwrap();
+    }
+    let store = CachedBatchStore::new(inner, BatchCacheConfig::default());
+    assert!(batches
+        .iter()
+        .all(|batch| !store.is_cached(&batch.digest())));
+
+    // Feed all but the last batch.
+    let (sender, receiver) = tokio::sync::mpsc::channel(10);
+    let warmer = store.spawn_warmer(receiver, 100);
+    let (fed, not_fed) = batches.split_at(9);
+    sender
+        .send(fed.iter().map(|batch| batch.digest()).collect())
+        .await
+        .unwrap();
+    drop(sender);
+    warmer.await.unwrap();
+
+    assert!(fed.iter().all(|batch| store.is_cached(&batch.digest())));
+    assert!(!store.is_cached(&not_fed[0].digest()));
+}
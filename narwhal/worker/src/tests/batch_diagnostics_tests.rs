@@ -0,0 +1,45 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::{batch_store_utils::TestBatchStore, MemoryBatchStore};
+
+#[test]
+fn diagnose_reports_stored_batch() {
+    let store = MemoryBatchStore::default();
+    let service = BatchDiagnosticsService::new(store.clone());
+
+    let batch = Batch::new(vec![vec![1; 10], vec![2; 10]]);
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    let diagnostics = service.diagnose(&digest).unwrap().unwrap();
+    assert_eq!(diagnostics.batch, batch);
+    assert_eq!(diagnostics.size, batch.size());
+    assert_eq!(diagnostics.created_at, batch.metadata().created_at);
+    assert!(diagnostics.digest_matches);
+
+    // A batch stored under the key of another digest is flagged.
+    let other_digest = Batch::new(vec![vec![3; 10]]).digest();
+    store.insert(&other_digest, &batch).unwrap();
+    assert!(
+        !service
+            .diagnose(&other_digest)
+            .unwrap()
+            .unwrap()
+            .digest_matches
+    );
+}
+
+#[test]
+fn self_test_detects_broken_store() {
+    let store = MemoryBatchStore::default();
+    let report = BatchDiagnosticsService::new(store.clone()).self_test();
+    assert!(report.is_healthy(), "{report}");
+    // The synthetic batch is cleaned up.
+    assert!(store.entries_after(None, 1).unwrap().is_empty());
+
+    let report =
+        BatchDiagnosticsService::new(TestBatchStore::default().dropping_writes()).self_test();
+    assert_eq!(report.error.as_deref(), Some("batch missing after write"));
+}
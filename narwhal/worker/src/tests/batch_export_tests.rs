@@ -0,0 +1,34 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use fastcrypto::hash::Hash;
+
+use super::*;
+use crate::MemoryBatchStore;
+
+#[test]
+fn export_and_import() {
+    // Enough batches to span several pages.
+    let store = MemoryBatchStore::default();
+    for i in 0..250u32 {
+        let batch = Batch::new(vec![i.to_le_bytes().to_vec(); 4]);
+        store.insert(&batch.digest(), &batch).unwrap();
+    }
+
+    let max_chunk_size = 100;
+    let chunks: Vec<_> = BatchExport::new(store.clone(), max_chunk_size)
+        .map(|chunk| chunk.unwrap())
+        .collect();
+    for chunk in &chunks {
+        let size: usize = chunk.iter().map(|(_, batch)| batch.size()).sum();
+        assert!(size <= max_chunk_size);
+    }
+
+    let restored = MemoryBatchStore::default();
+    let imported = import_batches(&restored, chunks.into_iter().map(Ok)).unwrap();
+    assert_eq!(imported, 250);
+    assert_eq!(
+        restored.entries_after(None, usize::MAX).unwrap(),
+        store.entries_after(None, usize::MAX).unwrap()
+    );
+}
@@ -0,0 +1,59 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use prometheus::Registry;
+use types::Batch;
+
+use super::*;
+use crate::MemoryBatchStore;
+
+#[test]
+fn detects_and_quarantines_corrupted_batches() {
+    let store = MemoryBatchStore::default();
+    let quarantine = MemoryBatchStore::default();
+    let metrics = Arc::new(WorkerMetrics::new(&Registry::new()));
+    let config = IntegrityScanConfig {
+        page_len: 2,
+        ..Default::default()
+    };
+    let scanner = BatchIntegrityScanner::new(store.clone(), config, metrics.clone())
+        .with_quarantine(quarantine.clone());
+
+    let mut good_keys = Vec::new();
+    for i in 0..4 {
+        let batch = Batch::new(vec![vec![i; 10]]);
+        let key = batch.digest();
+        store.insert(&key, &batch).unwrap();
+        good_keys.push(key);
+    }
+    // A batch stored under the key of another digest, as if it was corrupted on disk.
+    let corrupted = Batch::new(vec![vec![9; 10]]);
+    let corrupted_key = Batch::new(vec![vec![8; 10]]).digest();
+    store.insert(&corrupted_key, &corrupted).unwrap();
+
+    // Scan the whole store, one page at a time.
+    let mut cursor = None;
+    let mut pages = 0;
+    while let Some(last_key) = scanner.scan_page(cursor).unwrap() {
+        cursor = Some(last_key);
+        pages += 1;
+    }
+    assert_eq!(pages, 3);
+
+    let checks = |outcome: &str| {
+        metrics
+            .batch_integrity_checks
+            .with_label_values(&[outcome])
+            .get()
+    };
+    assert_eq!(checks("match"), 4);
+    assert_eq!(checks("mismatch"), 1);
+    assert_eq!(checks("quarantined"), 1);
+
+    // The corrupted batch moved to the quarantine store, the others were left in place.
+    assert_eq!(store.get(&corrupted_key).unwrap(), None);
+    assert_eq!(quarantine.get(&corrupted_key).unwrap(), Some(corrupted));
+    for key in good_keys {
+        assert!(store.get(&key).unwrap().is_some());
+    }
+}
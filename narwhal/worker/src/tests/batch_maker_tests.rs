@@ -3,11 +3,16 @@
 // SPDX-License-Identifier: Apache-2.0
 use super::*;
 
-use crate::NUM_SHUTDOWN_RECEIVERS;
+use crate::{
+    handlers::WorkerReceiverHandler, PartitionedBatchStore, TrivialTransactionValidator,
+    NUM_SHUTDOWN_RECEIVERS,
+};
+use config::AuthorityIdentifier;
 use prometheus::Registry;
-use test_utils::{create_batch_store, transaction};
+use test_utils::{create_batch_store, create_partitioned_batch_store, transaction};
 use types::MockWorkerToPrimary;
 use types::PreSubscribedBroadcastSender;
+use types::{RequestBatchRequest, WorkerToWorker};
 
 fn create_network_client() -> NetworkClient {
     NetworkClient::new_with_empty_id()
@@ -118,3 +123,78 @@ async fn batch_timeout() {
     // Ensure the batch is stored
     assert!(store.get(&batch.digest()).unwrap().is_some());
 }
+
+#[tokio::test]
+async fn sealed_batch_served_from_partitioned_store() {
+    let client = create_network_client();
+    let mut tx_shutdown = PreSubscribedBroadcastSender::new(NUM_SHUTDOWN_RECEIVERS);
+    let (tx_batch_maker, rx_batch_maker) = test_utils::test_channel!(1);
+    let (tx_quorum_waiter, mut rx_quorum_waiter) = test_utils::test_channel!(1);
+    let node_metrics = Arc::new(WorkerMetrics::new(&Registry::new()));
+
+    // Mock the primary client to always succeed.
+    let mut mock_server = MockWorkerToPrimary::new();
+    mock_server
+        .expect_report_our_batch()
+        .returning(|_| Ok(anemo::Response::new(())));
+    client.set_worker_to_primary_local_handler(Arc::new(mock_server));
+
+    // Two authorities share a single store.
+    let batches = create_partitioned_batch_store();
+    let authority_a = AuthorityIdentifier(0);
+    let authority_b = AuthorityIdentifier(1);
+
+    // Authority A seals a batch.
+    let id = 0;
+    let _batch_maker_handle = BatchMaker::spawn(
+        id,
+        /* max_batch_size */ 200,
+        /* max_batch_delay */
+        Duration::from_millis(1_000_000), // Ensure the timer is not triggered.
+        tx_shutdown.subscribe(),
+        rx_batch_maker,
+        tx_quorum_waiter,
+        node_metrics.clone(),
+        client.clone(),
+        PartitionedBatchStore::new(batches.clone(), 0, authority_a),
+    );
+    let tx = transaction();
+    let (s0, r0) = tokio::sync::oneshot::channel();
+    let (s1, r1) = tokio::sync::oneshot::channel();
+    tx_batch_maker.send((tx.clone(), s0)).await.unwrap();
+    tx_batch_maker.send((tx.clone(), s1)).await.unwrap();
+    let (batch, resp) = rx_quorum_waiter.recv().await.unwrap();
+    assert!(resp.send(()).is_ok());
+    assert!(r0.await.is_ok());
+    assert!(r1.await.is_ok());
+
+    // The handler of authority A serves the batch the batch maker stored, the handler of
+    // authority B does not see it.
+    let handler = |authority_id| {
+        WorkerReceiverHandler::new(
+            authority_id,
+            id,
+            client.clone(),
+            PartitionedBatchStore::new(batches.clone(), 0, authority_id),
+            TrivialTransactionValidator,
+            node_metrics.clone(),
+        )
+    };
+    let request = || {
+        anemo::Request::new(RequestBatchRequest {
+            batch: batch.digest(),
+        })
+    };
+    let response = handler(authority_a)
+        .request_batch(request())
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batch.unwrap().digest(), batch.digest());
+    let response = handler(authority_b)
+        .request_batch(request())
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batch, None);
+}
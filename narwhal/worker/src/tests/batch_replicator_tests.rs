@@ -0,0 +1,59 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use prometheus::Registry;
+use test_utils::{batch, test_network, CommitteeFixture, WorkerToWorkerMockServer};
+
+use super::*;
+
+#[tokio::test]
+async fn pushes_to_replication_factor_peers() {
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let myself = fixture.authorities().next().unwrap().worker(0);
+    let network = test_network(myself.keypair(), &myself.info().worker_address);
+
+    // Three candidate peers, of which two get the batch.
+    let mut listeners = Vec::new();
+    let mut peers = Vec::new();
+    for worker in fixture.authorities().skip(1).map(|a| a.worker(0)) {
+        let listener =
+            WorkerToWorkerMockServer::spawn(worker.keypair(), worker.info().worker_address.clone());
+        listeners.push(listener);
+        let address = worker.info().worker_address.to_anemo_address().unwrap();
+        peers.push(network.connect(address).await.unwrap());
+    }
+    assert_eq!(peers.len(), 3);
+
+    let metrics = Arc::new(WorkerMetrics::new(&Registry::new()));
+    let replicator = BatchReplicator::spawn(
+        network.clone(),
+        peers,
+        2,
+        BatchReplicator::DEFAULT_QUEUE_CAPACITY,
+        BatchReplicator::DEFAULT_TIMEOUT,
+        metrics.clone(),
+    );
+    let batch = batch();
+    replicator.replicate(batch.clone(), None);
+
+    let mut received = 0;
+    for (receiver, _network) in listeners.iter_mut() {
+        if let Ok(Some(message)) =
+            tokio::time::timeout(Duration::from_secs(1), receiver.recv()).await
+        {
+            assert_eq!(message.batch, batch);
+            received += 1;
+        }
+    }
+    assert_eq!(received, 2);
+    let successes = metrics
+        .batch_replication_pushes
+        .with_label_values(&["success"]);
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while successes.get() < 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
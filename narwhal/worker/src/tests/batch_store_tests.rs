@@ -0,0 +1,115 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use fastcrypto::hash::Hash;
+
+use super::*;
+
+#[tokio::test]
+async fn memory_store_matches_rocksdb() {
+    let rocksdb_store = test_utils::create_batch_store();
+    let memory_store = MemoryBatchStore::default();
+
+    let batches: Vec<_> = (0..10u8).map(|i| Batch::new(vec![vec![i; 10]])).collect();
+    let digests: Vec<_> = batches.iter().map(|batch| batch.digest()).collect();
+    for (digest, batch) in digests.iter().zip(batches.iter()) {
+        rocksdb_store.insert(digest, batch).unwrap();
+        memory_store.insert(digest, batch).unwrap();
+    }
+    rocksdb_store.multi_remove(&digests[..2]).unwrap();
+    memory_store.multi_remove(&digests[..2]).unwrap();
+    rocksdb_store.remove(&digests[2]).unwrap();
+    memory_store.remove(&digests[2]).unwrap();
+
+    // Point reads agree.
+    assert_eq!(
+        rocksdb_store.multi_get(&digests).unwrap(),
+        memory_store.multi_get(&digests).unwrap()
+    );
+    for digest in &digests {
+        assert_eq!(
+            rocksdb_store.contains_key(digest).unwrap(),
+            memory_store.contains_key(digest).unwrap()
+        );
+    }
+
+    // Both backends scan in the same key order.
+    let mut cursor = None;
+    loop {
+        let expected = rocksdb_store.entries_after(cursor, 3).unwrap();
+        let entries = memory_store.entries_after(cursor, 3).unwrap();
+        assert_eq!(entries, expected);
+        match entries.last() {
+            Some((key, _)) => cursor = Some(*key),
+            None => break,
+        }
+    }
+}
+
+#[tokio::test]
+async fn partitions_only_hold_their_authority_batches() {
+    let batches = test_utils::create_partitioned_batch_store();
+    let store_a = PartitionedBatchStore::new(batches.clone(), 1, AuthorityIdentifier(0));
+    let store_b = PartitionedBatchStore::new(batches, 1, AuthorityIdentifier(1));
+
+    let mut entries_a: Vec<_> = (0..4u8)
+        .map(|i| Batch::new(vec![vec![i; 10]]))
+        .map(|batch| (batch.digest(), batch))
+        .collect();
+    let entries_b: Vec<_> = (4..8u8)
+        .map(|i| Batch::new(vec![vec![i; 10]]))
+        .map(|batch| (batch.digest(), batch))
+        .collect();
+    store_a.multi_insert(&entries_a).unwrap();
+    store_b.multi_insert(&entries_b).unwrap();
+
+    // Each partition only serves and scans its own batches, keyed by their digest.
+    for (digest, _) in &entries_b {
+        assert!(!store_a.contains_key(digest).unwrap());
+        assert!(store_b.contains_key(digest).unwrap());
+    }
+    entries_a.sort_by_key(|(digest, _)| *digest);
+    assert_eq!(store_a.entries_after(None, 100).unwrap(), entries_a);
+    assert_eq!(
+        store_a.entries_after(Some(entries_a[1].0), 100).unwrap(),
+        entries_a[2..]
+    );
+
+    // Removing a whole partition leaves the others in place.
+    store_a.remove_epoch(1).unwrap();
+    assert!(store_a.entries_after(None, 100).unwrap().is_empty());
+    assert_eq!(
+        store_b.entries_after(None, 100).unwrap().len(),
+        entries_b.len()
+    );
+}
+
+#[tokio::test]
+async fn remove_epoch_only_removes_that_epoch() {
+    let batches = test_utils::create_partitioned_batch_store();
+    let authorities = [AuthorityIdentifier(0), AuthorityIdentifier(u16::MAX)];
+    let partition =
+        |epoch, authority| PartitionedBatchStore::new(batches.clone(), epoch, authority);
+    let entries: Vec<_> = (0..10u8)
+        .map(|i| Batch::new(vec![vec![i; 10]]))
+        .map(|batch| (batch.digest(), batch))
+        .collect();
+    for epoch in [1, 2, 3] {
+        for authority in authorities {
+            partition(epoch, authority).multi_insert(&entries).unwrap();
+        }
+    }
+
+    // A partition removes the batches of its authority in the epoch, and only those.
+    partition(3, authorities[0]).remove_epoch(2).unwrap();
+
+    for (digest, _) in &entries {
+        for authority in authorities {
+            assert!(partition(1, authority).contains_key(digest).unwrap());
+            assert!(partition(3, authority).contains_key(digest).unwrap());
+        }
+        assert!(!partition(2, authorities[0]).contains_key(digest).unwrap());
+        // The batches of the other authorities sharing the map survive.
+        assert!(partition(2, authorities[1]).contains_key(digest).unwrap());
+    }
+}
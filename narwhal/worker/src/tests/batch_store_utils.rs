@@ -0,0 +1,202 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+use store::TypedStoreError;
+use types::{Batch, BatchDigest, BatchSummary};
+
+use crate::{batch_store::StoreResult, BatchStore, MemoryBatchStore};
+
+/// A `MemoryBatchStore` misbehaving as configured by the test: slow or dropped writes, slow,
+/// failing or hanging reads. It records the calls made to it, and is shared by its clones.
+#[derive(Clone, Default)]
+pub struct TestBatchStore {
+    inner: MemoryBatchStore,
+    read_latency: Duration,
+    write_latency: Duration,
+    drop_writes: bool,
+    // If set, summaries are kept aside and served without reading the batches.
+    summaries: Option<Arc<Mutex<HashMap<BatchDigest, BatchSummary>>>>,
+    properties: Arc<Mutex<HashMap<String, u64>>>,
+    writing: Arc<AtomicUsize>,
+    /// The number of `get`, `multi_get` and `multi_remove` calls left to fail.
+    pub get_failures: Arc<AtomicUsize>,
+    pub multi_get_failures: Arc<AtomicUsize>,
+    pub multi_remove_failures: Arc<AtomicUsize>,
+    /// Reads hang while the gate is write-locked.
+    pub gate: Arc<RwLock<()>>,
+    /// The number of keys read by `multi_get`.
+    pub read_keys: Arc<AtomicUsize>,
+    /// The number of write calls, counting a `multi_insert` as a single write.
+    pub writes: Arc<AtomicUsize>,
+    /// The most write calls in progress at once.
+    pub max_writing: Arc<AtomicUsize>,
+    /// The number of keys of each `multi_remove` call.
+    pub multi_remove_sizes: Arc<Mutex<Vec<usize>>>,
+}
+
+impl TestBatchStore {
+    /// Batch reads, i.e. `get` and `multi_get`, take at least `latency`.
+    pub fn with_read_latency(mut self, latency: Duration) -> Self {
+        self.read_latency = latency;
+        self
+    }
+
+    /// Writes take at least `latency`.
+    pub fn with_write_latency(mut self, latency: Duration) -> Self {
+        self.write_latency = latency;
+        self
+    }
+
+    /// Writes succeed without storing anything.
+    pub fn dropping_writes(mut self) -> Self {
+        self.drop_writes = true;
+        self
+    }
+
+    /// Serves `multi_get_summaries` without reading the batches.
+    pub fn keeping_summaries(mut self) -> Self {
+        self.summaries = Some(Arc::default());
+        self
+    }
+
+    /// Sets the integer property reported under `name`, e.g. to fake RocksDB compactions.
+    pub fn set_property(&self, name: &str, value: u64) {
+        self.properties
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), value);
+    }
+
+    fn read<T>(&self, read: impl FnOnce(&MemoryBatchStore) -> StoreResult<T>) -> StoreResult<T> {
+        let _open = self.gate.read().unwrap();
+        read(&self.inner)
+    }
+
+    fn read_batches<T>(
+        &self,
+        failures: &AtomicUsize,
+        read: impl FnOnce(&MemoryBatchStore) -> StoreResult<T>,
+    ) -> StoreResult<T> {
+        self.read(|inner| {
+            inject_failure(failures)?;
+            std::thread::sleep(self.read_latency);
+            read(inner)
+        })
+    }
+
+    fn write(
+        &self,
+        entries: &[(BatchDigest, Batch)],
+        write: impl FnOnce(&MemoryBatchStore) -> StoreResult<()>,
+    ) -> StoreResult<()> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        let writing = self.writing.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_writing.fetch_max(writing, Ordering::SeqCst);
+        std::thread::sleep(self.write_latency);
+        let result = if self.drop_writes {
+            Ok(())
+        } else {
+            if let Some(summaries) = &self.summaries {
+                summaries.lock().unwrap().extend(
+                    entries
+                        .iter()
+                        .map(|(key, batch)| (*key, BatchSummary::new(batch))),
+                );
+            }
+            write(&self.inner)
+        };
+        self.writing.fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+
+    fn forget_summaries(&self, keys: &[BatchDigest]) {
+        if let Some(summaries) = &self.summaries {
+            let mut summaries = summaries.lock().unwrap();
+            for key in keys {
+                summaries.remove(key);
+            }
+        }
+    }
+}
+
+fn inject_failure(failures: &AtomicUsize) -> StoreResult<()> {
+    if failures
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok()
+    {
+        return Err(TypedStoreError::RocksDBError(
+            "injected failure".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+impl BatchStore for TestBatchStore {
+    fn get(&self, key: &BatchDigest) -> StoreResult<Option<Batch>> {
+        self.read_batches(&self.get_failures, |inner| inner.get(key))
+    }
+
+    fn multi_get(&self, keys: &[BatchDigest]) -> StoreResult<Vec<Option<Batch>>> {
+        self.read_batches(&self.multi_get_failures, |inner| {
+            self.read_keys.fetch_add(keys.len(), Ordering::SeqCst);
+            inner.multi_get(keys)
+        })
+    }
+
+    fn multi_get_summaries(&self, keys: &[BatchDigest]) -> StoreResult<Vec<Option<BatchSummary>>> {
+        let Some(summaries) = &self.summaries else {
+            return Ok(self
+                .multi_get(keys)?
+                .iter()
+                .map(|batch| batch.as_ref().map(BatchSummary::new))
+                .collect());
+        };
+        let summaries = summaries.lock().unwrap();
+        Ok(keys.iter().map(|key| summaries.get(key).copied()).collect())
+    }
+
+    fn insert(&self, key: &BatchDigest, batch: &Batch) -> StoreResult<()> {
+        self.write(&[(*key, batch.clone())], |inner| inner.insert(key, batch))
+    }
+
+    fn multi_insert(&self, entries: &[(BatchDigest, Batch)]) -> StoreResult<()> {
+        self.write(entries, |inner| inner.multi_insert(entries))
+    }
+
+    fn remove(&self, key: &BatchDigest) -> StoreResult<()> {
+        self.forget_summaries(&[*key]);
+        self.inner.remove(key)
+    }
+
+    fn multi_remove(&self, keys: &[BatchDigest]) -> StoreResult<()> {
+        inject_failure(&self.multi_remove_failures)?;
+        self.multi_remove_sizes.lock().unwrap().push(keys.len());
+        self.forget_summaries(keys);
+        self.inner.multi_remove(keys)
+    }
+
+    fn contains_key(&self, key: &BatchDigest) -> StoreResult<bool> {
+        self.read(|inner| inner.contains_key(key))
+    }
+
+    fn entries_after(
+        &self,
+        cursor: Option<BatchDigest>,
+        limit: usize,
+    ) -> StoreResult<Vec<(BatchDigest, Batch)>> {
+        self.read(|inner| inner.entries_after(cursor, limit))
+    }
+
+    fn int_property(&self, name: &str) -> StoreResult<Option<u64>> {
+        Ok(self.properties.lock().unwrap().get(name).copied())
+    }
+}
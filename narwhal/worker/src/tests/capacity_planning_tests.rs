@@ -0,0 +1,40 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::MemoryBatchStore;
+
+#[test]
+fn project_time_to_full() {
+    // The in-memory store has no properties, so only the synthetic samples count.
+    let planner = CapacityPlanner::new(MemoryBatchStore::default(), DiskBudget(10_000), 3);
+    let start = Instant::now();
+    let at = |secs| start + Duration::from_secs(secs);
+
+    let projection = planner.projection().unwrap();
+    assert_eq!(projection.ingest_bytes_per_sec, None);
+    assert_eq!(projection.available_bytes, Some(10_000));
+    assert_eq!(projection.time_to_full, None);
+
+    // 1000 bytes over 10 s: 100 bytes/s fill the 10_000 available bytes in 100 s.
+    planner.record(at(0), 0);
+    planner.record(at(10), 1_000);
+    let projection = planner.projection().unwrap();
+    assert_eq!(projection.ingest_bytes_per_sec, Some(100.0));
+    assert_eq!(projection.time_to_full, Some(Duration::from_secs(100)));
+
+    // The oldest sample falls out of the window: 4000 bytes over 10 s.
+    planner.record(at(15), 3_000);
+    planner.record(at(20), 5_000);
+    let projection = planner.projection().unwrap();
+    assert_eq!(projection.ingest_bytes_per_sec, Some(400.0));
+    assert_eq!(projection.time_to_full, Some(Duration::from_secs(25)));
+
+    // A store that stopped growing is not projected to fill.
+    planner.record(at(30), 5_000);
+    planner.record(at(40), 5_000);
+    planner.record(at(50), 5_000);
+    let projection = planner.projection().unwrap();
+    assert_eq!(projection.ingest_bytes_per_sec, Some(0.0));
+    assert_eq!(projection.time_to_full, None);
+}
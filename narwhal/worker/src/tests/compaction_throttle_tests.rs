@@ -0,0 +1,53 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::batch_store_utils::TestBatchStore;
+
+#[tokio::test]
+async fn throttles_while_compacting() {
+    let store = TestBatchStore::default();
+    let throttle = CompactionThrottle::new(CompactionThrottleConfig {
+        running_compactions: 1,
+        concurrency: 3,
+        compaction_concurrency: 1,
+        ..Default::default()
+    });
+    let wait = Duration::from_millis(100);
+
+    // Not compacting: up to `concurrency` calls at once.
+    throttle.sample(&store);
+    assert!(!throttle.is_compacting());
+    let permits = vec![throttle.acquire().await, throttle.acquire().await];
+    drop(permits);
+
+    // Compacting: a single call at once.
+    store.set_property("rocksdb.num-running-compactions", 1);
+    throttle.sample(&store);
+    assert!(throttle.is_compacting());
+    let permit = throttle.acquire().await;
+    assert!(tokio::time::timeout(wait, throttle.acquire())
+        .await
+        .is_err());
+    drop(permit);
+    let permit = tokio::time::timeout(wait, throttle.acquire())
+        .await
+        .unwrap();
+
+    // Compaction settled: concurrency recovers.
+    store.set_property("rocksdb.num-running-compactions", 0);
+    throttle.sample(&store);
+    assert!(!throttle.is_compacting());
+    let _permits = vec![
+        permit,
+        tokio::time::timeout(wait, throttle.acquire())
+            .await
+            .unwrap(),
+        tokio::time::timeout(wait, throttle.acquire())
+            .await
+            .unwrap(),
+    ];
+    assert!(tokio::time::timeout(wait, throttle.acquire())
+        .await
+        .is_err());
+}
@@ -2,30 +2,109 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::vec;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    vec,
+};
 
 use fastcrypto::hash::Hash;
-use test_utils::CommitteeFixture;
-use types::{MockWorkerToWorker, WorkerToWorkerServer};
+use prometheus::Registry;
+use test_utils::{AuthorityFixture, CommitteeFixture, WorkerFixture};
+use types::{
+    transaction_digest, BatchSlice, ByteRange, Certificate, Header, MockWorkerToPrimary,
+    MockWorkerToWorker, WorkerOurBatchMessage, WorkerToPrimary, WorkerToWorkerServer,
+};
 
 use super::*;
-use crate::TrivialTransactionValidator;
+use crate::{
+    batch_store_utils::TestBatchStore, method_permits::OverLimitPolicy, metrics::WorkerMetrics,
+    BatchCacheConfig, BatchInsertTimes, CachedBatchStore, InFlightSyncs, MemoryBatchStore,
+    PartitionedBatchStore, PeerRole, StoreMigration, StoreVersion, StoreWritePermits,
+    TrivialTransactionValidator, VersionedBatchStore,
+};
+
+/// A committee whose first authority runs the `PrimaryReceiverHandler` under test, with the
+/// network its worker fetches batches over.
+struct SyncFixture {
+    fixture: CommitteeFixture,
+    network: anemo::Network,
+}
+
+impl SyncFixture {
+    fn new() -> Self {
+        Self {
+            fixture: CommitteeFixture::builder().randomize_ports(true).build(),
+            network: test_utils::random_network(),
+        }
+    }
+
+    /// The authority running the handler under test.
+    fn authority(&self) -> &AuthorityFixture {
+        self.fixture.authorities().next().unwrap()
+    }
+
+    /// An authority the handler synchronizes batches from.
+    fn target(&self) -> &AuthorityFixture {
+        self.fixture.authorities().nth(1).unwrap()
+    }
+
+    /// Serves `server` as `worker`, connected with the network of the handler. The worker
+    /// serves as long as the returned network is kept alive.
+    async fn serve(&self, worker: &WorkerFixture, server: MockWorkerToWorker) -> anemo::Network {
+        let routes = anemo::Router::new().add_rpc_service(WorkerToWorkerServer::new(server));
+        let worker_network = worker.new_network(routes);
+        self.network
+            .connect_with_peer_id(
+                worker.info().worker_address.to_anemo_address().unwrap(),
+                anemo::PeerId(worker.info().name.0.to_bytes()),
+            )
+            .await
+            .unwrap();
+        worker_network
+    }
+
+    /// Starts building the handler of worker `id` of the authority, serving every method.
+    fn handler<V, S: BatchStore>(
+        &self,
+        id: WorkerId,
+        store: S,
+        validator: V,
+    ) -> PrimaryReceiverHandlerBuilder<V, S> {
+        let batch_fetcher = BatchFetcher::new(
+            self.authority().worker(id).info().name.clone(),
+            self.network.clone(),
+            store.clone(),
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        );
+        PrimaryReceiverHandler::builder(
+            self.authority().id(),
+            id,
+            self.fixture.committee(),
+            self.fixture.worker_cache(),
+            store,
+            validator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+        .network(self.network.clone())
+        .batch_fetcher(batch_fetcher)
+    }
+}
 
 #[tokio::test]
 async fn synchronize() {
     telemetry_subscribers::init_for_testing();
 
-    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
-    let committee = fixture.committee();
-    let worker_cache = fixture.worker_cache();
-    let authority_id = fixture.authorities().next().unwrap().id();
+    let sync = SyncFixture::new();
     let id = 0;
 
     // Create a new test store.
-    let store = test_utils::create_batch_store();
+    let store = MemoryBatchStore::default();
 
     // Create network with mock behavior to respond to RequestBatches request.
-    let target_primary = fixture.authorities().nth(1).unwrap();
+    let target_primary = sync.target();
     let batch = test_utils::batch();
     let digest = batch.digest();
     let message = WorkerSynchronizeMessage {
@@ -45,34 +124,12 @@ async fn synchronize() {
                 is_size_limit_reached: false,
             }))
         });
-    let routes = anemo::Router::new().add_rpc_service(WorkerToWorkerServer::new(mock_server));
-    let target_worker = target_primary.worker(id);
-    let _recv_network = target_worker.new_network(routes);
-    let send_network = test_utils::random_network();
-    send_network
-        .connect_with_peer_id(
-            target_worker
-                .info()
-                .worker_address
-                .to_anemo_address()
-                .unwrap(),
-            anemo::PeerId(target_worker.info().name.0.to_bytes()),
-        )
-        .await
-        .unwrap();
+    let _recv_network = sync.serve(target_primary.worker(id), mock_server).await;
 
-    let handler = PrimaryReceiverHandler {
-        authority_id,
-        id,
-        committee,
-        worker_cache,
-        store: store.clone(),
-        request_batch_timeout: Duration::from_secs(999),
-        request_batch_retry_nodes: 3, // Not used in this test.
-        network: Some(send_network),
-        batch_fetcher: None,
-        validator: TrivialTransactionValidator,
-    };
+    let handler = sync
+        .handler(id, store.clone(), TrivialTransactionValidator)
+        .build()
+        .unwrap();
 
     // Verify the batch is not in store
     assert!(store.get(&digest).unwrap().is_none());
@@ -86,90 +143,5003 @@ async fn synchronize() {
 }
 
 #[tokio::test]
-async fn synchronize_when_batch_exists() {
+async fn synchronize_skips_writes_of_batches_stored_concurrently() {
     telemetry_subscribers::init_for_testing();
 
-    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
-    let committee = fixture.committee();
-    let worker_cache = fixture.worker_cache();
-    let authority_id = fixture.authorities().next().unwrap().id();
+    let sync = SyncFixture::new();
     let id = 0;
 
-    // Create a new test store.
-    let store = test_utils::create_batch_store();
-
-    // Create network without mock behavior since it will not be needed.
-    let send_network = test_utils::random_network();
+    // Count the writes to the store by its version.
+    let store = VersionedBatchStore::new(MemoryBatchStore::default(), StoreVersion::in_memory());
 
-    let handler = PrimaryReceiverHandler {
-        authority_id,
-        id,
-        committee,
-        worker_cache,
-        store: store.clone(),
-        request_batch_timeout: Duration::from_secs(999),
-        request_batch_retry_nodes: 3, // Not used in this test.
-        network: Some(send_network),
-        batch_fetcher: None,
-        validator: TrivialTransactionValidator,
+    // Create network with mock behavior to respond to RequestBatches request.
+    let target_primary = sync.target();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    let message = WorkerSynchronizeMessage {
+        digests: vec![digest],
+        target: target_primary.id(),
+        is_certified: false,
     };
 
-    // Store the batch.
-    let batch = test_utils::batch();
-    let batch_id = batch.digest();
-    let missing = vec![batch_id];
-    store.insert(&batch_id, &batch).unwrap();
+    let mut mock_server = MockWorkerToWorker::new();
+    let mock_batch_response = batch.clone();
+    mock_server
+        .expect_request_batches()
+        .withf(move |request| request.body().batch_digests == vec![digest])
+        .return_once({
+            // A concurrent call stores the batch while it is being fetched.
+            let store = store.clone();
+            move |_| {
+                store.insert(&digest, &mock_batch_response).unwrap();
+                Ok(anemo::Response::new(RequestBatchesResponse {
+                    batches: vec![mock_batch_response],
+                    is_size_limit_reached: false,
+                }))
+            }
+        });
+    let _recv_network = sync.serve(target_primary.worker(id), mock_server).await;
 
-    // Send a sync request.
-    let target_primary = fixture.authorities().nth(1).unwrap();
+    let handler = sync
+        .handler(id, store.clone(), TrivialTransactionValidator)
+        .dedup_synchronize_writes(true)
+        .build()
+        .unwrap();
+
+    // The batch is found stored before being written.
+    let request = anemo::Request::new(message);
+    handler.synchronize(request).await.unwrap();
+    assert!(store.get(&digest).unwrap().is_some());
+    assert_eq!(store.version().current(), 1);
+    assert_eq!(handler.metrics.synchronize_dedup_writes.get(), 1);
+}
+
+#[tokio::test]
+async fn synchronize_reconnects_missing_peer() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    let store = MemoryBatchStore::default();
+
+    let target_primary = sync.target();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
     let message = WorkerSynchronizeMessage {
-        digests: missing.clone(),
+        digests: vec![digest],
         target: target_primary.id(),
         is_certified: false,
     };
-    // The sync request should succeed.
+
+    let mut mock_server = MockWorkerToWorker::new();
+    mock_server
+        .expect_request_batches()
+        .withf(move |request| request.body().batch_digests == vec![digest])
+        .return_once(move |_| {
+            Ok(anemo::Response::new(RequestBatchesResponse {
+                batches: vec![batch],
+                is_size_limit_reached: false,
+            }))
+        });
+    // Not connected with the target worker.
+    let routes = anemo::Router::new().add_rpc_service(WorkerToWorkerServer::new(mock_server));
+    let _recv_network = target_primary.worker(id).new_network(routes);
+
+    let mut handler = sync
+        .handler(id, store.clone(), TrivialTransactionValidator)
+        .build()
+        .unwrap();
+
+    // Without reconnecting, the missing connection fails the sync as retriable.
+    let status = handler
+        .synchronize(anemo::Request::new(message.clone()))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::ServiceUnavailable);
+    assert!(store.get(&digest).unwrap().is_none());
+
+    // With reconnecting, the sync goes through.
+    handler.reconnect_missing_peers = true;
     handler
         .synchronize(anemo::Request::new(message))
         .await
         .unwrap();
+    assert!(store.get(&digest).unwrap().is_some());
 }
 
 #[tokio::test]
-async fn delete_batches() {
+async fn synchronize_without_reachable_target() {
+    telemetry_subscribers::init_for_testing();
+
+    // Not connected with any worker of the target, and not reconnecting.
+    let sync = SyncFixture::new();
+    let store = MemoryBatchStore::default();
+
+    let target_primary = sync.target();
+    let digest = test_utils::batch().digest();
+    let message = WorkerSynchronizeMessage {
+        digests: vec![digest],
+        target: target_primary.id(),
+        is_certified: false,
+    };
+
+    let handler = sync
+        .handler(0, store.clone(), TrivialTransactionValidator)
+        .build()
+        .unwrap();
+
+    let status = handler
+        .synchronize(anemo::Request::new(message))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::ServiceUnavailable);
+    assert_eq!(
+        status.message(),
+        Some(
+            WorkerHandlerError::NoReachableSyncTarget(target_primary.id())
+                .to_string()
+                .as_str()
+        )
+    );
+    assert_eq!(handler.metrics.synchronize_no_reachable_target.get(), 1);
+    assert!(store.get(&digest).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn synchronize_requests_batches_in_the_shape_the_target_decodes() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    // Enough batches to be requested in compact form from workers serving it.
+    let batches: Vec<_> = (0..RequestBatchesV2Request::COMPACT_DIGESTS_THRESHOLD as u32)
+        .map(|i| Batch::new(vec![i.to_le_bytes().to_vec()]))
+        .collect();
+    let digests: Vec<_> = batches.iter().map(|batch| batch.digest()).collect();
+
+    // The worker of the first target serves request_batches_v2, the one of the second
+    // target predates the capabilities RPC.
+    let v2_target = sync.target();
+    let mut v2_server = MockWorkerToWorker::new();
+    v2_server.expect_capabilities().returning(|_| {
+        Ok(anemo::Response::new(WorkerCapabilitiesResponse {
+            protocol_version: 2,
+            features: Default::default(),
+        }))
+    });
+    v2_server.expect_request_batches().never();
+    let v2_batches = batches.clone();
+    v2_server
+        .expect_request_batches_v2()
+        .withf(|request| request.body().compact_batch_digests.is_some())
+        .return_once(move |_| {
+            Ok(anemo::Response::new(RequestBatchesV2Response {
+                batches: v2_batches,
+                is_size_limit_reached: false,
+                batch_ages_ms: None,
+                deferred_digests: Vec::new(),
+                batches_by_digest: None,
+            }))
+        });
+    let legacy_target = sync.fixture.authorities().nth(2).unwrap();
+    let mut legacy_server = MockWorkerToWorker::new();
+    legacy_server.expect_capabilities().returning(|_| {
+        Err(anemo::rpc::Status::new_with_message(
+            StatusCode::NotFound,
+            "unknown route",
+        ))
+    });
+    let legacy_batches = batches.clone();
+    let expected_digests: HashSet<_> = digests.iter().copied().collect();
+    legacy_server
+        .expect_request_batches()
+        .withf(move |request| {
+            request
+                .body()
+                .batch_digests
+                .iter()
+                .copied()
+                .collect::<HashSet<_>>()
+                == expected_digests
+        })
+        .return_once(move |_| {
+            Ok(anemo::Response::new(RequestBatchesResponse {
+                batches: legacy_batches,
+                is_size_limit_reached: false,
+            }))
+        });
+
+    let mut recv_networks = Vec::new();
+    for (target, server) in [(v2_target, v2_server), (legacy_target, legacy_server)] {
+        recv_networks.push(sync.serve(target.worker(id), server).await);
+    }
+
+    for target in [v2_target, legacy_target] {
+        let store = MemoryBatchStore::default();
+        let handler = sync
+            .handler(id, store.clone(), TrivialTransactionValidator)
+            .build()
+            .unwrap();
+        let message = WorkerSynchronizeMessage {
+            digests: digests.clone(),
+            target: target.id(),
+            is_certified: false,
+        };
+        handler
+            .synchronize(anemo::Request::new(message))
+            .await
+            .unwrap();
+        for digest in &digests {
+            assert!(store.get(digest).unwrap().is_some());
+        }
+    }
+}
+
+#[tokio::test]
+async fn synchronize_with_stale_worker_cache_is_retriable() {
     telemetry_subscribers::init_for_testing();
 
     let fixture = CommitteeFixture::builder().randomize_ports(true).build();
     let committee = fixture.committee();
-    let worker_cache = fixture.worker_cache();
-    let authority_id = fixture.authorities().next().unwrap().id();
+    let authority = fixture.authorities().next().unwrap();
     let id = 0;
+    let target_primary = fixture.authorities().nth(1).unwrap();
 
-    // Create a new test store.
-    let store = test_utils::create_batch_store();
-    let batch = test_utils::batch();
-    let digest = batch.digest();
-    store.insert(&digest, &batch).unwrap();
+    // The worker cache lags behind the committee and does not know the workers of the target.
+    let mut worker_cache = fixture.worker_cache();
+    worker_cache
+        .workers
+        .remove(&target_primary.public_key())
+        .unwrap();
 
-    // Send a delete request.
-    let handler = PrimaryReceiverHandler {
-        authority_id,
+    let store = MemoryBatchStore::default();
+    let network = test_utils::random_network();
+    let batch_fetcher = BatchFetcher::new(
+        authority.worker(id).info().name.clone(),
+        network.clone(),
+        store.clone(),
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    );
+    let handler = PrimaryReceiverHandler::builder(
+        authority.id(),
         id,
         committee,
         worker_cache,
-        store: store.clone(),
-        request_batch_timeout: Duration::from_secs(999),
-        request_batch_retry_nodes: 3, // Not used in this test.
-        network: None,
-        batch_fetcher: None,
-        validator: TrivialTransactionValidator,
+        store.clone(),
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    )
+    .network(network)
+    .batch_fetcher(batch_fetcher)
+    .build()
+    .unwrap();
+
+    let digest = test_utils::batch().digest();
+    let message = WorkerSynchronizeMessage {
+        digests: vec![digest],
+        target: target_primary.id(),
+        is_certified: false,
     };
-    let message = WorkerDeleteBatchesMessage {
+    let status = handler
+        .synchronize(anemo::Request::new(message))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::ServiceUnavailable);
+    assert!(status
+        .message()
+        .unwrap()
+        .contains("retry after reconfiguration"));
+    assert!(store.get(&digest).unwrap().is_none());
+
+    // A target outside of the committee is not retriable.
+    let message = WorkerSynchronizeMessage {
         digests: vec![digest],
+        target: AuthorityIdentifier(u16::MAX),
+        is_certified: false,
+    };
+    let status = handler
+        .synchronize(anemo::Request::new(message))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::InternalServerError);
+}
+
+#[tokio::test]
+async fn synchronize_invalid_batch_policies() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    // The target worker sends an invalid batch ahead of a valid one.
+    let target_primary = sync.target();
+    let invalid_batch = Batch::new(vec![vec![]]);
+    let valid_batch = Batch::new(vec![vec![1]]);
+    let message = WorkerSynchronizeMessage {
+        digests: vec![invalid_batch.digest(), valid_batch.digest()],
+        target: target_primary.id(),
+        is_certified: false,
+    };
+    let mut mock_server = MockWorkerToWorker::new();
+    let response_batches = vec![invalid_batch.clone(), valid_batch.clone()];
+    mock_server
+        .expect_request_batches()
+        .times(2)
+        .returning(move |_| {
+            Ok(anemo::Response::new(RequestBatchesResponse {
+                batches: response_batches.clone(),
+                is_size_limit_reached: false,
+            }))
+        });
+    let _recv_network = sync.serve(target_primary.worker(id), mock_server).await;
+
+    let store = MemoryBatchStore::default();
+    let validator = SlowValidator {
+        delay: Duration::ZERO,
+    };
+    let mut handler = sync
+        .handler(id, store.clone(), validator)
+        .invalid_batch_policy(InvalidBatchPolicy::FailFast)
+        .build()
+        .unwrap();
+
+    // Failing fast discards the valid batch following the invalid one.
+    let status = handler
+        .synchronize(anemo::Request::new(message.clone()))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::BadRequest);
+    assert!(!store.contains_key(&valid_batch.digest()).unwrap());
+
+    // Skipping the invalid batch stores the valid one. The invalid one is then requested from
+    // the target's other workers, which are not connected.
+    handler.invalid_batch_policy = InvalidBatchPolicy::SkipInvalid;
+    let status = handler
+        .synchronize(anemo::Request::new(message))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::ServiceUnavailable);
+    assert!(store.contains_key(&valid_batch.digest()).unwrap());
+    assert!(!store.contains_key(&invalid_batch.digest()).unwrap());
+    assert_eq!(handler.metrics.synchronize_invalid_batches.get(), 1);
+}
+
+#[tokio::test]
+async fn synchronize_verifies_attached_certificates() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let fixture = &sync.fixture;
+    let committee = fixture.committee();
+    let id = 0;
+
+    // The batch fails validation, so it is only stored if validation is skipped.
+    let target_primary = sync.target();
+    let batch = Batch::new(vec![vec![]]);
+    let header = Header::V1(
+        target_primary
+            .header_builder(&committee)
+            .with_payload_batch(batch.clone(), id, 0)
+            .build()
+            .unwrap(),
+    );
+    let certificate = fixture.certificate(&header);
+    // A certificate of another authority's header, and one lacking signatures.
+    let other_certificate = fixture.certificate(&fixture.header());
+    let unsigned_certificate = Certificate::new_unsigned(&committee, header, vec![]).unwrap();
+
+    let mut mock_server = MockWorkerToWorker::new();
+    let response_batch = batch.clone();
+    mock_server.expect_request_batches().returning(move |_| {
+        Ok(anemo::Response::new(RequestBatchesResponse {
+            batches: vec![response_batch.clone()],
+            is_size_limit_reached: false,
+        }))
+    });
+    let _recv_network = sync.serve(target_primary.worker(id), mock_server).await;
+
+    let store = MemoryBatchStore::default();
+    let validator = SlowValidator {
+        delay: Duration::ZERO,
+    };
+    let handler = sync
+        .handler(id, store.clone(), validator)
+        .certified_batch_verification(CertifiedBatchVerification::Certificate)
+        .invalid_batch_policy(InvalidBatchPolicy::FailFast)
+        .build()
+        .unwrap();
+    let message = |certificate: Option<Certificate>| WorkerSynchronizeV2Message {
+        digests: vec![batch.digest()],
+        target: target_primary.id(),
+        is_certified: true,
+        certificate,
+        target_worker_id: None,
     };
+
+    // Without a certificate proving that the batch is certified, it is validated.
+    for certificate in [None, Some(other_certificate), Some(unsigned_certificate)] {
+        let status = handler
+            .synchronize_v2(anemo::Request::new(message(certificate)))
+            .await
+            .unwrap_err();
+        assert_eq!(status.status(), StatusCode::BadRequest);
+        assert!(!store.contains_key(&batch.digest()).unwrap());
+    }
+
+    // With a valid certificate, validation is skipped.
     handler
-        .delete_batches(anemo::Request::new(message))
+        .synchronize_v2(anemo::Request::new(message(Some(certificate))))
         .await
         .unwrap();
+    assert!(store.contains_key(&batch.digest()).unwrap());
+}
 
-    assert!(store.get(&digest).unwrap().is_none());
+/// A validator taking 50ms to validate batches, recording the most batches validated at once.
+#[derive(Clone, Default)]
+struct ConcurrencyRecordingValidator {
+    validating: Arc<AtomicUsize>,
+    max_validating: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl TransactionValidator for ConcurrencyRecordingValidator {
+    type Error = eyre::Report;
+
+    fn validate(&self, _tx: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn validate_batch(&self, _batch: &Batch) -> Result<(), Self::Error> {
+        let validating = self.validating.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_validating.fetch_max(validating, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        self.validating.fetch_sub(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn synchronize_validates_batches_concurrently() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    let target_primary = sync.target();
+    let batches: Vec<_> = (0..6).map(|i| Batch::new(vec![vec![i]])).collect();
+    let message = WorkerSynchronizeMessage {
+        digests: batches.iter().map(|batch| batch.digest()).collect(),
+        target: target_primary.id(),
+        is_certified: false,
+    };
+    let mut mock_server = MockWorkerToWorker::new();
+    let response_batches = batches.clone();
+    mock_server.expect_request_batches().return_once(move |_| {
+        Ok(anemo::Response::new(RequestBatchesResponse {
+            batches: response_batches,
+            is_size_limit_reached: false,
+        }))
+    });
+    let _recv_network = sync.serve(target_primary.worker(id), mock_server).await;
+
+    let store = MemoryBatchStore::default();
+    let validator = ConcurrencyRecordingValidator::default();
+    let handler = sync
+        .handler(id, store.clone(), validator.clone())
+        .synchronize_validation_parallelism(4)
+        .invalid_batch_policy(InvalidBatchPolicy::FailFast)
+        .build()
+        .unwrap();
+
+    handler
+        .synchronize(anemo::Request::new(message))
+        .await
+        .unwrap();
+    assert_eq!(validator.max_validating.load(Ordering::SeqCst), 4);
+    for batch in batches {
+        assert_eq!(store.get(&batch.digest()).unwrap(), Some(batch));
+    }
+}
+
+#[tokio::test]
+async fn synchronize_retries_failed_store_reads() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    // The first read of the store fails.
+    let store = TestBatchStore::default();
+    store.get_failures.store(1, Ordering::SeqCst);
+
+    // Create network with mock behavior to respond to RequestBatches request.
+    let target_primary = sync.target();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    let mut mock_server = MockWorkerToWorker::new();
+    let mock_batch_response = batch.clone();
+    mock_server
+        .expect_request_batches()
+        .withf(move |request| request.body().batch_digests == vec![digest])
+        .return_once(move |_| {
+            Ok(anemo::Response::new(RequestBatchesResponse {
+                batches: vec![mock_batch_response],
+                is_size_limit_reached: false,
+            }))
+        });
+    let _recv_network = sync.serve(target_primary.worker(id), mock_server).await;
+
+    let handler = sync
+        .handler(id, store.clone(), TrivialTransactionValidator)
+        .synchronize_read_retries(2)
+        .build()
+        .unwrap();
+
+    // The failed read is retried instead of failing the sync, which fetches the batch.
+    let message = WorkerSynchronizeMessage {
+        digests: vec![digest],
+        target: target_primary.id(),
+        is_certified: false,
+    };
+    handler
+        .synchronize(anemo::Request::new(message))
+        .await
+        .unwrap();
+    assert_eq!(store.get_failures.load(Ordering::SeqCst), 0);
+    assert!(store.get(&digest).unwrap().is_some());
+}
+
+#[tokio::test]
+async fn synchronize_when_batch_exists() {
+    telemetry_subscribers::init_for_testing();
+
+    // Create network without mock behavior since it will not be needed.
+    let sync = SyncFixture::new();
+
+    // Create a new test store.
+    let store = MemoryBatchStore::default();
+
+    let handler = sync
+        .handler(0, store.clone(), TrivialTransactionValidator)
+        .build()
+        .unwrap();
+
+    // Store the batch.
+    let batch = test_utils::batch();
+    let batch_id = batch.digest();
+    let missing = vec![batch_id];
+    store.insert(&batch_id, &batch).unwrap();
+
+    // Send a sync request.
+    let message = WorkerSynchronizeMessage {
+        digests: missing.clone(),
+        target: sync.target().id(),
+        is_certified: false,
+    };
+    // The sync request should succeed.
+    handler
+        .synchronize(anemo::Request::new(message))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn delete_batches() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Create a new test store.
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    // Send a delete request.
+    let handler = PrimaryReceiverHandler::builder(
+        authority_id,
+        0,
+        fixture.committee(),
+        fixture.worker_cache(),
+        store.clone(),
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    )
+    .build_legacy_rpc()
+    .unwrap();
+    let message = WorkerDeleteBatchesMessage {
+        digests: vec![digest],
+    };
+    handler
+        .delete_batches(anemo::Request::new(message))
+        .await
+        .unwrap();
+
+    assert!(store.get(&digest).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn delete_batches_records_deleted_batch_ages() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let committee = fixture.committee();
+    let worker_cache = fixture.worker_cache();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Batches created 100 and 1000 seconds ago.
+    let store = MemoryBatchStore::default();
+    let now = now();
+    let batches: Vec<_> = [100_000u64, 1_000_000]
+        .into_iter()
+        .map(|age_ms| {
+            let mut batch = Batch::new(vec![age_ms.to_le_bytes().to_vec()]);
+            batch.metadata_mut().created_at = now - age_ms;
+            batch
+        })
+        .collect();
+    for batch in &batches {
+        store.insert(&batch.digest(), batch).unwrap();
+    }
+
+    let metrics = Arc::new(WorkerMetrics::new(&Registry::new()));
+    let handler = PrimaryReceiverHandler::builder(
+        authority_id,
+        0,
+        committee,
+        worker_cache,
+        store.clone(),
+        TrivialTransactionValidator,
+        metrics.clone(),
+    )
+    .record_deleted_batch_ages(true)
+    .build_legacy_rpc()
+    .unwrap();
+
+    // Deleting a batch that is not stored records nothing for it.
+    let mut digests: Vec<_> = batches.iter().map(|batch| batch.digest()).collect();
+    digests.push(test_utils::batch().digest());
+    handler
+        .delete_batches(anemo::Request::new(WorkerDeleteBatchesMessage { digests }))
+        .await
+        .unwrap();
+    assert!(batches
+        .iter()
+        .all(|batch| store.get(&batch.digest()).unwrap().is_none()));
+
+    let histogram = &metrics.deleted_batch_age;
+    assert_eq!(histogram.get_sample_count(), 2);
+    // Allow for the time elapsed since the batches were created.
+    let sum = histogram.get_sample_sum();
+    assert!((1_100.0..1_110.0).contains(&sum), "sum {sum}");
+}
+
+#[tokio::test]
+async fn synchronize_not_blocked_by_saturated_bulk_reads() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    // Create a new test store holding the batch.
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    // A single permit per pool, so holding the bulk permit saturates bulk reads.
+    let read_permits = StoreReadPermits::new(1, 1);
+    let worker_handler = WorkerReceiverHandler {
+        read_permits: read_permits.clone(),
+        ..WorkerReceiverHandler::new(
+            sync.authority().id(),
+            id,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let primary_handler = sync
+        .handler(id, store.clone(), TrivialTransactionValidator)
+        .read_permits(read_permits.clone())
+        .build()
+        .unwrap();
+    let bulk_permit = read_permits.acquire(ReadPriority::Bulk).await;
+
+    // Bulk reads queue behind the saturated pool.
+    let request = anemo::Request::new(RequestBatchesRequest {
+        batch_digests: vec![digest],
+    });
+    let mut bulk_read = worker_handler.request_batches(request);
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), &mut bulk_read)
+            .await
+            .is_err()
+    );
+
+    // Sync reads still proceed.
+    let message = WorkerSynchronizeMessage {
+        digests: vec![digest],
+        target: sync.target().id(),
+        is_certified: false,
+    };
+    tokio::time::timeout(
+        Duration::from_secs(5),
+        primary_handler.synchronize(anemo::Request::new(message)),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    // Releasing the bulk permit lets the queued read complete.
+    drop(bulk_permit);
+    let response = bulk_read.await.unwrap().into_body();
+    assert_eq!(response.batches, vec![batch]);
+}
+
+#[tokio::test]
+async fn fetch_batches_truncates_oversized_response() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+
+    // Store three batches of 100 bytes each, so only two fit under the cap.
+    let store = test_utils::create_batch_store();
+    let mut digests = HashSet::new();
+    for i in 0..3 {
+        let batch = Batch::new(vec![vec![i; 100]]);
+        digests.insert(batch.digest());
+        store.insert(&batch.digest(), &batch).unwrap();
+    }
+
+    let handler = sync
+        .handler(0, store.clone(), TrivialTransactionValidator)
+        .max_fetch_batches_response_size(250)
+        .build()
+        .unwrap();
+
+    let request = || {
+        anemo::Request::new(FetchBatchesRequest {
+            digests: digests.clone(),
+            known_workers: HashSet::new(),
+        })
+    };
+    let response = handler
+        .fetch_batches_v2(request())
+        .await
+        .unwrap()
+        .into_body();
+
+    assert!(response.is_size_limit_reached);
+    assert_eq!(response.batches.len(), 2);
+    assert_eq!(response.omitted_digests.len(), 1);
+    // Every requested digest is either returned or reported as omitted.
+    let accounted: HashSet<_> = response
+        .batches
+        .keys()
+        .chain(response.omitted_digests.iter())
+        .cloned()
+        .collect();
+    assert_eq!(accounted, digests);
+
+    // Legacy callers cannot request omitted batches again, so they get all of them.
+    let response = handler.fetch_batches(request()).await.unwrap().into_body();
+    assert_eq!(response.batches.len(), digests.len());
+}
+
+#[tokio::test]
+async fn partition_store_by_authority() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let mut authorities = fixture.authorities();
+    let authority_a = authorities.next().unwrap().id();
+    let authority_b = authorities.next().unwrap().id();
+    let id = 0;
+
+    // Mock the primary client to always succeed.
+    let client = NetworkClient::new_with_empty_id();
+    let mut mock_server = MockWorkerToPrimary::new();
+    mock_server
+        .expect_report_others_batch()
+        .returning(|_| Ok(anemo::Response::new(())));
+    client.set_worker_to_primary_local_handler(Arc::new(mock_server));
+
+    // Both authorities share a single store.
+    let batches = test_utils::create_partitioned_batch_store();
+    let handler = |authority_id| {
+        WorkerReceiverHandler::new(
+            authority_id,
+            id,
+            client.clone(),
+            PartitionedBatchStore::new(batches.clone(), 0, authority_id),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let handler_a = handler(authority_a);
+    let handler_b = handler(authority_b);
+
+    // Authority A stores a batch.
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    handler_a
+        .report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: batch.clone(),
+        }))
+        .await
+        .unwrap();
+
+    // Only authority A can read it back.
+    let request = || anemo::Request::new(RequestBatchRequest { batch: digest });
+    let response = handler_a.request_batch(request()).await.unwrap();
+    assert_eq!(response.into_body().batch, Some(batch));
+    let response = handler_b.request_batch(request()).await.unwrap();
+    assert_eq!(response.into_body().batch, None);
+}
+
+#[tokio::test]
+async fn synchronize_falls_back_to_other_workers_of_target() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    // Create a new test store.
+    let store = MemoryBatchStore::default();
+
+    let target_primary = sync.target();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    let message = WorkerSynchronizeMessage {
+        digests: vec![digest],
+        target: target_primary.id(),
+        is_certified: false,
+    };
+
+    // The target's worker with our id is lagging and does not have the batch, while its
+    // worker 1 does.
+    let mut lagging_server = MockWorkerToWorker::new();
+    lagging_server.expect_request_batches().returning(|_| {
+        Ok(anemo::Response::new(RequestBatchesResponse {
+            batches: vec![],
+            is_size_limit_reached: false,
+        }))
+    });
+    let mut holding_server = MockWorkerToWorker::new();
+    let mock_batch_response = batch.clone();
+    holding_server
+        .expect_request_batches()
+        .withf(move |request| request.body().batch_digests == vec![digest])
+        .return_once(move |_| {
+            Ok(anemo::Response::new(RequestBatchesResponse {
+                batches: vec![mock_batch_response],
+                is_size_limit_reached: false,
+            }))
+        });
+
+    let mut recv_networks = Vec::new();
+    for (worker_id, server) in [(0, lagging_server), (1, holding_server)] {
+        recv_networks.push(sync.serve(target_primary.worker(worker_id), server).await);
+    }
+
+    let handler = sync
+        .handler(id, store.clone(), TrivialTransactionValidator)
+        .build()
+        .unwrap();
+
+    // Send a sync request.
+    handler
+        .synchronize(anemo::Request::new(message))
+        .await
+        .unwrap();
+
+    // Verify the batch recovered from worker 1 is now stored.
+    assert!(store.get(&digest).unwrap().is_some())
+}
+
+#[tokio::test]
+async fn synchronize_attempt_budget_caps_attempts() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    let store = MemoryBatchStore::default();
+
+    let target_primary = sync.target();
+    let recovered_batch = Batch::new(vec![vec![1]]);
+    let missing_batch = Batch::new(vec![vec![2]]);
+    let message = WorkerSynchronizeMessage {
+        digests: vec![recovered_batch.digest(), missing_batch.digest()],
+        target: target_primary.id(),
+        is_certified: false,
+    };
+
+    // The target's worker with our id only has one of the batches, and its other workers
+    // keep failing.
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let mut partial_server = MockWorkerToWorker::new();
+    let partial_attempts = attempts.clone();
+    let partial_response = recovered_batch.clone();
+    partial_server.expect_request_batches().returning(move |_| {
+        partial_attempts.fetch_add(1, Ordering::SeqCst);
+        Ok(anemo::Response::new(RequestBatchesResponse {
+            batches: vec![partial_response.clone()],
+            is_size_limit_reached: false,
+        }))
+    });
+    let mut recv_networks = Vec::new();
+    let mut servers = vec![(0, partial_server)];
+    for worker_id in 1..=3 {
+        let mut failing_server = MockWorkerToWorker::new();
+        let failing_attempts = attempts.clone();
+        failing_server.expect_request_batches().returning(move |_| {
+            failing_attempts.fetch_add(1, Ordering::SeqCst);
+            Err(anemo::rpc::Status::internal("failing"))
+        });
+        servers.push((worker_id, failing_server));
+    }
+    for (worker_id, server) in servers {
+        recv_networks.push(sync.serve(target_primary.worker(worker_id), server).await);
+    }
+
+    let handler = sync
+        .handler(id, store.clone(), TrivialTransactionValidator)
+        .synchronize_attempt_budget(2)
+        .build()
+        .unwrap();
+
+    // The call gives up after two workers, out of the four it could try.
+    let status = handler
+        .synchronize(anemo::Request::new(message))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::ServiceUnavailable);
+    assert!(status
+        .message()
+        .unwrap()
+        .contains(&missing_batch.digest().to_string()));
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+    // The batch recovered before the budget ran out is kept.
+    assert_eq!(
+        store.get(&recovered_batch.digest()).unwrap(),
+        Some(recovered_batch)
+    );
+    assert_eq!(store.get(&missing_batch.digest()).unwrap(), None);
+}
+
+#[tokio::test]
+async fn synchronize_strict_mode_rejects_unrequested_certified_batch() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    // Create a new test store.
+    let store = MemoryBatchStore::default();
+
+    // The target responds with a batch that was never requested.
+    let target_primary = sync.target();
+    let digest = test_utils::batch().digest();
+    let unrequested_batch = Batch::new(vec![vec![42]]);
+    let message = WorkerSynchronizeMessage {
+        digests: vec![digest],
+        target: target_primary.id(),
+        is_certified: true,
+    };
+
+    let mut mock_server = MockWorkerToWorker::new();
+    let mock_batch_response = unrequested_batch.clone();
+    mock_server.expect_request_batches().return_once(move |_| {
+        Ok(anemo::Response::new(RequestBatchesResponse {
+            batches: vec![mock_batch_response],
+            is_size_limit_reached: false,
+        }))
+    });
+    let _recv_network = sync.serve(target_primary.worker(id), mock_server).await;
+
+    let handler = sync
+        .handler(id, store.clone(), TrivialTransactionValidator)
+        .certified_batch_verification(CertifiedBatchVerification::Digest)
+        .build()
+        .unwrap();
+
+    // The sync request is rejected and nothing is stored.
+    let result = handler.synchronize(anemo::Request::new(message)).await;
+    assert_eq!(result.unwrap_err().status(), StatusCode::BadRequest);
+    assert!(store.get(&unrequested_batch.digest()).unwrap().is_none());
+}
+
+#[tokio::test]
+async fn bulk_sync_resumes_after_retry() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Create a new test store holding many batches.
+    let store = MemoryBatchStore::default();
+    let mut expected = HashSet::new();
+    for i in 0..50u8 {
+        let batch = Batch::new(vec![vec![i; 100]]);
+        store.insert(&batch.digest(), &batch).unwrap();
+        expected.insert(batch.digest());
+    }
+
+    // Pages fit only a few batches, so the transfer needs many round trips.
+    let handler = WorkerReceiverHandler {
+        bulk_sync_sessions: BulkSyncSessions::new(
+            BulkSyncSessions::DEFAULT_MAX_SESSIONS,
+            BulkSyncSessions::DEFAULT_MAX_SESSIONS_PER_PEER,
+            250,
+        ),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let session_id = handler
+        .open_bulk_sync(anemo::Request::new(OpenBulkSyncRequest {}))
+        .await
+        .unwrap()
+        .into_body()
+        .session_id;
+    let request_page = |cursor| {
+        handler.request_bulk_sync_page(anemo::Request::new(RequestBulkSyncPageRequest {
+            session_id,
+            cursor,
+        }))
+    };
+
+    let mut received = HashSet::new();
+    let mut cursor = None;
+    let mut pages = 0;
+    loop {
+        let page = request_page(cursor).await.unwrap().into_body();
+        pages += 1;
+
+        // Simulate losing the response to the third page: retrying with the same cursor
+        // serves the same page again.
+        if pages == 3 {
+            let retried = request_page(cursor).await.unwrap().into_body();
+            assert_eq!(retried, page);
+        }
+        // Simulate the requester restarting after the fifth page: without a cursor, the
+        // server resumes from the last page the requester acknowledged.
+        if pages == 5 {
+            let resumed = request_page(None).await.unwrap().into_body();
+            assert_eq!(resumed, page);
+        }
+
+        received.extend(page.batches.iter().map(|batch| batch.digest()));
+        cursor = page.next_cursor;
+        if page.is_complete {
+            break;
+        }
+    }
+    assert!(pages > 5);
+    assert_eq!(received, expected);
+
+    // Unknown sessions are rejected.
+    let result = handler
+        .request_bulk_sync_page(anemo::Request::new(RequestBulkSyncPageRequest {
+            session_id: session_id + 1,
+            cursor: None,
+        }))
+        .await;
+    assert_eq!(result.unwrap_err().status(), StatusCode::NotFound);
+}
+
+#[tokio::test]
+async fn bulk_sync_sessions_bound_to_opener() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    store.insert(&batch.digest(), &batch).unwrap();
+
+    let handler = WorkerReceiverHandler {
+        bulk_sync_sessions: BulkSyncSessions::new(4, 2, BulkSyncSessions::DEFAULT_MAX_PAGE_SIZE),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let handler = &handler;
+    let open = |peer| async move {
+        let mut request = anemo::Request::new(OpenBulkSyncRequest {});
+        request.extensions_mut().insert(anemo::PeerId([peer; 32]));
+        handler
+            .open_bulk_sync(request)
+            .await
+            .unwrap()
+            .into_body()
+            .session_id
+    };
+    let request_page = |peer, session_id| {
+        let mut request = anemo::Request::new(RequestBulkSyncPageRequest {
+            session_id,
+            cursor: None,
+        });
+        request.extensions_mut().insert(anemo::PeerId([peer; 32]));
+        handler.request_bulk_sync_page(request)
+    };
+
+    // Only the opener of a session can page through it.
+    let session_id = open(1).await;
+    request_page(1, session_id).await.unwrap();
+    let status = request_page(2, session_id).await.unwrap_err();
+    assert_eq!(status.status(), StatusCode::NotFound);
+
+    // A peer opening more sessions than allowed evicts its own oldest ones.
+    let second = open(1).await;
+    let third = open(1).await;
+    let status = request_page(1, session_id).await.unwrap_err();
+    assert_eq!(status.status(), StatusCode::NotFound);
+    request_page(1, second).await.unwrap();
+    request_page(1, third).await.unwrap();
+
+    // Sessions of other peers are left alone.
+    let other = open(2).await;
+    for _ in 0..3 {
+        open(1).await;
+    }
+    request_page(2, other).await.unwrap();
+}
+
+#[tokio::test]
+async fn request_batches_attributes_metrics_to_peer() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Create a new test store holding the batch.
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    let metrics = Arc::new(WorkerMetrics::new(&Registry::new()));
+    let handler = WorkerReceiverHandler::new(
+        authority_id,
+        0,
+        NetworkClient::new_with_empty_id(),
+        store,
+        TrivialTransactionValidator,
+        metrics.clone(),
+    );
+
+    // Two peers request the batch, one of them twice.
+    let peer_a = anemo::PeerId([1; 32]);
+    let peer_b = anemo::PeerId([2; 32]);
+    for peer in [peer_a, peer_a, peer_b] {
+        let mut request = anemo::Request::new(RequestBatchesRequest {
+            batch_digests: vec![digest],
+        });
+        request.extensions_mut().insert(peer);
+        handler.request_batches(request).await.unwrap();
+    }
+
+    let calls = |peer: &anemo::PeerId| {
+        metrics
+            .peer_batch_requests
+            .with_label_values(&[&format!("{peer}"), "request_batches"])
+            .get()
+    };
+    let bytes = |peer: &anemo::PeerId| {
+        metrics
+            .peer_batch_request_bytes
+            .with_label_values(&[&format!("{peer}"), "request_batches"])
+            .get()
+    };
+    assert_eq!(calls(&peer_a), 2);
+    assert_eq!(calls(&peer_b), 1);
+    assert_eq!(bytes(&peer_a), 2 * batch.size() as u64);
+    assert_eq!(bytes(&peer_b), batch.size() as u64);
+}
+
+#[tokio::test]
+async fn request_batch_metadata() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = TestBatchStore::default().keeping_summaries();
+    let batch = Batch::new(vec![vec![1; 10], vec![2; 20], vec![3; 30]]);
+    store.insert(&batch.digest(), &batch).unwrap();
+    let missing_digest = Batch::new(vec![vec![4]]).digest();
+
+    let handler = WorkerReceiverHandler::new(
+        authority_id,
+        0,
+        NetworkClient::new_with_empty_id(),
+        store.clone(),
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    );
+
+    let response = handler
+        .request_batch_metadata(anemo::Request::new(RequestBatchMetadataRequest {
+            batch_digests: vec![batch.digest(), missing_digest],
+        }))
+        .await
+        .unwrap()
+        .into_body();
+    let summary = response.batch_summaries[0].unwrap();
+    assert_eq!(summary.transaction_count, batch.transactions().len());
+    assert_eq!(summary.size, batch.size());
+    assert_eq!(summary.created_at, batch.metadata().created_at);
+    assert_eq!(response.batch_summaries[1], None);
+
+    // The summaries were served without reading the batch.
+    assert_eq!(store.read_keys.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn request_batches_keyed_by_digest() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch_1 = Batch::new(vec![vec![1]]);
+    let batch_2 = Batch::new(vec![vec![2]]);
+    let missing_digest = Batch::new(vec![vec![3]]).digest();
+    for batch in [&batch_1, &batch_2] {
+        store.insert(&batch.digest(), batch).unwrap();
+    }
+
+    let handler = WorkerReceiverHandler::new(
+        authority_id,
+        0,
+        NetworkClient::new_with_empty_id(),
+        store,
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    );
+    let digests = vec![batch_1.digest(), missing_digest, batch_2.digest()];
+
+    // The map holds exactly the requested batches that are present.
+    let response = handler
+        .request_batches_v2(anemo::Request::new(
+            RequestBatchesV2Request::new(digests.clone()).keyed_by_digest(),
+        ))
+        .await
+        .unwrap()
+        .into_body();
+    assert!(response.batches.is_empty());
+    assert_eq!(
+        response.batches_by_digest,
+        Some(HashMap::from([
+            (batch_1.digest(), batch_1.clone()),
+            (batch_2.digest(), batch_2.clone()),
+        ]))
+    );
+
+    // Batches are listed by default.
+    let response = handler
+        .request_batches_v2(anemo::Request::new(RequestBatchesV2Request::new(digests)))
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batches, vec![batch_1, batch_2]);
+    assert_eq!(response.batches_by_digest, None);
+}
+
+#[tokio::test]
+async fn request_batches_bounded_by_frame_size() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Only two of these batches fit in a frame once encoded and compressed, although all of
+    // them fit in the response size limit.
+    let store = MemoryBatchStore::default();
+    let batches = (0..3u8)
+        .map(|i| Batch::new(vec![vec![i; 100_000]]))
+        .collect_vec();
+    let oversized_batch = Batch::new(vec![vec![3; 300_000]]);
+    for batch in batches.iter().chain([&oversized_batch]) {
+        store.insert(&batch.digest(), batch).unwrap();
+    }
+
+    let handler = WorkerReceiverHandler {
+        max_response_frame_size: Some(250_000),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    let response = handler
+        .request_batches(anemo::Request::new(RequestBatchesRequest {
+            batch_digests: batches.iter().map(|batch| batch.digest()).collect(),
+        }))
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batches, batches[..2]);
+    assert!(response.is_size_limit_reached);
+
+    // A batch that does not fit in a frame on its own is refused with a clear error, rather
+    // than failing to be sent.
+    let status = handler
+        .request_batches(anemo::Request::new(RequestBatchesRequest {
+            batch_digests: vec![oversized_batch.digest()],
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::ServiceUnavailable);
+    assert!(status.message().unwrap().contains("request fewer digests"));
+}
+
+#[tokio::test]
+async fn request_batches_drops_duplicate_digests() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = TestBatchStore::default();
+    let batch_1 = Batch::new(vec![vec![1]]);
+    let batch_2 = Batch::new(vec![vec![2]]);
+    store.insert(&batch_1.digest(), &batch_1).unwrap();
+    store.insert(&batch_2.digest(), &batch_2).unwrap();
+
+    let metrics = Arc::new(WorkerMetrics::new(&Registry::new()));
+    let handler = WorkerReceiverHandler::new(
+        authority_id,
+        0,
+        NetworkClient::new_with_empty_id(),
+        store.clone(),
+        TrivialTransactionValidator,
+        metrics.clone(),
+    );
+
+    let response = handler
+        .request_batches(anemo::Request::new(RequestBatchesRequest {
+            batch_digests: vec![
+                batch_2.digest(),
+                batch_1.digest(),
+                batch_2.digest(),
+                batch_2.digest(),
+            ],
+        }))
+        .await
+        .unwrap()
+        .into_body();
+
+    // Each batch is read and returned once, in the order first requested.
+    assert_eq!(response.batches, vec![batch_2, batch_1]);
+    assert_eq!(store.read_keys.load(Ordering::SeqCst), 2);
+    assert_eq!(metrics.request_batches_duplicate_digests.get(), 2);
+}
+
+#[tokio::test]
+async fn request_batches_serves_partial_response_on_chunk_failure() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Enough batches for two read chunks.
+    let store = TestBatchStore::default();
+    let batches: Vec<_> = (0..250u32)
+        .map(|i| Batch::new(vec![i.to_le_bytes().to_vec()]))
+        .collect();
+    for batch in &batches {
+        store.insert(&batch.digest(), batch).unwrap();
+    }
+
+    let handler = WorkerReceiverHandler {
+        request_batches_chunk_retries: Some(1),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    // The first chunk fails on both attempts, the second one recovers after a retry.
+    store.multi_get_failures.store(3, Ordering::SeqCst);
+    let request = anemo::Request::new(RequestBatchesRequest {
+        batch_digests: batches.iter().map(|batch| batch.digest()).collect(),
+    });
+    let response = handler.request_batches(request).await.unwrap().into_body();
+    assert_eq!(response.batches, batches[200..]);
+    assert!(!response.is_size_limit_reached);
+
+    // Without retries, a single failure fails the whole request.
+    let handler = WorkerReceiverHandler {
+        request_batches_chunk_retries: None,
+        ..handler
+    };
+    store.multi_get_failures.store(1, Ordering::SeqCst);
+    let request = anemo::Request::new(RequestBatchesRequest {
+        batch_digests: batches.iter().map(|batch| batch.digest()).collect(),
+    });
+    assert!(handler.request_batches(request).await.is_err());
+}
+
+#[tokio::test]
+async fn primary_receiver_handler_builder() {
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority = fixture.authorities().next().unwrap();
+    let id = 0;
+    let store = test_utils::create_batch_store();
+    let builder = || {
+        PrimaryReceiverHandler::builder(
+            authority.id(),
+            id,
+            fixture.committee(),
+            fixture.worker_cache(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let batch_fetcher = || {
+        BatchFetcher::new(
+            authority.worker(id).info().name.clone(),
+            test_utils::random_network(),
+            store.clone(),
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    // The local handler needs both a network and a batch fetcher.
+    let handler = builder()
+        .network(test_utils::random_network())
+        .batch_fetcher(batch_fetcher())
+        .build()
+        .unwrap();
+    assert_eq!(
+        handler.request_batch_timeout,
+        Parameters::default().sync_retry_delay
+    );
+    assert_eq!(
+        handler.request_batch_retry_nodes,
+        Parameters::default().sync_retry_nodes
+    );
+    assert_eq!(
+        builder().batch_fetcher(batch_fetcher()).build().err(),
+        Some(PrimaryReceiverHandlerBuilderError::MissingNetwork)
+    );
+    assert_eq!(
+        builder()
+            .network(test_utils::random_network())
+            .build()
+            .err(),
+        Some(PrimaryReceiverHandlerBuilderError::MissingBatchFetcher)
+    );
+
+    // The legacy RPC handler takes neither.
+    let handler = builder()
+        .request_batch_timeout(Duration::from_secs(1))
+        .build_legacy_rpc()
+        .unwrap();
+    assert_eq!(handler.request_batch_timeout, Duration::from_secs(1));
+    assert_eq!(
+        builder()
+            .network(test_utils::random_network())
+            .build_legacy_rpc()
+            .err(),
+        Some(PrimaryReceiverHandlerBuilderError::UnusedComponent(
+            "network"
+        ))
+    );
+}
+
+#[tokio::test]
+async fn intersect_batches() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // The store holds every third batch of a set large enough to span several chunks.
+    let store = test_utils::create_batch_store();
+    let mut digests = Vec::new();
+    let mut expected = Vec::new();
+    for i in 0..5_000u32 {
+        let batch = Batch::new(vec![i.to_le_bytes().to_vec()]);
+        if i % 3 == 0 {
+            store.insert(&batch.digest(), &batch).unwrap();
+            expected.push(batch.digest());
+        }
+        digests.push(batch.digest());
+    }
+    expected.sort();
+
+    let handler = WorkerReceiverHandler::new(
+        authority_id,
+        0,
+        NetworkClient::new_with_empty_id(),
+        store,
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    );
+
+    // Duplicates in the request are only reported once.
+    digests.extend(digests[..100].to_vec());
+    let request = anemo::Request::new(IntersectBatchesRequest {
+        batch_digests: digests,
+    });
+    let response = handler
+        .intersect_batches(request)
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batch_digests, expected);
+}
+
+#[tokio::test]
+async fn batch_sizes() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // The store holds every other batch, of growing sizes.
+    let store = MemoryBatchStore::default();
+    let mut digests = Vec::new();
+    let mut expected = Vec::new();
+    for i in 0..10usize {
+        let batch = Batch::new(vec![vec![0u8; i * 100]]);
+        if i % 2 == 0 {
+            store.insert(&batch.digest(), &batch).unwrap();
+            expected.push(Some(batch.size()));
+        } else {
+            expected.push(None);
+        }
+        digests.push(batch.digest());
+    }
+
+    let handler = WorkerReceiverHandler::new(
+        authority_id,
+        0,
+        NetworkClient::new_with_empty_id(),
+        store,
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    );
+
+    let request = anemo::Request::new(BatchSizesRequest {
+        batch_digests: digests,
+    });
+    let response = handler.batch_sizes(request).await.unwrap().into_body();
+    assert_eq!(response.batch_sizes, expected);
+    assert_eq!(
+        response.max_request_batches_response_size,
+        MAX_REQUEST_BATCHES_RESPONSE_SIZE
+    );
+}
+
+#[tokio::test]
+async fn report_batch_sheds_load_when_writes_are_slow() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Mock the primary client to always succeed.
+    let client = NetworkClient::new_with_empty_id();
+    let mut mock_server = MockWorkerToPrimary::new();
+    mock_server
+        .expect_report_others_batch()
+        .returning(|_| Ok(anemo::Response::new(())));
+    client.set_worker_to_primary_local_handler(Arc::new(mock_server));
+
+    let store = TestBatchStore::default().with_write_latency(Duration::from_millis(50));
+    let handler = WorkerReceiverHandler {
+        write_backpressure: Some(
+            WriteBackpressure::new(Duration::from_millis(10))
+                .with_probe_interval(Duration::from_millis(500)),
+        ),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            client,
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let report = |i: u8| {
+        handler.report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: Batch::new(vec![vec![i]]),
+        }))
+    };
+
+    // The first write is accepted, but it is slow so the next one is shed.
+    report(0).await.unwrap();
+    let result = report(1).await;
+    assert_eq!(result.unwrap_err().status(), StatusCode::ServiceUnavailable);
+    assert!(!store
+        .contains_key(&Batch::new(vec![vec![1]]).digest())
+        .unwrap());
+
+    // After the probe interval, a write is let through again.
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    report(2).await.unwrap();
+}
+
+#[tokio::test]
+async fn serve_reads_from_read_store() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Mock the primary client to always succeed.
+    let client = NetworkClient::new_with_empty_id();
+    let mut mock_server = MockWorkerToPrimary::new();
+    mock_server
+        .expect_report_others_batch()
+        .returning(|_| Ok(anemo::Response::new(())));
+    client.set_worker_to_primary_local_handler(Arc::new(mock_server));
+
+    let write_store = MemoryBatchStore::default();
+    let read_store = MemoryBatchStore::default();
+    let handler = WorkerReceiverHandler {
+        read_store: Some(read_store.clone()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            client,
+            write_store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    // Reported batches are written to the write store only.
+    let written = Batch::new(vec![vec![1]]);
+    handler
+        .report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: written.clone(),
+        }))
+        .await
+        .unwrap();
+    assert!(write_store.contains_key(&written.digest()).unwrap());
+    assert!(!read_store.contains_key(&written.digest()).unwrap());
+
+    // Requests are served from the read store.
+    let replicated = Batch::new(vec![vec![2]]);
+    read_store
+        .insert(&replicated.digest(), &replicated)
+        .unwrap();
+    let request = |batch: &Batch| {
+        anemo::Request::new(RequestBatchRequest {
+            batch: batch.digest(),
+        })
+    };
+    let response = handler.request_batch(request(&replicated)).await.unwrap();
+    assert_eq!(response.into_body().batch, Some(replicated.clone()));
+    let response = handler.request_batch(request(&written)).await.unwrap();
+    assert_eq!(response.into_body().batch, None);
+
+    let request = anemo::Request::new(RequestBatchesRequest {
+        batch_digests: vec![written.digest(), replicated.digest()],
+    });
+    let response = handler.request_batches(request).await.unwrap();
+    assert_eq!(response.into_body().batches, vec![replicated]);
+}
+
+#[test]
+fn worker_handler_error_status_codes() {
+    let fixture = CommitteeFixture::builder().build();
+    let worker = fixture
+        .authorities()
+        .next()
+        .unwrap()
+        .worker(0)
+        .info()
+        .name
+        .clone();
+    let store_error = || store::TypedStoreError::RocksDBError("error".to_string());
+
+    let cases = vec![
+        (
+            WorkerHandlerError::StoreRead(store_error()),
+            StatusCode::InternalServerError,
+        ),
+        (
+            WorkerHandlerError::StoreWrite(store_error()),
+            StatusCode::InternalServerError,
+        ),
+        (
+            WorkerHandlerError::StoreRemove(store_error()),
+            StatusCode::InternalServerError,
+        ),
+        (
+            WorkerHandlerError::InvalidBatch("invalid".to_string()),
+            StatusCode::BadRequest,
+        ),
+        (
+            WorkerHandlerError::UndersizedBatch {
+                digest: test_utils::batch().digest(),
+                size: 1,
+                min: 2,
+            },
+            StatusCode::BadRequest,
+        ),
+        (
+            WorkerHandlerError::UnrequestedBatch {
+                digest: test_utils::batch().digest(),
+                worker: worker.clone(),
+            },
+            StatusCode::BadRequest,
+        ),
+        (
+            WorkerHandlerError::NotFound("missing".to_string()),
+            StatusCode::NotFound,
+        ),
+        (
+            WorkerHandlerError::PeerNotConnected(worker),
+            StatusCode::ServiceUnavailable,
+        ),
+        (
+            WorkerHandlerError::NoReachableSyncTarget(AuthorityIdentifier(1)),
+            StatusCode::ServiceUnavailable,
+        ),
+        (
+            WorkerHandlerError::UnknownNode("unknown".to_string()),
+            StatusCode::InternalServerError,
+        ),
+        (
+            WorkerHandlerError::StaleWorkerCache {
+                epoch: 0,
+                target: AuthorityIdentifier(1),
+                reason: "unknown".to_string(),
+            },
+            StatusCode::ServiceUnavailable,
+        ),
+        (
+            WorkerHandlerError::SizeExceeded { size: 2, limit: 1 },
+            StatusCode::BadRequest,
+        ),
+        (
+            WorkerHandlerError::ResponseTooLarge { size: 2, limit: 1 },
+            StatusCode::ServiceUnavailable,
+        ),
+        (
+            WorkerHandlerError::UnsupportedViaRpc("synchronize"),
+            StatusCode::BadRequest,
+        ),
+        (
+            WorkerHandlerError::MethodDisabled("delete_batches"),
+            UNIMPLEMENTED,
+        ),
+        (
+            WorkerHandlerError::ObserverDenied("request_batch"),
+            StatusCode::BadRequest,
+        ),
+        (
+            WorkerHandlerError::Overloaded,
+            StatusCode::ServiceUnavailable,
+        ),
+        (
+            WorkerHandlerError::ValidationUnavailable("unavailable".to_string()),
+            StatusCode::ServiceUnavailable,
+        ),
+        (
+            WorkerHandlerError::NotReciprocating(anemo::PeerId([1; 32])),
+            StatusCode::TooManyRequests,
+        ),
+        (
+            WorkerHandlerError::ReportToPrimary("unreachable".to_string()),
+            StatusCode::InternalServerError,
+        ),
+        (
+            WorkerHandlerError::SyncFailed,
+            StatusCode::InternalServerError,
+        ),
+        (
+            WorkerHandlerError::SyncBudgetExhausted {
+                budget: 1,
+                missing: vec![],
+            },
+            StatusCode::ServiceUnavailable,
+        ),
+    ];
+    for (error, status_code) in cases {
+        let message = error.to_string();
+        let status = anemo::rpc::Status::from(error);
+        assert_eq!(status.status(), status_code);
+        assert_eq!(status.message(), Some(message.as_str()));
+    }
+}
+
+#[tokio::test]
+async fn fetch_batches_stream() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+
+    let store = test_utils::create_batch_store();
+    let mut expected = HashMap::new();
+    for i in 0..20u8 {
+        let batch = Batch::new(vec![vec![i; 100]]);
+        store.insert(&batch.digest(), &batch).unwrap();
+        expected.insert(batch.digest(), batch);
+    }
+
+    let handler = sync
+        .handler(0, store, TrivialTransactionValidator)
+        .build()
+        .unwrap();
+
+    // Consume the stream through a small window.
+    let request = FetchBatchesRequest {
+        digests: expected.keys().cloned().collect(),
+        known_workers: HashSet::new(),
+    };
+    let mut receiver = handler.fetch_batches_stream(request, 2).unwrap();
+    let mut fetched = HashMap::new();
+    while let Some((digest, batch)) = receiver.recv().await {
+        assert!(fetched.insert(digest, batch).is_none());
+    }
+    assert_eq!(fetched, expected);
+}
+
+#[tokio::test]
+async fn report_batch_mirrors_to_backup_store() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Mock the primary client to always succeed.
+    let client = NetworkClient::new_with_empty_id();
+    let mut mock_server = MockWorkerToPrimary::new();
+    mock_server
+        .expect_report_others_batch()
+        .returning(|_| Ok(anemo::Response::new(())));
+    client.set_worker_to_primary_local_handler(Arc::new(mock_server));
+
+    let metrics = Arc::new(WorkerMetrics::new(&Registry::new()));
+    let backup_store = MemoryBatchStore::default();
+    let handler = WorkerReceiverHandler {
+        mirror: Some(BatchMirror::spawn(
+            backup_store.clone(),
+            BatchMirror::DEFAULT_QUEUE_CAPACITY,
+            BatchMirror::DEFAULT_MAX_RETRIES,
+            BatchMirror::DEFAULT_RETRY_DELAY,
+            metrics.clone(),
+        )),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            client,
+            MemoryBatchStore::default(),
+            TrivialTransactionValidator,
+            metrics.clone(),
+        )
+    };
+
+    let batches: Vec<_> = (0..10u8).map(|i| Batch::new(vec![vec![i]])).collect();
+    for batch in &batches {
+        handler
+            .report_batch(anemo::Request::new(WorkerBatchMessage {
+                batch: batch.clone(),
+            }))
+            .await
+            .unwrap();
+    }
+
+    // The batches land in the backup store in the background.
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while metrics
+            .batch_mirror_writes
+            .with_label_values(&["success"])
+            .get()
+            < batches.len() as u64
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    for batch in &batches {
+        assert_eq!(
+            backup_store.get(&batch.digest()).unwrap(),
+            Some(batch.clone())
+        );
+    }
+}
+
+#[tokio::test]
+async fn disabled_methods_are_rejected_without_side_effects() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority = fixture.authorities().next().unwrap();
+    let id = 0;
+
+    // Create a new test store holding the batch.
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    let handler = PrimaryReceiverHandler::builder(
+        authority.id(),
+        id,
+        fixture.committee(),
+        fixture.worker_cache(),
+        store.clone(),
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    )
+    .enabled_methods(EnabledPrimaryToWorkerMethods {
+        synchronize: false,
+        fetch_batches: false,
+        delete_batches: false,
+    })
+    .build_legacy_rpc()
+    .unwrap();
+
+    let result = handler
+        .delete_batches(anemo::Request::new(WorkerDeleteBatchesMessage {
+            digests: vec![digest],
+        }))
+        .await;
+    assert_eq!(result.unwrap_err().status(), UNIMPLEMENTED);
+    assert!(store.contains_key(&digest).unwrap());
+
+    let result = handler
+        .synchronize(anemo::Request::new(WorkerSynchronizeMessage {
+            digests: vec![digest],
+            target: authority.id(),
+            is_certified: false,
+        }))
+        .await;
+    assert_eq!(result.unwrap_err().status(), UNIMPLEMENTED);
+
+    let result = handler
+        .fetch_batches(anemo::Request::new(FetchBatchesRequest {
+            digests: HashSet::from([digest]),
+            known_workers: HashSet::new(),
+        }))
+        .await;
+    assert_eq!(result.unwrap_err().status(), UNIMPLEMENTED);
+}
+
+#[tokio::test]
+async fn synchronize_drops_and_meters_unrequested_batches() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    // Create a new test store.
+    let store = MemoryBatchStore::default();
+
+    // The target responds with the requested batch, plus one that was never requested.
+    let target_primary = sync.target();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    let unrequested_batch = Batch::new(vec![vec![42]]);
+    let message = WorkerSynchronizeMessage {
+        digests: vec![digest],
+        target: target_primary.id(),
+        is_certified: false,
+    };
+
+    let mut mock_server = MockWorkerToWorker::new();
+    let mock_batch_response = vec![batch.clone(), unrequested_batch.clone()];
+    mock_server.expect_request_batches().return_once(move |_| {
+        Ok(anemo::Response::new(RequestBatchesResponse {
+            batches: mock_batch_response,
+            is_size_limit_reached: false,
+        }))
+    });
+    let _recv_network = sync.serve(target_primary.worker(id), mock_server).await;
+
+    let handler = sync
+        .handler(id, store.clone(), TrivialTransactionValidator)
+        .build()
+        .unwrap();
+
+    // The requested batch is stored, the other one is dropped and metered.
+    handler
+        .synchronize(anemo::Request::new(message))
+        .await
+        .unwrap();
+    assert!(store.contains_key(&digest).unwrap());
+    assert!(!store.contains_key(&unrequested_batch.digest()).unwrap());
+    assert_eq!(handler.metrics.synchronize_unrequested_batches.get(), 1);
+}
+
+#[tokio::test]
+async fn request_batches_caps_response_count() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Many tiny batches, far below the byte cap.
+    let store = MemoryBatchStore::default();
+    let batches: Vec<_> = (0..500u32)
+        .map(|i| Batch::new(vec![i.to_le_bytes().to_vec()]))
+        .collect();
+    for batch in &batches {
+        store.insert(&batch.digest(), batch).unwrap();
+    }
+
+    let handler = WorkerReceiverHandler {
+        max_request_batches_response_count: 300,
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    // The count cap is hit before the byte cap.
+    let request = anemo::Request::new(RequestBatchesRequest {
+        batch_digests: batches.iter().map(|batch| batch.digest()).collect(),
+    });
+    let response = handler.request_batches(request).await.unwrap().into_body();
+    assert_eq!(response.batches, batches[..300]);
+    assert!(response.is_size_limit_reached);
+
+    // Requests within the cap are served in full.
+    let request = anemo::Request::new(RequestBatchesRequest {
+        batch_digests: batches[300..].iter().map(|batch| batch.digest()).collect(),
+    });
+    let response = handler.request_batches(request).await.unwrap().into_body();
+    assert_eq!(response.batches, batches[300..]);
+    assert!(!response.is_size_limit_reached);
+}
+
+#[tokio::test]
+async fn committee_and_worker_cache_epochs_must_agree() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder()
+        .randomize_ports(true)
+        .epoch(5)
+        .build();
+    let committee = fixture.committee();
+    let authority_id = fixture.authorities().next().unwrap().id();
+    let id = 0;
+
+    // The worker cache is from the previous epoch.
+    let mut worker_cache = fixture.worker_cache();
+    worker_cache.epoch = committee.epoch() - 1;
+
+    // The mismatch is caught at construction.
+    let result = PrimaryReceiverHandler::builder(
+        authority_id,
+        id,
+        committee,
+        worker_cache,
+        MemoryBatchStore::default(),
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    )
+    .build_legacy_rpc();
+    assert_eq!(
+        result.err(),
+        Some(PrimaryReceiverHandlerBuilderError::EpochMismatch {
+            worker_cache_epoch: 4,
+            committee_epoch: 5,
+        })
+    );
+}
+
+#[tokio::test]
+async fn request_batches_annotates_batch_ages() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Batches created a second apart, oldest first, and stored before the worker started.
+    let store = MemoryBatchStore::default();
+    let created_at = now() - 10_000;
+    let batches: Vec<_> = (0..10u64)
+        .map(|i| {
+            let mut batch = Batch::new(vec![i.to_le_bytes().to_vec()]);
+            batch.metadata_mut().created_at = created_at + i * 1_000;
+            batch
+        })
+        .collect();
+    for batch in &batches {
+        store.insert(&batch.digest(), batch).unwrap();
+    }
+
+    let handler = WorkerReceiverHandler {
+        notify_primary: false,
+        batch_insert_times: Some(BatchInsertTimes::default()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let request_batches = |batches: &[&Batch], keyed_by_digest| {
+        handler.request_batches_v2(anemo::Request::new(RequestBatchesV2Request {
+            batch_digests: batches.iter().map(|batch| batch.digest()).collect(),
+            compact_batch_digests: None,
+            keyed_by_digest,
+            already_have: Vec::new(),
+        }))
+    };
+
+    // The ages of batches stored before the worker started fall back to their creation time.
+    let response = request_batches(&batches.iter().collect_vec(), false)
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batches, batches);
+    let ages = response.batch_ages_ms.unwrap();
+    assert_eq!(ages.len(), batches.len());
+    assert!(ages[0] >= 10_000);
+    for pair in ages.windows(2) {
+        assert_eq!(pair[0] - pair[1], 1_000);
+    }
+
+    // A batch created long ago but reported just now is fresh.
+    let mut reported = Batch::new(vec![vec![42]]);
+    reported.metadata_mut().created_at = created_at - 3_600_000;
+    handler
+        .report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: reported.clone(),
+        }))
+        .await
+        .unwrap();
+    let response = request_batches(&[&reported], false)
+        .await
+        .unwrap()
+        .into_body();
+    assert!(response.batch_ages_ms.unwrap()[0] < 10_000);
+
+    // Ages are also returned keyed by digest, in the order the batches were requested.
+    let response = request_batches(&[&reported, &batches[0]], true)
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batches_by_digest.unwrap().len(), 2);
+    let ages = response.batch_ages_ms.unwrap();
+    assert_eq!(ages.len(), 2);
+    assert!(ages[0] < 10_000);
+    assert!(ages[1] >= 10_000);
+
+    // Without annotation, no ages are sent.
+    let handler = WorkerReceiverHandler {
+        batch_insert_times: None,
+        ..handler.clone()
+    };
+    let response = handler
+        .request_batches_v2(anemo::Request::new(RequestBatchesV2Request::new(
+            batches.iter().map(|batch| batch.digest()).collect(),
+        )))
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batches.len(), batches.len());
+    assert!(response.batch_ages_ms.is_none());
+}
+
+#[tokio::test]
+async fn delete_batches_in_chunks() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = TestBatchStore::default();
+    let batches: Vec<_> = (0..25_000u32)
+        .map(|i| Batch::new(vec![i.to_le_bytes().to_vec()]))
+        .collect();
+    for batch in &batches {
+        store.insert(&batch.digest(), batch).unwrap();
+    }
+
+    let handler = PrimaryReceiverHandler::builder(
+        authority_id,
+        0,
+        fixture.committee(),
+        fixture.worker_cache(),
+        store.clone(),
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    )
+    .delete_batches_chunking(DeleteBatchesChunking {
+        chunk_size: 1_000,
+        concurrency: 8,
+    })
+    .build_legacy_rpc()
+    .unwrap();
+
+    let digests = batches.iter().map(|batch| batch.digest()).collect();
+    assert_eq!(handler.remove_batches(digests).await.unwrap(), 25_000);
+
+    // Every batch is removed, in write batches of at most the chunk size.
+    assert!(store.entries_after(None, 1).unwrap().is_empty());
+    let mut multi_remove_sizes = store.multi_remove_sizes.lock().unwrap().clone();
+    multi_remove_sizes.sort();
+    assert_eq!(multi_remove_sizes, vec![1_000; 25]);
+}
+
+#[tokio::test]
+async fn report_batch_notifies_observer_of_new_batches() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Mock the primary client to always succeed.
+    let client = NetworkClient::new_with_empty_id();
+    let mut mock_server = MockWorkerToPrimary::new();
+    mock_server
+        .expect_report_others_batch()
+        .returning(|_| Ok(anemo::Response::new(())));
+    client.set_worker_to_primary_local_handler(Arc::new(mock_server));
+
+    let metrics = Arc::new(WorkerMetrics::new(&Registry::new()));
+    let (observer, mut notifications) = BatchObserver::new(2, metrics.clone());
+    let handler = WorkerReceiverHandler {
+        observer: Some(observer),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            client,
+            MemoryBatchStore::default(),
+            TrivialTransactionValidator,
+            metrics.clone(),
+        )
+    };
+    let report = |batch: &Batch| {
+        handler.report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: batch.clone(),
+        }))
+    };
+
+    // A batch reported twice is only notified once.
+    let batches: Vec<_> = (0..5u8).map(|i| Batch::new(vec![vec![i]])).collect();
+    report(&batches[0]).await.unwrap();
+    report(&batches[1]).await.unwrap();
+    report(&batches[0]).await.unwrap();
+    for batch in &batches[..2] {
+        let stored_batch = notifications.try_recv().unwrap();
+        assert_eq!(stored_batch.digest, batch.digest());
+        assert_eq!(stored_batch.size, batch.size());
+    }
+    assert!(notifications.try_recv().is_err());
+
+    // Notifications are dropped, without failing the report, once the queue is full.
+    for batch in &batches[2..] {
+        report(batch).await.unwrap();
+    }
+    let sent = metrics
+        .stored_batch_notifications
+        .with_label_values(&["sent"])
+        .get();
+    let dropped = metrics
+        .stored_batch_notifications
+        .with_label_values(&["dropped"])
+        .get();
+    assert_eq!((sent, dropped), (4, 1));
+}
+
+/// A primary that takes `delay` to respond to the first `slow_reports` reports of batches
+/// of other authorities.
+struct SlowPrimary {
+    delay: Duration,
+    slow_reports: AtomicUsize,
+}
+
+#[async_trait]
+impl WorkerToPrimary for SlowPrimary {
+    async fn report_our_batch(
+        &self,
+        _request: anemo::Request<WorkerOurBatchMessage>,
+    ) -> Result<anemo::Response<()>, anemo::rpc::Status> {
+        Ok(anemo::Response::new(()))
+    }
+
+    async fn report_others_batch(
+        &self,
+        _request: anemo::Request<WorkerOthersBatchMessage>,
+    ) -> Result<anemo::Response<()>, anemo::rpc::Status> {
+        if self
+            .slow_reports
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+        {
+            tokio::time::sleep(self.delay).await;
+        }
+        Ok(anemo::Response::new(()))
+    }
+}
+
+#[tokio::test]
+async fn report_batch_is_not_blocked_by_slow_primary() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // The primary is too slow for the first attempt and the retry.
+    let client = NetworkClient::new_with_empty_id();
+    client.set_worker_to_primary_local_handler(Arc::new(SlowPrimary {
+        delay: Duration::from_secs(60),
+        slow_reports: AtomicUsize::new(2),
+    }));
+
+    let metrics = Arc::new(WorkerMetrics::new(&Registry::new()));
+    let handler = WorkerReceiverHandler {
+        others_batch_reporter: Some(OthersBatchReporter::spawn(
+            client.clone(),
+            Duration::from_millis(100),
+            1,
+            OthersBatchReporter::DEFAULT_QUEUE_CAPACITY,
+            Duration::from_millis(10),
+            metrics.clone(),
+        )),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            client,
+            MemoryBatchStore::default(),
+            TrivialTransactionValidator,
+            metrics.clone(),
+        )
+    };
+
+    // The batch is accepted once both attempts time out, without waiting for the primary.
+    let start = Instant::now();
+    handler
+        .report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: test_utils::batch(),
+        }))
+        .await
+        .unwrap();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let outcomes = |status| {
+        metrics
+            .report_others_batch_outcomes
+            .with_label_values(&[status])
+            .get()
+    };
+    assert_eq!(outcomes("retry"), 1);
+    assert_eq!(outcomes("deferred"), 1);
+
+    // The report then goes through in the background.
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while outcomes("reported_in_background") < 1 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+/// A validator taking `delay` to validate batches, which rejects those whose first
+/// transaction is empty.
+#[derive(Clone)]
+struct SlowValidator {
+    delay: Duration,
+}
+
+#[async_trait]
+impl TransactionValidator for SlowValidator {
+    type Error = eyre::Report;
+
+    fn validate(&self, _tx: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn validate_batch(&self, batch: &Batch) -> Result<(), Self::Error> {
+        tokio::time::sleep(self.delay).await;
+        if batch
+            .transactions()
+            .first()
+            .map_or(true, |tx| tx.is_empty())
+        {
+            eyre::bail!("Invalid batch");
+        }
+        Ok(())
+    }
+}
+
+/// A validator failing transiently for batches whose first transaction is `[0]`, and
+/// permanently for those whose first transaction is `[1]`.
+#[derive(Clone)]
+struct ClassifyingValidator;
+
+#[async_trait]
+impl TransactionValidator for ClassifyingValidator {
+    type Error = ValidationError;
+
+    fn validate(&self, _tx: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn validate_batch(&self, batch: &Batch) -> Result<(), Self::Error> {
+        match batch.transactions().first().map(|tx| tx.as_slice()) {
+            Some([0]) => Err(ValidationError::transient(eyre::eyre!(
+                "Validator unavailable"
+            ))),
+            Some([1]) => Err(ValidationError::permanent(eyre::eyre!("Malformed batch"))),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn validation_error_kinds_map_to_status() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let handler = WorkerReceiverHandler::new(
+        authority_id,
+        0,
+        NetworkClient::new_with_empty_id(),
+        store.clone(),
+        ClassifyingValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    );
+
+    // Plain reports are permanent failures.
+    assert_eq!(
+        WorkerHandlerError::from(ValidationError::from(eyre::eyre!("invalid"))).to_string(),
+        "Invalid batch: invalid"
+    );
+
+    for (tx, status_code) in [
+        (vec![0], StatusCode::ServiceUnavailable),
+        (vec![1], StatusCode::BadRequest),
+    ] {
+        let batch = Batch::new(vec![tx]);
+        let status = handler
+            .report_batch(anemo::Request::new(WorkerBatchMessage {
+                batch: batch.clone(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.status(), status_code);
+        assert!(!store.contains_key(&batch.digest()).unwrap());
+    }
+}
+
+#[tokio::test]
+async fn speculative_write_never_persists_invalid_batches() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Mock the primary client to always succeed.
+    let client = NetworkClient::new_with_empty_id();
+    let mut mock_server = MockWorkerToPrimary::new();
+    mock_server
+        .expect_report_others_batch()
+        .returning(|_| Ok(anemo::Response::new(())));
+    client.set_worker_to_primary_local_handler(Arc::new(mock_server));
+
+    // Validation is slow enough for the speculative write to land first.
+    let store = MemoryBatchStore::default();
+    let staging = MemoryBatchStore::default();
+    let handler = WorkerReceiverHandler {
+        speculative_write: Some(staging.clone()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            client,
+            store.clone(),
+            SlowValidator {
+                delay: Duration::from_millis(200),
+            },
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let report = |batch: &Batch| {
+        handler.report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: batch.clone(),
+        }))
+    };
+
+    // Valid batches are moved from the staging store into the store.
+    let valid_batch = Batch::new(vec![vec![1]]);
+    report(&valid_batch).await.unwrap();
+    assert!(store.contains_key(&valid_batch.digest()).unwrap());
+    assert!(staging.entries_after(None, 10).unwrap().is_empty());
+
+    // Invalid batches are rejected, and never written to the store.
+    let invalid_batch = Batch::new(vec![vec![]]);
+    let reported = tokio::spawn({
+        let handler = handler.clone();
+        let invalid_batch = invalid_batch.clone();
+        async move {
+            handler
+                .report_batch(anemo::Request::new(WorkerBatchMessage {
+                    batch: invalid_batch,
+                }))
+                .await
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(staging.contains_key(&invalid_batch.digest()).unwrap());
+    assert!(!store.contains_key(&invalid_batch.digest()).unwrap());
+    let status = reported.await.unwrap().unwrap_err();
+    assert_eq!(status.status(), StatusCode::BadRequest);
+    assert!(!store.contains_key(&invalid_batch.digest()).unwrap());
+    assert!(staging.entries_after(None, 10).unwrap().is_empty());
+    assert_eq!(store.entries_after(None, 10).unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn validator_breaker_trips_and_recovers() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Mock the primary client to always succeed.
+    let client = NetworkClient::new_with_empty_id();
+    let mut mock_server = MockWorkerToPrimary::new();
+    mock_server
+        .expect_report_others_batch()
+        .returning(|_| Ok(anemo::Response::new(())));
+    client.set_worker_to_primary_local_handler(Arc::new(mock_server));
+
+    let cooldown = Duration::from_millis(200);
+    let store = MemoryBatchStore::default();
+    let handler = WorkerReceiverHandler {
+        validator_breaker: Some(ValidatorCircuitBreaker::new(2, cooldown)),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            client,
+            store.clone(),
+            SlowValidator {
+                delay: Duration::ZERO,
+            },
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let report = |batch: &Batch| {
+        handler.report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: batch.clone(),
+        }))
+    };
+
+    // Consecutive failed validations trip the breaker.
+    for i in 0..2u8 {
+        let invalid_batch = Batch::new(vec![vec![], vec![i]]);
+        let status = report(&invalid_batch).await.unwrap_err();
+        assert_eq!(status.status(), StatusCode::BadRequest);
+    }
+
+    // Batches are then rejected without validation until the cooldown ends.
+    let valid_batch = Batch::new(vec![vec![1]]);
+    let status = report(&valid_batch).await.unwrap_err();
+    assert_eq!(status.status(), StatusCode::ServiceUnavailable);
+    assert!(!store.contains_key(&valid_batch.digest()).unwrap());
+
+    // A successful probe closes the breaker.
+    tokio::time::sleep(cooldown).await;
+    report(&valid_batch).await.unwrap();
+    assert!(store.contains_key(&valid_batch.digest()).unwrap());
+    report(&Batch::new(vec![vec![2]])).await.unwrap();
+}
+
+#[tokio::test]
+async fn complete_batch_responses_are_cacheable() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    store.insert(&batch.digest(), &batch).unwrap();
+    let missing_digest = Batch::new(vec![vec![42]]).digest();
+
+    let handler = WorkerReceiverHandler::new(
+        authority_id,
+        0,
+        NetworkClient::new_with_empty_id(),
+        store,
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    );
+    fn cache_control<T>(response: &anemo::Response<T>) -> Option<String> {
+        response.headers().get(CACHE_CONTROL_HEADER_KEY).cloned()
+    }
+    let immutable = Some(IMMUTABLE_CACHE_CONTROL.to_owned());
+
+    // Found batches are cacheable, missing ones are not.
+    let response = handler
+        .request_batch(anemo::Request::new(RequestBatchRequest {
+            batch: batch.digest(),
+        }))
+        .await
+        .unwrap();
+    assert_eq!(cache_control(&response), immutable);
+    let response = handler
+        .request_batch(anemo::Request::new(RequestBatchRequest {
+            batch: missing_digest,
+        }))
+        .await
+        .unwrap();
+    assert_eq!(cache_control(&response), None);
+
+    // Only responses holding every requested batch are cacheable.
+    let response = handler
+        .request_batches(anemo::Request::new(RequestBatchesRequest {
+            batch_digests: vec![batch.digest()],
+        }))
+        .await
+        .unwrap();
+    assert_eq!(cache_control(&response), immutable);
+    let response = handler
+        .request_batches(anemo::Request::new(RequestBatchesRequest {
+            batch_digests: vec![batch.digest(), missing_digest],
+        }))
+        .await
+        .unwrap();
+    assert_eq!(cache_control(&response), None);
+}
+
+#[tokio::test]
+async fn request_batches_records_size_limit_events() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batches: Vec<_> = (0..10u8).map(|i| Batch::new(vec![vec![i]])).collect();
+    for batch in &batches {
+        store.insert(&batch.digest(), batch).unwrap();
+    }
+
+    let size_limit_events = SizeLimitEvents::new(2);
+    let handler = WorkerReceiverHandler {
+        max_request_batches_response_count: 4,
+        size_limit_events: size_limit_events.clone(),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let request_batches = |count: usize| {
+        let request = anemo::Request::new(RequestBatchesRequest {
+            batch_digests: batches[..count]
+                .iter()
+                .map(|batch| batch.digest())
+                .collect(),
+        });
+        handler.request_batches(request)
+    };
+
+    // Requests served in full are not recorded.
+    request_batches(3).await.unwrap();
+    assert!(size_limit_events.recent().is_empty());
+
+    // Size limited ones are, and only the most recent are kept.
+    for count in [5, 6, 7] {
+        request_batches(count).await.unwrap();
+    }
+    let events = size_limit_events.recent();
+    assert_eq!(
+        events
+            .iter()
+            .map(|event| (event.requested, event.served))
+            .collect::<Vec<_>>(),
+        vec![(6, 4), (7, 4)]
+    );
+    assert!(events.iter().all(|event| event.peer.is_none()));
+}
+
+#[tokio::test]
+async fn hanging_store_operations_time_out() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = TestBatchStore::default();
+    let batch = test_utils::batch();
+    store.insert(&batch.digest(), &batch).unwrap();
+
+    let handler = WorkerReceiverHandler {
+        store_timeout: Some(Duration::from_millis(100)),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let request_batch = || {
+        handler.request_batch(anemo::Request::new(RequestBatchRequest {
+            batch: batch.digest(),
+        }))
+    };
+
+    // While the store hangs, reads fail as retriable instead of hanging the handler.
+    let gate = store.gate.write().unwrap();
+    let status = tokio::time::timeout(Duration::from_secs(5), request_batch())
+        .await
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::ServiceUnavailable);
+
+    // Once the store recovers, reads succeed again.
+    drop(gate);
+    let response = request_batch().await.unwrap().into_body();
+    assert_eq!(response.batch, Some(batch.clone()));
+}
+
+#[tokio::test]
+async fn handlers_honor_request_deadlines() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+
+    let store = TestBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    // Store operations time out long after the callers' deadline.
+    let store_timeout = Duration::from_secs(60);
+    let worker_handler = WorkerReceiverHandler {
+        store_timeout: Some(store_timeout),
+        inherit_request_deadline: true,
+        ..WorkerReceiverHandler::new(
+            sync.authority().id(),
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let primary_handler = sync
+        .handler(0, store.clone(), TrivialTransactionValidator)
+        .inherit_request_deadline(true)
+        .store_timeout(store_timeout)
+        .build()
+        .unwrap();
+
+    let deadline = Duration::from_millis(100);
+    let gate = store.gate.write().unwrap();
+    let results = tokio::time::timeout(Duration::from_secs(5), async {
+        vec![
+            worker_handler
+                .request_batch(
+                    anemo::Request::new(RequestBatchRequest { batch: digest })
+                        .with_timeout(deadline),
+                )
+                .await
+                .map(|_| ()),
+            worker_handler
+                .request_batches(
+                    anemo::Request::new(RequestBatchesRequest {
+                        batch_digests: vec![digest],
+                    })
+                    .with_timeout(deadline),
+                )
+                .await
+                .map(|_| ()),
+            worker_handler
+                .batch_sizes(
+                    anemo::Request::new(BatchSizesRequest {
+                        batch_digests: vec![digest],
+                    })
+                    .with_timeout(deadline),
+                )
+                .await
+                .map(|_| ()),
+            primary_handler
+                .synchronize(
+                    anemo::Request::new(WorkerSynchronizeMessage {
+                        digests: vec![digest],
+                        target: sync.target().id(),
+                        is_certified: false,
+                    })
+                    .with_timeout(deadline),
+                )
+                .await
+                .map(|_| ()),
+        ]
+    })
+    .await
+    .expect("Handlers should return once the deadline passes");
+    for result in results {
+        let status = result.unwrap_err();
+        assert_eq!(status.status(), StatusCode::ServiceUnavailable);
+        assert!(status.message().unwrap().contains("deadline"));
+    }
+
+    // Without a deadline, reads wait for the store.
+    drop(gate);
+    let response = worker_handler
+        .request_batch(anemo::Request::new(RequestBatchRequest { batch: digest }))
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batch, Some(batch));
+}
+
+#[tokio::test]
+async fn request_batches_defers_digests_past_deadline() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Five chunks of digests, each taking 200ms to read.
+    let store = TestBatchStore::default().with_read_latency(Duration::from_millis(200));
+    let digests: Vec<_> = (0..5 * BATCH_DIGESTS_READ_CHUNK_SIZE as u32)
+        .map(|i| {
+            let batch = Batch::new(vec![i.to_le_bytes().to_vec()]);
+            let digest = batch.digest();
+            store.insert(&digest, &batch).unwrap();
+            digest
+        })
+        .collect();
+
+    let worker_handler = WorkerReceiverHandler {
+        store_timeout: Some(Duration::from_secs(60)),
+        inherit_request_deadline: true,
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    // The deadline leaves time for some chunks only.
+    let response = worker_handler
+        .request_batches_v2(
+            anemo::Request::new(RequestBatchesV2Request::from(RequestBatchesRequest {
+                batch_digests: digests.clone(),
+            }))
+            .with_timeout(Duration::from_secs(1)),
+        )
+        .await
+        .expect("Partial responses should not fail")
+        .into_body();
+    assert!(!response.batches.is_empty());
+    assert!(!response.deferred_digests.is_empty());
+    assert!(!response.is_size_limit_reached);
+
+    // The batches read and the deferred digests split the request in order.
+    let read: Vec<_> = response
+        .batches
+        .iter()
+        .map(|batch| batch.digest())
+        .collect();
+    assert_eq!(read, digests[..read.len()]);
+    assert_eq!(response.deferred_digests, digests[read.len()..]);
+
+    // Legacy requesters are told to request the deferred digests again.
+    assert!(RequestBatchesResponse::from(response).is_size_limit_reached);
+}
+
+#[tokio::test]
+async fn get_batch_matches_request_batch() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let stored = Batch::new(vec![vec![1]]);
+    let deleted = Batch::new(vec![vec![2]]);
+    let missing = Batch::new(vec![vec![3]]);
+    store.insert(&stored.digest(), &stored).unwrap();
+    store.insert(&deleted.digest(), &deleted).unwrap();
+    let tombstones = BatchTombstones::new(Duration::from_secs(60));
+    tombstones.mark(&[deleted.digest()]).unwrap();
+
+    let handler = WorkerReceiverHandler {
+        tombstones: Some(tombstones),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    for (batch, expected) in [
+        (&stored, Some(stored.clone())),
+        (&deleted, None),
+        (&missing, None),
+    ] {
+        let digest = batch.digest();
+        let response = handler
+            .request_batch(anemo::Request::new(RequestBatchRequest { batch: digest }))
+            .await
+            .unwrap()
+            .into_body();
+        assert_eq!(response.batch, expected);
+        assert_eq!(handler.get_batch(&digest).await.unwrap(), expected);
+    }
+}
+
+#[tokio::test]
+async fn tombstoned_batches_are_not_served_in_bulk() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let stored = Batch::new(vec![vec![1]]);
+    let deleted = Batch::new(vec![vec![2]]);
+    store.insert(&stored.digest(), &stored).unwrap();
+    store.insert(&deleted.digest(), &deleted).unwrap();
+    let tombstones = BatchTombstones::new(Duration::from_secs(60));
+    tombstones.mark(&[deleted.digest()]).unwrap();
+
+    let handler = WorkerReceiverHandler {
+        tombstones: Some(tombstones),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    let response = handler
+        .intersect_batches(anemo::Request::new(IntersectBatchesRequest {
+            batch_digests: vec![stored.digest(), deleted.digest()],
+        }))
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batch_digests, vec![stored.digest()]);
+
+    let session_id = handler
+        .open_bulk_sync(anemo::Request::new(OpenBulkSyncRequest {}))
+        .await
+        .unwrap()
+        .into_body()
+        .session_id;
+    let page = handler
+        .request_bulk_sync_page(anemo::Request::new(RequestBulkSyncPageRequest {
+            session_id,
+            cursor: None,
+        }))
+        .await
+        .unwrap()
+        .into_body();
+    assert!(page.is_complete);
+    assert_eq!(page.batches, vec![stored]);
+}
+
+#[tokio::test]
+async fn synchronize_restores_tombstoned_batches() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+    let tombstones = BatchTombstones::new(Duration::from_secs(60));
+    tombstones.mark(&[digest]).unwrap();
+
+    let handler = sync
+        .handler(0, store, TrivialTransactionValidator)
+        .tombstones(tombstones.clone())
+        .build()
+        .unwrap();
+
+    // The batch is still stored, so it is served again without fetching it from the target,
+    // which is not even reachable.
+    handler
+        .synchronize(anemo::Request::new(WorkerSynchronizeMessage {
+            digests: vec![digest],
+            target: sync.target().id(),
+            is_certified: false,
+        }))
+        .await
+        .unwrap();
+    assert!(!tombstones.contains(&digest));
+}
+
+#[tokio::test]
+async fn report_batch_declines_batches_of_already_stored_transactions() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Mock the primary client to always succeed.
+    let client = NetworkClient::new_with_empty_id();
+    let mut mock_server = MockWorkerToPrimary::new();
+    mock_server
+        .expect_report_others_batch()
+        .returning(|_| Ok(anemo::Response::new(())));
+    client.set_worker_to_primary_local_handler(Arc::new(mock_server));
+
+    let store = MemoryBatchStore::default();
+    let handler = WorkerReceiverHandler {
+        tx_dedup: Some(TransactionDedup::default()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            client,
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let report = |batch: &Batch| {
+        handler.report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: batch.clone(),
+        }))
+    };
+
+    let first = Batch::new(vec![vec![1], vec![2]]);
+    report(&first).await.unwrap();
+    assert!(store.contains_key(&first.digest()).unwrap());
+
+    // A different batch of the same transactions is declined.
+    let reordered = Batch::new(vec![vec![2], vec![1]]);
+    let status = report(&reordered).await.unwrap_err();
+    assert_eq!(status.status(), StatusCode::BadRequest);
+    assert!(!store.contains_key(&reordered.digest()).unwrap());
+
+    // A batch with any new transaction is stored.
+    let partially_new = Batch::new(vec![vec![1], vec![3]]);
+    report(&partially_new).await.unwrap();
+    assert!(store.contains_key(&partially_new.digest()).unwrap());
+
+    // Reporting an already stored batch again is still acknowledged.
+    report(&first).await.unwrap();
+}
+
+#[tokio::test]
+async fn report_batches_validates_concurrently() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // Mock the primary client to always succeed.
+    let client = NetworkClient::new_with_empty_id();
+    let mut mock_server = MockWorkerToPrimary::new();
+    mock_server
+        .expect_report_others_batch()
+        .returning(|_| Ok(anemo::Response::new(())));
+    client.set_worker_to_primary_local_handler(Arc::new(mock_server));
+
+    let delay = Duration::from_millis(500);
+    let store = MemoryBatchStore::default();
+    let handler = WorkerReceiverHandler {
+        report_batches_parallelism: 4,
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            client,
+            store.clone(),
+            SlowValidator { delay },
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    // Batches whose first transaction is empty are invalid.
+    let batches = vec![
+        Batch::new(vec![vec![1]]),
+        Batch::new(vec![vec![]]),
+        Batch::new(vec![vec![2]]),
+        Batch::new(vec![vec![], vec![3]]),
+    ];
+    let start = Instant::now();
+    let response = handler
+        .report_batches(anemo::Request::new(WorkerBatchesMessage {
+            batches: batches.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_body();
+    // Validating the batches one by one would take 4 delays.
+    assert!(start.elapsed() < delay * 2);
+
+    // Errors are reported in the order of the batches, and only valid batches are stored.
+    let rejected = response.errors.iter().map(Option::is_some).collect_vec();
+    assert_eq!(rejected, vec![false, true, false, true]);
+    for (batch, rejected) in batches.iter().zip(rejected) {
+        assert_eq!(store.contains_key(&batch.digest()).unwrap(), !rejected);
+    }
+}
+
+#[tokio::test]
+async fn deleted_batches_can_be_restored_during_grace_period() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    let grace_period = Duration::from_millis(500);
+    let tombstones = BatchTombstones::new(grace_period);
+    let primary_handler = PrimaryReceiverHandler::builder(
+        authority_id,
+        0,
+        fixture.committee(),
+        fixture.worker_cache(),
+        store.clone(),
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    )
+    .tombstones(tombstones.clone())
+    .build_legacy_rpc()
+    .unwrap();
+    let worker_handler = WorkerReceiverHandler {
+        tombstones: Some(tombstones),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let request_batch =
+        || worker_handler.request_batch(anemo::Request::new(RequestBatchRequest { batch: digest }));
+    let delete_batch = || {
+        primary_handler.delete_batches(anemo::Request::new(WorkerDeleteBatchesMessage {
+            digests: vec![digest],
+        }))
+    };
+
+    // A deleted batch is no longer served, but stays stored until restored.
+    delete_batch().await.unwrap();
+    assert_eq!(request_batch().await.unwrap().into_body().batch, None);
+    assert!(store.contains_key(&digest).unwrap());
+    assert_eq!(primary_handler.restore_batches(&[digest]), 1);
+    tokio::time::sleep(grace_period * 2).await;
+    assert_eq!(
+        request_batch().await.unwrap().into_body().batch,
+        Some(batch)
+    );
+
+    // Once the grace period elapses, the batch is removed.
+    delete_batch().await.unwrap();
+    tokio::time::sleep(grace_period * 2).await;
+    assert!(!store.contains_key(&digest).unwrap());
+    assert_eq!(primary_handler.restore_batches(&[digest]), 0);
+}
+
+#[tokio::test]
+async fn tombstones_are_persisted() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batches: Vec<_> = (0..3u8).map(|i| Batch::new(vec![vec![i; 10]])).collect();
+    for batch in &batches {
+        store.insert(&batch.digest(), batch).unwrap();
+    }
+    let tombstone_store = test_utils::create_batch_tombstone_store();
+    let handler = |tombstones| {
+        PrimaryReceiverHandler::builder(
+            authority_id,
+            0,
+            fixture.committee(),
+            fixture.worker_cache(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+        .tombstones(tombstones)
+        .build_legacy_rpc()
+        .unwrap()
+    };
+    let grace_period = Duration::from_secs(60);
+
+    // Deleted batches are tombstoned until restored.
+    let handler_before_restart = handler(BatchTombstones::persisted(
+        grace_period,
+        tombstone_store.clone(),
+    ));
+    handler_before_restart
+        .delete_batches(anemo::Request::new(WorkerDeleteBatchesMessage {
+            digests: batches.iter().map(|batch| batch.digest()).collect(),
+        }))
+        .await
+        .unwrap();
+    assert_eq!(
+        handler_before_restart.restore_batches(&[batches[0].digest()]),
+        1
+    );
+    assert!(!store::Map::contains_key(&tombstone_store, &batches[0].digest()).unwrap());
+    assert!(store::Map::contains_key(&tombstone_store, &batches[1].digest()).unwrap());
+
+    // After a restart, the other batches are still not served, and removed once the rest of
+    // their grace period elapses.
+    let tombstone_of_elapsed_grace_period = types::now();
+    store::Map::insert(
+        &tombstone_store,
+        &batches[2].digest(),
+        &tombstone_of_elapsed_grace_period,
+    )
+    .unwrap();
+    let tombstones = BatchTombstones::persisted(grace_period, tombstone_store.clone());
+    handler(tombstones.clone())
+        .resume_tombstoned_removals()
+        .unwrap();
+    assert!(!tombstones.contains(&batches[0].digest()));
+    assert!(tombstones.contains(&batches[1].digest()));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(store.contains_key(&batches[0].digest()).unwrap());
+    assert!(store.contains_key(&batches[1].digest()).unwrap());
+    assert!(!store.contains_key(&batches[2].digest()).unwrap());
+    assert!(!store::Map::contains_key(&tombstone_store, &batches[2].digest()).unwrap());
+}
+
+#[tokio::test]
+async fn failed_removals_of_tombstoned_batches_are_retried() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = TestBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+    store.multi_remove_failures.store(1, Ordering::SeqCst);
+
+    let grace_period = Duration::from_millis(100);
+    let tombstones = BatchTombstones::new(grace_period);
+    let handler = PrimaryReceiverHandler::builder(
+        authority_id,
+        0,
+        fixture.committee(),
+        fixture.worker_cache(),
+        store.clone(),
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    )
+    .tombstones(tombstones.clone())
+    .build_legacy_rpc()
+    .unwrap();
+    handler
+        .delete_batches(anemo::Request::new(WorkerDeleteBatchesMessage {
+            digests: vec![digest],
+        }))
+        .await
+        .unwrap();
+
+    // The first removal fails, and the batch stays tombstoned until the retry succeeds.
+    tokio::time::sleep(grace_period * 2).await;
+    assert_eq!(store.multi_remove_failures.load(Ordering::SeqCst), 0);
+    assert!(store.contains_key(&digest).unwrap());
+    assert!(tombstones.contains(&digest));
+    tokio::time::sleep(TOMBSTONE_REMOVAL_RETRY_DELAY * 2).await;
+    assert!(!store.contains_key(&digest).unwrap());
+    assert!(!tombstones.contains(&digest));
+}
+
+#[tokio::test]
+async fn method_concurrency_limits_are_independent() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let handler = PrimaryReceiverHandler::builder(
+        authority_id,
+        0,
+        fixture.committee(),
+        fixture.worker_cache(),
+        MemoryBatchStore::default(),
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    )
+    .method_concurrency_limits(MethodConcurrencyLimits {
+        synchronize: Some(1),
+        fetch_batches: Some(1),
+        delete_batches: Some(1),
+        over_limit: OverLimitPolicy::Reject,
+    })
+    .build_legacy_rpc()
+    .unwrap();
+    let synchronize = || {
+        handler.synchronize(anemo::Request::new(WorkerSynchronizeMessage {
+            digests: vec![],
+            target: authority_id,
+            is_certified: false,
+        }))
+    };
+    let fetch_batches = || {
+        handler.fetch_batches(anemo::Request::new(FetchBatchesRequest {
+            digests: HashSet::new(),
+            known_workers: HashSet::new(),
+        }))
+    };
+    let delete_batches = || {
+        handler.delete_batches(anemo::Request::new(WorkerDeleteBatchesMessage {
+            digests: vec![],
+        }))
+    };
+    let is_over_limit = |status: anemo::rpc::Status| status.status() == StatusCode::TooManyRequests;
+
+    // A saturated synchronize doesn't affect the other methods. Neither synchronize nor
+    // fetch_batches are served via the legacy RPC, but their limit is checked first.
+    let synchronize_permit = handler
+        .method_permits
+        .acquire(PrimaryToWorkerMethod::Synchronize)
+        .await
+        .unwrap();
+    assert!(is_over_limit(synchronize().await.unwrap_err()));
+    assert!(!is_over_limit(fetch_batches().await.unwrap_err()));
+    delete_batches().await.unwrap();
+
+    // Neither does a saturated delete_batches.
+    drop(synchronize_permit);
+    let _delete_batches_permit = handler
+        .method_permits
+        .acquire(PrimaryToWorkerMethod::DeleteBatches)
+        .await
+        .unwrap();
+    assert!(is_over_limit(delete_batches().await.unwrap_err()));
+    assert!(!is_over_limit(synchronize().await.unwrap_err()));
+    assert!(!is_over_limit(fetch_batches().await.unwrap_err()));
+}
+
+#[tokio::test]
+async fn read_transform_only_applies_to_designated_peers() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    // Light clients get batches with every transaction redacted.
+    let light_client = anemo::PeerId([1; 32]);
+    let worker_peer = anemo::PeerId([2; 32]);
+    let redact = |batch: Batch| {
+        Batch::new(
+            batch
+                .transactions()
+                .iter()
+                .map(|_| b"redacted".to_vec())
+                .collect(),
+        )
+    };
+    let handler = WorkerReceiverHandler {
+        read_transform: Some(BatchReadTransform::new([light_client], redact)),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    for peer in [light_client, worker_peer] {
+        let expected = if peer == light_client {
+            redact(batch.clone())
+        } else {
+            batch.clone()
+        };
+
+        let mut request = anemo::Request::new(RequestBatchRequest { batch: digest });
+        request.extensions_mut().insert(peer);
+        let response = handler.request_batch(request).await.unwrap();
+        // Transformed batches are not cacheable, since they differ between peers.
+        assert_eq!(
+            response.headers().contains_key(CACHE_CONTROL_HEADER_KEY),
+            peer == worker_peer
+        );
+        assert_eq!(response.into_body().batch, Some(expected.clone()));
+
+        let mut request = anemo::Request::new(RequestBatchesRequest {
+            batch_digests: vec![digest],
+        });
+        request.extensions_mut().insert(peer);
+        let response = handler.request_batches(request).await.unwrap();
+        assert_eq!(response.into_body().batches, vec![expected]);
+    }
+}
+
+#[tokio::test]
+async fn report_batch_without_primary() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // No primary handler is installed on the client.
+    let store = MemoryBatchStore::default();
+    let handler = WorkerReceiverHandler {
+        notify_primary: false,
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    let batch = test_utils::batch();
+    handler
+        .report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: batch.clone(),
+        }))
+        .await
+        .unwrap();
+    assert_eq!(store.get(&batch.digest()).unwrap(), Some(batch));
+}
+
+#[tokio::test]
+async fn reset_peer_rate_limit() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    // A single request, which is not regained during the test.
+    let peer_rate_limits = PeerRateLimits::new(0.0, 1);
+    let handler = WorkerReceiverHandler {
+        peer_rate_limits: Some(peer_rate_limits.clone()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let peer = anemo::PeerId([1; 32]);
+    let request_batch = || {
+        let mut request = anemo::Request::new(RequestBatchRequest { batch: digest });
+        request.extensions_mut().insert(peer);
+        handler.request_batch(request)
+    };
+
+    request_batch().await.unwrap();
+    let status = request_batch().await.unwrap_err();
+    assert_eq!(status.status(), StatusCode::TooManyRequests);
+    let near_limit = peer_rate_limits.near_limit();
+    assert_eq!(near_limit.len(), 1);
+    assert_eq!(near_limit[0].peer, peer);
+
+    // Resetting the peer's bucket lets it make requests again.
+    assert!(peer_rate_limits.reset(&peer));
+    assert!(peer_rate_limits.near_limit().is_empty());
+    request_batch().await.unwrap();
+}
+
+#[tokio::test]
+async fn rate_limit_every_method() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    // A single request, which is not regained during the test.
+    let handler = WorkerReceiverHandler {
+        peer_rate_limits: Some(PeerRateLimits::new(0.0, 1)),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    fn from_peer<T>(body: T) -> anemo::Request<T> {
+        let mut request = anemo::Request::new(body);
+        request.extensions_mut().insert(anemo::PeerId([1; 32]));
+        request
+    }
+
+    // The first request takes the token, whatever the method.
+    handler
+        .batch_sizes(from_peer(BatchSizesRequest {
+            batch_digests: vec![digest],
+        }))
+        .await
+        .unwrap();
+
+    // Every other method is refused the peer's requests from then on.
+    let statuses = [
+        handler
+            .intersect_batches(from_peer(IntersectBatchesRequest {
+                batch_digests: vec![digest],
+            }))
+            .await
+            .unwrap_err(),
+        handler
+            .batch_sizes(from_peer(BatchSizesRequest {
+                batch_digests: vec![digest],
+            }))
+            .await
+            .unwrap_err(),
+        handler
+            .request_batch_metadata(from_peer(RequestBatchMetadataRequest {
+                batch_digests: vec![digest],
+            }))
+            .await
+            .unwrap_err(),
+        handler
+            .open_bulk_sync(from_peer(OpenBulkSyncRequest {}))
+            .await
+            .unwrap_err(),
+        handler
+            .request_bulk_sync_page(from_peer(RequestBulkSyncPageRequest {
+                session_id: 0,
+                cursor: None,
+            }))
+            .await
+            .unwrap_err(),
+        handler
+            .sample_batches(from_peer(SampleBatchesRequest {
+                cursor: None,
+                stride: 1,
+                max_samples: 1,
+            }))
+            .await
+            .unwrap_err(),
+    ];
+    for status in statuses {
+        assert_eq!(status.status(), StatusCode::TooManyRequests);
+    }
+}
+
+#[tokio::test]
+async fn serve_observer_peers_metadata_only() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    // Observers get a single request, which is not regained during the test.
+    let committee_peer = anemo::PeerId([1; 32]);
+    let observer_peer = anemo::PeerId([2; 32]);
+    let handler = WorkerReceiverHandler {
+        peer_roles: Some(PeerRoles::new(
+            [committee_peer],
+            PeerRateLimits::new(0.0, 1),
+        )),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let roles = handler.peer_roles.as_ref().unwrap();
+    assert_eq!(roles.role(Some(&committee_peer)), PeerRole::Committee);
+    assert_eq!(roles.role(Some(&observer_peer)), PeerRole::Observer);
+    assert_eq!(roles.role(None), PeerRole::Committee);
+
+    let request_batch = |peer| {
+        let mut request = anemo::Request::new(RequestBatchRequest { batch: digest });
+        request.extensions_mut().insert(peer);
+        handler.request_batch(request)
+    };
+    let batch_sizes = |peer| {
+        let mut request = anemo::Request::new(BatchSizesRequest {
+            batch_digests: vec![digest],
+        });
+        request.extensions_mut().insert(peer);
+        handler.batch_sizes(request)
+    };
+
+    // Observers are refused batches, but served metadata within their rate limit.
+    let status = request_batch(observer_peer).await.unwrap_err();
+    assert_eq!(status.status(), StatusCode::BadRequest);
+    batch_sizes(observer_peer).await.unwrap();
+    let status = batch_sizes(observer_peer).await.unwrap_err();
+    assert_eq!(status.status(), StatusCode::TooManyRequests);
+
+    // Committee peers are served fully, without the observer rate limits.
+    let response = request_batch(committee_peer).await.unwrap().into_body();
+    assert_eq!(response.batch, Some(batch));
+    batch_sizes(committee_peer).await.unwrap();
+    batch_sizes(committee_peer).await.unwrap();
+}
+
+#[tokio::test]
+async fn throttle_non_reciprocating_peer() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    // Peers are served at most twice what they report, after a grace of two batches.
+    let reciprocity = PeerReciprocity::new(2 * batch.size() as u64, 2.0);
+    let handler = WorkerReceiverHandler {
+        notify_primary: false,
+        reciprocity: Some(reciprocity.clone()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let peer = anemo::PeerId([1; 32]);
+    let request_batch = || {
+        let mut request = anemo::Request::new(RequestBatchRequest { batch: digest });
+        request.extensions_mut().insert(peer);
+        handler.request_batch(request)
+    };
+
+    // A peer that never reports batches is served until its grace is used up.
+    for _ in 0..3 {
+        request_batch().await.unwrap();
+    }
+    let status = request_batch().await.unwrap_err();
+    assert_eq!(status.status(), StatusCode::TooManyRequests);
+    assert_eq!(reciprocity.balance(&peer).received, 0);
+
+    // Reporting enough batches restores the peer.
+    let mut request = anemo::Request::new(WorkerBatchMessage {
+        batch: Batch::new(vec![vec![1; 2 * batch.size()]]),
+    });
+    request.extensions_mut().insert(peer);
+    handler.report_batch(request).await.unwrap();
+    request_batch().await.unwrap();
+
+    // Other peers are not affected.
+    let mut request = anemo::Request::new(RequestBatchRequest { batch: digest });
+    request.extensions_mut().insert(anemo::PeerId([2; 32]));
+    handler.request_batch(request).await.unwrap();
+}
+
+#[tokio::test]
+async fn bulk_sync_refused_to_non_reciprocating_peer() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    store.insert(&batch.digest(), &batch).unwrap();
+
+    let reciprocity = PeerReciprocity::new(0, 2.0);
+    let handler = WorkerReceiverHandler {
+        notify_primary: false,
+        reciprocity: Some(reciprocity.clone()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let peer = anemo::PeerId([1; 32]);
+    fn from_peer<T>(body: T) -> anemo::Request<T> {
+        let mut request = anemo::Request::new(body);
+        request.extensions_mut().insert(anemo::PeerId([1; 32]));
+        request
+    }
+
+    // Pages served to the peer count against its balance.
+    let session_id = handler
+        .open_bulk_sync(from_peer(OpenBulkSyncRequest {}))
+        .await
+        .unwrap()
+        .into_body()
+        .session_id;
+    handler
+        .request_bulk_sync_page(from_peer(RequestBulkSyncPageRequest {
+            session_id,
+            cursor: None,
+        }))
+        .await
+        .unwrap();
+    assert_eq!(reciprocity.balance(&peer).served, batch.size() as u64);
+
+    // Without reporting batches back, the peer is refused further pages and sessions.
+    let status = handler
+        .request_bulk_sync_page(from_peer(RequestBulkSyncPageRequest {
+            session_id,
+            cursor: None,
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::TooManyRequests);
+    let status = handler
+        .open_bulk_sync(from_peer(OpenBulkSyncRequest {}))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::TooManyRequests);
+}
+
+#[tokio::test]
+async fn request_batch_returns_certificate_to_light_clients() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let committee = fixture.committee();
+    let worker_cache = fixture.worker_cache();
+    let authority_id = fixture.authorities().next().unwrap().id();
+    let id = 0;
+
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    // The primary attached the certificate including the batch to a synchronize request.
+    let header = Header::V1(
+        fixture
+            .authorities()
+            .nth(1)
+            .unwrap()
+            .header_builder(&committee)
+            .with_payload_batch(batch.clone(), id, 0)
+            .build()
+            .unwrap(),
+    );
+    let certificate = fixture.certificate(&header);
+    let light_client = anemo::PeerId([1; 32]);
+    let batch_certificates = BatchCertificates::new([light_client], 10);
+    batch_certificates.record(Arc::new(certificate.clone()), id);
+
+    let handler = WorkerReceiverHandler {
+        notify_primary: false,
+        batch_certificates: Some(batch_certificates),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            id,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let request_batch = |peer, include_certificate| {
+        let mut request = anemo::Request::new(RequestBatchV2Request {
+            batch: digest,
+            include_certificate,
+            range: None,
+            caller_has_batch: false,
+        });
+        request.extensions_mut().insert(peer);
+        handler.request_batch_v2(request)
+    };
+
+    // The certificate proves the served batch is certified.
+    let response = request_batch(light_client, true).await.unwrap().into_body();
+    assert_eq!(response.batch, Some(batch.clone()));
+    assert_eq!(response.certificate, Some(certificate));
+    assert!(response.verify_certificate(&digest, &committee, &worker_cache));
+    let other_digest = Batch::new(vec![vec![1]]).digest();
+    assert!(!response.verify_certificate(&other_digest, &committee, &worker_cache));
+
+    // Certificates are only returned when asked for, and only to light clients.
+    let response = request_batch(light_client, false)
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.certificate, None);
+    let response = request_batch(anemo::PeerId([2; 32]), true)
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batch, Some(batch));
+    assert_eq!(response.certificate, None);
+}
+
+#[tokio::test]
+async fn locate_transactions() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let handler = WorkerReceiverHandler {
+        notify_primary: false,
+        index_transactions: true,
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    let batch = Batch::new(vec![vec![1; 10], vec![2; 10]]);
+    handler
+        .report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: batch.clone(),
+        }))
+        .await
+        .unwrap();
+
+    let locate = |transactions: Vec<Vec<u8>>| {
+        handler.locate_transactions(anemo::Request::new(LocateTransactionsRequest {
+            transaction_digests: transactions
+                .iter()
+                .map(|transaction| transaction_digest(transaction))
+                .collect(),
+        }))
+    };
+
+    // Both transactions of the reported batch are located, unlike unknown transactions.
+    let response = locate(vec![vec![1; 10], vec![3; 10], vec![2; 10]])
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(
+        response.batch_digests,
+        vec![Some(batch.digest()), None, Some(batch.digest())]
+    );
+
+    // Removed batches are no longer located.
+    store.remove(&batch.digest()).unwrap();
+    let response = locate(vec![vec![1; 10]]).await.unwrap().into_body();
+    assert_eq!(response.batch_digests, vec![None]);
+}
+
+#[tokio::test]
+async fn validation_permits_bound_concurrent_validations() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let validator = ConcurrencyRecordingValidator::default();
+    let handler = WorkerReceiverHandler {
+        notify_primary: false,
+        validation_permits: Some(ValidationPermits::new(2)),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            validator.clone(),
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    // A burst of concurrent reports only validates two batches at once.
+    let batches = (0..8u8).map(|i| Batch::new(vec![vec![i]])).collect_vec();
+    let reports = batches.iter().map(|batch| {
+        handler.report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: batch.clone(),
+        }))
+    });
+    for result in futures::future::join_all(reports).await {
+        result.unwrap();
+    }
+    assert_eq!(validator.max_validating.load(Ordering::SeqCst), 2);
+    for batch in batches {
+        assert!(store.contains_key(&batch.digest()).unwrap());
+    }
+}
+
+#[tokio::test]
+async fn request_batch_prefetches_neighbors() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // The batches are stored behind the cache, without being cached.
+    let inner = MemoryBatchStore::default();
+    let store = CachedBatchStore::new(inner.clone(), BatchCacheConfig::default());
+    let prefetcher = BatchPrefetcher::new(1, BatchPrefetcher::DEFAULT_CAPACITY, 1);
+    let batches = (0..5u8).map(|i| Batch::new(vec![vec![i]])).collect_vec();
+    for batch in &batches {
+        inner.insert(&batch.digest(), batch).unwrap();
+        prefetcher.record(batch.digest());
+    }
+
+    let handler = WorkerReceiverHandler {
+        prefetcher: Some(prefetcher.clone()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    let response = handler
+        .request_batch(anemo::Request::new(RequestBatchRequest {
+            batch: batches[2].digest(),
+        }))
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batch, Some(batches[2].clone()));
+
+    // The batches stored right before and after the served one are cached in the background.
+    let neighbors = [batches[1].digest(), batches[3].digest()];
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !neighbors.iter().all(|digest| store.is_cached(digest)) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert!(!store.is_cached(&batches[0].digest()));
+    assert!(!store.is_cached(&batches[4].digest()));
+}
+
+#[tokio::test]
+async fn request_batch_in_ranges() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch = Batch::new(vec![vec![1; 100], vec![2; 200]]);
+    store.insert(&batch.digest(), &batch).unwrap();
+    let handler = WorkerReceiverHandler {
+        notify_primary: false,
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    let request_range = |offset, len| {
+        handler.request_batch_v2(anemo::Request::new(RequestBatchV2Request {
+            batch: batch.digest(),
+            include_certificate: false,
+            range: Some(ByteRange { offset, len }),
+            caller_has_batch: false,
+        }))
+    };
+
+    // Fetch the first half, then resume from where it stopped.
+    let first = request_range(0, 150).await.unwrap().into_body();
+    assert_eq!(first.batch, None);
+    let first = first.slice.unwrap();
+    assert_eq!(first.bytes.len(), 150);
+    let offset = first.offset + first.bytes.len() as u64;
+    let rest = request_range(offset, first.total_len)
+        .await
+        .unwrap()
+        .into_body()
+        .slice
+        .unwrap();
+    assert_eq!(rest.offset + rest.bytes.len() as u64, first.total_len);
+
+    let reassembled = BatchSlice::reassemble(&[first.clone(), rest]).unwrap();
+    assert_eq!(reassembled.digest(), batch.digest());
+    // Slices that do not cover the batch are not reassembled.
+    assert_eq!(BatchSlice::reassemble(&[first]), None);
+}
+
+#[tokio::test]
+async fn report_batch_rejects_undersized_batches() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let handler = WorkerReceiverHandler {
+        notify_primary: false,
+        min_batch_size: 100,
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    let report = |batch: &Batch| {
+        handler.report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: batch.clone(),
+        }))
+    };
+
+    let undersized_batch = Batch::new(vec![vec![1; 60], vec![2; 39]]);
+    let status = report(&undersized_batch).await.unwrap_err();
+    assert_eq!(status.status(), StatusCode::BadRequest);
+    assert_eq!(store.get(&undersized_batch.digest()).unwrap(), None);
+
+    let batch = Batch::new(vec![vec![1; 60], vec![2; 40]]);
+    report(&batch).await.unwrap();
+    assert_eq!(store.get(&batch.digest()).unwrap(), Some(batch));
+}
+
+#[tokio::test]
+async fn overlapping_synchronize_fetches_shared_batches_once() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    let store = MemoryBatchStore::default();
+
+    // The target worker serves any requested batch, counting the requests for each.
+    let target_primary = sync.target();
+    let batches: Vec<_> = (0..3u8).map(|i| Batch::new(vec![vec![i; 10]])).collect();
+    let (x, y, z) = (
+        batches[0].digest(),
+        batches[1].digest(),
+        batches[2].digest(),
+    );
+    let served: HashMap<_, _> = batches.iter().map(|b| (b.digest(), b.clone())).collect();
+    let fetches = Arc::new(Mutex::new(HashMap::<BatchDigest, usize>::new()));
+    let mut mock_server = MockWorkerToWorker::new();
+    let recorded_fetches = fetches.clone();
+    mock_server
+        .expect_request_batches()
+        .returning(move |request| {
+            let digests = request.body().batch_digests.clone();
+            let mut fetches = recorded_fetches.lock().unwrap();
+            for digest in &digests {
+                *fetches.entry(*digest).or_default() += 1;
+            }
+            Ok(anemo::Response::new(RequestBatchesResponse {
+                batches: digests
+                    .iter()
+                    .map(|digest| served[digest].clone())
+                    .collect(),
+                is_size_limit_reached: false,
+            }))
+        });
+    let _recv_network = sync.serve(target_primary.worker(id), mock_server).await;
+
+    let handler = sync
+        .handler(id, store.clone(), TrivialTransactionValidator)
+        .in_flight_syncs(InFlightSyncs::default())
+        .build()
+        .unwrap();
+
+    // Both calls need y.
+    let synchronize = |digests| {
+        handler.synchronize(anemo::Request::new(WorkerSynchronizeMessage {
+            digests,
+            target: target_primary.id(),
+            is_certified: false,
+        }))
+    };
+    let (first, second) = futures::join!(synchronize(vec![x, y]), synchronize(vec![y, z]));
+    first.unwrap();
+    second.unwrap();
+
+    for digest in [x, y, z] {
+        assert!(store.get(&digest).unwrap().is_some());
+        assert_eq!(fetches.lock().unwrap()[&digest], 1);
+    }
+    assert!(!handler.in_flight_syncs.as_ref().unwrap().is_in_flight(&y));
+}
+
+#[tokio::test]
+async fn request_batches_audit_logs_served_requests() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batches: Vec<_> = (0..10u8).map(|i| Batch::new(vec![vec![i]])).collect();
+    for batch in &batches {
+        store.insert(&batch.digest(), batch).unwrap();
+    }
+
+    let audit = RequestBatchesAudit::new(3);
+    let handler = WorkerReceiverHandler {
+        max_request_batches_response_count: 4,
+        request_batches_audit: Some(audit.clone()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let request_batches = |count: usize| {
+        let request = anemo::Request::new(RequestBatchesRequest {
+            batch_digests: batches[..count]
+                .iter()
+                .map(|batch| batch.digest())
+                .collect(),
+        });
+        handler.request_batches(request)
+    };
+
+    // Only the most recent calls are kept, oldest first.
+    for count in [1, 2, 3, 6] {
+        request_batches(count).await.unwrap();
+    }
+    let served = audit.recent();
+    assert_eq!(
+        served
+            .iter()
+            .map(|served| (
+                served.requested,
+                served.served,
+                served.bytes,
+                served.is_size_limit_reached
+            ))
+            .collect::<Vec<_>>(),
+        vec![(2, 2, 2, false), (3, 3, 3, false), (6, 4, 4, true)]
+    );
+    assert!(served
+        .windows(2)
+        .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+}
+
+#[tokio::test]
+async fn report_batch_acks_batches_rejected_by_primary() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // The primary rejects the first report, and fails the second.
+    let client = NetworkClient::new_with_empty_id();
+    let mut mock_server = MockWorkerToPrimary::new();
+    let mut statuses = vec![
+        anemo::rpc::Status::internal("primary store failed"),
+        anemo::rpc::Status::new_with_message(StatusCode::BadRequest, "unknown worker"),
+    ];
+    mock_server
+        .expect_report_others_batch()
+        .times(2)
+        .returning(move |_| Err(statuses.pop().unwrap()));
+    client.set_worker_to_primary_local_handler(Arc::new(mock_server));
+
+    let store = MemoryBatchStore::default();
+    let handler = WorkerReceiverHandler::new(
+        authority_id,
+        0,
+        client.clone(),
+        store.clone(),
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    );
+
+    // The batch is stored, and reporting it again would be rejected all the same.
+    let batch = Batch::new(vec![vec![1; 10]]);
+    handler
+        .report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: batch.clone(),
+        }))
+        .await
+        .unwrap();
+    assert_eq!(store.get(&batch.digest()).unwrap(), Some(batch));
+
+    // Other failures may pass on retry, so they still fail the report.
+    let batch = Batch::new(vec![vec![2; 10]]);
+    let status = handler
+        .report_batch(anemo::Request::new(WorkerBatchMessage { batch }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::InternalServerError);
+}
+
+#[tokio::test]
+async fn synchronize_from_explicit_target_worker() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    // Create a new test store.
+    let store = MemoryBatchStore::default();
+
+    let target_primary = sync.target();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    let message = WorkerSynchronizeV2Message {
+        digests: vec![digest],
+        target: target_primary.id(),
+        is_certified: false,
+        certificate: None,
+        target_worker_id: Some(1),
+    };
+
+    // The primary asks to sync from the target's worker 1, so its worker with our id is never
+    // contacted.
+    let mut lagging_server = MockWorkerToWorker::new();
+    lagging_server.expect_request_batches().never();
+    let mut holding_server = MockWorkerToWorker::new();
+    let mock_batch_response = batch.clone();
+    holding_server
+        .expect_request_batches()
+        .withf(move |request| request.body().batch_digests == vec![digest])
+        .return_once(move |_| {
+            Ok(anemo::Response::new(RequestBatchesResponse {
+                batches: vec![mock_batch_response],
+                is_size_limit_reached: false,
+            }))
+        });
+
+    let mut recv_networks = Vec::new();
+    for (worker_id, server) in [(0, lagging_server), (1, holding_server)] {
+        recv_networks.push(sync.serve(target_primary.worker(worker_id), server).await);
+    }
+
+    let handler = sync
+        .handler(id, store.clone(), TrivialTransactionValidator)
+        .synchronize_attempt_budget(1)
+        .build()
+        .unwrap();
+
+    // Send a sync request.
+    handler
+        .synchronize_v2(anemo::Request::new(message))
+        .await
+        .unwrap();
+
+    // Verify the batch fetched from worker 1 is now stored.
+    assert!(store.get(&digest).unwrap().is_some())
+}
+
+#[tokio::test]
+async fn synchronize_records_certificates_of_target_worker() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let committee = sync.fixture.committee();
+    let target_primary = sync.target();
+
+    // The batch is already stored, so nothing is fetched from the target.
+    let store = MemoryBatchStore::default();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    store.insert(&digest, &batch).unwrap();
+
+    // The batch was included by the target's worker 1.
+    let header = Header::V1(
+        target_primary
+            .header_builder(&committee)
+            .with_payload_batch(batch, 1, 0)
+            .build()
+            .unwrap(),
+    );
+    let certificate = sync.fixture.certificate(&header);
+    let batch_certificates = BatchCertificates::new([], 10);
+
+    let handler = sync
+        .handler(0, store, TrivialTransactionValidator)
+        .batch_certificates(batch_certificates.clone())
+        .build()
+        .unwrap();
+
+    let synchronize = |certificate| {
+        handler.synchronize_v2(anemo::Request::new(WorkerSynchronizeV2Message {
+            digests: vec![digest],
+            target: target_primary.id(),
+            is_certified: true,
+            certificate: Some(certificate),
+            target_worker_id: Some(1),
+        }))
+    };
+
+    // Certificates failing verification are not recorded.
+    let unsigned_certificate =
+        Certificate::new_unsigned(&committee, certificate.header().clone(), vec![]).unwrap();
+    synchronize(unsigned_certificate).await.unwrap();
+    assert_eq!(batch_certificates.get(&digest), None);
+
+    synchronize(certificate.clone()).await.unwrap();
+    assert_eq!(batch_certificates.get(&digest), Some(certificate));
+}
+
+#[tokio::test]
+async fn sample_batches_from_stored_digests() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let mut stored = HashSet::new();
+    for i in 0..50u8 {
+        let batch = Batch::new(vec![vec![i; 100]]);
+        store.insert(&batch.digest(), &batch).unwrap();
+        stored.insert(batch.digest());
+    }
+    // A batch stored under a key that is not its digest is not served, so never sampled.
+    let corrupted = Batch::new(vec![vec![u8::MAX; 100]]);
+    store
+        .insert(&BatchDigest::new([7; crypto::DIGEST_LENGTH]), &corrupted)
+        .unwrap();
+
+    let handler = WorkerReceiverHandler::new(
+        authority_id,
+        0,
+        NetworkClient::new_with_empty_id(),
+        store,
+        TrivialTransactionValidator,
+        Arc::new(WorkerMetrics::new(&Registry::new())),
+    );
+
+    // A sample starting from a random point holds distinct, stored digests.
+    let response = handler
+        .sample_batches(anemo::Request::new(SampleBatchesRequest {
+            cursor: None,
+            stride: 3,
+            max_samples: 10,
+        }))
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batch_digests.len(), 10);
+    let sampled: HashSet<_> = response.batch_digests.iter().copied().collect();
+    assert_eq!(sampled.len(), 10);
+    assert!(sampled.is_subset(&stored));
+    assert!(!sampled.contains(&corrupted.digest()));
+
+    // Sampling every batch wraps around the store and returns each stored digest once.
+    let response = handler
+        .sample_batches(anemo::Request::new(SampleBatchesRequest {
+            cursor: None,
+            stride: 1,
+            max_samples: 100,
+        }))
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batch_digests.len(), stored.len());
+    assert_eq!(
+        response.batch_digests.into_iter().collect::<HashSet<_>>(),
+        stored
+    );
+    assert_eq!(response.next_cursor, None);
+
+    // Over-sized samples are rejected.
+    assert!(handler
+        .sample_batches(anemo::Request::new(SampleBatchesRequest {
+            cursor: None,
+            stride: 1,
+            max_samples: 1_000_000,
+        }))
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn request_batch_falls_back_to_archive_store() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    // One batch was moved to the archive, the other is still in the hot store.
+    let store = MemoryBatchStore::default();
+    let archive_store = MemoryBatchStore::default();
+    let hot_batch = Batch::new(vec![vec![1; 100]]);
+    let archived_batch = Batch::new(vec![vec![2; 100]]);
+    store.insert(&hot_batch.digest(), &hot_batch).unwrap();
+    archive_store
+        .insert(&archived_batch.digest(), &archived_batch)
+        .unwrap();
+    let metrics = Arc::new(WorkerMetrics::new(&Registry::new()));
+    let handler = WorkerReceiverHandler {
+        archive_store: Some(archive_store.clone()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            metrics.clone(),
+        )
+    };
+    let archive_reads = |outcome| {
+        metrics
+            .archive_batch_reads
+            .with_label_values(&[outcome])
+            .get()
+    };
+
+    // The hot store is read first.
+    let response = handler
+        .request_batch(anemo::Request::new(RequestBatchRequest {
+            batch: hot_batch.digest(),
+        }))
+        .await
+        .unwrap();
+    assert_eq!(response.into_body().batch, Some(hot_batch.clone()));
+    assert_eq!(archive_reads("hit"), 0);
+
+    // A batch missing from the hot store is served from the archive.
+    let response = handler
+        .request_batch(anemo::Request::new(RequestBatchRequest {
+            batch: archived_batch.digest(),
+        }))
+        .await
+        .unwrap();
+    assert_eq!(response.into_body().batch, Some(archived_batch.clone()));
+    assert_eq!(archive_reads("hit"), 1);
+
+    // Also by request_batches, while batches missing from both stores are still absent.
+    let missing_digest = Batch::new(vec![vec![3; 100]]).digest();
+    let response = handler
+        .request_batches(anemo::Request::new(RequestBatchesRequest {
+            batch_digests: vec![hot_batch.digest(), archived_batch.digest(), missing_digest],
+        }))
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.batches, vec![hot_batch, archived_batch]);
+    assert_eq!(archive_reads("hit"), 2);
+    assert_eq!(archive_reads("miss"), 1);
+}
+
+#[tokio::test]
+async fn request_batch_confirms_batches_the_caller_has() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch = Batch::new(vec![vec![1; 100]]);
+    store.insert(&batch.digest(), &batch).unwrap();
+    let handler = WorkerReceiverHandler {
+        notify_primary: false,
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store,
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let request = |digest| {
+        anemo::Request::new(RequestBatchV2Request {
+            batch: digest,
+            include_certificate: false,
+            range: None,
+            caller_has_batch: true,
+        })
+    };
+
+    // The stored batch is confirmed without being sent.
+    let response = handler
+        .request_batch_v2(request(batch.digest()))
+        .await
+        .unwrap()
+        .into_body();
+    assert!(response.not_modified);
+    assert_eq!(response.batch, None);
+
+    // A batch the worker does not store is not confirmed.
+    let missing_digest = Batch::new(vec![vec![2; 100]]).digest();
+    let response = handler
+        .request_batch_v2(request(missing_digest))
+        .await
+        .unwrap()
+        .into_body();
+    assert!(!response.not_modified);
+    assert_eq!(response.batch, None);
+}
+
+#[tokio::test]
+async fn request_batches_reuses_pooled_buffers() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batches: Vec<_> = (0..3u8).map(|i| Batch::new(vec![vec![i; 100]])).collect();
+    for batch in &batches {
+        store.insert(&batch.digest(), batch).unwrap();
+    }
+    let pool = ResponseBufferPool::new(1);
+    let handler = WorkerReceiverHandler {
+        notify_primary: false,
+        response_buffers: Some(pool.clone()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    let missing_digest = Batch::new(vec![vec![3; 100]]).digest();
+    let digests: Vec<_> = batches
+        .iter()
+        .map(|batch| batch.digest())
+        .chain([missing_digest])
+        .collect();
+    for _ in 0..5 {
+        let response = handler
+            .request_batches(anemo::Request::new(RequestBatchesRequest {
+                batch_digests: digests.clone(),
+            }))
+            .await
+            .unwrap()
+            .into_body();
+        assert_eq!(response.batches, batches);
+    }
+    let response = handler
+        .request_batches_v2(anemo::Request::new(
+            RequestBatchesV2Request::new(digests.clone()).keyed_by_digest(),
+        ))
+        .await
+        .unwrap()
+        .into_body();
+    let expected: HashMap<_, _> = batches
+        .iter()
+        .map(|batch| (batch.digest(), batch.clone()))
+        .collect();
+    assert_eq!(response.batches_by_digest.unwrap(), expected);
+
+    // Sequential requests all share the buffers created for the first one.
+    assert_eq!(pool.created(), 1);
+    assert_eq!(pool.reused(), 5);
+}
+
+#[tokio::test]
+async fn capabilities_reflect_enabled_features() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let handler = WorkerReceiverHandler {
+        batch_insert_times: Some(BatchInsertTimes::default()),
+        notify_primary: false,
+        store_version: Some(StoreVersion::in_memory()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            MemoryBatchStore::default(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    let response = handler
+        .capabilities(anemo::Request::new(WorkerCapabilitiesRequest {}))
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(response.protocol_version, WORKER_PROTOCOL_VERSION);
+    assert_eq!(
+        response.features,
+        [WorkerFeature::BatchAges, WorkerFeature::StoreVersion]
+            .into_iter()
+            .collect()
+    );
+}
+
+#[tokio::test]
+async fn store_migration_serves_reads_from_snapshot_while_writes_are_paused() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batch = Batch::new(vec![vec![1; 100]]);
+    store.insert(&batch.digest(), &batch).unwrap();
+    let migration = StoreMigration::default();
+    let handler = WorkerReceiverHandler {
+        notify_primary: false,
+        store_migration: Some(migration.clone()),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    // The migration snapshot keeps serving the batch while the store is rewritten.
+    let snapshot = MemoryBatchStore::default();
+    snapshot.insert(&batch.digest(), &batch).unwrap();
+    migration.begin(snapshot).await;
+    store.remove(&batch.digest()).unwrap();
+    let response = handler
+        .request_batch(anemo::Request::new(RequestBatchRequest {
+            batch: batch.digest(),
+        }))
+        .await
+        .unwrap();
+    assert_eq!(response.into_body().batch, Some(batch.clone()));
+    let response = handler
+        .request_batches(anemo::Request::new(RequestBatchesRequest {
+            batch_digests: vec![batch.digest()],
+        }))
+        .await
+        .unwrap();
+    assert_eq!(response.into_body().batches, vec![batch.clone()]);
+
+    // Reported batches are refused until the migration ends.
+    let reported_batch = Batch::new(vec![vec![2; 100]]);
+    let status = handler
+        .report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: reported_batch.clone(),
+        }))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::ServiceUnavailable);
+    assert!(store.get(&reported_batch.digest()).unwrap().is_none());
+
+    // Afterwards, reads and writes go to the store again.
+    assert!(migration.end());
+    handler
+        .report_batch(anemo::Request::new(WorkerBatchMessage {
+            batch: reported_batch.clone(),
+        }))
+        .await
+        .unwrap();
+    assert!(store.get(&reported_batch.digest()).unwrap().is_some());
+    let response = handler
+        .request_batch(anemo::Request::new(RequestBatchRequest {
+            batch: batch.digest(),
+        }))
+        .await
+        .unwrap();
+    assert_eq!(response.into_body().batch, None);
+}
+
+#[tokio::test]
+async fn synchronize_propagates_trace_id() {
+    telemetry_subscribers::init_for_testing();
+
+    let sync = SyncFixture::new();
+    let id = 0;
+
+    // Create a new test store.
+    let store = MemoryBatchStore::default();
+
+    // Create network with mock behavior to respond to RequestBatches request.
+    let target_primary = sync.target();
+    let batch = test_utils::batch();
+    let digest = batch.digest();
+    let message = WorkerSynchronizeMessage {
+        digests: vec![digest],
+        target: target_primary.id(),
+        is_certified: false,
+    };
+
+    let mut mock_server = MockWorkerToWorker::new();
+    let mock_batch_response = batch.clone();
+    mock_server
+        .expect_request_batches()
+        .withf(move |request| {
+            request.body().batch_digests == vec![digest]
+                && request.headers().get(TRACE_ID_HEADER_KEY) == Some(&"trace-1".to_string())
+        })
+        .return_once(move |_| {
+            Ok(anemo::Response::new(RequestBatchesResponse {
+                batches: vec![mock_batch_response],
+                is_size_limit_reached: false,
+            }))
+        });
+    let _recv_network = sync.serve(target_primary.worker(id), mock_server).await;
+
+    let handler = sync
+        .handler(id, store.clone(), TrivialTransactionValidator)
+        .build()
+        .unwrap();
+
+    // The mock only serves the batch if the request carries the trace id of the sync request.
+    let mut request = anemo::Request::new(message);
+    request
+        .headers_mut()
+        .insert(TRACE_ID_HEADER_KEY.to_owned(), "trace-1".to_owned());
+    handler.synchronize(request).await.unwrap();
+    assert!(store.get(&digest).unwrap().is_some())
+}
+
+/// A validator verifying the signatures of many batches at once, counting its verifications.
+/// Batches whose first transaction is empty have an invalid signature.
+#[derive(Clone, Default)]
+struct BatchVerifyingValidator {
+    single_verifications: Arc<AtomicUsize>,
+    batch_verifications: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl TransactionValidator for BatchVerifyingValidator {
+    type Error = eyre::Report;
+
+    fn validate(&self, _tx: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn validate_batch(&self, batch: &Batch) -> Result<(), Self::Error> {
+        self.single_verifications.fetch_add(1, Ordering::SeqCst);
+        Self::verify(batch)
+    }
+
+    async fn validate_batches(&self, batches: &[&Batch]) -> Vec<Result<(), Self::Error>> {
+        self.batch_verifications.fetch_add(1, Ordering::SeqCst);
+        batches.iter().map(|batch| Self::verify(batch)).collect()
+    }
+}
+
+impl BatchVerifyingValidator {
+    fn verify(batch: &Batch) -> Result<(), eyre::Report> {
+        if batch
+            .transactions()
+            .first()
+            .map_or(true, |tx| tx.is_empty())
+        {
+            eyre::bail!("Invalid signature");
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn report_batches_validates_batches_together() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+    // Mock the primary client to always succeed.
+    let client = NetworkClient::new_with_empty_id();
+    let mut mock_server = MockWorkerToPrimary::new();
+    mock_server
+        .expect_report_others_batch()
+        .returning(|_| Ok(anemo::Response::new(())));
+    client.set_worker_to_primary_local_handler(Arc::new(mock_server));
+
+    let validator = BatchVerifyingValidator::default();
+    let store = MemoryBatchStore::default();
+    let handler = WorkerReceiverHandler {
+        report_batches_parallelism: 4,
+        validate_batches_together: true,
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            client,
+            store.clone(),
+            validator.clone(),
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+
+    let batches = vec![
+        Batch::new(vec![vec![1]]),
+        Batch::new(vec![vec![]]),
+        Batch::new(vec![vec![2]]),
+    ];
+    let response = handler
+        .report_batches(anemo::Request::new(WorkerBatchesMessage {
+            batches: batches.clone(),
+        }))
+        .await
+        .unwrap()
+        .into_body();
+
+    // The signatures of all the batches are verified with a single call.
+    assert_eq!(validator.batch_verifications.load(Ordering::SeqCst), 1);
+    assert_eq!(validator.single_verifications.load(Ordering::SeqCst), 0);
+    let rejected = response.errors.iter().map(Option::is_some).collect_vec();
+    assert_eq!(rejected, vec![false, true, false]);
+    for (batch, rejected) in batches.iter().zip(rejected) {
+        assert_eq!(store.contains_key(&batch.digest()).unwrap(), !rejected);
+    }
+}
+
+#[tokio::test]
+async fn request_batches_skips_batches_the_caller_already_has() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = MemoryBatchStore::default();
+    let batches: Vec<_> = (0..3u8).map(|i| Batch::new(vec![vec![i; 100]])).collect();
+    for batch in &batches {
+        store.insert(&batch.digest(), batch).unwrap();
+    }
+    let metrics = Arc::new(WorkerMetrics::new(&Registry::new()));
+    let handler = WorkerReceiverHandler::new(
+        authority_id,
+        0,
+        NetworkClient::new_with_empty_id(),
+        store.clone(),
+        TrivialTransactionValidator,
+        metrics.clone(),
+    );
+
+    // The caller fetched the second batch after building the request.
+    let request =
+        RequestBatchesV2Request::new(batches.iter().map(|batch| batch.digest()).collect())
+            .already_have([batches[1].digest()]);
+    let response = handler
+        .request_batches_v2(anemo::Request::new(request))
+        .await
+        .unwrap()
+        .into_body();
+    assert_eq!(
+        response.batches,
+        vec![batches[0].clone(), batches[2].clone()]
+    );
+    assert!(!response.is_size_limit_reached);
+    assert_eq!(metrics.request_batches_already_have_digests.get(), 1);
+
+    // Only a small set of digests can be skipped.
+    let request = RequestBatchesV2Request::new(vec![batches[0].digest()]).already_have(
+        (0..=RequestBatchesV2Request::MAX_ALREADY_HAVE_DIGESTS)
+            .map(|i| BatchDigest::new([(i % 256) as u8; crypto::DIGEST_LENGTH])),
+    );
+    let status = handler
+        .request_batches_v2(anemo::Request::new(request))
+        .await
+        .unwrap_err();
+    assert_eq!(status.status(), StatusCode::BadRequest);
+}
+
+#[tokio::test]
+async fn write_permits_bound_concurrent_report_writes() {
+    telemetry_subscribers::init_for_testing();
+
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let authority_id = fixture.authorities().next().unwrap().id();
+
+    let store = TestBatchStore::default().with_write_latency(Duration::from_millis(50));
+    let handler = |over_limit| WorkerReceiverHandler {
+        // Writes run on the blocking pool, so that they can overlap.
+        store_timeout: Some(Duration::from_secs(10)),
+        notify_primary: false,
+        write_permits: Some(StoreWritePermits::new(2, over_limit)),
+        ..WorkerReceiverHandler::new(
+            authority_id,
+            0,
+            NetworkClient::new_with_empty_id(),
+            store.clone(),
+            TrivialTransactionValidator,
+            Arc::new(WorkerMetrics::new(&Registry::new())),
+        )
+    };
+    let report = |handler: &WorkerReceiverHandler<_, _>, i: u8| {
+        let handler = handler.clone();
+        async move {
+            handler
+                .report_batch(anemo::Request::new(WorkerBatchMessage {
+                    batch: Batch::new(vec![vec![i; 100]]),
+                }))
+                .await
+        }
+    };
+
+    // Queued reports all succeed, but never write more than 2 batches at once.
+    let queueing = handler(OverLimitPolicy::Queue);
+    let results = futures::future::join_all((0..6).map(|i| report(&queueing, i))).await;
+    assert!(results.iter().all(Result::is_ok));
+    assert_eq!(store.max_writing.load(Ordering::SeqCst), 2);
+
+    // Reports beyond the limit are rejected as retriable instead.
+    let rejecting = handler(OverLimitPolicy::Reject);
+    let results = futures::future::join_all((6..12).map(|i| report(&rejecting, i))).await;
+    let rejected = results
+        .iter()
+        .filter_map(|result| result.as_ref().err())
+        .collect_vec();
+    assert_eq!(rejected.len(), 4);
+    assert!(rejected
+        .iter()
+        .all(|status| status.status() == StatusCode::ServiceUnavailable));
+    assert_eq!(store.max_writing.load(Ordering::SeqCst), 2);
 }
@@ -0,0 +1,96 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crypto::NetworkKeyPair;
+use fastcrypto::traits::KeyPair;
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::*;
+
+fn test_pks(count: u8) -> Vec<NetworkPublicKey> {
+    (0..count)
+        .map(|i| {
+            let mut rng = StdRng::from_seed([i; 32]);
+            NetworkKeyPair::generate(&mut rng).public().clone()
+        })
+        .collect()
+}
+
+fn stats(latency_ms: Option<u64>, successes: u64) -> PeerStats {
+    PeerStats {
+        latency: latency_ms.map(Duration::from_millis),
+        successes,
+        failures: 0,
+    }
+}
+
+#[test]
+fn round_robin_rotates_start() {
+    let peers = test_pks(3);
+    let selector = RoundRobin::default();
+    let digests = HashSet::new();
+    let rounds: Vec<_> = (0..4)
+        .map(|_| selector.select(&digests, &peers, &HashMap::new()))
+        .collect();
+    // The same order, shifted by one worker every round.
+    for (i, round) in rounds.iter().enumerate() {
+        assert_eq!(round.len(), 3);
+        let mut expected = rounds[0].clone();
+        expected.rotate_left(i % 3);
+        assert_eq!(round, &expected);
+    }
+    // The order does not depend on the order of the known workers.
+    let mut reversed = peers.clone();
+    reversed.reverse();
+    assert_eq!(
+        RoundRobin::default().select(&digests, &reversed, &HashMap::new()),
+        rounds[0]
+    );
+}
+
+#[test]
+fn latency_weighted_prefers_fast_peers() {
+    let peers = test_pks(4);
+    let stats = HashMap::from([
+        (peers[0].clone(), stats(Some(300), 1)),
+        (peers[1].clone(), stats(Some(10), 1)),
+        (peers[2].clone(), stats(Some(100), 1)),
+    ]);
+    let selected = LatencyWeighted.select(&HashSet::new(), &peers, &stats);
+    assert_eq!(
+        selected,
+        vec![
+            peers[1].clone(),
+            peers[2].clone(),
+            peers[0].clone(),
+            peers[3].clone()
+        ]
+    );
+}
+
+#[test]
+fn known_first_prefers_peers_that_served() {
+    let peers = test_pks(5);
+    let stats = HashMap::from([
+        (peers[3].clone(), stats(Some(50), 2)),
+        (peers[1].clone(), stats(Some(50), 7)),
+        (peers[4].clone(), stats(None, 0)),
+    ]);
+    let selected = KnownFirst.select(&HashSet::new(), &peers, &stats);
+    assert_eq!(selected[..2], [peers[1].clone(), peers[3].clone()]);
+    let unknown: HashSet<_> = selected[2..].iter().cloned().collect();
+    assert_eq!(
+        unknown,
+        HashSet::from([peers[0].clone(), peers[2].clone(), peers[4].clone()])
+    );
+}
+
+#[test]
+fn peer_stats_smooth_latency() {
+    let mut peer = PeerStats::default();
+    peer.record(Some(Duration::from_millis(100)));
+    peer.record(None);
+    peer.record(Some(Duration::from_millis(500)));
+    assert_eq!(peer.latency, Some(Duration::from_millis(200)));
+    assert_eq!((peer.successes, peer.failures), (2, 1));
+}
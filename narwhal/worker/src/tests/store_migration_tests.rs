@@ -0,0 +1,38 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::Duration;
+
+use super::*;
+use crate::MemoryBatchStore;
+
+#[tokio::test]
+async fn begin_waits_for_writes_in_progress() {
+    let migration = StoreMigration::default();
+    let write = migration.write().unwrap();
+    assert!(migration.snapshot().is_none());
+
+    // The window opens once the write in progress completes.
+    let begin = tokio::spawn({
+        let migration = migration.clone();
+        async move { migration.begin(MemoryBatchStore::default()).await }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!begin.is_finished());
+    assert!(migration.snapshot().is_none());
+    // New writes are refused while the writes in progress drain.
+    assert!(matches!(
+        migration.write(),
+        Err(WorkerHandlerError::Overloaded)
+    ));
+
+    drop(write);
+    begin.await.unwrap();
+    assert!(migration.snapshot().is_some());
+    assert!(migration.write().is_err());
+
+    assert!(migration.end());
+    assert!(migration.snapshot().is_none());
+    assert!(migration.write().is_ok());
+    assert!(!migration.end());
+}
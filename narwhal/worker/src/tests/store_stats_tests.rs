@@ -0,0 +1,33 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use fastcrypto::hash::Hash;
+use prometheus::Registry;
+use test_utils::create_batch_store;
+use types::Batch;
+
+use super::*;
+
+#[test]
+fn sample_rocksdb_properties() {
+    let store = create_batch_store();
+    let metrics = WorkerMetrics::new(&Registry::new());
+
+    // Flush a few batches to an SST file.
+    for i in 0..10u8 {
+        let batch = Batch::new(vec![vec![i; 100]]);
+        store.insert(&batch.digest(), &batch).unwrap();
+    }
+    store.flush().unwrap();
+
+    StoreStatsSampler::sample(&store, &metrics);
+    let property = |name: &str| {
+        metrics
+            .batch_store_property
+            .with_label_values(&[name])
+            .get()
+    };
+    assert!(property("rocksdb.total-sst-files-size") > 0);
+    assert_eq!(property("rocksdb.num-files-at-level0"), 1);
+    assert_eq!(property("rocksdb.compaction-pending"), 0);
+}
@@ -0,0 +1,62 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use fastcrypto::hash::Hash;
+
+use super::*;
+use crate::MemoryBatchStore;
+
+#[test]
+fn version_advances_on_insert_and_delete() {
+    let store = VersionedBatchStore::new(MemoryBatchStore::default(), StoreVersion::in_memory());
+    let version = || store.version().current();
+    assert_eq!(version(), 0);
+
+    let batch = Batch::new(vec![vec![1; 100]]);
+    store.insert(&batch.digest(), &batch).unwrap();
+    assert_eq!(version(), 1);
+
+    // Reads leave the version unchanged.
+    store.get(&batch.digest()).unwrap();
+    store.contains_key(&batch.digest()).unwrap();
+    store.entries_after(None, 10).unwrap();
+    assert_eq!(version(), 1);
+
+    store.remove(&batch.digest()).unwrap();
+    assert_eq!(version(), 2);
+    let other_batch = Batch::new(vec![vec![2; 100]]);
+    store
+        .multi_insert(&[(other_batch.digest(), other_batch.clone())])
+        .unwrap();
+    assert_eq!(version(), 3);
+    store.multi_remove(&[other_batch.digest()]).unwrap();
+    assert_eq!(version(), 4);
+}
+
+#[test]
+fn version_stays_monotonic_across_restarts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("store_version");
+
+    let store_version = StoreVersion::load(&path, 100).unwrap();
+    assert_eq!(store_version.current(), 0);
+    let store = VersionedBatchStore::new(MemoryBatchStore::default(), store_version.clone());
+    for i in 0..10u8 {
+        let batch = Batch::new(vec![vec![i; 100]]);
+        store.insert(&batch.digest(), &batch).unwrap();
+    }
+    assert_eq!(store_version.current(), 10);
+
+    // After a crash before the next persist, the version resumes past every version served,
+    // from the versions reserved on load.
+    let resumed = StoreVersion::load(&path, 100).unwrap();
+    assert_eq!(resumed.current(), 100);
+
+    // Persisting moves the reservation along with the version.
+    let store = VersionedBatchStore::new(MemoryBatchStore::default(), resumed.clone());
+    let batch = Batch::new(vec![vec![0; 100]]);
+    store.insert(&batch.digest(), &batch).unwrap();
+    resumed.persist().unwrap();
+    let resumed = StoreVersion::load(&path, 100).unwrap();
+    assert_eq!(resumed.current(), 201);
+}
@@ -86,9 +86,11 @@ async fn reject_invalid_clients_transactions() {
         NilTxValidator,
         client,
         batch_store,
+        test_utils::create_batch_tombstone_store(),
         metrics,
         &mut tx_shutdown,
-    );
+    )
+    .unwrap();
 
     // Wait till other services have been able to start up
     tokio::task::yield_now().await;
@@ -181,9 +183,11 @@ async fn handle_remote_clients_transactions() {
         TrivialTransactionValidator::default(),
         client.clone(),
         batch_store,
+        test_utils::create_batch_tombstone_store(),
         metrics,
         &mut tx_shutdown,
-    );
+    )
+    .unwrap();
 
     // Spawn a network listener to receive our batch's digest.
     let mut peer_networks = Vec::new();
@@ -299,9 +303,11 @@ async fn handle_local_clients_transactions() {
         TrivialTransactionValidator::default(),
         client.clone(),
         batch_store,
+        test_utils::create_batch_tombstone_store(),
         metrics,
         &mut tx_shutdown,
-    );
+    )
+    .unwrap();
 
     // Spawn a network listener to receive our batch's digest.
     let mut peer_networks = Vec::new();
@@ -449,9 +455,11 @@ async fn get_network_peers_from_admin_server() {
         TrivialTransactionValidator::default(),
         client_1.clone(),
         store.batch_store.clone(),
+        test_utils::create_batch_tombstone_store(),
         metrics_1.clone(),
         &mut tx_shutdown,
-    );
+    )
+    .unwrap();
 
     let primary_1_peer_id = Hex::encode(authority_1.network_keypair().copy().public().0.as_bytes());
     let worker_1_peer_id = Hex::encode(worker_1_keypair.copy().public().0.as_bytes());
@@ -573,9 +581,11 @@ async fn get_network_peers_from_admin_server() {
         TrivialTransactionValidator::default(),
         client_2,
         store.batch_store,
+        test_utils::create_batch_tombstone_store(),
         metrics_2.clone(),
         &mut tx_shutdown_worker,
-    );
+    )
+    .unwrap();
 
     // Wait for tasks to start. Sleeping longer here to ensure all primaries and workers
     // have  a chance to connect to each other.
@@ -668,3 +678,93 @@ async fn get_network_peers_from_admin_server() {
         );
     }
 }
+
+#[tokio::test]
+async fn spawn_with_worker_cache_of_another_epoch_fails() {
+    let fixture = CommitteeFixture::builder().randomize_ports(true).build();
+    let committee = fixture.committee();
+    let mut worker_cache = fixture.worker_cache();
+    worker_cache.epoch = committee.epoch() + 1;
+
+    let worker_id = 0;
+    let my_primary = fixture.authorities().next().unwrap();
+    let myself = my_primary.worker(worker_id);
+    let client = NetworkClient::new_from_keypair(&my_primary.network_keypair());
+
+    let registry = Registry::new();
+    let metrics = initialise_metrics(&registry);
+
+    let mut tx_shutdown = PreSubscribedBroadcastSender::new(NUM_SHUTDOWN_RECEIVERS);
+
+    // The worker refuses to start rather than panicking.
+    let result = Worker::spawn(
+        my_primary.authority().clone(),
+        myself.keypair(),
+        worker_id,
+        committee.clone(),
+        worker_cache,
+        Parameters::default(),
+        TrivialTransactionValidator::default(),
+        client,
+        crate::MemoryBatchStore::default(),
+        test_utils::create_batch_tombstone_store(),
+        metrics,
+        &mut tx_shutdown,
+    );
+    assert_eq!(
+        result.err(),
+        Some(PrimaryReceiverHandlerBuilderError::EpochMismatch {
+            worker_cache_epoch: committee.epoch() + 1,
+            committee_epoch: committee.epoch(),
+        })
+    );
+}
+
+#[tokio::test]
+async fn spawn_removes_batches_of_previous_epoch() {
+    let fixture = CommitteeFixture::builder()
+        .randomize_ports(true)
+        .epoch(2)
+        .build();
+    let committee = fixture.committee();
+    let worker_cache = fixture.worker_cache();
+
+    let worker_id = 0;
+    let my_primary = fixture.authorities().next().unwrap();
+    let myself = my_primary.worker(worker_id);
+    let client = NetworkClient::new_from_keypair(&my_primary.network_keypair());
+
+    let batches = test_utils::create_partitioned_batch_store();
+    let partition =
+        |epoch| crate::PartitionedBatchStore::new(batches.clone(), epoch, my_primary.id());
+    let batch = batch();
+    let digest = batch.digest();
+    for epoch in [1, 2] {
+        partition(epoch).insert(&digest, &batch).unwrap();
+    }
+
+    let registry = Registry::new();
+    let metrics = initialise_metrics(&registry);
+
+    let mut tx_shutdown = PreSubscribedBroadcastSender::new(NUM_SHUTDOWN_RECEIVERS);
+
+    Worker::spawn(
+        my_primary.authority().clone(),
+        myself.keypair(),
+        worker_id,
+        committee.clone(),
+        worker_cache,
+        Parameters::default(),
+        TrivialTransactionValidator::default(),
+        client,
+        partition(2),
+        test_utils::create_batch_tombstone_store(),
+        metrics,
+        &mut tx_shutdown,
+    )
+    .unwrap();
+
+    // Only the batches of the current epoch are kept.
+    assert!(!partition(1).contains_key(&digest).unwrap());
+    assert!(partition(2).contains_key(&digest).unwrap());
+}
@@ -0,0 +1,53 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::atomic::Ordering;
+
+use fastcrypto::hash::Hash;
+use futures::future::join_all;
+use prometheus::Registry;
+
+use super::*;
+use crate::batch_store_utils::TestBatchStore;
+
+#[tokio::test]
+async fn coalesces_rapid_inserts() {
+    let store = TestBatchStore::default();
+    let metrics = Arc::new(WorkerMetrics::new(&Registry::new()));
+    let batches: Vec<_> = (0..20u8).map(|i| Batch::new(vec![vec![i; 10]])).collect();
+
+    // Rapid inserts within the window are committed together.
+    let coalescer = WriteCoalescer::spawn(
+        store.clone(),
+        Duration::from_millis(100),
+        100,
+        metrics.clone(),
+    );
+    let acks = join_all(
+        batches
+            .iter()
+            .map(|batch| coalescer.insert(batch.digest(), batch.clone())),
+    )
+    .await;
+    for (ack, batch) in acks.into_iter().zip(&batches) {
+        assert_eq!(ack.unwrap(), *batch);
+        assert_eq!(store.get(&batch.digest()).unwrap().as_ref(), Some(batch));
+    }
+    assert!(store.writes.load(Ordering::SeqCst) < batches.len());
+
+    // Reaching the batch limit commits without waiting for the window to close.
+    store.writes.store(0, Ordering::SeqCst);
+    let coalescer = WriteCoalescer::spawn(store.clone(), Duration::from_secs(60), 4, metrics);
+    let acks = tokio::time::timeout(
+        Duration::from_secs(10),
+        join_all(
+            batches[..8]
+                .iter()
+                .map(|batch| coalescer.insert(batch.digest(), batch.clone())),
+        ),
+    )
+    .await
+    .unwrap();
+    assert!(acks.iter().all(|ack| ack.is_ok()));
+    assert_eq!(store.writes.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,79 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use fastcrypto::hash::HashFunction;
+use types::{Batch, BatchAPI};
+
+type TransactionDigest = [u8; crypto::DIGEST_LENGTH];
+
+/// Tracks the transactions of the batches stored by `report_batch`, so that batches made
+/// only of transactions already stored in other batches can be declined.
+///
+/// A declined batch is neither stored nor acknowledged, so the reporting worker never counts
+/// this worker towards the batch's availability. Batches fetched by `synchronize` are not
+/// affected, since the primary needs them regardless. Only the most recent `capacity`
+/// transactions are remembered.
+#[derive(Clone)]
+pub struct TransactionDedup {
+    inner: Arc<Mutex<Inner>>,
+    capacity: usize,
+}
+
+#[derive(Default)]
+struct Inner {
+    seen: HashSet<TransactionDigest>,
+    // Insertion order, to evict the oldest transactions first.
+    order: VecDeque<TransactionDigest>,
+}
+
+impl TransactionDedup {
+    pub const DEFAULT_CAPACITY: usize = 1_000_000;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner::default())),
+            capacity,
+        }
+    }
+
+    /// Records the transactions of a stored batch.
+    pub fn record(&self, batch: &Batch) {
+        let mut inner = self.inner.lock().unwrap();
+        for transaction in batch.transactions() {
+            let digest = Self::digest(transaction);
+            if inner.seen.insert(digest) {
+                inner.order.push_back(digest);
+            }
+        }
+        while inner.order.len() > self.capacity {
+            if let Some(evicted) = inner.order.pop_front() {
+                inner.seen.remove(&evicted);
+            }
+        }
+    }
+
+    /// Returns true if the batch holds transactions, all of which were already recorded.
+    pub fn is_redundant(&self, batch: &Batch) -> bool {
+        let inner = self.inner.lock().unwrap();
+        !batch.transactions().is_empty()
+            && batch
+                .transactions()
+                .iter()
+                .all(|transaction| inner.seen.contains(&Self::digest(transaction)))
+    }
+
+    fn digest(transaction: &[u8]) -> TransactionDigest {
+        crypto::DefaultHashFunction::digest(transaction).digest
+    }
+}
+
+impl Default for TransactionDedup {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
@@ -3,18 +3,74 @@ use std::fmt::{Debug, Display};
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 use async_trait::async_trait;
+use thiserror::Error;
 use types::Batch;
 
+/// Whether a failed validation could succeed if retried.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationErrorKind {
+    /// The transaction or batch is malformed or otherwise invalid, and is rejected for good.
+    Permanent,
+    /// Validation could not complete, e.g. because a dependency of the validator is
+    /// temporarily unavailable. The transaction or batch may be valid.
+    Transient,
+}
+
+/// A validation failure, classified by whether it could succeed if retried. Plain
+/// `eyre::Report`s convert into permanent failures.
+#[derive(Debug, Error)]
+#[error("{error}")]
+pub struct ValidationError {
+    pub kind: ValidationErrorKind,
+    pub error: eyre::Report,
+}
+
+impl ValidationError {
+    pub fn permanent(error: impl Into<eyre::Report>) -> Self {
+        Self {
+            kind: ValidationErrorKind::Permanent,
+            error: error.into(),
+        }
+    }
+
+    pub fn transient(error: impl Into<eyre::Report>) -> Self {
+        Self {
+            kind: ValidationErrorKind::Transient,
+            error: error.into(),
+        }
+    }
+}
+
+impl From<eyre::Report> for ValidationError {
+    fn from(error: eyre::Report) -> Self {
+        Self::permanent(error)
+    }
+}
+
 /// Defines the validation procedure for receiving either a new single transaction (from a client)
 /// of a batch of transactions (from another validator). Invalid transactions will not receive
 /// further processing.
 #[async_trait]
 pub trait TransactionValidator: Clone + Send + Sync + 'static {
-    type Error: Display + Debug + Send + Sync + 'static;
+    /// Batch validation errors are classified as permanent or transient through their
+    /// conversion into `ValidationError`, so that batches are only rejected for good when
+    /// they are invalid.
+    type Error: Into<ValidationError> + Display + Debug + Send + Sync + 'static;
     /// Determines if a transaction valid for the worker to consider putting in a batch
     fn validate(&self, t: &[u8]) -> Result<(), Self::Error>;
     /// Determines if this batch can be voted on
     async fn validate_batch(&self, b: &Batch) -> Result<(), Self::Error>;
+    /// Determines if each of these batches can be voted on, returning a result per batch in
+    /// order. Validators of signed transactions can override it to verify the signatures of
+    /// all the batches at once, which is much faster than batch by batch for schemes
+    /// supporting batch verification. Validates the batches one by one by default.
+    async fn validate_batches(&self, batches: &[&Batch]) -> Vec<Result<(), Self::Error>> {
+        let mut results = Vec::with_capacity(batches.len());
+        for batch in batches {
+            results.push(self.validate_batch(batch).await);
+        }
+        results
+    }
 }
 
 /// Simple validator that accepts all transactions and batches.
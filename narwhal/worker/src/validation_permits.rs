@@ -0,0 +1,46 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Bounds the number of batches validated concurrently by the handlers of a worker, across
+/// report_batch, report_batches and synchronize calls.
+///
+/// Each call already bounds its own validation parallelism, but a burst of concurrent calls
+/// can still oversubscribe the cores with CPU-heavy validators. Calls wait for a permit
+/// before invoking the validator instead.
+#[derive(Clone)]
+pub struct ValidationPermits {
+    semaphore: Arc<Semaphore>,
+}
+
+impl ValidationPermits {
+    pub fn new(permits: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(permits)),
+        }
+    }
+
+    /// Waits for a validation permit. The permit is released on drop.
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Validation semaphore should never be closed")
+    }
+
+    /// Returns the number of currently available permits.
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+}
+
+impl Default for ValidationPermits {
+    /// One permit per core.
+    fn default() -> Self {
+        Self::new(std::thread::available_parallelism().map_or(1, |cores| cores.get()))
+    }
+}
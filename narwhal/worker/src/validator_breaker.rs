@@ -0,0 +1,63 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Rejects batches needing validation while the transaction validator keeps failing, e.g.
+/// during an outage of its backend, instead of paying for a failed validation per batch.
+///
+/// After `failure_threshold` consecutive failed validations, the breaker trips and rejects
+/// batches for `cooldown`. A single batch is then let through to probe the validator: if it
+/// validates, the breaker closes again, otherwise it rejects batches for another cooldown.
+/// Since invalid batches count as failures too, the threshold should be well above the
+/// number of invalid batches expected in a row.
+#[derive(Clone)]
+pub struct ValidatorCircuitBreaker {
+    failure_threshold: usize,
+    cooldown: Duration,
+    // The number of consecutive failed validations, and when the breaker last tripped or let
+    // a probe through.
+    state: Arc<Mutex<(usize, Instant)>>,
+}
+
+impl ValidatorCircuitBreaker {
+    pub fn new(failure_threshold: usize, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            state: Arc::new(Mutex::new((0, Instant::now()))),
+        }
+    }
+
+    /// Returns true if the next batch should be rejected without validating it.
+    pub fn should_reject(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let (failures, opened_at) = &mut *state;
+        if *failures < self.failure_threshold {
+            return false;
+        }
+        if opened_at.elapsed() < self.cooldown {
+            return true;
+        }
+        // Let this batch through as a probe, and keep rejecting until it completes.
+        *opened_at = Instant::now();
+        false
+    }
+
+    /// Accounts the outcome of a validation.
+    pub fn record(&self, success: bool) {
+        let mut state = self.state.lock().unwrap();
+        let (failures, opened_at) = &mut *state;
+        if success {
+            *failures = 0;
+            return;
+        }
+        *failures += 1;
+        if *failures == self.failure_threshold {
+            *opened_at = Instant::now();
+        }
+    }
+}
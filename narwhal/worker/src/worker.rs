@@ -2,11 +2,23 @@
 // Copyright (c) Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 use crate::{
+    batch_diagnostics::BatchDiagnosticsService,
     batch_fetcher::BatchFetcher,
+    batch_insert_times::BatchInsertTimes,
     batch_maker::BatchMaker,
-    handlers::{PrimaryReceiverHandler, WorkerReceiverHandler},
+    batch_store::BatchStore,
+    batch_tombstones::BatchTombstones,
+    handlers::{
+        CertifiedBatchVerification, EnabledPrimaryToWorkerMethods, PrimaryReceiverHandler,
+        PrimaryReceiverHandlerBuilder, PrimaryReceiverHandlerBuilderError, WorkerReceiverHandler,
+    },
+    method_permits::{MethodConcurrencyLimits, OverLimitPolicy},
     metrics::WorkerChannelMetrics,
+    peer_rate_limits::PeerRateLimits,
     quorum_waiter::QuorumWaiter,
+    read_permits::StoreReadPermits,
+    size_limit_events::SizeLimitEvents,
+    write_backpressure::WriteBackpressure,
     TransactionValidator, NUM_SHUTDOWN_RECEIVERS,
 };
 use anemo::{codegen::InboundRequestLayer, types::Address};
@@ -18,7 +30,10 @@ use anemo_tower::{
     trace::{DefaultMakeSpan, DefaultOnFailure, TraceLayer},
 };
 use anemo_tower::{rate_limit, set_header::SetResponseHeaderLayer};
-use config::{Authority, AuthorityIdentifier, Committee, Parameters, WorkerCache, WorkerId};
+use config::{
+    Authority, AuthorityIdentifier, Committee, Parameters, PrimaryToWorkerMethod, WorkerCache,
+    WorkerId,
+};
 use crypto::{traits::KeyPair as _, NetworkKeyPair, NetworkPublicKey};
 use mysten_metrics::metered_channel::channel_with_total;
 use mysten_metrics::spawn_logged_monitored_task;
@@ -34,10 +49,10 @@ use store::rocks::DBMap;
 use tap::TapFallible;
 use tokio::task::JoinHandle;
 use tower::ServiceBuilder;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use types::{
     Batch, BatchDigest, ConditionalBroadcastReceiver, PreSubscribedBroadcastSender,
-    PrimaryToWorkerServer, WorkerToWorkerServer,
+    PrimaryToWorkerServer, TimestampMs, WorkerToWorkerServer,
 };
 
 #[cfg(test)]
@@ -50,7 +65,7 @@ pub const CHANNEL_CAPACITY: usize = 1_000;
 use crate::metrics::{Metrics, WorkerEndpointMetrics, WorkerMetrics};
 use crate::transactions_server::TxServer;
 
-pub struct Worker {
+pub struct Worker<S = DBMap<BatchDigest, Batch>> {
     /// This authority.
     authority: Authority,
     // The private-public key pair of this worker.
@@ -64,10 +79,10 @@ pub struct Worker {
     /// The configuration parameters
     parameters: Parameters,
     /// The persistent storage.
-    store: DBMap<BatchDigest, Batch>,
+    store: S,
 }
 
-impl Worker {
+impl<S: BatchStore> Worker<S> {
     pub fn spawn(
         authority: Authority,
         keypair: NetworkKeyPair,
@@ -77,10 +92,11 @@ impl Worker {
         parameters: Parameters,
         validator: impl TransactionValidator,
         client: NetworkClient,
-        store: DBMap<BatchDigest, Batch>,
+        store: S,
+        tombstone_store: DBMap<BatchDigest, TimestampMs>,
         metrics: Metrics,
         tx_shutdown: &mut PreSubscribedBroadcastSender,
-    ) -> Vec<JoinHandle<()>> {
+    ) -> Result<Vec<JoinHandle<()>>, PrimaryReceiverHandlerBuilderError> {
         let worker_name = keypair.public().clone();
         let worker_peer_id = PeerId(worker_name.0.to_bytes());
         info!("Boot worker node with id {} peer id {}", id, worker_peer_id,);
@@ -96,6 +112,14 @@ impl Worker {
             store,
         };
 
+        // Workers only serve requests of their epoch, see `AllowedEpoch`, so the batches of
+        // the previous epoch are no longer needed once the next one starts.
+        if let Some(previous_epoch) = committee.epoch().checked_sub(1) {
+            if let Err(e) = worker.store.remove_epoch(previous_epoch) {
+                warn!("Failed to remove the batches of epoch {previous_epoch}: {e:?}");
+            }
+        }
+
         let node_metrics = Arc::new(metrics.worker_metrics.unwrap());
         let endpoint_metrics = metrics.endpoint_metrics.unwrap();
         let channel_metrics: Arc<WorkerChannelMetrics> = Arc::new(metrics.channel_metrics.unwrap());
@@ -105,11 +129,47 @@ impl Worker {
 
         let mut shutdown_receivers = tx_shutdown.subscribe_n(NUM_SHUTDOWN_RECEIVERS);
 
+        // Store read permits are shared by both handlers, so that sync reads are prioritized
+        // over serving other workers.
+        let read_permits = StoreReadPermits::default();
+        // Shared with the admin server, for operators to find callers that over-request.
+        let size_limit_events = SizeLimitEvents::default();
+        // Both handlers store and delete batches, so they share the tombstones and insert times.
+        let handler_parameters = &parameters.worker_handlers;
+        let tombstones = handler_parameters
+            .batch_tombstone_grace_period_ms
+            .map(|grace_period| {
+                BatchTombstones::persisted(Duration::from_millis(grace_period), tombstone_store)
+            });
+        let batch_insert_times = handler_parameters
+            .record_batch_insert_times
+            .then(BatchInsertTimes::default);
+        let peer_rate_limits = handler_parameters
+            .worker_to_worker_rate_limit
+            .map(|per_second| {
+                PeerRateLimits::new(
+                    per_second,
+                    handler_parameters.worker_to_worker_rate_limit_burst(),
+                )
+            });
+
         let mut worker_service = WorkerToWorkerServer::new(WorkerReceiverHandler {
-            id: worker.id,
-            client: client.clone(),
-            store: worker.store.clone(),
-            validator: validator.clone(),
+            read_permits: read_permits.clone(),
+            size_limit_events: size_limit_events.clone(),
+            batch_insert_times: batch_insert_times.clone(),
+            write_backpressure: handler_parameters
+                .batch_write_latency_threshold_ms
+                .map(|threshold| WriteBackpressure::new(Duration::from_millis(threshold))),
+            tombstones: tombstones.clone(),
+            peer_rate_limits: peer_rate_limits.clone(),
+            ..WorkerReceiverHandler::new(
+                worker.authority.id(),
+                worker.id,
+                client.clone(),
+                worker.store.clone(),
+                validator.clone(),
+                node_metrics.clone(),
+            )
         });
         // Apply rate limits from configuration as needed.
         if let Some(limit) = parameters.anemo.report_batch_rate_limit {
@@ -127,21 +187,26 @@ impl Worker {
                     rate_limit::WaitMode::Block,
                 ),
             ));
+            worker_service = worker_service.add_layer_for_request_batch_v2(
+                InboundRequestLayer::new(rate_limit::RateLimitLayer::new(
+                    governor::Quota::per_second(limit),
+                    rate_limit::WaitMode::Block,
+                )),
+            );
         }
 
         // Legacy RPC interface, only used by delete_batches() for external consensus.
-        let primary_service = PrimaryToWorkerServer::new(PrimaryReceiverHandler {
-            authority_id: worker.authority.id(),
-            id: worker.id,
-            committee: worker.committee.clone(),
-            worker_cache: worker.worker_cache.clone(),
-            store: worker.store.clone(),
-            request_batch_timeout: worker.parameters.sync_retry_delay,
-            request_batch_retry_nodes: worker.parameters.sync_retry_nodes,
-            network: None,
-            batch_fetcher: None,
-            validator: validator.clone(),
-        });
+        let primary_service = PrimaryToWorkerServer::new(
+            worker
+                .primary_receiver_handler(
+                    validator.clone(),
+                    node_metrics.clone(),
+                    read_permits.clone(),
+                    tombstones.clone(),
+                    batch_insert_times.clone(),
+                )
+                .build_legacy_rpc()?,
+        );
 
         // Receive incoming messages from other workers.
         let address = worker
@@ -276,21 +341,22 @@ impl Worker {
             worker.store.clone(),
             node_metrics.clone(),
         );
-        client.set_primary_to_worker_local_handler(
-            worker_peer_id,
-            Arc::new(PrimaryReceiverHandler {
-                authority_id: worker.authority.id(),
-                id: worker.id,
-                committee: worker.committee.clone(),
-                worker_cache: worker.worker_cache.clone(),
-                store: worker.store.clone(),
-                request_batch_timeout: worker.parameters.sync_retry_delay,
-                request_batch_retry_nodes: worker.parameters.sync_retry_nodes,
-                network: Some(network.clone()),
-                batch_fetcher: Some(batch_fetcher),
-                validator: validator.clone(),
-            }),
-        );
+        let local_handler = worker
+            .primary_receiver_handler(
+                validator.clone(),
+                node_metrics.clone(),
+                read_permits,
+                tombstones,
+                batch_insert_times,
+            )
+            .network(network.clone())
+            .batch_fetcher(batch_fetcher)
+            .build()?;
+        // The batches deleted before a restart are still removed once their grace period ends.
+        if let Err(e) = local_handler.resume_tombstoned_removals() {
+            warn!("Failed to reload batch tombstones: {e:?}");
+        }
+        client.set_primary_to_worker_local_handler(worker_peer_id, Arc::new(local_handler));
 
         let mut peer_types = HashMap::new();
 
@@ -350,10 +416,17 @@ impl Worker {
             id, network_admin_server_base_port
         );
 
-        let admin_handles = network::admin::start_admin_server(
+        let mut admin_routes = size_limit_events
+            .admin_routes()
+            .merge(BatchDiagnosticsService::new(worker.store.clone()).admin_routes());
+        if let Some(peer_rate_limits) = &peer_rate_limits {
+            admin_routes = admin_routes.merge(peer_rate_limits.admin_routes());
+        }
+        let admin_handles = network::admin::start_admin_server_with_routes(
             network_admin_server_base_port,
             network.clone(),
             shutdown_receivers.pop().unwrap(),
+            admin_routes,
         );
 
         let client_flow_handles = worker.handle_clients_transactions(
@@ -387,7 +460,59 @@ impl Worker {
         let mut handles = vec![connection_monitor_handle, network_shutdown_handle];
         handles.extend(admin_handles);
         handles.extend(client_flow_handles);
-        handles
+        Ok(handles)
+    }
+
+    /// Returns a builder of the handler of our primary's messages, configured from the
+    /// parameters.
+    fn primary_receiver_handler<V: TransactionValidator>(
+        &self,
+        validator: V,
+        metrics: Arc<WorkerMetrics>,
+        read_permits: StoreReadPermits,
+        tombstones: Option<BatchTombstones>,
+        batch_insert_times: Option<BatchInsertTimes>,
+    ) -> PrimaryReceiverHandlerBuilder<V, S> {
+        let parameters = &self.parameters.worker_handlers;
+        let mut builder = PrimaryReceiverHandler::builder(
+            self.authority.id(),
+            self.id,
+            self.committee.clone(),
+            self.worker_cache.clone(),
+            self.store.clone(),
+            validator,
+            metrics,
+        )
+        .request_batch_timeout(self.parameters.sync_retry_delay)
+        .request_batch_retry_nodes(self.parameters.sync_retry_nodes)
+        .read_permits(read_permits)
+        .method_concurrency_limits(MethodConcurrencyLimits {
+            synchronize: parameters.synchronize_concurrency_limit,
+            fetch_batches: parameters.fetch_batches_concurrency_limit,
+            delete_batches: parameters.delete_batches_concurrency_limit,
+            over_limit: if parameters.reject_calls_over_concurrency_limit {
+                OverLimitPolicy::Reject
+            } else {
+                OverLimitPolicy::Queue
+            },
+        });
+        if let Some(methods) = &parameters.enabled_primary_to_worker_methods {
+            builder = builder.enabled_methods(EnabledPrimaryToWorkerMethods {
+                synchronize: methods.contains(&PrimaryToWorkerMethod::Synchronize),
+                fetch_batches: methods.contains(&PrimaryToWorkerMethod::FetchBatches),
+                delete_batches: methods.contains(&PrimaryToWorkerMethod::DeleteBatches),
+            });
+        }
+        if parameters.verify_batch_certificates {
+            builder = builder.certified_batch_verification(CertifiedBatchVerification::Certificate);
+        }
+        if let Some(tombstones) = tombstones {
+            builder = builder.tombstones(tombstones);
+        }
+        if let Some(batch_insert_times) = batch_insert_times {
+            builder = builder.batch_insert_times(batch_insert_times);
+        }
+        builder
     }
 
     // Spawns a task responsible for explicitly shutting down the network
@@ -0,0 +1,64 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Sheds incoming batch writes while the batch store is slow to write, e.g. during
+/// compaction stalls, instead of queueing up ever more writes.
+///
+/// The write latency is tracked as an exponentially weighted moving average. Once the
+/// average has not been updated for `probe_interval`, a write is let through again to
+/// refresh it, so shedding stops once the store recovers.
+#[derive(Clone)]
+pub struct WriteBackpressure {
+    threshold: Duration,
+    probe_interval: Duration,
+    // The average write latency, and when it was last updated.
+    state: Arc<Mutex<Option<(Duration, Instant)>>>,
+}
+
+impl WriteBackpressure {
+    pub const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+    // Weight of the latest sample in the average.
+    const SMOOTHING_FACTOR: f64 = 0.2;
+
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            probe_interval: Self::DEFAULT_PROBE_INTERVAL,
+            state: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn with_probe_interval(mut self, probe_interval: Duration) -> Self {
+        self.probe_interval = probe_interval;
+        self
+    }
+
+    /// Returns true if the next write should be rejected.
+    pub fn should_shed(&self) -> bool {
+        match *self.state.lock().unwrap() {
+            Some((latency, updated_at)) => {
+                latency > self.threshold && updated_at.elapsed() < self.probe_interval
+            }
+            None => false,
+        }
+    }
+
+    /// Accounts the latency of a completed write.
+    pub fn record(&self, latency: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let average = match *state {
+            Some((average, _)) => {
+                average.mul_f64(1.0 - Self::SMOOTHING_FACTOR)
+                    + latency.mul_f64(Self::SMOOTHING_FACTOR)
+            }
+            None => latency,
+        };
+        *state = Some((average, Instant::now()));
+    }
+}
@@ -0,0 +1,88 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+use types::{Batch, BatchDigest};
+
+use crate::{
+    batch_store::{BatchStore, StoreResult},
+    metrics::WorkerMetrics,
+};
+
+#[cfg(test)]
+#[path = "tests/write_coalescer_tests.rs"]
+pub mod write_coalescer_tests;
+
+type PendingWrite = (BatchDigest, Batch, oneshot::Sender<StoreResult<Batch>>);
+
+/// Coalesces the batch inserts of a high rate of `report_batch` calls into fewer store
+/// writes.
+///
+/// The first insert queued opens a window, during which further inserts are accumulated.
+/// Once the window closes, or as soon as `max_batches` inserts are pending, they are
+/// committed to the store in a single atomic `BatchStore::multi_insert`. Each insert only
+/// completes once its write is committed, with the outcome of the whole write.
+#[derive(Clone)]
+pub struct WriteCoalescer {
+    sender: mpsc::Sender<PendingWrite>,
+}
+
+impl WriteCoalescer {
+    pub const DEFAULT_WINDOW: Duration = Duration::from_millis(5);
+    pub const DEFAULT_MAX_BATCHES: usize = 100;
+
+    /// Spawns the task writing to `store`. It stops once every handle is dropped.
+    pub fn spawn<S: BatchStore>(
+        store: S,
+        window: Duration,
+        max_batches: usize,
+        metrics: Arc<WorkerMetrics>,
+    ) -> Self {
+        let max_batches = max_batches.max(1);
+        let (sender, mut receiver) = mpsc::channel::<PendingWrite>(max_batches);
+        tokio::spawn(async move {
+            while let Some(first) = receiver.recv().await {
+                let mut pending = vec![first];
+                let window_end = Instant::now() + window;
+                while pending.len() < max_batches {
+                    match tokio::time::timeout_at(window_end, receiver.recv()).await {
+                        Ok(Some(write)) => pending.push(write),
+                        // The window closed, or every handle was dropped.
+                        Ok(None) | Err(_) => break,
+                    }
+                }
+                let (entries, acks): (Vec<_>, Vec<_>) = pending
+                    .into_iter()
+                    .map(|(key, batch, ack)| ((key, batch), ack))
+                    .unzip();
+                metrics
+                    .coalesced_write_batches
+                    .observe(entries.len() as f64);
+                let result = store.multi_insert(&entries);
+                for ((_, batch), ack) in entries.into_iter().zip(acks) {
+                    // The caller may have given up waiting.
+                    let _ = ack.send(result.clone().map(|()| batch));
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    /// Inserts `batch` under `key` along with the other pending inserts, returning the batch
+    /// once the write is committed.
+    pub async fn insert(&self, key: BatchDigest, batch: Batch) -> StoreResult<Batch> {
+        let (ack, result) = oneshot::channel();
+        self.sender
+            .send((key, batch, ack))
+            .await
+            .expect("Write coalescer task should not stop while handles exist");
+        result
+            .await
+            .expect("Write coalescer task should acknowledge every insert")
+    }
+}
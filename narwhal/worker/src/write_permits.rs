@@ -0,0 +1,44 @@
+// Copyright (c) Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{handlers::WorkerHandlerError, method_permits::OverLimitPolicy};
+
+/// Bounds the number of concurrent batch store writes of report_batch and report_batches.
+///
+/// A burst of reports otherwise issues as many concurrent writes, which can overwhelm the
+/// write path of the store. Unlike `StoreReadPermits`, which bound reads, writes beyond the
+/// limit can also be rejected, so that reporting workers back off.
+#[derive(Clone)]
+pub struct StoreWritePermits {
+    semaphore: Arc<Semaphore>,
+    over_limit: OverLimitPolicy,
+}
+
+impl StoreWritePermits {
+    pub fn new(permits: usize, over_limit: OverLimitPolicy) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(permits)),
+            over_limit,
+        }
+    }
+
+    /// Waits for a write permit, or fails with `Overloaded` if none is available and
+    /// writes beyond the limit are rejected. The permit is released on drop.
+    pub async fn acquire(&self) -> Result<OwnedSemaphorePermit, WorkerHandlerError> {
+        let semaphore = self.semaphore.clone();
+        let permit = match self.over_limit {
+            OverLimitPolicy::Queue => semaphore.acquire_owned().await.ok(),
+            OverLimitPolicy::Reject => semaphore.try_acquire_owned().ok(),
+        };
+        permit.ok_or(WorkerHandlerError::Overloaded)
+    }
+
+    /// Returns the number of currently available permits.
+    pub fn available(&self) -> usize {
+        self.semaphore.available_permits()
+    }
+}